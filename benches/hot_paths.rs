@@ -0,0 +1,143 @@
+// benches/hot_paths.rs
+//
+// Tokenizer/parser, expansion and argv construction, the per-command hot
+// paths. Besides the criterion timings, allocations per command are printed
+// for the pooled and the plain variant, as seen when a script runs the same
+// command in a loop.
+use bumpalo::Bump;
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use rust_shell::config::Config;
+use rust_shell::memory_pool::{recycle_command, Argv};
+use rust_shell::shell::{executor::Executor, expand::expand_command, parser::Parser, Shell};
+use rust_shell::CommandType;
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::ffi::CString;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+struct CountingAlloc;
+
+static ALLOCATIONS: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for CountingAlloc {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+}
+
+#[global_allocator]
+static GLOBAL: CountingAlloc = CountingAlloc;
+
+const LINE: &str = "grep -n --color=never pattern src/main.rs src/lib.rs > out.txt";
+const PIPELINE: &str = "cat access.log | grep -v healthcheck | cut -d ' ' -f 1,7,9 | sort -k 2 \
+                        | uniq -c | sort -rn | head -n 20 | awk '{ print $2, $1 }' > top.txt";
+const SCRIPT: &str = "for f in *.log; do
+  if [[ -s $f ]]; then
+    grep -c ERROR \"$f\" | tee -a counts.txt > /dev/null
+  else
+    echo \"empty: $f\" >&2
+  fi
+done
+cat sizes.txt | while read -r name size; do
+  total=$((total + size)); echo \"$name $size\"
+done
+backup() { tar czf \"$1.tgz\" \"$1\" && mv \"$1.tgz\" /backups/; }
+";
+const LOOP: &str = "for i in {1..50}; do\n  if [[ $i == *0 ]]; then tens=$i; else last=$i; fi\ndone";
+
+// Parses `line` and gets each simple command in it ready to exec, as the
+// executor does. The arena is reused like the one of a script line.
+fn parse_and_recycle(shell: &mut Shell, arena: &mut Bump, line: &str) {
+    arena.reset();
+    let stages = match Parser::new(line, arena).unwrap().parse() {
+        Ok(CommandType::Simple(command)) => vec![command],
+        Ok(CommandType::Pipeline(stages)) => stages.iter()
+            .filter_map(|stage| match stage.command {
+                CommandType::Simple(command) => Some(command),
+                _ => None,
+            })
+            .collect(),
+        _ => return,
+    };
+    for command in stages {
+        let command = expand_command(shell, &command).unwrap();
+        let argv = Argv::build(&command.program, &command.args).unwrap();
+        black_box(&argv);
+        argv.recycle();
+        recycle_command(command);
+    }
+}
+
+// Parsing alone, into a reused arena
+fn parse_only(arena: &mut Bump, input: &str) {
+    arena.reset();
+    black_box(Parser::new(input, arena).unwrap().parse().unwrap());
+}
+
+// A loop run in the shell itself, no process started: its body is walked
+// once per word.
+fn run_loop(shell: &mut Shell, arena: &mut Bump, script: &str) {
+    arena.reset();
+    let parsed = Parser::new(script, arena).unwrap().parse().unwrap();
+    black_box(Executor::new().execute(shell, parsed));
+}
+
+fn plain_argv(program: &str, args: &[String]) -> Vec<CString> {
+    std::iter::once(program)
+        .chain(args.iter().map(|s| s.as_str()))
+        .map(|s| CString::new(s).unwrap())
+        .collect()
+}
+
+fn allocations_per_iteration(iterations: usize, mut f: impl FnMut()) -> f64 {
+    // Warm the pools first so steady-state behaviour is measured
+    for _ in 0..16 {
+        f();
+    }
+    let before = ALLOCATIONS.load(Ordering::Relaxed);
+    for _ in 0..iterations {
+        f();
+    }
+    (ALLOCATIONS.load(Ordering::Relaxed) - before) as f64 / iterations as f64
+}
+
+fn report_allocations(shell: &mut Shell, arena: &mut Bump) {
+    let args: Vec<String> = LINE.split_whitespace().skip(1).map(String::from).collect();
+    let pooled = allocations_per_iteration(1000, || parse_and_recycle(shell, arena, LINE));
+    let pipeline = allocations_per_iteration(1000, || parse_and_recycle(shell, arena, PIPELINE));
+    let script = allocations_per_iteration(1000, || parse_only(arena, SCRIPT));
+    let argv_pooled = allocations_per_iteration(1000, || {
+        Argv::build("grep", &args).unwrap().recycle()
+    });
+    let argv_plain = allocations_per_iteration(1000, || {
+        black_box(plain_argv("grep", &args));
+    });
+    eprintln!("allocations per command: parse+argv {:.1}, argv pooled {:.1}, argv plain {:.1}",
+              pooled, argv_pooled, argv_plain);
+    eprintln!("allocations per 8-stage pipeline: parse+argv {:.1}", pipeline);
+    eprintln!("allocations per script parse: {:.1}", script);
+}
+
+fn bench_hot_paths(c: &mut Criterion) {
+    let mut shell = Shell::with_config(Config::default());
+    let mut arena = Bump::new();
+    report_allocations(&mut shell, &mut arena);
+
+    let args: Vec<String> = LINE.split_whitespace().skip(1).map(String::from).collect();
+    c.bench_function("parse command line", |b| b.iter(|| parse_and_recycle(&mut shell, &mut arena, black_box(LINE))));
+    c.bench_function("parse long pipeline", |b| b.iter(|| parse_and_recycle(&mut shell, &mut arena, black_box(PIPELINE))));
+    c.bench_function("parse only long pipeline", |b| b.iter(|| parse_only(&mut arena, black_box(PIPELINE))));
+    c.bench_function("parse script", |b| b.iter(|| parse_only(&mut arena, black_box(SCRIPT))));
+    c.bench_function("run loop", |b| b.iter(|| run_loop(&mut shell, &mut arena, black_box(LOOP))));
+    c.bench_function("argv pooled", |b| {
+        b.iter(|| Argv::build("grep", black_box(&args)).unwrap().recycle())
+    });
+    c.bench_function("argv plain", |b| b.iter(|| plain_argv("grep", black_box(&args))));
+}
+
+criterion_group!(benches, bench_hot_paths);
+criterion_main!(benches);
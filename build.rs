@@ -0,0 +1,22 @@
+// build.rs
+//
+// Records the compiler version as RSHELL_RUSTC_VERSION. A plugin embeds the
+// value its own copy of rust_shell was built with, and the shell refuses one
+// that differs from its own (see plugin.rs).
+use std::env;
+use std::process::Command;
+
+fn main() {
+    println!("cargo:rerun-if-changed=build.rs");
+    println!("cargo:rerun-if-env-changed=RUSTC");
+    let rustc = env::var("RUSTC").unwrap_or_else(|_| "rustc".to_string());
+    let version = Command::new(&rustc)
+        .arg("--version")
+        .output()
+        .ok()
+        .filter(|output| output.status.success())
+        .and_then(|output| String::from_utf8(output.stdout).ok())
+        .map(|version| version.trim().to_string())
+        .unwrap_or_else(|| "unknown".to_string());
+    println!("cargo:rustc-env=RSHELL_RUSTC_VERSION={}", version);
+}
@@ -1,12 +1,48 @@
 // src/alias.rs
 use std::collections::HashMap;
+use std::sync::Arc;
+use crate::memory_pool::interner;
 use crate::shell::Shell;
+use crate::utils::string_utils::shell_quote;
+use crate::outln;
+
+// Alias names are interned; the config file keeps plain strings
+pub type AliasMap = HashMap<Arc<str>, String>;
+
+pub fn from_config(aliases: &HashMap<String, String>) -> AliasMap {
+    aliases.iter()
+        .map(|(name, value)| (interner().intern(name), value.clone()))
+        .collect()
+}
+
+pub fn to_config(aliases: &AliasMap) -> HashMap<String, String> {
+    aliases.iter()
+        .map(|(name, value)| (name.to_string(), value.clone()))
+        .collect()
+}
+
+// The aliases to write back to the config at exit. Those the startup files
+// defined are left out (or keep the config's value) unless changed since,
+// so the files stay where they live.
+pub fn to_save(current: &AliasMap, config: &AliasMap, startup: &AliasMap) -> AliasMap {
+    let mut saved = current.clone();
+    for (name, value) in startup {
+        if config.get(name) == Some(value) || current.get(name) != Some(value) {
+            continue;
+        }
+        match config.get(name) {
+            Some(original) => saved.insert(name.clone(), original.clone()),
+            None => saved.remove(name),
+        };
+    }
+    saved
+}
 
 pub fn builtin_alias(shell: &mut Shell, args: &[String]) -> i32 {
     if args.is_empty() {
         // Display all aliases
         for (name, value) in &shell.aliases {
-            println!("alias {}='{}'", name, value);
+            outln!("alias {}={}", name, shell_quote(value));
         }
         return 0;
     }
@@ -14,16 +50,14 @@ pub fn builtin_alias(shell: &mut Shell, args: &[String]) -> i32 {
     for arg in args {
         if let Some(eq_pos) = arg.find('=') {
             let name = arg[..eq_pos].to_string();
+            // The parser has already removed any quoting around the value
             let value = arg[eq_pos + 1..].to_string();
             
-            // Remove quotes if present
-            let value = value.trim_matches(|c| c == '\'' || c == '"').to_string();
-            
-            shell.aliases.insert(name, value);
+            shell.aliases.insert(interner().intern(&name), value);
         } else {
             // Show specific alias
-            if let Some(value) = shell.aliases.get(arg) {
-                println!("alias {}='{}'", arg, value);
+            if let Some(value) = shell.aliases.get(arg.as_str()) {
+                outln!("alias {}={}", arg, shell_quote(value));
             } else {
                 eprintln!("alias: {}: not found", arg);
                 return 1;
@@ -43,45 +77,47 @@ pub fn builtin_unalias(shell: &mut Shell, args: &[String]) -> i32 {
     for name in args {
         if name == "-a" {
             shell.aliases.clear();
-            return 0;
+            break;
         }
         
-        if shell.aliases.remove(name).is_none() {
+        if shell.aliases.remove(name.as_str()).is_none() {
             eprintln!("unalias: {}: not found", name);
+            interner().purge_unused();
             return 1;
         }
     }
     
+    interner().purge_unused();
     0
 }
 
-pub fn expand_aliases(input: &str, aliases: &HashMap<String, String>) -> String {
-    let mut result = String::new();
-    let mut words = input.split_whitespace();
-    let mut is_first = true;
-    let mut in_pipeline = false;
-    
-    while let Some(word) = words.next() {
-        if !is_first && !in_pipeline {
-            result.push(' ');
-        }
-        
-        // Check if this word should be expanded as an alias
-        if (is_first || in_pipeline) && aliases.contains_key(word) {
-            result.push_str(&aliases[word]);
-        } else {
-            result.push_str(word);
-        }
-        
-        // Check for pipeline or command separator
-        if word == "|" || word == "||" || word == "&&" || word == ";" {
-            in_pipeline = true;
-        } else {
-            in_pipeline = false;
-        }
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn startup_file_aliases_are_not_saved() {
+        let map = |pairs: &[(&str, &str)]| -> AliasMap {
+            pairs.iter().map(|(name, value)| (interner().intern(name), value.to_string())).collect()
+        };
+        let config = map(&[("ll", "ls -l"), ("g", "git")]);
+        let startup = map(&[("ll", "ls -la"), ("g", "git"), ("hi", "echo hello"), ("k", "kubectl")]);
+        let current = map(&[("ll", "ls -la"), ("g", "git"), ("hi", "echo hi"), ("new", "x")]);
+        let saved = to_save(&current, &config, &startup);
+        assert_eq!(saved, map(&[("ll", "ls -l"), ("g", "git"), ("hi", "echo hi"), ("new", "x")]));
+    }
+
+    #[test]
+    fn removed_names_leave_the_interner() {
+        let mut shell = Shell::new();
+        shell.set_var("RSHELL_PURGE_TEST", "1".to_string());
+        builtin_alias(&mut shell, &["rshell_purge_test=true".to_string()]);
+        let variable = std::sync::Arc::downgrade(&interner().intern("RSHELL_PURGE_TEST"));
+        let alias = std::sync::Arc::downgrade(&interner().intern("rshell_purge_test"));
         
-        is_first = false;
+        shell.unset_var("RSHELL_PURGE_TEST");
+        builtin_unalias(&mut shell, &["rshell_purge_test".to_string()]);
+        assert!(variable.upgrade().is_none());
+        assert!(alias.upgrade().is_none());
     }
-    
-    result
-}
\ No newline at end of file
+}
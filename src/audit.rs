@@ -0,0 +1,295 @@
+// src/audit.rs
+use crate::config::AuditConfig;
+use crate::shell::Shell;
+use crate::utils::helpers::{format_bytes, format_duration, format_utc_timestamp, tty_name};
+use crate::utils::string_utils::expand_tilde;
+use crate::outln;
+use serde::{Deserialize, Serialize};
+use std::fs::{self, OpenOptions};
+use std::io::{self, Write};
+use std::os::unix::fs::OpenOptionsExt;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+// One line of the audit log
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AuditRecord {
+    pub time: String,
+    pub timestamp: u64,
+    pub user: String,
+    pub host: String,
+    pub pid: u32,
+    #[serde(default)]
+    pub tty: Option<String>,
+    pub cwd: String,
+    pub command: String,
+    pub duration_ms: f64,
+    pub exit_code: i32,
+}
+
+impl AuditRecord {
+    // Describes a command line that started in `cwd` and has just finished
+    pub fn new(shell: &Shell, cwd: &str, command: &str, duration: Duration, exit_code: i32) -> Self {
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or(0);
+        Self {
+            time: format_utc_timestamp(timestamp),
+            timestamp,
+            user: shell.username.clone(),
+            host: shell.hostname.clone(),
+            pid: std::process::id(),
+            tty: tty_name(),
+            cwd: cwd.to_string(),
+            command: command.to_string(),
+            duration_ms: (duration.as_secs_f64() * 1e6).round() / 1e3,
+            exit_code,
+        }
+    }
+}
+
+pub struct AuditLog {
+    config: AuditConfig,
+    path: PathBuf,
+}
+
+impl AuditLog {
+    pub fn new(config: AuditConfig) -> Self {
+        let path = PathBuf::from(expand_tilde(&config.path));
+        Self { config, path }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        !self.config.path.is_empty()
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    // `path.<n>`; generation 0 is the live file
+    fn generation(&self, n: usize) -> PathBuf {
+        if n == 0 {
+            self.path.clone()
+        } else {
+            let mut name = self.path.clone().into_os_string();
+            name.push(format!(".{}", n));
+            PathBuf::from(name)
+        }
+    }
+
+    // Existing log files, oldest first
+    pub fn files(&self) -> Vec<PathBuf> {
+        (0..=self.config.keep)
+            .rev()
+            .map(|n| self.generation(n))
+            .filter(|path| path.exists())
+            .collect()
+    }
+
+    // Shifts path -> path.1 -> path.2 ..., dropping the oldest
+    fn rotate(&self) -> io::Result<()> {
+        if self.config.keep == 0 {
+            return fs::remove_file(&self.path);
+        }
+        let _ = fs::remove_file(self.generation(self.config.keep));
+        for n in (0..self.config.keep).rev() {
+            let from = self.generation(n);
+            if from.exists() {
+                fs::rename(&from, self.generation(n + 1))?;
+            }
+        }
+        Ok(())
+    }
+
+    pub fn record(&self, record: &AuditRecord) -> io::Result<()> {
+        if !self.is_enabled() {
+            return Ok(());
+        }
+        let limit = self.config.max_size_kb.saturating_mul(1024);
+        if limit > 0 && fs::metadata(&self.path).map(|m| m.len() >= limit).unwrap_or(false) {
+            self.rotate()?;
+        }
+        if let Some(parent) = self.path.parent().filter(|p| !p.as_os_str().is_empty()) {
+            fs::create_dir_all(parent)?;
+        }
+        let mut line = serde_json::to_string(record).map_err(io::Error::other)?;
+        line.push('\n');
+        // A single write per record keeps lines whole when several shells share the file.
+        // The commands may hold secrets, so only the user can read a new log.
+        OpenOptions::new().create(true).append(true).mode(0o600).open(&self.path)?.write_all(line.as_bytes())
+    }
+
+    // Every readable record, oldest first; malformed lines are skipped
+    pub fn records(&self) -> Vec<AuditRecord> {
+        self.files()
+            .iter()
+            .filter_map(|path| fs::read_to_string(path).ok())
+            .flat_map(|text| {
+                text.lines()
+                    .filter_map(|line| serde_json::from_str(line).ok())
+                    .collect::<Vec<AuditRecord>>()
+            })
+            .collect()
+    }
+}
+
+// Logs a command line that started in `cwd`, if the audit log is on;
+// a log that cannot be written is reported, not fatal
+pub fn record_command(shell: &Shell, cwd: &str, line: &str, elapsed: Duration, exit_code: i32) {
+    if !shell.audit.is_enabled() {
+        return;
+    }
+    let record = AuditRecord::new(shell, cwd, line, elapsed, exit_code);
+    if let Err(e) = shell.audit.record(&record) {
+        eprintln!("rshell: audit log {}: {}", shell.audit.path().display(), e);
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct SearchQuery {
+    pub text: Option<String>,
+    pub cwd: Option<String>,
+    pub failed: bool,
+    pub limit: Option<usize>,
+}
+
+impl SearchQuery {
+    pub fn matches(&self, record: &AuditRecord) -> bool {
+        self.text.as_ref().is_none_or(|text| record.command.contains(text.as_str()))
+            && self.cwd.as_ref().is_none_or(|cwd| record.cwd.starts_with(cwd.as_str()))
+            && (!self.failed || record.exit_code != 0)
+    }
+}
+
+// The last `limit` matching records, oldest first
+pub fn search(records: Vec<AuditRecord>, query: &SearchQuery) -> Vec<AuditRecord> {
+    let mut found: Vec<AuditRecord> = records.into_iter().filter(|r| query.matches(r)).collect();
+    if let Some(limit) = query.limit {
+        let skip = found.len().saturating_sub(limit);
+        found.drain(..skip);
+    }
+    found
+}
+
+pub fn builtin_audit(shell: &mut Shell, args: &[String]) -> i32 {
+    match args.first().map(String::as_str) {
+        None | Some("status") => {
+            if !shell.audit.is_enabled() {
+                outln!("audit log: disabled (set [audit] path in the config)");
+                return 0;
+            }
+            let files = shell.audit.files();
+            let size: u64 = files.iter().filter_map(|f| fs::metadata(f).ok()).map(|m| m.len()).sum();
+            outln!("audit log: {}", shell.audit.path().display());
+            outln!("files:     {} ({})", files.len(), format_bytes(size));
+            0
+        }
+        Some("search") => audit_search(shell, &args[1..]),
+        Some(other) => {
+            eprintln!("audit: unknown subcommand '{}'", other);
+            eprintln!("usage: audit [status] | audit search [--failed] [--cwd DIR] [-n N] [--json] [TEXT]");
+            1
+        }
+    }
+}
+
+fn audit_search(shell: &Shell, args: &[String]) -> i32 {
+    if !shell.audit.is_enabled() {
+        eprintln!("audit: the audit log is disabled");
+        return 1;
+    }
+    let mut query = SearchQuery::default();
+    let mut json = false;
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--failed" => query.failed = true,
+            "--json" => json = true,
+            "--cwd" => match args.next() {
+                Some(dir) => query.cwd = Some(expand_tilde(dir)),
+                None => {
+                    eprintln!("audit: --cwd needs a directory");
+                    return 1;
+                }
+            },
+            "-n" => match args.next().and_then(|n| n.parse().ok()) {
+                Some(n) => query.limit = Some(n),
+                None => {
+                    eprintln!("audit: -n needs a number");
+                    return 1;
+                }
+            },
+            _ => query.text = Some(arg.clone()),
+        }
+    }
+
+    let theme = &shell.config.theme;
+    for record in search(shell.audit.records(), &query) {
+        if json {
+            if let Ok(line) = serde_json::to_string(&record) {
+                outln!("{}", line);
+            }
+            continue;
+        }
+        let (color, reset) = if record.exit_code == 0 {
+            ("", "")
+        } else {
+            (theme.error_color.as_str(), theme.reset_color.as_str())
+        };
+        outln!("{}  {}{:>3}{}  {:>8}  {}  {}",
+                 record.time, color, record.exit_code, reset,
+                 format_duration(Duration::from_secs_f64(record.duration_ms.max(0.0) / 1e3)),
+                 record.cwd, record.command);
+    }
+    0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::os::unix::fs::PermissionsExt;
+
+    fn record(command: &str, cwd: &str, exit_code: i32) -> AuditRecord {
+        AuditRecord {
+            time: format_utc_timestamp(0),
+            timestamp: 0,
+            user: "me".to_string(),
+            host: "box".to_string(),
+            pid: 1,
+            tty: None,
+            cwd: cwd.to_string(),
+            command: command.to_string(),
+            duration_ms: 5.0,
+            exit_code,
+        }
+    }
+
+    #[test]
+    fn rotation_and_search() {
+        let dir = std::env::temp_dir().join(format!("rshell-audit-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        let config = AuditConfig {
+            path: dir.join("audit.jsonl").to_string_lossy().to_string(),
+            max_size_kb: 1,
+            keep: 2,
+        };
+        let log = AuditLog::new(config);
+        for i in 0..40 {
+            log.record(&record(&format!("make target{}", i), "/src/app", i % 3)).unwrap();
+        }
+        assert_eq!(log.files().len(), 3);
+        assert_eq!(fs::metadata(log.path()).unwrap().permissions().mode() & 0o777, 0o600);
+        assert!(fs::metadata(log.path()).unwrap().len() <= 1024 + 256);
+
+        let records = log.records();
+        assert_eq!(records.last().unwrap().command, "make target39");
+        let query = SearchQuery { failed: true, limit: Some(2), ..Default::default() };
+        let found: Vec<_> = search(records.clone(), &query).into_iter().map(|r| r.command).collect();
+        assert_eq!(found, ["make target37", "make target38"]);
+        let query = SearchQuery { cwd: Some("/tmp".to_string()), ..Default::default() };
+        assert!(search(records, &query).is_empty());
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
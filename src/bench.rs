@@ -0,0 +1,275 @@
+// src/bench.rs
+use crate::shell::executor::Executor;
+use crate::shell::parser::Parser;
+use crate::shell::Shell;
+use crate::utils::helpers::format_duration;
+use crate::outln;
+use bumpalo::Bump;
+use nix::fcntl::{open, OFlag};
+use nix::sys::stat::Mode;
+use nix::unistd::{close, dup, dup2};
+use std::io::Write;
+use std::os::unix::io::RawFd;
+use std::time::{Duration, Instant};
+
+const DEFAULT_RUNS: usize = 10;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct BenchStats {
+    pub mean: f64,
+    pub stddev: f64,
+    pub min: f64,
+    pub max: f64,
+    pub median: f64,
+    pub outliers: usize,
+}
+
+impl BenchStats {
+    // Summarises run times in seconds. Outliers are samples outside
+    // 1.5 * IQR of the quartiles.
+    pub fn from_samples(samples: &[f64]) -> Option<Self> {
+        if samples.is_empty() {
+            return None;
+        }
+        let mut sorted = samples.to_vec();
+        sorted.sort_by(|a, b| a.total_cmp(b));
+
+        let n = sorted.len() as f64;
+        let mean = sorted.iter().sum::<f64>() / n;
+        let stddev = if sorted.len() > 1 {
+            (sorted.iter().map(|x| (x - mean).powi(2)).sum::<f64>() / (n - 1.0)).sqrt()
+        } else {
+            0.0
+        };
+
+        let q1 = quantile(&sorted, 0.25);
+        let q3 = quantile(&sorted, 0.75);
+        let iqr = q3 - q1;
+        let outliers = sorted
+            .iter()
+            .filter(|&&x| x < q1 - 1.5 * iqr || x > q3 + 1.5 * iqr)
+            .count();
+
+        Some(Self {
+            mean,
+            stddev,
+            min: sorted[0],
+            max: sorted[sorted.len() - 1],
+            median: quantile(&sorted, 0.5),
+            outliers,
+        })
+    }
+}
+
+// Linear interpolation between closest ranks; `sorted` must be non-empty
+fn quantile(sorted: &[f64], q: f64) -> f64 {
+    let pos = q * (sorted.len() - 1) as f64;
+    let lower = pos.floor() as usize;
+    let upper = pos.ceil() as usize;
+    sorted[lower] + (sorted[upper] - sorted[lower]) * (pos - lower as f64)
+}
+
+// How many times slower `other` is than `fastest`, with propagated uncertainty
+pub fn relative_speed(fastest: &BenchStats, other: &BenchStats) -> (f64, f64) {
+    let ratio = other.mean / fastest.mean;
+    let error = ratio
+        * ((fastest.stddev / fastest.mean).powi(2) + (other.stddev / other.mean).powi(2)).sqrt();
+    (ratio, error)
+}
+
+fn format_seconds(secs: f64) -> String {
+    format_duration(Duration::from_secs_f64(secs))
+}
+
+struct BenchOptions {
+    warmup: usize,
+    runs: usize,
+    ignore_failure: bool,
+    show_output: bool,
+    commands: Vec<String>,
+}
+
+fn parse_options(args: &[String]) -> Result<BenchOptions, String> {
+    let mut options = BenchOptions {
+        warmup: 0,
+        runs: DEFAULT_RUNS,
+        ignore_failure: false,
+        show_output: false,
+        commands: Vec::new(),
+    };
+
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        match arg.as_str() {
+            "-w" | "--warmup" => options.warmup = parse_count(arg, iter.next())?,
+            "-r" | "--runs" => {
+                options.runs = parse_count(arg, iter.next())?;
+                if options.runs == 0 {
+                    return Err("--runs must be at least 1".to_string());
+                }
+            }
+            "-i" | "--ignore-failure" => options.ignore_failure = true,
+            "--show-output" => options.show_output = true,
+            _ => options.commands.push(arg.clone()),
+        }
+    }
+
+    if options.commands.is_empty() {
+        return Err("no command given".to_string());
+    }
+    Ok(options)
+}
+
+fn parse_count(flag: &str, value: Option<&String>) -> Result<usize, String> {
+    let value = value.ok_or_else(|| format!("{} requires an argument", flag))?;
+    value.parse().map_err(|_| format!("{}: invalid count '{}'", flag, value))
+}
+
+// Points stdout/stderr at /dev/null for the lifetime of the guard
+struct SilencedOutput {
+    saved: Vec<(RawFd, RawFd)>,
+}
+
+impl SilencedOutput {
+    fn new() -> nix::Result<Self> {
+        let _ = std::io::stdout().flush();
+        let null = open("/dev/null", OFlag::O_WRONLY, Mode::empty())?;
+        let mut saved = Vec::new();
+        for fd in [1, 2] {
+            saved.push((fd, dup(fd)?));
+            dup2(null, fd)?;
+        }
+        close(null).ok();
+        Ok(Self { saved })
+    }
+}
+
+impl Drop for SilencedOutput {
+    fn drop(&mut self) {
+        let _ = std::io::stdout().flush();
+        for (fd, copy) in self.saved.drain(..) {
+            dup2(copy, fd).ok();
+            close(copy).ok();
+        }
+    }
+}
+
+fn run_once(shell: &mut Shell, executor: &mut Executor, command: &str) -> Result<(Duration, i32), String> {
+    let arena = Bump::new();
+    let mut parser = Parser::with_aliases(command, &shell.aliases, &arena).map_err(|e| e.message)?;
+    let parsed = parser.parse().map_err(|e| e.message)?;
+    let start = Instant::now();
+    let exit_code = executor.execute(shell, parsed);
+    Ok((start.elapsed(), exit_code))
+}
+
+fn bench_command(shell: &mut Shell, options: &BenchOptions, command: &str) -> Result<BenchStats, String> {
+    let mut executor = Executor::new();
+    let mut samples = Vec::with_capacity(options.runs);
+
+    let _silenced = if options.show_output {
+        None
+    } else {
+        Some(SilencedOutput::new().map_err(|e| format!("cannot redirect output: {}", e))?)
+    };
+
+    for i in 0..options.warmup + options.runs {
+        let (elapsed, exit_code) = run_once(shell, &mut executor, command)?;
+        if exit_code != 0 && !options.ignore_failure {
+            return Err(format!(
+                "'{}' exited with status {} (use -i to ignore failures)", command, exit_code
+            ));
+        }
+        if i >= options.warmup {
+            samples.push(elapsed.as_secs_f64());
+        }
+    }
+
+    BenchStats::from_samples(&samples).ok_or_else(|| "no samples collected".to_string())
+}
+
+pub fn builtin_bench(shell: &mut Shell, args: &[String]) -> i32 {
+    let options = match parse_options(args) {
+        Ok(options) => options,
+        Err(e) => {
+            eprintln!("bench: {}", e);
+            eprintln!("Usage: bench [-w warmups] [-r runs] [-i] [--show-output] <command>...");
+            return 1;
+        }
+    };
+
+    let mut results = Vec::new();
+    for command in &options.commands {
+        let stats = match bench_command(shell, &options, command) {
+            Ok(stats) => stats,
+            Err(e) => {
+                eprintln!("bench: {}", e);
+                return 1;
+            }
+        };
+
+        outln!("Benchmark {}: {}", results.len() + 1, command);
+        outln!("  Time (mean ± σ):   {} ± {}",
+                 format_seconds(stats.mean), format_seconds(stats.stddev));
+        outln!("  Median:            {}", format_seconds(stats.median));
+        outln!("  Range (min … max): {} … {}    {} runs",
+                 format_seconds(stats.min), format_seconds(stats.max), options.runs);
+        if stats.outliers > 0 {
+            outln!("  Warning: {} statistical outlier(s) detected; \
+                      consider more warmup runs or a quieter system", stats.outliers);
+        }
+        outln!();
+        results.push((command.clone(), stats));
+    }
+
+    if results.len() > 1 {
+        let (fastest_cmd, fastest) = results
+            .iter()
+            .min_by(|a, b| a.1.mean.total_cmp(&b.1.mean))
+            .cloned()
+            .unwrap();
+        outln!("Summary");
+        outln!("  '{}' ran", fastest_cmd);
+        for (command, stats) in &results {
+            if *command == fastest_cmd {
+                continue;
+            }
+            let (ratio, error) = relative_speed(&fastest, stats);
+            outln!("    {:.2} ± {:.2} times faster than '{}'", ratio, error, command);
+        }
+    }
+    0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn stats_for_samples() {
+        let stats = BenchStats::from_samples(&[1.0, 2.0, 3.0, 4.0, 5.0]).unwrap();
+        assert_eq!(stats.mean, 3.0);
+        assert_eq!(stats.median, 3.0);
+        assert_eq!(stats.min, 1.0);
+        assert_eq!(stats.max, 5.0);
+        assert!((stats.stddev - 2.5f64.sqrt()).abs() < 1e-9);
+        assert_eq!(stats.outliers, 0);
+    }
+
+    #[test]
+    fn detects_outliers() {
+        let stats = BenchStats::from_samples(&[1.0, 1.1, 0.9, 1.0, 1.05, 9.0]).unwrap();
+        assert_eq!(stats.outliers, 1);
+    }
+
+    #[test]
+    fn parses_options() {
+        let args: Vec<String> = ["-w", "3", "-r", "5", "sleep 0.1", "true"]
+            .iter().map(|s| s.to_string()).collect();
+        let options = parse_options(&args).unwrap();
+        assert_eq!(options.warmup, 3);
+        assert_eq!(options.runs, 5);
+        assert_eq!(options.commands, vec!["sleep 0.1", "true"]);
+        assert!(parse_options(&["-r".to_string(), "0".to_string(), "x".to_string()]).is_err());
+    }
+}
@@ -0,0 +1,87 @@
+// src/bookmark.rs
+//
+// Named directories: `bookmark add work ~/code/work`, then `cd @work` or
+// `cd @work/api`. Bookmarks live in the config file's [bookmarks] table.
+use std::path::Path;
+use crate::shell::Shell;
+use crate::utils::string_utils::expand_tilde;
+use crate::outln;
+
+// `@name` or `@name/rest` with the bookmark's directory put in; None if
+// `path` names no bookmark
+pub fn resolve(shell: &Shell, path: &str) -> Option<String> {
+    let rest = path.strip_prefix('@')?;
+    let (name, tail) = match rest.split_once('/') {
+        Some((name, tail)) => (name, Some(tail)),
+        None => (rest, None),
+    };
+    let dir = shell.config.bookmarks.get(name)?;
+    Some(match tail {
+        Some(tail) => format!("{}/{}", dir.trim_end_matches('/'), tail),
+        None => dir.clone(),
+    })
+}
+
+pub fn builtin_bookmark(shell: &mut Shell, args: &[String]) -> i32 {
+    match args.first().map(|s| s.as_str()) {
+        None | Some("list") => {
+            let mut bookmarks: Vec<_> = shell.config.bookmarks.iter().collect();
+            bookmarks.sort();
+            for (name, dir) in bookmarks {
+                outln!("@{:<15} {}", name, dir);
+            }
+            0
+        }
+        Some("add") => {
+            let Some(name) = args.get(1) else {
+                eprintln!("bookmark: usage: bookmark add NAME [DIR]");
+                return 2;
+            };
+            if name.is_empty() || name.contains(['/', '@']) || name.contains(char::is_whitespace) {
+                eprintln!("bookmark: {}: invalid bookmark name", name);
+                return 1;
+            }
+            // Stored absolute, so `cd @name` works from anywhere
+            let dir = match args.get(2) {
+                Some(dir) => {
+                    let dir = expand_tilde(dir);
+                    crate::utils::path_utils::normalize_logical(Path::new(&shell.current_dir), Path::new(&dir))
+                        .to_string_lossy()
+                        .to_string()
+                }
+                None => shell.current_dir.clone(),
+            };
+            if !Path::new(&dir).is_dir() {
+                eprintln!("bookmark: {}: not a directory", dir);
+                return 1;
+            }
+            shell.config.bookmarks.insert(name.clone(), dir.clone());
+            save(shell, &format!("@{} -> {}", name, dir))
+        }
+        Some("rm" | "remove") => {
+            let Some(name) = args.get(1) else {
+                eprintln!("bookmark: usage: bookmark rm NAME");
+                return 2;
+            };
+            if shell.config.bookmarks.remove(name.trim_start_matches('@')).is_none() {
+                eprintln!("bookmark: {}: no such bookmark", name);
+                return 1;
+            }
+            save(shell, &format!("Removed @{}", name.trim_start_matches('@')))
+        }
+        Some(other) => {
+            eprintln!("bookmark: unknown subcommand '{}'", other);
+            eprintln!("Usage: bookmark [list|add NAME [DIR]|rm NAME]");
+            1
+        }
+    }
+}
+
+fn save(shell: &Shell, message: &str) -> i32 {
+    if let Err(e) = shell.save_config() {
+        eprintln!("bookmark: failed to save config: {}", e);
+        return 1;
+    }
+    outln!("{}", message);
+    0
+}
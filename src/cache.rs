@@ -9,7 +9,8 @@ use std::num::NonZeroUsize;
 
 #[derive(Clone)]
 pub struct CommandCache {
-    // Path to executable mapping
+    // Path to executable mapping; keyed by plain strings, as misses and
+    // evicted names must not outlive their entry
     path_cache: Arc<RwLock<LruCache<String, Option<PathBuf>>>>,
     // Directory contents cache
     dir_cache: Arc<RwLock<HashMap<PathBuf, DirCacheEntry>>>,
@@ -17,6 +18,8 @@ pub struct CommandCache {
     builtins: Arc<HashSet<String>>,
     // Command completion cache
     completion_cache: Arc<RwLock<HashMap<String, Vec<String>>>>,
+    // `git` output for completion, by directory and arguments
+    git_cache: Arc<RwLock<HashMap<(PathBuf, String), DirCacheEntry>>>,
     // Stats for monitoring
     stats: Arc<RwLock<CacheStats>>,
 }
@@ -37,16 +40,18 @@ pub struct CacheStats {
     pub completion_misses: usize,
 }
 
+impl Default for CommandCache {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl CommandCache {
     pub fn new() -> Self {
-        let mut builtins = HashSet::new();
-        for cmd in &[
-            "cd", "pwd", "echo", "export", "unset", "exit", 
-            "history", "jobs", "help", "fg", "bg", "kill",
-            "alias", "unalias", "theme", "config"
-        ] {
-            builtins.insert(cmd.to_string());
-        }
+        let builtins = crate::shell::builtins::BUILTINS.iter()
+            .chain(crate::structured::COMMANDS)
+            .map(|cmd| cmd.to_string())
+            .collect();
         
         Self {
             path_cache: Arc::new(RwLock::new(LruCache::new(
@@ -55,6 +60,7 @@ impl CommandCache {
             dir_cache: Arc::new(RwLock::new(HashMap::new())),
             builtins: Arc::new(builtins),
             completion_cache: Arc::new(RwLock::new(HashMap::new())),
+            git_cache: Arc::new(RwLock::new(HashMap::new())),
             stats: Arc::new(RwLock::new(CacheStats::default())),
         }
     }
@@ -117,6 +123,30 @@ impl CommandCache {
         })
     }
     
+    // Every executable named `cmd` along PATH in order, the ones the first
+    // shadows included. Not cached: this is for `which -a`.
+    pub fn find_all(&self, cmd: &str) -> Vec<PathBuf> {
+        if cmd.contains('/') {
+            return self.find_command(cmd).into_iter().collect();
+        }
+        let Ok(path_var) = std::env::var("PATH") else {
+            return Vec::new();
+        };
+        path_var.split(':')
+            .map(|dir| Path::new(dir).join(cmd))
+            .filter(|path| path.exists() && is_executable(path))
+            .collect()
+    }
+    
+    // The PATH lookups remembered so far that found something, by name
+    pub fn cached_paths(&self) -> Vec<(String, PathBuf)> {
+        let mut paths: Vec<(String, PathBuf)> = self.path_cache.read().unwrap().iter()
+            .filter_map(|(cmd, path)| path.as_ref().map(|path| (cmd.to_string(), path.clone())))
+            .collect();
+        paths.sort();
+        paths
+    }
+    
     pub fn get_dir_contents(&self, dir: &Path) -> Option<Vec<String>> {
         let now = SystemTime::now();
         
@@ -205,10 +235,55 @@ impl CommandCache {
         completions
     }
     
+    // The lines `git ARGS` prints in `dir`, such as branch or remote names,
+    // kept for 30 seconds; nothing outside a repository or without git
+    pub fn git_lines(&self, dir: &Path, args: &[&str]) -> Vec<String> {
+        let key = (dir.to_path_buf(), args.join(" "));
+        let now = SystemTime::now();
+        if let Some(entry) = self.git_cache.read().unwrap().get(&key) {
+            if now.duration_since(entry.timestamp).unwrap_or(Duration::MAX) < Duration::from_secs(30) {
+                self.stats.write().unwrap().completion_hits += 1;
+                return entry.entries.clone();
+            }
+        }
+        self.stats.write().unwrap().completion_misses += 1;
+        let lines: Vec<String> = std::process::Command::new("git")
+            .arg("-C")
+            .arg(dir)
+            .args(args)
+            .stdin(std::process::Stdio::null())
+            .stderr(std::process::Stdio::null())
+            .output()
+            .ok()
+            .filter(|output| output.status.success())
+            .map(|output| String::from_utf8_lossy(&output.stdout).lines().map(String::from).collect())
+            .unwrap_or_default();
+        self.git_cache.write().unwrap().insert(key, DirCacheEntry { entries: lines.clone(), timestamp: now });
+        lines
+    }
+    
+    // Completions stored under `key` by someone else, such as the options
+    // scraped from a command's --help
+    pub fn cached_completions(&self, key: &str) -> Option<Vec<String>> {
+        let found = self.completion_cache.read().unwrap().get(key).cloned();
+        let mut stats = self.stats.write().unwrap();
+        if found.is_some() {
+            stats.completion_hits += 1;
+        } else {
+            stats.completion_misses += 1;
+        }
+        found
+    }
+    
+    pub fn store_completions(&self, key: &str, completions: Vec<String>) {
+        self.completion_cache.write().unwrap().insert(key.to_string(), completions);
+    }
+    
     pub fn invalidate(&self) {
         self.path_cache.write().unwrap().clear();
         self.dir_cache.write().unwrap().clear();
         self.completion_cache.write().unwrap().clear();
+        self.git_cache.write().unwrap().clear();
     }
     
     pub fn invalidate_path(&self, path: &Path) {
@@ -221,11 +296,59 @@ impl CommandCache {
         }
     }
     
+    // Estimated heap footprint of the path, directory and completion caches
+    pub fn memory_usage(&self) -> usize {
+        let path_cache = self.path_cache.read().unwrap();
+        let path_bytes = path_cache.len() * std::mem::size_of::<(String, Option<PathBuf>)>()
+            + path_cache.iter()
+                .map(|(cmd, path)| cmd.capacity() + path.as_ref().map_or(0, |p| p.as_os_str().len()))
+                .sum::<usize>();
+        let dir_bytes: usize = self.dir_cache.read().unwrap().iter()
+            .map(|(dir, entry)| dir.as_os_str().len() + strings_bytes(&entry.entries))
+            .sum();
+        let completion_bytes: usize = self.completion_cache.read().unwrap().iter()
+            .map(|(prefix, completions)| prefix.capacity() + strings_bytes(completions))
+            .sum();
+        let git_bytes: usize = self.git_cache.read().unwrap().iter()
+            .map(|((dir, args), entry)| dir.as_os_str().len() + args.capacity() + strings_bytes(&entry.entries))
+            .sum();
+        path_bytes + dir_bytes + completion_bytes + git_bytes
+    }
+    
+    // Evicts the cheapest-to-rebuild data first until usage fits in `limit` bytes:
+    // completions and git output, then directory listings, then least recently used PATH lookups.
+    // Returns whether anything was evicted.
+    pub fn evict_to(&self, limit: usize) -> bool {
+        if self.memory_usage() <= limit {
+            return false;
+        }
+        self.completion_cache.write().unwrap().clear();
+        self.git_cache.write().unwrap().clear();
+        if self.memory_usage() > limit {
+            self.dir_cache.write().unwrap().clear();
+        }
+        while self.memory_usage() > limit {
+            let mut path_cache = self.path_cache.write().unwrap();
+            if path_cache.is_empty() {
+                break;
+            }
+            for _ in 0..path_cache.len().div_ceil(10) {
+                path_cache.pop_lru();
+            }
+        }
+        true
+    }
+    
     pub fn get_stats(&self) -> CacheStats {
         self.stats.read().unwrap().clone()
     }
 }
 
+fn strings_bytes(strings: &[String]) -> usize {
+    std::mem::size_of_val(strings)
+        + strings.iter().map(|s| s.capacity()).sum::<usize>()
+}
+
 fn is_executable(path: &Path) -> bool {
     use std::os::unix::fs::PermissionsExt;
     path.metadata()
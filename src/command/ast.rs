@@ -0,0 +1,140 @@
+// Indented dump of a parsed command, for `rshell --ast` and `debug ast`.
+// Words are shown as the parser kept them, before any expansion.
+use super::{CommandType,SimpleCommand};
+use std::fmt::Write;
+
+pub fn render(command:&CommandType)->String{
+    let mut out=String::new();
+    write_node(&mut out,command,0,"");
+    out
+}
+
+fn write_node(out:&mut String,command:&CommandType,depth:usize,suffix:&str){
+    let indent="  ".repeat(depth);
+    match command{
+        CommandType::Simple(command)=>write_simple(out,command,depth,suffix),
+        CommandType::Pipeline(stages)=>{
+            let _=writeln!(out,"{}Pipeline{}",indent,suffix);
+            for stage in stages.iter(){
+                // `|&` after a stage pipes its stderr too
+                write_node(out,&stage.command,depth+1,if stage.pipe_stderr{" |&"}else{""});
+            }
+        }
+        CommandType::And(left,right)|CommandType::Or(left,right)=>{
+            let name=if matches!(command,CommandType::And(..)){"And"}else{"Or"};
+            let _=writeln!(out,"{}{}{}",indent,name,suffix);
+            write_node(out,left,depth+1,"");
+            write_node(out,right,depth+1,"");
+        }
+        CommandType::Sequence(commands)=>{
+            let _=writeln!(out,"{}Sequence{}",indent,suffix);
+            for command in commands.iter(){
+                write_node(out,command,depth+1,"");
+            }
+        }
+        CommandType::Subshell(inner)=>{
+            let _=writeln!(out,"{}Subshell{}",indent,suffix);
+            write_node(out,inner,depth+1,"");
+        }
+        CommandType::Not(inner)=>{
+            let _=writeln!(out,"{}Not{}",indent,suffix);
+            write_node(out,inner,depth+1,"");
+        }
+        CommandType::Background(inner)=>{
+            let _=writeln!(out,"{}Background{}",indent,suffix);
+            write_node(out,inner,depth+1,"");
+        }
+        CommandType::Coproc(name,inner)=>{
+            let _=writeln!(out,"{}Coproc {}{}",indent,name,suffix);
+            write_node(out,inner,depth+1,"");
+        }
+        CommandType::If{branches,else_body}=>{
+            let _=writeln!(out,"{}If{}",indent,suffix);
+            for (i,(condition,body)) in branches.iter().enumerate(){
+                let _=writeln!(out,"{}  {}",indent,if i==0{"if"}else{"elif"});
+                write_node(out,condition,depth+2,"");
+                let _=writeln!(out,"{}  then",indent);
+                write_node(out,body,depth+2,"");
+            }
+            if let Some(body)=else_body{
+                let _=writeln!(out,"{}  else",indent);
+                write_node(out,body,depth+2,"");
+            }
+        }
+        CommandType::Group(inner)=>{
+            let _=writeln!(out,"{}Group{}",indent,suffix);
+            write_node(out,inner,depth+1,"");
+        }
+        CommandType::Function{name,body}=>{
+            let _=writeln!(out,"{}Function {}{}",indent,name,suffix);
+            write_node(out,body,depth+1,"");
+        }
+        CommandType::For{name,words,body}|CommandType::Select{name,words,body}=>{
+            let kind=if matches!(command,CommandType::Select{..}){"Select"}else{"For"};
+            let _=write!(out,"{}{} {}",indent,kind,name);
+            if let Some(words)=words{
+                let _=write!(out," in");
+                for word in words.iter(){
+                    let _=write!(out," {:?}",word);
+                }
+            }
+            let _=writeln!(out,"{}",suffix);
+            write_node(out,body,depth+1,"");
+        }
+        CommandType::While{condition,body,until}=>{
+            let _=writeln!(out,"{}{}{}",indent,if *until{"Until"}else{"While"},suffix);
+            write_node(out,condition,depth+1,"");
+            let _=writeln!(out,"{}  do",indent);
+            write_node(out,body,depth+2,"");
+        }
+        CommandType::Conditional(expr)=>{
+            let _=writeln!(out,"{}Conditional [[ {} ]]{}",indent,expr,suffix);
+        }
+        CommandType::Arithmetic(expr)=>{
+            let _=writeln!(out,"{}Arithmetic (({})){}",indent,expr,suffix);
+        }
+    }
+}
+
+fn write_simple(out:&mut String,command:&SimpleCommand,depth:usize,suffix:&str){
+    let indent="  ".repeat(depth);
+    let _=write!(out,"{}Simple {:?}",indent,command.program);
+    for arg in command.args{
+        let _=write!(out," {:?}",arg);
+    }
+    let _=writeln!(out,"{}",suffix);
+    for (name,value) in command.env_overrides{
+        let _=writeln!(out,"{}  assign {}={:?}",indent,name,value);
+    }
+    for redirection in command.redirects{
+        let _=writeln!(out,"{}  redirect {} {:?}",indent,redirection.fd,redirection.target);
+    }
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+    use crate::shell::parser::Parser;
+    use bumpalo::Bump;
+
+    #[test]
+    fn nests_by_precedence(){
+        let arena=Bump::new();
+        let parsed=Parser::new("A=1 make 2>&1 |& tee log && ! (cd \"$D\"; ls) || echo 'no'",&arena).unwrap().parse().unwrap();
+        assert_eq!(render(&parsed),concat!(
+            "Or\n",
+            "  And\n",
+            "    Pipeline\n",
+            "      Simple \"make\" |&\n",
+            "        assign A=\"1\"\n",
+            "        redirect 2 Dup(1)\n",
+            "      Simple \"tee\" \"log\"\n",
+            "    Not\n",
+            "      Subshell\n",
+            "        Sequence\n",
+            "          Simple \"cd\" \"\\\"$D\\\"\"\n",
+            "          Simple \"ls\"\n",
+            "  Simple \"echo\" \"'no'\"\n",
+        ));
+    }
+}
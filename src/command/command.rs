@@ -1,3 +1,6 @@
+use bumpalo::Bump;
+use self_cell::self_cell;
+
 #[derive(Debug, Clone, PartialEq)]
 pub enum RedirectType {
     Input,           // 
@@ -5,17 +8,58 @@ pub enum RedirectType {
     Append,          // >>
     Error,           // 2>
     ErrorAppend,     // 2>>
+    HereDoc{expand:bool}, // <<EOF, or <<'EOF' without expansion
+    DupStderrToStdout, // 2>&1
+    OutputBoth,      // &>
+    OutputBothAppend, // &>>
+    DupOutput,       // >&M, >&-
+    DupInput,        // <&M, <&-
+}
+
+// Where a redirection points its file descriptor. Parsed commands hold
+// `&str` words from the parse arena, runnable ones `String`s.
+#[derive(Debug,Clone,Copy,PartialEq)]
+pub enum RedirectTarget<S=String>{
+    Read(S),
+    Write(S),
+    Append(S),
+    Dup(i32),
+    // `>&$FD`: which fd is only known once the word is expanded
+    DupWord(S),
+    Close,
+    HereDoc(HereDoc<S>),
 }
 
+#[derive(Debug,Clone,Copy,PartialEq)]
+pub struct Redirection<S=String>{
+    pub fd:i32,
+    pub target:RedirectTarget<S>,
+}
+
+impl<S> Redirection<S>{
+    pub fn new(fd:i32,target:RedirectTarget<S>)->Self{
+        Self{fd,target}
+    }
+}
+
+#[derive(Debug,Clone,Copy,PartialEq)]
+pub struct HereDoc<S=String>{
+    pub body:S,
+    // $VAR in the body is expanded unless the delimiter was quoted
+    pub expand:bool,
+}
+
+// A command ready to run: its words expanded and owned, so it can go to
+// another thread or outlive the line it came from
 #[derive(Debug,Clone)]
 pub struct Command{
     pub program:String,
     pub args:Vec<String>,
-    pub stdin_redirect:Option<String>,
-    pub stdout_redirect:Option<String>,
-    pub stderr_redirect:Option<String>,
-    pub append_stdout:bool,
-    pub background:bool,
+    // Applied left to right, so `> f 2>&1` and `2>&1 > f` differ as in sh
+    pub redirects:Vec<Redirection>,
+    // `NAME=value` words before the program, set only for this command.
+    // With no program they assign shell variables instead.
+    pub env_overrides:Vec<(String,String)>,
 }
 
 impl Command{
@@ -23,19 +67,294 @@ impl Command{
         Self{
             program,
             args:Vec::new(),
-            stdin_redirect:None,
-            stdout_redirect:None,
-            stderr_redirect:None,
-            append_stdout:false,
-            background:false,
+            redirects:Vec::new(),
+            env_overrides:Vec::new(),
         }
     }
 }
 
-#[derive(Debug,Clone)]
-pub enum CommandType{
-    Simple(Command),
-    Pipeline(Vec<Command>),
-    And(Box<CommandType>,Box<CommandType>),
-    Or(Box<CommandType>,Box<CommandType>),
+// A simple command as parsed, words as typed. Everything in the tree
+// lives in the arena the parser was given (or in the input line itself),
+// so nodes are plain references and copying one copies no strings.
+#[derive(Debug,Clone,Copy)]
+pub struct SimpleCommand<'a>{
+    pub program:&'a str,
+    pub args:&'a [&'a str],
+    pub redirects:&'a [Redirection<&'a str>],
+    pub env_overrides:&'a [(&'a str,&'a str)],
+}
+
+impl SimpleCommand<'_>{
+    // An owned copy, unexpanded
+    pub fn to_command(&self)->Command{
+        Command{
+            program:self.program.to_string(),
+            args:self.args.iter().map(|arg|arg.to_string()).collect(),
+            redirects:self.redirects.iter().map(|redirection|redirection.owned()).collect(),
+            env_overrides:self.env_overrides.iter().map(|(name,value)|(name.to_string(),value.to_string())).collect(),
+        }
+    }
+}
+
+impl Redirection<&str>{
+    pub fn owned(&self)->Redirection{
+        let target=match self.target{
+            RedirectTarget::Read(path)=>RedirectTarget::Read(path.to_string()),
+            RedirectTarget::Write(path)=>RedirectTarget::Write(path.to_string()),
+            RedirectTarget::Append(path)=>RedirectTarget::Append(path.to_string()),
+            RedirectTarget::Dup(fd)=>RedirectTarget::Dup(fd),
+            RedirectTarget::DupWord(word)=>RedirectTarget::DupWord(word.to_string()),
+            RedirectTarget::Close=>RedirectTarget::Close,
+            RedirectTarget::HereDoc(HereDoc{body,expand})=>RedirectTarget::HereDoc(HereDoc{body:body.to_string(),expand}),
+        };
+        Redirection::new(self.fd,target)
+    }
+}
+
+// One command of a pipeline. `|&` after it sends its stderr down the pipe too.
+#[derive(Debug,Clone,Copy)]
+pub struct PipeStage<'a>{
+    pub command:CommandType<'a>,
+    pub pipe_stderr:bool,
+}
+
+impl<'a> PipeStage<'a>{
+    pub fn new(command:CommandType<'a>)->Self{
+        Self{command,pipe_stderr:false}
+    }
+}
+
+#[derive(Debug,Clone,Copy)]
+pub enum CommandType<'a>{
+    Simple(SimpleCommand<'a>),
+    Pipeline(&'a [PipeStage<'a>]),
+    And(&'a CommandType<'a>,&'a CommandType<'a>),
+    Or(&'a CommandType<'a>,&'a CommandType<'a>),
+    Sequence(&'a [CommandType<'a>]),
+    Subshell(&'a CommandType<'a>),
+    // `! pipeline`: succeeds when the pipeline fails and vice versa
+    Not(&'a CommandType<'a>),
+    // `list &`: the whole and-or list runs as one background job
+    Background(&'a CommandType<'a>),
+    // `[[ expression ]]`
+    Conditional(&'a CondExpr<'a>),
+    // `(( expression ))`, as typed between the parentheses
+    Arithmetic(&'a str),
+    // `coproc [NAME] command`: a background job with pipes to and from the shell
+    Coproc(&'a str,&'a CommandType<'a>),
+    // `if`/`elif` conditions with their bodies, tried in order
+    If{branches:&'a [(CommandType<'a>,CommandType<'a>)],else_body:Option<&'a CommandType<'a>>},
+    // `while`, or with `until` set `until`: runs the body as long as the
+    // condition succeeds (fails)
+    While{condition:&'a CommandType<'a>,body:&'a CommandType<'a>,until:bool},
+    // `for name in words`; without `in`, over the positional parameters.
+    // The words expand (and glob) when the loop starts.
+    For{name:&'a str,words:Option<&'a [&'a str]>,body:&'a CommandType<'a>},
+    // `select name in words`: a numbered menu of the words, with the body
+    // run for each choice read
+    Select{name:&'a str,words:Option<&'a [&'a str]>,body:&'a CommandType<'a>},
+    // `{ list; }`: a list run in the shell itself, unlike `( list )`
+    Group(&'a CommandType<'a>),
+    // `name() body` defines a function; running the definition stores it
+    Function{name:&'a str,body:&'a CommandType<'a>},
+}
+
+impl CommandType<'_>{
+    // A deep copy into `arena`, for a tree that has to outlive its line
+    pub fn copy_to<'b>(&self,arena:&'b Bump)->CommandType<'b>{
+        let str=|s:&str|->&'b str{arena.alloc_str(s)};
+        let node=|command:&CommandType<'_>|->&'b CommandType<'b>{arena.alloc(command.copy_to(arena))};
+        match self{
+            CommandType::Simple(command)=>CommandType::Simple(SimpleCommand{
+                program:str(command.program),
+                args:arena.alloc_slice_fill_iter(command.args.iter().map(|arg|str(arg))),
+                redirects:arena.alloc_slice_fill_iter(command.redirects.iter().map(|redirection|{
+                    let target=match redirection.target{
+                        RedirectTarget::Read(path)=>RedirectTarget::Read(str(path)),
+                        RedirectTarget::Write(path)=>RedirectTarget::Write(str(path)),
+                        RedirectTarget::Append(path)=>RedirectTarget::Append(str(path)),
+                        RedirectTarget::Dup(fd)=>RedirectTarget::Dup(fd),
+                        RedirectTarget::DupWord(word)=>RedirectTarget::DupWord(str(word)),
+                        RedirectTarget::Close=>RedirectTarget::Close,
+                        RedirectTarget::HereDoc(HereDoc{body,expand})=>RedirectTarget::HereDoc(HereDoc{body:str(body),expand}),
+                    };
+                    Redirection::new(redirection.fd,target)
+                })),
+                env_overrides:arena.alloc_slice_fill_iter(command.env_overrides.iter().map(|(name,value)|(str(name),str(value)))),
+            }),
+            CommandType::Pipeline(stages)=>CommandType::Pipeline(arena.alloc_slice_fill_iter(stages.iter().map(|stage|{
+                PipeStage{command:stage.command.copy_to(arena),pipe_stderr:stage.pipe_stderr}
+            }))),
+            CommandType::And(left,right)=>CommandType::And(node(left),node(right)),
+            CommandType::Or(left,right)=>CommandType::Or(node(left),node(right)),
+            CommandType::Sequence(commands)=>CommandType::Sequence(arena.alloc_slice_fill_iter(commands.iter().map(|command|command.copy_to(arena)))),
+            CommandType::Subshell(inner)=>CommandType::Subshell(node(inner)),
+            CommandType::Not(inner)=>CommandType::Not(node(inner)),
+            CommandType::Background(inner)=>CommandType::Background(node(inner)),
+            CommandType::Conditional(expr)=>CommandType::Conditional(arena.alloc(expr.copy_to(arena))),
+            CommandType::Arithmetic(expr)=>CommandType::Arithmetic(str(expr)),
+            CommandType::Coproc(name,inner)=>CommandType::Coproc(str(name),node(inner)),
+            CommandType::If{branches,else_body}=>CommandType::If{
+                branches:arena.alloc_slice_fill_iter(branches.iter().map(|(condition,body)|(condition.copy_to(arena),body.copy_to(arena)))),
+                else_body:else_body.map(&node),
+            },
+            CommandType::While{condition,body,until}=>CommandType::While{condition:node(condition),body:node(body),until:*until},
+            CommandType::For{name,words,body}=>CommandType::For{
+                name:str(name),
+                words:words.map(|words|&*arena.alloc_slice_fill_iter(words.iter().map(|word|str(word)))),
+                body:node(body),
+            },
+            CommandType::Select{name,words,body}=>CommandType::Select{
+                name:str(name),
+                words:words.map(|words|&*arena.alloc_slice_fill_iter(words.iter().map(|word|str(word)))),
+                body:node(body),
+            },
+            CommandType::Group(inner)=>CommandType::Group(node(inner)),
+            CommandType::Function{name,body}=>CommandType::Function{name:str(name),body:node(body)},
+        }
+    }
+}
+
+self_cell!(
+    // A function's body, copied out of the line that defined it into an
+    // arena of its own
+    pub struct FunctionBody{
+        owner:Bump,
+        #[covariant]
+        dependent:CommandType,
+    }
+    impl{Debug}
+);
+
+impl FunctionBody{
+    pub fn copy_of(body:&CommandType<'_>)->Self{
+        Self::new(Bump::new(),|arena|body.copy_to(arena))
+    }
+}
+
+impl std::fmt::Display for FunctionBody{
+    fn fmt(&self,f:&mut std::fmt::Formatter<'_>)->std::fmt::Result{
+        self.borrow_dependent().fmt(f)
+    }
+}
+
+// The expression inside `[[ ]]`. Operands are kept as typed and expanded
+// when it runs, without field splitting or globbing.
+#[derive(Debug,Clone,Copy)]
+pub enum CondExpr<'a>{
+    // `-f file`, `-z word`
+    Unary(&'a str,&'a str),
+    // `a == pattern`, `a =~ regex`, `a < b`, `1 -lt 2`
+    Binary(&'a str,&'a str,&'a str),
+    // A lone word, true when it expands to something non-empty
+    Word(&'a str),
+    Not(&'a CondExpr<'a>),
+    And(&'a CondExpr<'a>,&'a CondExpr<'a>),
+    Or(&'a CondExpr<'a>,&'a CondExpr<'a>),
+}
+
+impl CondExpr<'_>{
+    fn copy_to<'b>(&self,arena:&'b Bump)->CondExpr<'b>{
+        let str=|s:&str|->&'b str{arena.alloc_str(s)};
+        let node=|expr:&CondExpr<'_>|->&'b CondExpr<'b>{arena.alloc(expr.copy_to(arena))};
+        match self{
+            CondExpr::Unary(op,word)=>CondExpr::Unary(str(op),str(word)),
+            CondExpr::Binary(left,op,right)=>CondExpr::Binary(str(left),str(op),str(right)),
+            CondExpr::Word(word)=>CondExpr::Word(str(word)),
+            CondExpr::Not(inner)=>CondExpr::Not(node(inner)),
+            CondExpr::And(left,right)=>CondExpr::And(node(left),node(right)),
+            CondExpr::Or(left,right)=>CondExpr::Or(node(left),node(right)),
+        }
+    }
+}
+
+impl std::fmt::Display for CondExpr<'_>{
+    fn fmt(&self,f:&mut std::fmt::Formatter<'_>)->std::fmt::Result{
+        match self{
+            CondExpr::Unary(op,word)=>write!(f,"{} {}",op,word),
+            CondExpr::Binary(left,op,right)=>write!(f,"{} {} {}",left,op,right),
+            CondExpr::Word(word)=>f.write_str(word),
+            CondExpr::Not(inner)=>write!(f,"! {}",inner),
+            CondExpr::And(left,right)=>{
+                // `&&` binds tighter, so an `||` under it needs its parentheses back
+                let group=|expr:&CondExpr|match expr{
+                    CondExpr::Or(..)=>format!("( {} )",expr),
+                    _=>expr.to_string(),
+                };
+                write!(f,"{} && {}",group(left),group(right))
+            }
+            CondExpr::Or(left,right)=>write!(f,"{} || {}",left,right),
+        }
+    }
+}
+
+// The command roughly as typed (without redirections), for job listings
+impl std::fmt::Display for CommandType<'_>{
+    fn fmt(&self,f:&mut std::fmt::Formatter<'_>)->std::fmt::Result{
+        match self{
+            CommandType::Simple(command)=>{
+                for (name,value) in command.env_overrides{
+                    write!(f,"{}={} ",name,value)?;
+                }
+                write!(f,"{}",command.program)?;
+                for arg in command.args{
+                    write!(f," {}",arg)?;
+                }
+                Ok(())
+            }
+            CommandType::Pipeline(stages)=>{
+                for (i,stage) in stages.iter().enumerate(){
+                    if i>0{
+                        f.write_str(if stages[i-1].pipe_stderr{" |& "}else{" | "})?;
+                    }
+                    write!(f,"{}",stage.command)?;
+                }
+                Ok(())
+            }
+            CommandType::And(left,right)=>write!(f,"{} && {}",left,right),
+            CommandType::Or(left,right)=>write!(f,"{} || {}",left,right),
+            CommandType::Sequence(commands)=>{
+                for (i,command) in commands.iter().enumerate(){
+                    if i>0{
+                        // `&` already separates a background command
+                        let background=matches!(commands[i-1],CommandType::Background(_));
+                        f.write_str(if background{" "}else{"; "})?;
+                    }
+                    write!(f,"{}",command)?;
+                }
+                Ok(())
+            }
+            CommandType::Subshell(inner)=>write!(f,"({})",inner),
+            CommandType::Not(inner)=>write!(f,"! {}",inner),
+            CommandType::Background(inner)=>write!(f,"{} &",inner),
+            CommandType::Conditional(expr)=>write!(f,"[[ {} ]]",expr),
+            CommandType::Arithmetic(expr)=>write!(f,"(({}))",expr),
+            CommandType::Coproc(name,inner) if *name=="COPROC"=>write!(f,"coproc {}",inner),
+            CommandType::Coproc(name,inner)=>write!(f,"coproc {} {}",name,inner),
+            CommandType::If{branches,else_body}=>{
+                for (i,(condition,body)) in branches.iter().enumerate(){
+                    write!(f,"{} {}; then {}; ",if i==0{"if"}else{"elif"},condition,body)?;
+                }
+                if let Some(body)=else_body{
+                    write!(f,"else {}; ",body)?;
+                }
+                f.write_str("fi")
+            }
+            CommandType::Group(inner)=>write!(f,"{{ {}; }}",inner),
+            CommandType::Function{name,body}=>write!(f,"{}() {}",name,body),
+            CommandType::For{name,words,body}|CommandType::Select{name,words,body}=>{
+                write!(f,"{} {}",if matches!(self,CommandType::Select{..}){"select"}else{"for"},name)?;
+                if let Some(words)=words{
+                    write!(f," in")?;
+                    for word in *words{
+                        write!(f," {}",word)?;
+                    }
+                }
+                write!(f,"; do {}; done",body)
+            }
+            CommandType::While{condition,body,until}=>{
+                write!(f,"{} {}; do {}; done",if *until{"until"}else{"while"},condition,body)
+            }
+        }
+    }
 }
\ No newline at end of file
@@ -1,5 +1,7 @@
+#[allow(clippy::module_inception)]
 pub mod command;
-pub use command::{Command, CommandType, RedirectType};
+pub mod ast;
+pub use command::{Command, CommandType, CondExpr, FunctionBody, HereDoc, PipeStage, RedirectTarget, RedirectType, Redirection, SimpleCommand};
 
 // Parsing error type for command parsing
 #[derive(Debug,Clone)]
@@ -0,0 +1,99 @@
+// src/command_not_found.rs
+//
+// What the shell says when a command cannot run. A name that is nowhere on
+// PATH gets `rshell: foo: command not found`, and below it the builtins,
+// functions, aliases and PATH commands (from `CommandCache`) a typo or two
+// away, closest first: `gti` suggests `git`, `sl` `ls`. That is worked out
+// in the parent before fork; a forked child whose exec fails only writes
+// the reason and exits.
+use nix::errno::Errno;
+use crate::shell::Shell;
+
+// At most this many suggestions
+const MAX_SUGGESTIONS: usize = 3;
+
+// Says `program` was not found, with what might have been meant. The caller
+// sets the status to 127.
+pub fn report(shell: &Shell, program: &str) {
+    eprintln!("rshell: {}: command not found", program);
+    let mut names: Vec<String> = shell.command_cache.get_completions("");
+    names.extend(shell.functions.keys().cloned());
+    names.extend(shell.aliases.keys().map(|name| name.to_string()));
+    let suggestions = suggestions(program, names);
+    if !suggestions.is_empty() {
+        eprintln!("rshell: did you mean: {}", suggestions.join(", "));
+    }
+}
+
+// In a forked child whose exec failed: writes why with plain write(2)s, as
+// nothing may allocate there, and exits 127 if there was no such file or
+// else 126
+pub fn exec_failed(program: &str, error: Errno) -> ! {
+    // A name searched for along a PATH given to just this command
+    let reason = if error == Errno::ENOENT && !program.contains('/') { "command not found" } else { error.desc() };
+    for part in [b"rshell: ".as_slice(), program.as_bytes(), b": ", reason.as_bytes(), b"\n"] {
+        unsafe {
+            libc::write(libc::STDERR_FILENO, part.as_ptr().cast(), part.len());
+        }
+    }
+    unsafe { libc::_exit(if error == Errno::ENOENT { 127 } else { 126 }) }
+}
+
+// The names close enough to `typed` to be what was meant
+pub fn suggestions(typed: &str, names: Vec<String>) -> Vec<String> {
+    // One edit for short names, then one more for every four characters
+    let limit = 1 + typed.chars().count() / 4;
+    let mut close: Vec<(usize, String)> = names.into_iter()
+        .filter(|name| name != typed && name.chars().count().abs_diff(typed.chars().count()) <= limit)
+        .filter_map(|name| {
+            let distance = distance(typed, &name);
+            (distance <= limit).then_some((distance, name))
+        })
+        .collect();
+    close.sort();
+    close.dedup();
+    close.into_iter().take(MAX_SUGGESTIONS).map(|(_, name)| name).collect()
+}
+
+// Edits from `a` to `b`: inserting, deleting or replacing a character, or
+// swapping two next to each other (optimal string alignment distance)
+fn distance(a: &str, b: &str) -> usize {
+    let a: Vec<char> = a.chars().collect();
+    let b: Vec<char> = b.chars().collect();
+    // Three rows of the table: two back, the previous and the current
+    let mut before: Vec<usize> = vec![0; b.len() + 1];
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    let mut current = vec![0; b.len() + 1];
+    for i in 1..=a.len() {
+        current[0] = i;
+        for j in 1..=b.len() {
+            let cost = usize::from(a[i - 1] != b[j - 1]);
+            current[j] = (previous[j] + 1).min(current[j - 1] + 1).min(previous[j - 1] + cost);
+            if i > 1 && j > 1 && a[i - 1] == b[j - 2] && a[i - 2] == b[j - 1] {
+                current[j] = current[j].min(before[j - 2] + 1);
+            }
+        }
+        std::mem::swap(&mut before, &mut previous);
+        std::mem::swap(&mut previous, &mut current);
+    }
+    previous[b.len()]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn close_names() {
+        assert_eq!(distance("gti", "git"), 1);
+        assert_eq!(distance("kitten", "sitting"), 3);
+        assert_eq!(distance("", "ls"), 2);
+
+        let names = ["git", "grep", "ls", "sl", "make", "cargo", "gitk"].map(String::from).to_vec();
+        assert_eq!(suggestions("gti", names.clone()), ["git"]);
+        assert_eq!(suggestions("gitt", names.clone()), ["git", "gitk"]);
+        assert_eq!(suggestions("crago", names.clone()), ["cargo"]);
+        assert_eq!(suggestions("ls", names.clone()), ["sl"]);
+        assert!(suggestions("xyzzy", names).is_empty());
+    }
+}
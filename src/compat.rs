@@ -0,0 +1,247 @@
+// src/compat.rs
+//
+// POSIX sh compatibility corpus. Each case is a short snippet with the
+// output and status /bin/sh gives it; `rshell --sh-compat` runs them all
+// and reports per feature how much of the language rshell handles.
+use crate::config::Config;
+use crate::scripting::ScriptEngine;
+use crate::shell::Shell;
+use nix::sys::signal::{kill, Signal};
+use nix::sys::wait::{waitpid, WaitPidFlag, WaitStatus};
+use nix::unistd::{close, dup2, fork, pipe, ForkResult};
+use std::fs::File;
+use std::io::{Read, Write};
+use std::os::unix::io::FromRawFd;
+use std::thread;
+use std::time::{Duration, Instant};
+
+#[derive(Debug, Clone, Copy)]
+pub struct CompatCase {
+    pub feature: &'static str,
+    pub name: &'static str,
+    pub script: &'static str,
+    pub stdout: &'static str,
+    pub status: i32,
+}
+
+const fn case(feature: &'static str, name: &'static str, script: &'static str, stdout: &'static str, status: i32) -> CompatCase {
+    CompatCase { feature, name, script, stdout, status }
+}
+
+pub const CORPUS: &[CompatCase] = &[
+    case("simple commands", "echo", "echo hello world", "hello world\n", 0),
+    case("simple commands", "exit status", "false", "", 1),
+    case("simple commands", "external command", "printf '%s-%s\\n' a b", "a-b\n", 0),
+    case("quoting", "single quotes", "echo 'a  b'", "a  b\n", 0),
+    case("quoting", "double quotes", "echo \"a  b\"", "a  b\n", 0),
+    case("quoting", "backslash", "echo a\\ b", "a b\n", 0),
+    case("quoting", "empty argument", "printf '[%s]\\n' ''", "[]\n", 0),
+    case("pipelines", "two stages", "echo abc | tr a-z A-Z", "ABC\n", 0),
+    case("pipelines", "three stages", "printf 'b\\na\\n' | sort | head -n 1", "a\n", 0),
+    case("pipelines", "negation", "! false", "", 0),
+    case("and-or lists", "and", "true && echo yes", "yes\n", 0),
+    case("and-or lists", "or", "false || echo no", "no\n", 0),
+    case("and-or lists", "chain", "false && echo a || echo b", "b\n", 0),
+    case("sequential lists", "semicolon", "echo a; echo b", "a\nb\n", 0),
+    case("sequential lists", "newlines", "echo a\necho b", "a\nb\n", 0),
+    case("redirection", "output and input", "echo x > /tmp/.rshell_compat\ncat < /tmp/.rshell_compat", "x\n", 0),
+    case("redirection", "stderr to stdout", "sh -c 'echo err >&2' 2>&1", "err\n", 0),
+    case("variables", "assignment", "A=1\necho $A", "1\n", 0),
+    case("variables", "braces", "A=x\necho ${A}y", "xy\n", 0),
+    case("variables", "env prefix", "A=5 sh -c 'echo $A'", "5\n", 0),
+    case("special parameters", "last status", "false\necho $?", "1\n", 0),
+    case("parameter expansion", "default", "echo ${UNSET_VAR:-fallback}", "fallback\n", 0),
+    case("parameter expansion", "length", "A=hello\necho ${#A}", "5\n", 0),
+    case("command substitution", "dollar paren", "echo $(echo inner)", "inner\n", 0),
+    case("command substitution", "backticks", "echo `echo inner`", "inner\n", 0),
+    case("arithmetic", "expansion", "echo $((2 + 3 * 4))", "14\n", 0),
+    case("subshells", "parentheses", "(echo sub)", "sub\n", 0),
+    case("subshells", "isolated cd", "(cd /)\npwd | grep -c '^/$' || true", "0\n", 0),
+    case("here-documents", "basic", "cat <<EOF\nline\nEOF", "line\n", 0),
+    case("compound commands", "if", "if true; then echo yes; else echo no; fi", "yes\n", 0),
+    case("compound commands", "for", "for i in 1 2 3; do echo $i; done", "1\n2\n3\n", 0),
+    case("compound commands", "while", "i=0; while [ $i -lt 2 ]; do echo $i; i=$((i+1)); done", "0\n1\n", 0),
+    case("compound commands", "case", "case ab in a*) echo match;; *) echo no;; esac", "match\n", 0),
+    case("functions", "define and call", "f() { echo called $1; }\nf arg", "called arg\n", 0),
+    case("comments", "trailing comment", "echo a # not printed", "a\n", 0),
+    case("shell options", "errexit", "set -e\nfalse || echo handled\nfalse\necho no", "handled\n", 1),
+    case("shell options", "line number", "echo $LINENO\n\necho $LINENO", "1\n3\n", 0),
+];
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SnippetOutput {
+    pub stdout: String,
+    pub status: i32,
+}
+
+// Anything that can run a snippet and capture what it printed
+pub trait SnippetRunner {
+    fn run(&mut self, script: &str) -> SnippetOutput;
+}
+
+// Runs each snippet in a forked child with a fresh default-config Shell,
+// so state never leaks between cases. Not for use inside the test harness,
+// whose output capture would swallow the child's stdout.
+pub struct ForkRunner {
+    pub timeout: Duration,
+}
+
+impl Default for ForkRunner {
+    fn default() -> Self {
+        Self { timeout: Duration::from_secs(5) }
+    }
+}
+
+impl SnippetRunner for ForkRunner {
+    fn run(&mut self, script: &str) -> SnippetOutput {
+        let Ok((read_fd, write_fd)) = pipe() else {
+            return SnippetOutput { stdout: String::new(), status: -1 };
+        };
+        match unsafe { fork() } {
+            Ok(ForkResult::Child) => {
+                let _ = close(read_fd);
+                let _ = dup2(write_fd, 1);
+                if let Ok(null) = File::open("/dev/null") {
+                    use std::os::unix::io::AsRawFd;
+                    let _ = dup2(null.as_raw_fd(), 0);
+                    let _ = dup2(null.as_raw_fd(), 2);
+                }
+                let mut shell = Shell::with_config(Config::default());
+                let status = ScriptEngine::new().execute_str(&mut shell, script).unwrap_or(2);
+                let _ = std::io::stdout().flush();
+                std::process::exit(status);
+            }
+            Ok(ForkResult::Parent { child }) => {
+                let _ = close(write_fd);
+                let reader = thread::spawn(move || {
+                    let mut stdout = String::new();
+                    let _ = unsafe { File::from_raw_fd(read_fd) }.read_to_string(&mut stdout);
+                    stdout
+                });
+                let started = Instant::now();
+                let status = loop {
+                    match waitpid(child, Some(WaitPidFlag::WNOHANG)) {
+                        Ok(WaitStatus::Exited(_, code)) => break code,
+                        Ok(WaitStatus::Signaled(_, sig, _)) => break 128 + sig as i32,
+                        Ok(_) if started.elapsed() < self.timeout => thread::sleep(Duration::from_millis(2)),
+                        Ok(_) => {
+                            let _ = kill(child, Signal::SIGKILL);
+                        }
+                        Err(_) => break -1,
+                    }
+                };
+                SnippetOutput { stdout: reader.join().unwrap_or_default(), status }
+            }
+            Err(_) => {
+                let _ = close(read_fd);
+                let _ = close(write_fd);
+                SnippetOutput { stdout: String::new(), status: -1 }
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct CaseResult {
+    pub case: CompatCase,
+    pub output: SnippetOutput,
+}
+
+impl CaseResult {
+    pub fn passed(&self) -> bool {
+        self.output.stdout == self.case.stdout && self.output.status == self.case.status
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct CompatReport {
+    pub results: Vec<CaseResult>,
+}
+
+impl CompatReport {
+    pub fn passed(&self) -> usize {
+        self.results.iter().filter(|r| r.passed()).count()
+    }
+
+    // (feature, passed, total) in corpus order
+    pub fn by_feature(&self) -> Vec<(&'static str, usize, usize)> {
+        let mut features: Vec<(&'static str, usize, usize)> = Vec::new();
+        for result in &self.results {
+            let passed = result.passed() as usize;
+            match features.iter_mut().find(|(feature, _, _)| *feature == result.case.feature) {
+                Some(entry) => {
+                    entry.1 += passed;
+                    entry.2 += 1;
+                }
+                None => features.push((result.case.feature, passed, 1)),
+            }
+        }
+        features
+    }
+
+    pub fn render(&self, verbose: bool) -> String {
+        let mut out = String::new();
+        for (feature, passed, total) in self.by_feature() {
+            let mark = if passed == total { "ok  " } else { "FAIL" };
+            out.push_str(&format!("{} {:<22} {}/{}\n", mark, feature, passed, total));
+            if !verbose {
+                continue;
+            }
+            for result in self.results.iter().filter(|r| r.case.feature == feature && !r.passed()) {
+                out.push_str(&format!("       {}: expected {:?} (status {}), got {:?} (status {})\n",
+                                      result.case.name, result.case.stdout, result.case.status,
+                                      result.output.stdout, result.output.status));
+            }
+        }
+        out.push_str(&format!("{}/{} cases pass\n", self.passed(), self.results.len()));
+        out
+    }
+}
+
+pub fn run_corpus(cases: &[CompatCase], runner: &mut impl SnippetRunner) -> CompatReport {
+    let results = cases.iter()
+        .map(|case| CaseResult { case: *case, output: runner.run(case.script) })
+        .collect();
+    CompatReport { results }
+}
+
+// `rshell --sh-compat [--verbose] [feature...]`; exits non-zero on any failure
+pub fn run_cli(args: &[String]) -> i32 {
+    let verbose = args.iter().any(|a| a == "-v" || a == "--verbose");
+    let features: Vec<&String> = args.iter().filter(|a| !a.starts_with('-')).collect();
+    let cases: Vec<CompatCase> = CORPUS.iter()
+        .filter(|c| features.is_empty() || features.iter().any(|f| c.feature.contains(f.as_str())))
+        .copied()
+        .collect();
+    let report = run_corpus(&cases, &mut ForkRunner::default());
+    print!("{}", report.render(verbose));
+    i32::from(report.passed() != report.results.len())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Echoes the script back, as a shell that only knows `echo` would
+    struct EchoRunner;
+
+    impl SnippetRunner for EchoRunner {
+        fn run(&mut self, script: &str) -> SnippetOutput {
+            match script.strip_prefix("echo ") {
+                Some(text) if !text.contains(['$', '\n', ';', '|', '\'', '"', '\\', '#']) => {
+                    SnippetOutput { stdout: format!("{}\n", text), status: 0 }
+                }
+                _ => SnippetOutput { stdout: String::new(), status: 127 },
+            }
+        }
+    }
+
+    #[test]
+    fn report_groups_by_feature() {
+        let report = run_corpus(&CORPUS[..4], &mut EchoRunner);
+        assert_eq!(report.by_feature(), [("simple commands", 1, 3), ("quoting", 0, 1)]);
+        let text = report.render(true);
+        assert!(text.starts_with("FAIL simple commands        1/3\n"));
+        assert!(text.ends_with("1/4 cases pass\n"));
+    }
+}
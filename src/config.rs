@@ -13,6 +13,240 @@ pub struct Config {
     pub keybindings: HashMap<String, String>,
     pub env_vars: HashMap<String, String>,
     pub plugins: Vec<String>,
+    #[serde(default)]
+    pub tasks: HashMap<String, TaskConfig>,
+    #[serde(default)]
+    pub metrics: MetricsConfig,
+    #[serde(default)]
+    pub memory: MemoryConfig,
+    #[serde(default)]
+    pub audit: AuditConfig,
+    #[serde(default)]
+    pub safety: SafetyConfig,
+    #[serde(default)]
+    pub options: ShellOptions,
+    // `bookmark` names for directories, reached with `cd @name`
+    #[serde(default)]
+    pub bookmarks: HashMap<String, String>,
+}
+
+// Switches for how words are expanded into file names, and for how scripts
+// run; changed at runtime with `set -o NAME` / `set +o NAME`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ShellOptions {
+    // `**` matches any number of directories
+    #[serde(default = "default_true")]
+    pub globstar: bool,
+    // ?(..) *(..) +(..) @(..) !(..) pattern groups
+    #[serde(default = "default_true")]
+    pub extglob: bool,
+    // A pattern matching nothing expands to no words instead of itself
+    #[serde(default)]
+    pub nullglob: bool,
+    // A pattern matching nothing is an error and the command does not run
+    #[serde(default)]
+    pub failglob: bool,
+    // Patterns match hidden files without a leading `.`
+    #[serde(default)]
+    pub dotglob: bool,
+    // `!!`, `!n`, `!prefix`, `!$` and `!*` on interactive lines recall history
+    #[serde(default = "default_true")]
+    pub histexpand: bool,
+    // The rest are only ever set by `set`, never read from or saved to the
+    // config file. `-e`: a failing command ends the shell.
+    #[serde(skip)]
+    pub errexit: bool,
+    // `-u`: expanding an unset variable is an error
+    #[serde(skip)]
+    pub nounset: bool,
+    // `-x`: commands are printed to stderr, expanded, before they run
+    #[serde(skip)]
+    pub xtrace: bool,
+    // A pipeline fails if any command in it does, not just the last
+    #[serde(skip)]
+    pub pipefail: bool,
+}
+
+impl Default for ShellOptions {
+    fn default() -> Self {
+        Self {
+            globstar: true,
+            extglob: true,
+            nullglob: false,
+            failglob: false,
+            dotglob: false,
+            histexpand: true,
+            errexit: false,
+            nounset: false,
+            xtrace: false,
+            pipefail: false,
+        }
+    }
+}
+
+impl ShellOptions {
+    pub fn list(&self) -> [(&'static str, bool); 10] {
+        [
+            ("dotglob", self.dotglob),
+            ("errexit", self.errexit),
+            ("extglob", self.extglob),
+            ("failglob", self.failglob),
+            ("globstar", self.globstar),
+            ("histexpand", self.histexpand),
+            ("nounset", self.nounset),
+            ("nullglob", self.nullglob),
+            ("pipefail", self.pipefail),
+            ("xtrace", self.xtrace),
+        ]
+    }
+
+    pub fn get_mut(&mut self, name: &str) -> Option<&mut bool> {
+        match name {
+            "dotglob" => Some(&mut self.dotglob),
+            "errexit" => Some(&mut self.errexit),
+            "extglob" => Some(&mut self.extglob),
+            "failglob" => Some(&mut self.failglob),
+            "globstar" => Some(&mut self.globstar),
+            "histexpand" => Some(&mut self.histexpand),
+            "nounset" => Some(&mut self.nounset),
+            "nullglob" => Some(&mut self.nullglob),
+            "pipefail" => Some(&mut self.pipefail),
+            "xtrace" => Some(&mut self.xtrace),
+            _ => None,
+        }
+    }
+}
+
+// Commands matching a pattern are announced before they run; `confirm`
+// asks y/N and `critical` wants "yes" typed out.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SafetyConfig {
+    #[serde(default = "default_true")]
+    pub confirm_dangerous: bool,
+    #[serde(default = "default_danger_patterns")]
+    pub patterns: Vec<DangerPattern>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DangerPattern {
+    pub pattern: String,
+    #[serde(default)]
+    pub severity: Severity,
+    #[serde(default)]
+    pub reason: String,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    Warn,
+    #[default]
+    Confirm,
+    Critical,
+}
+
+fn default_danger_patterns() -> Vec<DangerPattern> {
+    let pattern = |pattern: &str, severity, reason: &str| DangerPattern {
+        pattern: pattern.to_string(),
+        severity,
+        reason: reason.to_string(),
+    };
+    vec![
+        pattern("rm -rf /", Severity::Critical, "deletes the whole filesystem"),
+        pattern("mkfs*", Severity::Critical, "formats a filesystem"),
+        pattern("dd of=/dev/sd*", Severity::Critical, "overwrites a disk"),
+        pattern("dd of=/dev/nvme*", Severity::Critical, "overwrites a disk"),
+        pattern("chmod -R 777 /", Severity::Critical, "makes every file world-writable"),
+    ]
+}
+
+impl Default for SafetyConfig {
+    fn default() -> Self {
+        Self {
+            confirm_dangerous: true,
+            patterns: default_danger_patterns(),
+        }
+    }
+}
+
+// Append-only JSONL log of executed commands; disabled while `path` is empty.
+// The file is rotated to `path.1` .. `path.<keep>` once it reaches `max_size_kb`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AuditConfig {
+    #[serde(default)]
+    pub path: String,
+    #[serde(default = "default_audit_max_size_kb")]
+    pub max_size_kb: u64,
+    #[serde(default = "default_audit_keep")]
+    pub keep: usize,
+}
+
+fn default_audit_max_size_kb() -> u64 {
+    10240
+}
+
+fn default_audit_keep() -> usize {
+    5
+}
+
+impl Default for AuditConfig {
+    fn default() -> Self {
+        Self {
+            path: String::new(),
+            max_size_kb: default_audit_max_size_kb(),
+            keep: default_audit_keep(),
+        }
+    }
+}
+
+// Soft limits in kilobytes; 0 leaves a consumer unbounded. Exceeding one
+// compacts history or evicts cache entries after the current command.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct MemoryConfig {
+    #[serde(default)]
+    pub history_soft_limit_kb: usize,
+    #[serde(default)]
+    pub cache_soft_limit_kb: usize,
+}
+
+// Periodic export of shell metrics; disabled while `path` is empty
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MetricsConfig {
+    #[serde(default)]
+    pub path: String,
+    #[serde(default)]
+    pub format: MetricsFormat,
+    #[serde(default = "default_metrics_interval_secs")]
+    pub interval_secs: u64,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum MetricsFormat {
+    #[default]
+    Prometheus,
+    Json,
+}
+
+fn default_metrics_interval_secs() -> u64 {
+    60
+}
+
+impl Default for MetricsConfig {
+    fn default() -> Self {
+        Self {
+            path: String::new(),
+            format: MetricsFormat::Prometheus,
+            interval_secs: default_metrics_interval_secs(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TaskConfig {
+    pub command: String,
+    #[serde(default)]
+    pub deps: Vec<String>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -26,6 +260,48 @@ pub struct GeneralConfig {
     pub auto_cd: bool,
     pub bell_style: BellStyle,
     pub edit_mode: EditMode,
+    #[serde(default = "default_duration_threshold_ms")]
+    pub duration_threshold_ms: u64,
+    #[serde(default = "default_true")]
+    pub slow_command_hints: bool,
+    #[serde(default = "default_slow_command_threshold_ms")]
+    pub slow_command_threshold_ms: u64,
+    // Columns `{cwd}` may take in the prompt before its start is elided; 0 = no limit
+    #[serde(default)]
+    pub prompt_cwd_max_width: usize,
+    // External program whose output replaces prompt_format (e.g. starship)
+    #[serde(default)]
+    pub prompt_command: String,
+    #[serde(default = "default_prompt_command_timeout_ms")]
+    pub prompt_command_timeout_ms: u64,
+    // Load trusted .envrc/.env files when entering their directory tree
+    #[serde(default = "default_true")]
+    pub load_envrc: bool,
+    // Login shells take over the environment `sh -l` builds from /etc/profile
+    #[serde(default = "default_true")]
+    pub import_sh_profile: bool,
+    // Ctrl-R matches the query's characters in order rather than as a substring
+    #[serde(default)]
+    pub history_search_fuzzy: bool,
+    // How Tab offers completions: a list, or cycling through fuzzy matches
+    #[serde(default)]
+    pub completion_style: CompletionStyle,
+}
+
+fn default_duration_threshold_ms() -> u64 {
+    2000
+}
+
+fn default_prompt_command_timeout_ms() -> u64 {
+    500
+}
+
+fn default_true() -> bool {
+    true
+}
+
+fn default_slow_command_threshold_ms() -> u64 {
+    10000
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -41,6 +317,17 @@ pub enum EditMode {
     Vi,
 }
 
+// `List` shows the candidates that start with the word under the ones
+// Tab completed in common; `Menu` matches fuzzily and puts them on the line
+// one at a time: Tab or Down for the next, Shift-Tab or Up for the
+// previous, Esc back to what was typed
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub enum CompletionStyle {
+    #[default]
+    List,
+    Menu,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Theme {
     pub name: String,
@@ -58,6 +345,40 @@ pub struct Theme {
     pub reset_color: String,
     pub prompt_symbol: String,
     pub prompt_symbol_error: String,
+    // Before each further line of a command that is not finished yet
+    #[serde(default = "default_continuation_prompt")]
+    pub continuation_prompt: String,
+    // Terminal integration: OSC 7 reports the cwd so new tabs open there,
+    // OSC 0 sets the window title. Titles take the prompt placeholders plus
+    // {command}; an empty format leaves the title alone.
+    #[serde(default = "default_true")]
+    pub report_cwd: bool,
+    #[serde(default = "default_title_format")]
+    pub title_format: String,
+    #[serde(default = "default_command_title_format")]
+    pub command_title_format: String,
+    // OSC 133 marks around prompt, input and output, for terminals that can
+    // jump between prompts and flag failed commands
+    #[serde(default = "default_true")]
+    pub semantic_prompts: bool,
+    // Inside tmux/screen: wrap OSC 7/133 so they reach the outer terminal,
+    // and name the window after the running command
+    #[serde(default = "default_true")]
+    pub multiplexer_passthrough: bool,
+    #[serde(default = "default_true")]
+    pub multiplexer_window_names: bool,
+}
+
+fn default_continuation_prompt() -> String {
+    "> ".to_string()
+}
+
+fn default_title_format() -> String {
+    "{user}@{host}: {cwd}".to_string()
+}
+
+fn default_command_title_format() -> String {
+    "{user}@{host}: {command}".to_string()
 }
 
 impl Default for Config {
@@ -69,6 +390,13 @@ impl Default for Config {
             keybindings: HashMap::new(),
             env_vars: HashMap::new(),
             plugins: Vec::new(),
+            tasks: HashMap::new(),
+            metrics: MetricsConfig::default(),
+            memory: MemoryConfig::default(),
+            audit: AuditConfig::default(),
+            safety: SafetyConfig::default(),
+            options: ShellOptions::default(),
+            bookmarks: HashMap::new(),
         }
     }
 }
@@ -85,6 +413,16 @@ impl Default for GeneralConfig {
             auto_cd: false,
             bell_style: BellStyle::None,
             edit_mode: EditMode::Emacs,
+            duration_threshold_ms: default_duration_threshold_ms(),
+            slow_command_hints: true,
+            slow_command_threshold_ms: default_slow_command_threshold_ms(),
+            prompt_cwd_max_width: 0,
+            prompt_command: String::new(),
+            prompt_command_timeout_ms: default_prompt_command_timeout_ms(),
+            load_envrc: true,
+            import_sh_profile: true,
+            history_search_fuzzy: false,
+            completion_style: CompletionStyle::List,
         }
     }
 }
@@ -107,6 +445,13 @@ impl Default for Theme {
             reset_color: "\x1b[0m".to_string(),         // Reset
             prompt_symbol: "$".to_string(),
             prompt_symbol_error: "$".to_string(),
+            continuation_prompt: default_continuation_prompt(),
+            report_cwd: true,
+            title_format: default_title_format(),
+            command_title_format: default_command_title_format(),
+            semantic_prompts: true,
+            multiplexer_passthrough: true,
+            multiplexer_window_names: true,
         }
     }
 }
@@ -133,6 +478,7 @@ impl Theme {
             reset_color: "\x1b[0m".to_string(),
             prompt_symbol: "🌊".to_string(),
             prompt_symbol_error: "💀".to_string(),
+            ..Self::default()
         }
     }
     
@@ -153,6 +499,7 @@ impl Theme {
             reset_color: "\x1b[0m".to_string(),
             prompt_symbol: "🌲".to_string(),
             prompt_symbol_error: "🔥".to_string(),
+            ..Self::default()
         }
     }
     
@@ -173,6 +520,7 @@ impl Theme {
             reset_color: "\x1b[0m".to_string(),
             prompt_symbol: "🦇".to_string(),
             prompt_symbol_error: "💉".to_string(),
+            ..Self::default()
         }
     }
 }
@@ -225,6 +573,9 @@ impl Config {
         Ok(home.join(".config").join("rshell").join("config.toml"))
     }
     
+    // Names get_theme_by_name knows, for `theme set` completion
+    pub const THEMES: &'static [&'static str] = &["default", "ocean", "forest", "dracula"];
+
     pub fn get_theme_by_name(&self, name: &str) -> Theme {
         match name {
             "default" => Theme::default(),
@@ -0,0 +1,339 @@
+// src/envrc.rs
+//
+// direnv-style per-directory environments. The nearest `.envrc` (or `.env`)
+// at or above the working directory is loaded once trusted, and its changes
+// are undone when the shell leaves that directory tree. Only assignments
+// (`KEY=value`, `export KEY=value`) and direnv's `PATH_add dir` are
+// understood; the files are never run.
+use crate::shell::Shell;
+use crate::utils::env_utils::expand_variables;
+use crate::outln;
+use sha2::{Digest, Sha256};
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::io::{self, BufRead, Write};
+use std::path::{Path, PathBuf};
+
+pub const FILE_NAMES: &[&str] = &[".envrc", ".env"];
+
+// SHA-256 in hex, so a file cannot be changed to one that still passes
+// for the contents that were trusted
+pub fn content_hash(bytes: &[u8]) -> String {
+    Sha256::digest(bytes).iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+// Nearest env file at or above `dir`
+pub fn find_envrc(dir: &Path) -> Option<PathBuf> {
+    dir.ancestors()
+        .flat_map(|d| FILE_NAMES.iter().map(move |name| d.join(name)))
+        .find(|path| path.is_file())
+}
+
+fn parse_value(raw: &str, env: &HashMap<String, String>) -> String {
+    let raw = raw.trim();
+    if let Some(rest) = raw.strip_prefix('\'') {
+        return rest.split('\'').next().unwrap_or("").to_string();
+    }
+    if let Some(rest) = raw.strip_prefix('"') {
+        let mut value = String::new();
+        let mut chars = rest.chars();
+        while let Some(c) = chars.next() {
+            match c {
+                '"' => break,
+                '\\' => match chars.next() {
+                    Some('n') => value.push('\n'),
+                    Some(other) => value.push(other),
+                    None => break,
+                },
+                _ => value.push(c),
+            }
+        }
+        return expand_variables(&value, env);
+    }
+    let unquoted = raw.split(" #").next().unwrap_or("").trim_end();
+    expand_variables(unquoted, env)
+}
+
+// Returns the assignments in file order, plus the lines that were skipped.
+// `env` supplies $VAR values and receives each assignment as it is read.
+pub fn parse(text: &str, dir: &Path, env: &mut HashMap<String, String>) -> (Vec<(String, String)>, Vec<String>) {
+    let mut assignments = Vec::new();
+    let mut skipped = Vec::new();
+    for line in text.lines().map(str::trim) {
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let (key, value) = if let Some(dir_arg) = line.strip_prefix("PATH_add ") {
+            let added = dir.join(parse_value(dir_arg, env));
+            let path = match env.get("PATH") {
+                Some(path) if !path.is_empty() => format!("{}:{}", added.display(), path),
+                _ => added.display().to_string(),
+            };
+            ("PATH".to_string(), path)
+        } else {
+            let assignment = line.strip_prefix("export ").unwrap_or(line).trim_start();
+            match assignment.split_once('=') {
+                Some((key, raw)) if crate::utils::helpers::is_valid_var_name(key) => {
+                    (key.to_string(), parse_value(raw, env))
+                }
+                _ => {
+                    skipped.push(line.to_string());
+                    continue;
+                }
+            }
+        };
+        env.insert(key.clone(), value.clone());
+        assignments.push((key, value));
+    }
+    (assignments, skipped)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Trust {
+    Allowed,
+    Denied,
+    Unknown,
+}
+
+// Decisions live in ~/.config/rshell/envrc_trust as "allow|deny <hash> <path>";
+// a decision only holds for the file contents it was made for
+fn trust_file() -> PathBuf {
+    dirs::home_dir().unwrap_or_default().join(".config").join("rshell").join("envrc_trust")
+}
+
+fn read_decisions() -> Vec<(String, String, PathBuf)> {
+    let Ok(text) = fs::read_to_string(trust_file()) else {
+        return Vec::new();
+    };
+    text.lines()
+        .filter_map(|line| {
+            let mut parts = line.splitn(3, ' ');
+            let decision = parts.next()?.to_string();
+            // Decisions made for a shorter hash than SHA-256 no longer count
+            let hash = parts.next().filter(|hash| hash.len() == 64)?.to_string();
+            Some((decision, hash, PathBuf::from(parts.next()?)))
+        })
+        .collect()
+}
+
+pub fn trust_of(file: &Path, hash: &str) -> Trust {
+    match read_decisions().into_iter().find(|(_, h, path)| path == file && h == hash) {
+        Some((decision, _, _)) if decision == "allow" => Trust::Allowed,
+        Some(_) => Trust::Denied,
+        None => Trust::Unknown,
+    }
+}
+
+pub fn record_decision(file: &Path, hash: &str, allow: bool) -> io::Result<()> {
+    let mut decisions = read_decisions();
+    decisions.retain(|(_, _, path)| path != file);
+    decisions.push((if allow { "allow" } else { "deny" }.to_string(), hash.to_string(), file.to_path_buf()));
+    let path = trust_file();
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let text: String = decisions.iter()
+        .map(|(decision, hash, file)| format!("{} {} {}\n", decision, hash, file.display()))
+        .collect();
+    fs::write(path, text)
+}
+
+struct ActiveEnvrc {
+    file: PathBuf,
+    hash: String,
+    // Values the variables had before loading, restored on unload
+    saved: Vec<(String, Option<String>)>,
+}
+
+#[derive(Default)]
+pub struct EnvrcState {
+    active: Option<ActiveEnvrc>,
+    // Files already reported as blocked this session
+    reported: HashSet<(PathBuf, String)>,
+}
+
+fn stdin_is_terminal() -> bool {
+    unsafe { libc::isatty(libc::STDIN_FILENO) == 1 }
+}
+
+fn note(shell: &Shell, message: &str) {
+    let theme = &shell.config.theme;
+    eprintln!("{}envrc: {}{}", theme.hint_color, message, theme.reset_color);
+}
+
+fn unload(shell: &mut Shell) {
+    let Some(active) = shell.envrc.active.take() else {
+        return;
+    };
+    for (key, value) in active.saved.into_iter().rev() {
+        match value {
+            Some(value) => shell.set_var(&key, value),
+            None => shell.unset_var(&key),
+        };
+    }
+    note(shell, &format!("unloaded {}", active.file.display()));
+}
+
+fn load(shell: &mut Shell, file: &Path, hash: String, text: &str) {
+    let mut env: HashMap<String, String> = shell.environment.iter()
+        .map(|(k, v)| (k.to_string(), v.clone()))
+        .collect();
+    let dir = file.parent().unwrap_or(Path::new("/"));
+    let (assignments, skipped) = parse(text, dir, &mut env);
+    let mut saved: Vec<(String, Option<String>)> = Vec::new();
+    for (key, value) in &assignments {
+        if !saved.iter().any(|(k, _)| k == key) {
+            saved.push((key.clone(), shell.environment.get(key.as_str()).cloned()));
+        }
+        shell.export_var(key, value.clone());
+    }
+    let keys: Vec<String> = saved.iter().map(|(k, _)| format!("+{}", k)).collect();
+    note(shell, &format!("loaded {} {}", file.display(), keys.join(" ")));
+    for line in skipped {
+        note(shell, &format!("skipped unsupported line: {}", line));
+    }
+    shell.envrc.active = Some(ActiveEnvrc { file: file.to_path_buf(), hash, saved });
+}
+
+fn ask_trust(shell: &Shell, file: &Path) -> Option<bool> {
+    if !stdin_is_terminal() {
+        return None;
+    }
+    eprint!("{}envrc: {} is new or changed. Load it? [y/N] {}",
+            shell.config.theme.hint_color, file.display(), shell.config.theme.reset_color);
+    let _ = io::stderr().flush();
+    let mut answer = String::new();
+    io::stdin().lock().read_line(&mut answer).ok()?;
+    Some(matches!(answer.trim(), "y" | "Y" | "yes"))
+}
+
+// Brings the environment in line with the working directory; called before
+// each prompt
+pub fn update(shell: &mut Shell) {
+    let current = if shell.config.general.load_envrc {
+        find_envrc(Path::new(&shell.current_dir))
+            .and_then(|file| fs::read_to_string(&file).ok().map(|text| (file, text)))
+    } else {
+        None
+    };
+    let current = current.map(|(file, text)| {
+        let hash = content_hash(text.as_bytes());
+        (file, hash, text)
+    });
+
+    if let Some(active) = &shell.envrc.active {
+        if current.as_ref().is_some_and(|(file, hash, _)| *file == active.file && *hash == active.hash) {
+            return;
+        }
+        unload(shell);
+    }
+    let Some((file, hash, text)) = current else {
+        return;
+    };
+    match trust_of(&file, &hash) {
+        Trust::Allowed => load(shell, &file, hash, &text),
+        Trust::Denied => {}
+        Trust::Unknown => {
+            if !shell.envrc.reported.insert((file.clone(), hash.clone())) {
+                return;
+            }
+            match ask_trust(shell, &file) {
+                Some(allow) => {
+                    if let Err(e) = record_decision(&file, &hash, allow) {
+                        note(shell, &format!("could not save the decision: {}", e));
+                    }
+                    if allow {
+                        load(shell, &file, hash, &text);
+                    }
+                }
+                None => note(shell, &format!("{} is blocked; run `envrc allow` to load it", file.display())),
+            }
+        }
+    }
+}
+
+pub fn builtin_envrc(shell: &mut Shell, args: &[String]) -> i32 {
+    let subcommand = args.first().map(String::as_str).unwrap_or("status");
+    let target = match args.get(1) {
+        Some(path) => {
+            let path = Path::new(&shell.current_dir).join(path);
+            if path.is_dir() { find_envrc(&path) } else { Some(path) }
+        }
+        None => find_envrc(Path::new(&shell.current_dir)),
+    };
+
+    match subcommand {
+        "status" => {
+            match &shell.envrc.active {
+                Some(active) => {
+                    let keys: Vec<&str> = active.saved.iter().map(|(k, _)| k.as_str()).collect();
+                    outln!("loaded:  {} ({})", active.file.display(), keys.join(" "));
+                }
+                None => outln!("loaded:  nothing"),
+            }
+            if let Some(file) = &target {
+                let trust = fs::read(file).map(|bytes| trust_of(file, &content_hash(&bytes)));
+                let state = match trust {
+                    Ok(Trust::Allowed) => "allowed",
+                    Ok(Trust::Denied) => "denied",
+                    Ok(Trust::Unknown) => "not yet trusted",
+                    Err(_) => "unreadable",
+                };
+                outln!("found:   {} ({})", file.display(), state);
+            }
+            if !shell.config.general.load_envrc {
+                outln!("automatic loading is off (general.load_envrc = false)");
+            }
+            0
+        }
+        "allow" | "deny" | "reload" => {
+            let Some(file) = target else {
+                eprintln!("envrc: no {} here or in any parent directory", FILE_NAMES.join(" or "));
+                return 1;
+            };
+            if subcommand != "reload" {
+                let bytes = match fs::read(&file) {
+                    Ok(bytes) => bytes,
+                    Err(e) => {
+                        eprintln!("envrc: {}: {}", file.display(), e);
+                        return 1;
+                    }
+                };
+                if let Err(e) = record_decision(&file, &content_hash(&bytes), subcommand == "allow") {
+                    eprintln!("envrc: {}", e);
+                    return 1;
+                }
+            }
+            unload(shell);
+            update(shell);
+            0
+        }
+        _ => {
+            eprintln!("usage: envrc [status | allow [path] | deny [path] | reload]");
+            2
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_assignments() {
+        let text = "# comment\nexport A=1\nB=\"$A two\" \nC='$A' \nD=plain # trailing\nPATH_add bin\nlayout python\n";
+        let mut env = HashMap::from([("PATH".to_string(), "/usr/bin".to_string())]);
+        let (assignments, skipped) = parse(text, Path::new("/proj"), &mut env);
+        let expected = [("A", "1"), ("B", "1 two"), ("C", "$A"), ("D", "plain"), ("PATH", "/proj/bin:/usr/bin")];
+        assert_eq!(assignments.len(), expected.len());
+        for ((key, value), (k, v)) in assignments.iter().zip(expected) {
+            assert_eq!((key.as_str(), value.as_str()), (k, v));
+        }
+        assert_eq!(skipped, ["layout python"]);
+    }
+
+    #[test]
+    fn hashes_contents_with_sha256() {
+        assert_eq!(content_hash(b"abc"), "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad");
+    }
+}
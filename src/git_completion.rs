@@ -0,0 +1,130 @@
+// src/git_completion.rs
+//
+// Completion for `git`: subcommands (and the user's git aliases), branch
+// and tag names for the commands that take a revision, and remote names
+// then branches for `push`, `pull` and `fetch`. The names come from git
+// plumbing run only when they are needed, through `CommandCache::git_lines`.
+use std::path::Path;
+use crate::cache::CommandCache;
+
+const SUBCOMMANDS: &[&str] = &[
+    "add", "am", "bisect", "blame", "branch", "checkout", "cherry-pick", "clean", "clone",
+    "commit", "config", "describe", "diff", "fetch", "grep", "init", "log", "merge", "mv",
+    "pull", "push", "rebase", "reflog", "remote", "reset", "restore", "revert", "rm", "show",
+    "stash", "status", "switch", "tag", "worktree",
+];
+
+// Subcommands whose arguments are revisions
+const TAKE_REVISIONS: &[&str] = &[
+    "branch", "checkout", "cherry-pick", "diff", "log", "merge", "rebase", "reset", "revert",
+    "show", "switch",
+];
+
+// The names git takes after `git ARGS...` in `dir`, for the caller to
+// match against `word`; None where git wants something else, usually files,
+// or an option
+pub fn complete(cache: &CommandCache, dir: &Path, args: &[&str], word: &str) -> Option<Vec<String>> {
+    if word.starts_with('-') || args.contains(&"--") {
+        return None;
+    }
+    // Options before the subcommand, like `git -C dir` or `git --no-pager`,
+    // are skipped; `-C`'s and `-c`'s values with them
+    let mut rest = args.iter();
+    let subcommand = loop {
+        match rest.next() {
+            Some(&("-C" | "-c")) => {
+                rest.next();
+            }
+            Some(arg) if arg.starts_with('-') => {}
+            Some(arg) => break Some(*arg),
+            None => break None,
+        }
+    };
+    let operands: Vec<&str> = rest.copied().filter(|arg| !arg.starts_with('-')).collect();
+    let mut names: Vec<String> = match subcommand {
+        None => {
+            let aliases = cache.git_lines(dir, &["config", "--get-regexp", r"^alias\."]);
+            SUBCOMMANDS.iter()
+                .map(|name| name.to_string())
+                .chain(aliases.iter().filter_map(|line| {
+                    line.strip_prefix("alias.")?.split_whitespace().next().map(String::from)
+                }))
+                .collect()
+        }
+        Some("push" | "pull" | "fetch") if operands.is_empty() => remotes(cache, dir),
+        Some("push" | "pull" | "fetch") => branches(cache, dir, false),
+        Some("remote") if operands.is_empty() => {
+            ["add", "remove", "rename", "set-url", "show", "prune"].map(String::from).to_vec()
+        }
+        Some("remote") => remotes(cache, dir),
+        Some(subcommand) if TAKE_REVISIONS.contains(&subcommand) => {
+            let tags = !matches!(subcommand, "branch" | "switch");
+            branches(cache, dir, tags)
+        }
+        Some(_) => return None,
+    };
+    names.sort();
+    names.dedup();
+    Some(names)
+}
+
+fn remotes(cache: &CommandCache, dir: &Path) -> Vec<String> {
+    cache.git_lines(dir, &["remote"])
+}
+
+// Local and remote-tracking branches, and tags if asked for
+fn branches(cache: &CommandCache, dir: &Path, tags: bool) -> Vec<String> {
+    let mut refs = vec!["for-each-ref", "--format=%(refname)", "refs/heads", "refs/remotes"];
+    if tags {
+        refs.push("refs/tags");
+    }
+    cache.git_lines(dir, &refs)
+        .iter()
+        .filter_map(|name| {
+            name.strip_prefix("refs/heads/")
+                .or_else(|| name.strip_prefix("refs/remotes/").filter(|name| !name.ends_with("/HEAD")))
+                .or_else(|| name.strip_prefix("refs/tags/"))
+                .map(String::from)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::process::Command;
+
+    #[test]
+    fn branches_remotes_and_subcommands() {
+        let dir = std::env::temp_dir().join(format!("rshell-git-complete-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let git = |args: &[&str]| {
+            Command::new("git").arg("-C").arg(&dir).args(args)
+                .env("GIT_AUTHOR_NAME", "t").env("GIT_AUTHOR_EMAIL", "t@t")
+                .env("GIT_COMMITTER_NAME", "t").env("GIT_COMMITTER_EMAIL", "t@t")
+                .output()
+                .is_ok_and(|output| output.status.success())
+        };
+        if !git(&["init", "-q", "-b", "main"]) {
+            // No git to test with
+            std::fs::remove_dir_all(&dir).unwrap();
+            return;
+        }
+        assert!(git(&["commit", "-q", "--allow-empty", "-m", "x"]));
+        assert!(git(&["branch", "feature"]));
+        assert!(git(&["tag", "v1"]));
+        assert!(git(&["remote", "add", "origin", "https://example.com/x.git"]));
+
+        let cache = CommandCache::new();
+        let complete = |args: &[&str], word: &str| complete(&cache, &dir, args, word);
+        assert_eq!(complete(&["checkout"], ""), Some(vec!["feature".into(), "main".into(), "v1".into()]));
+        assert_eq!(complete(&["switch"], "f"), Some(vec!["feature".into(), "main".into()]));
+        assert_eq!(complete(&["push"], ""), Some(vec!["origin".into()]));
+        assert_eq!(complete(&["push", "origin"], "m"), Some(vec!["feature".into(), "main".into()]));
+        assert!(complete(&[], "chec").is_some_and(|names| names.contains(&"checkout".to_string())));
+        assert_eq!(complete(&["checkout"], "-"), None);
+        assert_eq!(complete(&["add"], ""), None);
+        assert_eq!(complete(&["checkout", "--"], ""), None);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -1,29 +1,61 @@
 // src/history.rs
 use std::collections::VecDeque;
+use std::ffi::CString;
 use std::fs::{File, OpenOptions};
-use std::io::{BufRead, BufReader, Write};
-use std::path::PathBuf;
+use std::io::{self, BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+use crate::memory_pool::StringInterner;
+use crate::performance::ResourceUsage;
+
+#[derive(Debug, Clone)]
+pub struct HistoryEntry {
+    // Interned: a command run many times is stored once
+    pub command: Arc<str>,
+    pub duration: Option<Duration>,
+    pub exit_code: Option<i32>,
+    pub rusage: Option<ResourceUsage>,
+}
+
+impl HistoryEntry {
+    pub fn new(command: Arc<str>) -> Self {
+        Self {
+            command,
+            duration: None,
+            exit_code: None,
+            rusage: None,
+        }
+    }
+}
 
 pub struct History {
-    commands: VecDeque<String>,
+    commands: VecDeque<HistoryEntry>,
+    interner: StringInterner,
     max_size: usize,
     file_path: Option<PathBuf>,
+    // Entries added since the last `write_to`/`append_to`
+    unsaved: usize,
 }
 
 impl History {
     pub fn new(max_size: usize) -> Self {
         Self {
             commands: VecDeque::new(),
+            interner: StringInterner::new(),
             max_size,
             file_path: None,
+            unsaved: 0,
         }
     }
     
     pub fn with_file(max_size: usize, file_path: PathBuf) -> Self {
         let mut history = Self {
             commands: VecDeque::new(),
+            interner: StringInterner::new(),
             max_size,
             file_path: Some(file_path),
+            unsaved: 0,
         };
         history.load_from_file();
         history
@@ -36,55 +68,166 @@ impl History {
         
         // Don't add duplicate consecutive commands
         if let Some(last) = self.commands.back() {
-            if last == &command {
+            if *last.command == command {
                 return;
             }
         }
         
-        self.commands.push_back(command);
+        let command = self.interner.intern(&command);
+        self.commands.push_back(HistoryEntry::new(command));
+        self.unsaved += 1;
+        self.trim();
         
-        // Maintain max size
+        self.save_to_file();
+    }
+    
+    // Puts commands from earlier sessions, oldest first, before this one's
+    pub fn load_older<'a>(&mut self, commands: impl DoubleEndedIterator<Item = &'a str>) {
+        for command in commands.rev() {
+            let command = self.interner.intern(command);
+            self.commands.push_front(HistoryEntry::new(command));
+        }
+        // Too many: the oldest go, not this session's
         while self.commands.len() > self.max_size {
             self.commands.pop_front();
         }
-        
-        self.save_to_file();
     }
     
-    pub fn get(&self, index: usize) -> Option<&String> {
+    // Attach timing and status to the most recent entry once it has run
+    pub fn record_result(&mut self, duration: Duration, exit_code: i32, rusage: Option<ResourceUsage>) {
+        if let Some(last) = self.commands.back_mut() {
+            last.duration = Some(duration);
+            last.exit_code = Some(exit_code);
+            last.rusage = rusage;
+        }
+    }
+    
+    pub fn get(&self, index: usize) -> Option<&HistoryEntry> {
         self.commands.get(index)
     }
     
-    pub fn get_all(&self) -> Vec<&String> {
-        self.commands.iter().collect()
+    pub fn get_all(&self) -> Vec<&str> {
+        self.commands.iter().map(|entry| &*entry.command).collect()
     }
     
-    pub fn search(&self, pattern: &str) -> Vec<&String> {
-        self.commands
-            .iter()
-            .filter(|cmd| cmd.contains(pattern))
-            .collect()
+    pub fn entries(&self) -> impl Iterator<Item = &HistoryEntry> {
+        self.commands.iter()
+    }
+    
+    pub fn len(&self) -> usize {
+        self.commands.len()
+    }
+    
+    pub fn is_empty(&self) -> bool {
+        self.commands.is_empty()
+    }
+    
+    // Distinct commands kept in memory; repeats share one allocation
+    pub fn unique_commands(&self) -> usize {
+        self.interner.len()
+    }
+    
+    // Entries matching the extended regular expression `pattern` (regcomp(3),
+    // as for `[[ =~ ]]`), with their 0-based indexes
+    pub fn search(&self, pattern: &str) -> Result<Vec<(usize, &str)>, String> {
+        let regex = CString::new(pattern).map_err(|_| format!("{}: invalid regular expression", pattern))?;
+        unsafe {
+            let mut compiled: libc::regex_t = std::mem::zeroed();
+            if libc::regcomp(&mut compiled, regex.as_ptr(), libc::REG_EXTENDED | libc::REG_NOSUB) != 0 {
+                return Err(format!("{}: invalid regular expression", pattern));
+            }
+            let found = self.commands
+                .iter()
+                .enumerate()
+                .filter(|(_, entry)| {
+                    CString::new(&*entry.command).is_ok_and(|command| {
+                        libc::regexec(&compiled, command.as_ptr(), 0, std::ptr::null_mut(), 0) == 0
+                    })
+                })
+                .map(|(i, entry)| (i, &*entry.command))
+                .collect();
+            libc::regfree(&mut compiled);
+            Ok(found)
+        }
+    }
+    
+    // Removes the entry at 0-based `index`
+    pub fn remove(&mut self, index: usize) -> bool {
+        let removed = self.commands.remove(index).is_some();
+        if removed {
+            self.unsaved = self.unsaved.min(self.commands.len());
+            self.save_to_file();
+        }
+        removed
+    }
+    
+    // `history -w`: replaces `path` with the whole history
+    pub fn write_to(&mut self, path: &Path) -> io::Result<()> {
+        let mut file = File::create(path)?;
+        for entry in &self.commands {
+            writeln!(file, "{}", entry.command)?;
+        }
+        self.unsaved = 0;
+        Ok(())
+    }
+    
+    // `history -a`: adds the entries not yet written out to `path`
+    pub fn append_to(&mut self, path: &Path) -> io::Result<()> {
+        let mut file = OpenOptions::new().create(true).append(true).open(path)?;
+        let start = self.commands.len() - self.unsaved.min(self.commands.len());
+        for entry in self.commands.range(start..) {
+            writeln!(file, "{}", entry.command)?;
+        }
+        self.unsaved = 0;
+        Ok(())
+    }
+    
+    // Estimated heap footprint of the in-memory history
+    pub fn memory_usage(&self) -> usize {
+        self.commands.capacity() * std::mem::size_of::<HistoryEntry>() + self.interner.heap_bytes()
+    }
+    
+    // Drops the oldest entries until the history fits in `limit` bytes.
+    // Returns how many entries were removed.
+    pub fn compact(&mut self, limit: usize) -> usize {
+        let before = self.commands.len();
+        while self.memory_usage() > limit && !self.commands.is_empty() {
+            let excess = self.commands.len().div_ceil(10);
+            self.commands.drain(..excess);
+            self.interner.purge_unused();
+            self.commands.shrink_to_fit();
+        }
+        before - self.commands.len()
     }
     
     pub fn clear(&mut self) {
         self.commands.clear();
+        self.unsaved = 0;
+        self.interner.clear();
         self.save_to_file();
     }
     
+    // Maintain max size. Commands that fell out of the window stay interned
+    // until there are enough of them to be worth a sweep.
+    fn trim(&mut self) {
+        while self.commands.len() > self.max_size {
+            self.commands.pop_front();
+        }
+        if self.interner.len() > self.max_size + self.max_size / 2 {
+            self.interner.purge_unused();
+        }
+    }
+    
     fn load_from_file(&mut self) {
         if let Some(ref path) = self.file_path {
             if let Ok(file) = File::open(path) {
                 let reader = BufReader::new(file);
-                for line in reader.lines() {
-                    if let Ok(command) = line {
-                        self.commands.push_back(command);
-                    }
+                for command in reader.lines().map_while(Result::ok) {
+                    let command = self.interner.intern(&command);
+                    self.commands.push_back(HistoryEntry::new(command));
                 }
                 
-                // Maintain max size
-                while self.commands.len() > self.max_size {
-                    self.commands.pop_front();
-                }
+                self.trim();
             }
         }
     }
@@ -97,8 +240,8 @@ impl History {
                 .truncate(true)
                 .open(path)
             {
-                for command in &self.commands {
-                    writeln!(file, "{}", command).unwrap_or(());
+                for entry in &self.commands {
+                    writeln!(file, "{}", entry.command).unwrap_or(());
                 }
             }
         }
@@ -110,3 +253,54 @@ impl Default for History {
         Self::new(1000)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn repeated_commands_share_storage() {
+        let mut history = History::new(10);
+        for command in ["ls", "make", "ls", "make", "ls"] {
+            history.add(command.to_string());
+        }
+        assert_eq!(history.len(), 5);
+        assert_eq!(history.unique_commands(), 2);
+        assert!(Arc::ptr_eq(&history.get(0).unwrap().command, &history.get(2).unwrap().command));
+    }
+
+    #[test]
+    fn compaction_keeps_recent_commands() {
+        let mut history = History::new(1000);
+        for i in 0..500 {
+            history.add(format!("command number {}", i));
+        }
+        let limit = history.memory_usage() / 4;
+        assert!(history.compact(limit) > 0);
+        assert!(history.memory_usage() <= limit);
+        assert_eq!(history.get_all().last(), Some(&"command number 499"));
+    }
+
+    #[test]
+    fn search_and_remove() {
+        let mut history = History::new(10);
+        for command in ["git status", "ls -l", "git push origin"] {
+            history.add(command.to_string());
+        }
+        assert_eq!(history.search("^git (status|push)").unwrap(), vec![(0, "git status"), (2, "git push origin")]);
+        assert!(history.search("(").is_err());
+        assert!(history.remove(1));
+        assert!(!history.remove(5));
+        assert_eq!(history.get_all(), vec!["git status", "git push origin"]);
+    }
+
+    #[test]
+    fn trimming_forgets_old_commands() {
+        let mut history = History::new(2);
+        for i in 0..10 {
+            history.add(format!("cmd{}", i));
+        }
+        assert_eq!(history.get_all(), vec!["cmd8", "cmd9"]);
+        assert!(history.unique_commands() <= 3);
+    }
+}
@@ -0,0 +1,157 @@
+// src/history_expansion.rs
+//
+// `!` history expansion, done on an interactive line before it is parsed:
+// `!!` is the previous command, `!42` entry 42 (as `history` numbers them),
+// `!-2` the one before the previous, `!git` the latest starting with `git`;
+// `!$`, `!^` and `!*` are the previous command's last word, first argument
+// and all arguments. Nothing is expanded inside single quotes or after a
+// backslash, and a `!` followed by a blank, `=`, `(` or the end of the line,
+// or following `$`, `${` or `[`, stays as it is. Off with `set +H`.
+
+// `line` with its history references replaced, or None if it has none.
+// `entries` are oldest first.
+pub fn expand(line: &str, entries: &[&str]) -> Result<Option<String>, String> {
+    if !line.contains('!') {
+        return Ok(None);
+    }
+    let chars: Vec<char> = line.chars().collect();
+    let mut expanded = String::with_capacity(line.len());
+    let mut changed = false;
+    let (mut single, mut double) = (false, false);
+    let mut i = 0;
+    while i < chars.len() {
+        let ch = chars[i];
+        i += 1;
+        match ch {
+            '\\' if !single => {
+                expanded.push(ch);
+                if let Some(&next) = chars.get(i) {
+                    expanded.push(next);
+                    i += 1;
+                }
+            }
+            '\'' if !double => {
+                single = !single;
+                expanded.push(ch);
+            }
+            '"' if !single => {
+                double = !double;
+                expanded.push(ch);
+            }
+            '!' if !single && !literal(&chars, i, double) => {
+                let (text, used) = reference(&chars[i..], entries)?;
+                expanded.push_str(&text);
+                i += used;
+                changed = true;
+            }
+            _ => expanded.push(ch),
+        }
+    }
+    Ok(changed.then_some(expanded))
+}
+
+// Whether the `!` just before `chars[next]` means itself
+fn literal(chars: &[char], next: usize, double: bool) -> bool {
+    let before = next.checked_sub(2).map(|i| chars[i]);
+    if matches!(before, Some('$' | '[')) || (before == Some('{') && next >= 3 && chars[next - 3] == '$') {
+        return true;
+    }
+    match chars.get(next) {
+        None => true,
+        Some(ch) => ch.is_whitespace() || matches!(ch, '=' | '(') || (double && *ch == '"'),
+    }
+}
+
+// The text for the reference after a `!`, and how many characters of
+// `rest` it took
+fn reference(rest: &[char], entries: &[&str]) -> Result<(String, usize), String> {
+    let previous = || entries.last().copied().ok_or_else(|| "!!: event not found".to_string());
+    match rest[0] {
+        '!' => Ok((previous()?.to_string(), 1)),
+        '$' => {
+            let words = words(previous()?);
+            Ok((words.last().map_or_else(String::new, |word| word.to_string()), 1))
+        }
+        '^' => match words(previous()?).get(1) {
+            Some(word) => Ok((word.to_string(), 1)),
+            None => Err("!^: bad word specifier".to_string()),
+        },
+        '*' => Ok((words(previous()?).get(1..).unwrap_or_default().join(" "), 1)),
+        _ => {
+            let back = rest[0] == '-';
+            let digits = rest[usize::from(back)..].iter().take_while(|ch| ch.is_ascii_digit()).count();
+            if digits > 0 {
+                let used = usize::from(back) + digits;
+                let text: String = rest[..used].iter().collect();
+                let n: usize = text.trim_start_matches('-').parse().unwrap_or(0);
+                let index = if back { entries.len().checked_sub(n) } else { n.checked_sub(1) };
+                return match index.and_then(|index| entries.get(index)) {
+                    Some(command) => Ok((command.to_string(), used)),
+                    None => Err(format!("!{}: event not found", text)),
+                };
+            }
+            let used = rest.iter()
+                .take_while(|ch| !ch.is_whitespace() && !matches!(ch, ';' | '|' | '&' | '<' | '>' | '(' | ')' | '"' | '\'' | '`'))
+                .count();
+            let prefix: String = rest[..used].iter().collect();
+            match entries.iter().rev().find(|command| command.starts_with(&prefix)) {
+                Some(command) => Ok((command.to_string(), used)),
+                None => Err(format!("!{}: event not found", prefix)),
+            }
+        }
+    }
+}
+
+// The words of a command as typed, quotes kept
+fn words(command: &str) -> Vec<&str> {
+    let mut words = Vec::new();
+    let mut start = None;
+    let mut quote = None;
+    let mut escaped = false;
+    for (i, ch) in command.char_indices() {
+        if escaped {
+            escaped = false;
+            continue;
+        }
+        match (ch, quote) {
+            ('\\', Some('\'')) => {}
+            ('\\', _) => escaped = true,
+            (_, Some(open)) if ch == open => quote = None,
+            (_, Some(_)) => {}
+            ('\'' | '"', None) => quote = Some(ch),
+            (ch, None) if ch.is_whitespace() => {
+                if let Some(begin) = start.take() {
+                    words.push(&command[begin..i]);
+                }
+                continue;
+            }
+            _ => {}
+        }
+        start.get_or_insert(i);
+    }
+    if let Some(begin) = start {
+        words.push(&command[begin..]);
+    }
+    words
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn references() {
+        let entries = ["git status", "cp 'a b.txt' /tmp", "make test"];
+        let expand = |line: &str| expand(line, &entries);
+        assert_eq!(expand("sudo !!"), Ok(Some("sudo make test".to_string())));
+        assert_eq!(expand("!2"), Ok(Some("cp 'a b.txt' /tmp".to_string())));
+        assert_eq!(expand("!-3 -s"), Ok(Some("git status -s".to_string())));
+        assert_eq!(expand("!gi"), Ok(Some("git status".to_string())));
+        assert_eq!(expand("echo !$ !*"), Ok(Some("echo test test".to_string())));
+        assert_eq!(expand("echo \"!-2\""), Ok(Some("echo \"cp 'a b.txt' /tmp\"".to_string())));
+        assert_eq!(expand("echo '!!' \\!! $! ! a!= !( [!a]"), Ok(None));
+        assert_eq!(expand("!nope"), Err("!nope: event not found".to_string()));
+        assert_eq!(expand("!9"), Err("!9: event not found".to_string()));
+        assert_eq!(words("cp 'a b.txt' \"x\\\" y\" z"), ["cp", "'a b.txt'", "\"x\\\" y\"", "z"]);
+    }
+}
@@ -0,0 +1,222 @@
+// src/history_search.rs
+//
+// Ctrl-R: reverse incremental search through `shell.history`. The key
+// handler stops the line editor (as an interrupt) and leaves the line being
+// edited here; the prompt loop then runs the search on the terminal itself
+// and starts the next line with the result. Typing narrows the match, Ctrl-R
+// again moves to the next older one and Backspace widens it. Enter runs the
+// match, Esc or an arrow key leaves it on the line for editing, and Ctrl-G or
+// Ctrl-C gives back the line as it was. With `history_search_fuzzy` the
+// query's characters only need to appear in order.
+use std::io::{self, Write};
+use std::sync::{Arc, Mutex};
+use rustyline::{Cmd, ConditionalEventHandler, Event, EventContext, RepeatCount};
+use crate::terminal;
+use crate::utils::helpers::truncate_to_width;
+
+// Bound to Ctrl-R: saves the line being edited and ends `readline`
+#[derive(Clone, Default)]
+pub struct SearchKey {
+    requested: Arc<Mutex<Option<String>>>,
+}
+
+impl SearchKey {
+    // The line that was being edited when Ctrl-R was pressed, once
+    pub fn take(&self) -> Option<String> {
+        self.requested.lock().unwrap().take()
+    }
+}
+
+impl ConditionalEventHandler for SearchKey {
+    fn handle(&self, _evt: &Event, _n: RepeatCount, _positive: bool, ctx: &EventContext) -> Option<Cmd> {
+        *self.requested.lock().unwrap() = Some(ctx.line().to_string());
+        Some(Cmd::Interrupt)
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub enum Outcome {
+    Run(String),
+    Edit(String),
+    Cancel,
+}
+
+// Whether `command` matches `query`: as a substring, or fuzzily as a
+// case-insensitive subsequence
+pub fn matches(command: &str, query: &str, fuzzy: bool) -> bool {
+    if !fuzzy {
+        return command.contains(query);
+    }
+    let mut command = command.chars().flat_map(char::to_lowercase);
+    query.chars().flat_map(char::to_lowercase).all(|wanted| command.any(|ch| ch == wanted))
+}
+
+// The `skip`th distinct entry matching `query`, newest first
+pub fn find<'h>(entries: &[&'h str], query: &str, fuzzy: bool, skip: usize) -> Option<&'h str> {
+    let mut seen = Vec::new();
+    entries.iter().rev()
+        .filter(|command| matches(command, query, fuzzy))
+        .filter(|command| {
+            let new = !seen.contains(command);
+            seen.push(*command);
+            new
+        })
+        .nth(skip)
+        .copied()
+}
+
+// Runs the search on the terminal, with `entries` oldest first
+pub fn run(entries: &[&str], fuzzy: bool) -> Outcome {
+    let Some(saved) = raw_mode() else {
+        return Outcome::Cancel;
+    };
+    let mut query = String::new();
+    let mut skip = 0;
+    let mut shown = "";
+    let outcome = loop {
+        let found = find(entries, &query, fuzzy, skip);
+        if let Some(command) = found {
+            shown = command;
+        }
+        draw(&query, shown, found.is_none());
+        let Some(key) = read_key() else {
+            break Outcome::Cancel;
+        };
+        match key {
+            Key::Char(ch) => {
+                query.push(ch);
+                skip = 0;
+            }
+            Key::Backspace => {
+                query.pop();
+                skip = 0;
+            }
+            Key::Search if found.is_some() => skip += 1,
+            Key::Search => {}
+            Key::Enter if !shown.is_empty() => break Outcome::Run(shown.to_string()),
+            Key::Edit if !shown.is_empty() => break Outcome::Edit(shown.to_string()),
+            Key::Enter | Key::Edit | Key::Cancel => break Outcome::Cancel,
+        }
+    };
+    restore(&saved);
+    let mut stdout = io::stdout();
+    match &outcome {
+        Outcome::Run(_) => {
+            let _ = writeln!(stdout);
+        }
+        _ => {
+            let _ = write!(stdout, "\r\x1b[K");
+        }
+    }
+    let _ = stdout.flush();
+    outcome
+}
+
+fn draw(query: &str, shown: &str, failed: bool) {
+    let label = format!("({}reverse-i-search)`{}': ", if failed { "failed " } else { "" }, query);
+    let columns = terminal::columns().unwrap_or(80) as usize;
+    // One row only, so `\r` gets back to its start
+    let line = truncate_to_width(&format!("{}{}", label, shown), columns.saturating_sub(1));
+    let mut stdout = io::stdout();
+    let _ = write!(stdout, "\r\x1b[K{}", line);
+    let _ = stdout.flush();
+}
+
+enum Key {
+    Char(char),
+    Backspace,
+    Search,
+    Enter,
+    Edit,
+    Cancel,
+}
+
+fn read_byte() -> Option<u8> {
+    let mut byte = [0u8; 1];
+    loop {
+        match nix::unistd::read(0, &mut byte) {
+            Ok(1) => return Some(byte[0]),
+            Err(nix::errno::Errno::EINTR) => continue,
+            _ => return None,
+        }
+    }
+}
+
+fn read_key() -> Option<Key> {
+    let byte = read_byte()?;
+    Some(match byte {
+        0x12 => Key::Search,
+        b'\r' | b'\n' => Key::Enter,
+        0x7f | 0x08 => Key::Backspace,
+        0x03 | 0x07 => Key::Cancel,
+        0x1b => {
+            // An arrow key or other sequence, or Esc alone: in both cases
+            // the match is kept for editing; the rest of a sequence, already
+            // waiting, is dropped
+            discard_pending();
+            Key::Edit
+        }
+        byte if byte < 0x20 => Key::Edit,
+        byte => {
+            let len = match byte {
+                0xc0..=0xdf => 2,
+                0xe0..=0xef => 3,
+                0xf0..=0xf7 => 4,
+                _ => 1,
+            };
+            let mut bytes = vec![byte];
+            for _ in 1..len {
+                bytes.push(read_byte()?);
+            }
+            match std::str::from_utf8(&bytes).ok().and_then(|text| text.chars().next()) {
+                Some(ch) => Key::Char(ch),
+                None => return read_key(),
+            }
+        }
+    })
+}
+
+// Drops input already typed, such as the rest of an escape sequence
+fn discard_pending() {
+    let mut pending: libc::c_int = 0;
+    if unsafe { libc::ioctl(0, libc::FIONREAD, &mut pending) } == 0 && pending > 0 {
+        let mut buffer = vec![0u8; pending as usize];
+        let _ = nix::unistd::read(0, &mut buffer);
+    }
+}
+
+// Byte-at-a-time input without echo or signals, returning the settings to
+// restore
+fn raw_mode() -> Option<libc::termios> {
+    let mut settings: libc::termios = unsafe { std::mem::zeroed() };
+    if unsafe { libc::tcgetattr(0, &mut settings) } != 0 {
+        return None;
+    }
+    let saved = settings;
+    settings.c_lflag &= !(libc::ICANON | libc::ECHO | libc::ISIG | libc::IEXTEN);
+    settings.c_iflag &= !(libc::ICRNL | libc::IXON);
+    settings.c_cc[libc::VMIN] = 1;
+    settings.c_cc[libc::VTIME] = 0;
+    (unsafe { libc::tcsetattr(0, libc::TCSANOW, &settings) } == 0).then_some(saved)
+}
+
+fn restore(saved: &libc::termios) {
+    unsafe { libc::tcsetattr(0, libc::TCSANOW, saved) };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn newest_distinct_match_first() {
+        let entries = ["git checkout main", "ls", "git commit -m x", "git checkout main", "make"];
+        assert_eq!(find(&entries, "git c", false, 0), Some("git checkout main"));
+        assert_eq!(find(&entries, "git c", false, 1), Some("git commit -m x"));
+        assert_eq!(find(&entries, "git c", false, 2), None);
+        assert_eq!(find(&entries, "gcx", false, 0), None);
+        assert_eq!(find(&entries, "gcx", true, 0), Some("git commit -m x"));
+        assert!(matches("Cargo Build", "cb", true));
+        assert!(!matches("cargo build", "bc", true));
+    }
+}
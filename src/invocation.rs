@@ -0,0 +1,118 @@
+// src/invocation.rs
+//
+// The command line rshell was started with:
+//
+//   rshell [-il] [-c COMMAND [NAME [ARG...]]]
+//   rshell [-il] [-s] [SCRIPT [ARG...]]
+//
+// Without -c or a script, commands come from stdin: interactively with the
+// line editor when stdin is a terminal (or -i is given), otherwise read as a
+// script. Interactive shells read the rc files unless --norc is given.
+use std::io::IsTerminal;
+
+#[derive(Debug, Default, PartialEq)]
+pub struct Invocation {
+    pub login: bool,
+    pub interactive: bool,
+    pub profile_startup: bool,
+    // `--norc`: skip the rc files
+    pub no_rc: bool,
+    // `-c COMMAND`
+    pub command: Option<String>,
+    // A script file, or NAME after `-c COMMAND`: becomes `$0`
+    pub script: Option<String>,
+    // `$1`..
+    pub args: Vec<String>,
+}
+
+impl Invocation {
+    pub fn parse(argv0: &str, args: &[String]) -> Result<Self, String> {
+        let mut invocation = Invocation {
+            login: crate::login::is_login_invocation(argv0, &[]),
+            ..Invocation::default()
+        };
+        let mut force_interactive = false;
+        let mut read_stdin = false;
+        let mut run_command = false;
+        let mut args = args.iter();
+        let mut operands = Vec::new();
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--login" => invocation.login = true,
+                "--profile-startup" => invocation.profile_startup = true,
+                "--norc" => invocation.no_rc = true,
+                "--" => {
+                    operands.extend(args.by_ref().cloned());
+                    break;
+                }
+                flags if flags.starts_with('-') && flags.len() > 1 && !flags.starts_with("--") => {
+                    for flag in flags[1..].chars() {
+                        match flag {
+                            'c' => run_command = true,
+                            'i' => force_interactive = true,
+                            'l' => invocation.login = true,
+                            's' => read_stdin = true,
+                            _ => return Err(format!("-{}: invalid option", flag)),
+                        }
+                    }
+                }
+                option if option.starts_with("--") => return Err(format!("{}: invalid option", option)),
+                _ => {
+                    operands.push(arg.clone());
+                    operands.extend(args.by_ref().cloned());
+                    break;
+                }
+            }
+        }
+        let mut operands = operands.into_iter();
+        if run_command {
+            invocation.command = Some(operands.next().ok_or("-c: option requires an argument")?);
+            invocation.script = operands.next();
+        } else if !read_stdin {
+            invocation.script = operands.next();
+        }
+        invocation.args = operands.collect();
+        invocation.interactive = force_interactive
+            || (invocation.command.is_none() && invocation.script.is_none() && std::io::stdin().is_terminal());
+        Ok(invocation)
+    }
+
+    // Whether commands come from somewhere other than the line editor
+    pub fn runs_script(&self) -> bool {
+        self.command.is_some() || self.script.is_some() || !self.interactive
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &[&str]) -> Result<Invocation, String> {
+        let args: Vec<String> = args.iter().map(|a| a.to_string()).collect();
+        Invocation::parse("rshell", &args)
+    }
+
+    #[test]
+    fn command_line() {
+        let c = parse(&["-lc", "echo $1", "name", "a", "b"]).unwrap();
+        assert!(c.login && !c.interactive);
+        assert_eq!(c.command.as_deref(), Some("echo $1"));
+        assert_eq!(c.script.as_deref(), Some("name"));
+        assert_eq!(c.args, ["a", "b"]);
+
+        let script = parse(&["--login", "run.sh", "-x", "--", "y"]).unwrap();
+        assert!(script.login && script.runs_script());
+        assert_eq!(script.script.as_deref(), Some("run.sh"));
+        assert_eq!(script.args, ["-x", "--", "y"]);
+
+        let stdin = parse(&["-s", "a", "b"]).unwrap();
+        assert_eq!((stdin.script, stdin.args), (None, vec!["a".to_string(), "b".to_string()]));
+        assert!(parse(&["-i"]).unwrap().interactive);
+        assert!(parse(&["--norc", "-i"]).unwrap().no_rc);
+        assert!(Invocation::parse("-rshell", &[]).unwrap().login);
+
+        assert_eq!(parse(&["-c"]).unwrap_err(), "-c: option requires an argument");
+        assert_eq!(parse(&["-z"]).unwrap_err(), "-z: invalid option");
+        assert_eq!(parse(&["--nope"]).unwrap_err(), "--nope: invalid option");
+    }
+}
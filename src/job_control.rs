@@ -1,30 +1,59 @@
 // src/job_control.rs
 use crate::shell::{Shell, JobStatus};
+use crate::signal_handler;
+use crate::outln;
 use nix::sys::signal::{kill, Signal};
 use nix::sys::wait::{waitpid, WaitStatus};
 use nix::unistd::Pid;
 
-pub fn fg_command(shell: &mut Shell, args: &[String]) -> i32 {
-    let job_id = if args.is_empty() {
-        // Get the most recent job
-        shell.jobs.iter().rposition(|j| matches!(j.status, JobStatus::Running | JobStatus::Stopped))
-    } else {
-        // Parse job ID
-        args[0].parse::<usize>().ok().and_then(|id| {
-            if id > 0 && id <= shell.jobs.len() {
-                Some(id - 1)
-            } else {
-                None
+// Jobs still running or stopped, the current one (`%+`) first and the
+// previous (`%-`) next: stopped jobs before running ones, most recent first
+pub fn job_order(shell: &Shell) -> Vec<usize> {
+    let mut order: Vec<usize> = (0..shell.jobs.len())
+        .rev()
+        .filter(|&i| !matches!(shell.jobs[i].status, JobStatus::Done))
+        .collect();
+    order.sort_by_key(|&i| !matches!(shell.jobs[i].status, JobStatus::Stopped));
+    order
+}
+
+// The index in `shell.jobs` of a job spec: `%N` or `N`, `%+`/`%%` (current),
+// `%-` (previous), `%name` (command starts with name) or `%?text` (command
+// contains text)
+pub fn find_job(shell: &Shell, spec: &str) -> Result<usize, String> {
+    let name = spec.strip_prefix('%').unwrap_or(spec);
+    let order = job_order(shell);
+    let found = match name {
+        "" | "+" | "%" => order.first().copied(),
+        "-" => order.get(1).copied(),
+        _ if name.bytes().all(|b| b.is_ascii_digit()) => {
+            name.parse::<usize>().ok().filter(|&id| id > 0 && id <= shell.jobs.len()).map(|id| id - 1)
+        }
+        _ => {
+            let matches: Vec<usize> = order.iter().copied()
+                .filter(|&i| match name.strip_prefix('?') {
+                    Some(text) => shell.jobs[i].command.contains(text),
+                    None => shell.jobs[i].command.starts_with(name),
+                })
+                .collect();
+            if matches.len() > 1 {
+                return Err(format!("{}: ambiguous job spec", spec));
             }
-        })
+            matches.first().copied()
+        }
     };
+    found.ok_or_else(|| format!("{}: no such job", spec))
+}
+
+pub fn fg_command(shell: &mut Shell, args: &[String]) -> i32 {
+    let job_id = find_job(shell, args.first().map_or("%+", String::as_str));
     
     match job_id {
-        Some(idx) => {
+        Ok(idx) => {
             let job = &mut shell.jobs[idx];
             let pid = job.pid;
             
-            println!("{}", job.command);
+            outln!("{}", job.command);
             
             // Send SIGCONT if job was stopped
             if matches!(job.status, JobStatus::Stopped) {
@@ -35,7 +64,7 @@ pub fn fg_command(shell: &mut Shell, args: &[String]) -> i32 {
             match waitpid(Pid::from_raw(pid), None) {
                 Ok(WaitStatus::Exited(_, code)) => {
                     job.status = JobStatus::Done;
-                    code as i32
+                    code
                 }
                 Ok(WaitStatus::Signaled(_, sig, _)) => {
                     job.status = JobStatus::Done;
@@ -43,98 +72,166 @@ pub fn fg_command(shell: &mut Shell, args: &[String]) -> i32 {
                 }
                 Ok(WaitStatus::Stopped(_, _)) => {
                     job.status = JobStatus::Stopped;
-                    148  // 128 + SIGTSTP (20)
+                    128 + Signal::SIGTSTP as i32
                 }
                 _ => 1,
             }
         }
-        None => {
-            eprintln!("fg: no such job");
+        Err(e) => {
+            eprintln!("fg: {}", e);
             1
         }
     }
 }
 
 pub fn bg_command(shell: &mut Shell, args: &[String]) -> i32 {
-    let job_id = if args.is_empty() {
-        shell.jobs.iter().rposition(|j| matches!(j.status, JobStatus::Stopped))
-    } else {
-        args[0].parse::<usize>().ok().and_then(|id| {
-            if id > 0 && id <= shell.jobs.len() {
-                Some(id - 1)
-            } else {
-                None
-            }
-        })
-    };
+    let job_id = find_job(shell, args.first().map_or("%+", String::as_str));
     
     match job_id {
-        Some(idx) => {
+        Ok(idx) => {
             let job = &mut shell.jobs[idx];
             if matches!(job.status, JobStatus::Stopped) {
                 kill(Pid::from_raw(job.pid), Signal::SIGCONT).ok();
                 job.status = JobStatus::Running;
-                println!("[{}] {} &", idx + 1, job.command);
+                outln!("[{}] {} &", idx + 1, job.command);
                 0
             } else {
                 eprintln!("bg: job already running");
                 1
             }
         }
-        None => {
-            eprintln!("bg: no such job");
+        Err(e) => {
+            eprintln!("bg: {}", e);
             1
         }
     }
 }
 
+// `kill [-s SIG | -SIG] %job|pid...` sends SIGTERM or the signal given by
+// name or number; `kill -l` lists the signals and `kill -l N` names one
+// (N may be an exit status like 137)
 pub fn kill_job(shell: &mut Shell, args: &[String]) -> i32 {
-    if args.is_empty() {
-        eprintln!("kill: usage: kill [job_id|pid]");
-        return 1;
+    const USAGE: &str = "kill: usage: kill [-s sigspec | -sigspec] pid | %job ... or kill -l [sigspec]";
+    let (signal, targets) = match args {
+        [] => {
+            eprintln!("{}", USAGE);
+            return 2;
+        }
+        [flag, rest @ ..] if flag == "-l" || flag == "-L" => return list_signals(rest),
+        [flag, name, rest @ ..] if flag == "-s" || flag == "-n" => (name.as_str(), rest),
+        [flag] if flag == "-s" || flag == "-n" => {
+            eprintln!("kill: {}: option requires an argument", flag);
+            eprintln!("{}", USAGE);
+            return 2;
+        }
+        [flag, rest @ ..] if flag == "--" => ("TERM", rest),
+        [flag, rest @ ..] if flag.len() > 1 && flag.starts_with('-') && !rest.is_empty() => (&flag[1..], rest),
+        _ => ("TERM", args),
+    };
+    // Signal 0 only checks that the process exists
+    let signal = match signal {
+        "0" => None,
+        name => match signal_handler::parse_signal(name) {
+            Some(signal) => Some(signal),
+            None => {
+                eprintln!("kill: {}: invalid signal specification", name);
+                return 1;
+            }
+        },
+    };
+    if targets.is_empty() {
+        eprintln!("{}", USAGE);
+        return 2;
     }
+    let signal_name = signal.map_or("signal 0", |signal| signal.as_str());
     
-    let target = &args[0];
-    
-    if target.starts_with('%') {
-        // Job ID
-        if let Ok(job_id) = target[1..].parse::<usize>() {
-            if job_id > 0 && job_id <= shell.jobs.len() {
-                let pid = shell.jobs[job_id - 1].pid;
-                match kill(Pid::from_raw(pid), Signal::SIGTERM) {
-                    Ok(_) => {
-                        println!("Sent SIGTERM to job {}", job_id);
-                        0
-                    }
-                    Err(e) => {
-                        eprintln!("kill: failed to kill job {}: {}", job_id, e);
-                        1
-                    }
+    let mut status = 0;
+    for target in targets {
+        let (pid, what) = if target.starts_with('%') {
+            match find_job(shell, target) {
+                Ok(idx) => (shell.jobs[idx].pid, format!("job {}", shell.jobs[idx].id)),
+                Err(e) => {
+                    eprintln!("kill: {}", e);
+                    status = 1;
+                    continue;
                 }
-            } else {
-                eprintln!("kill: %{}: no such job", job_id);
-                1
             }
         } else {
-            eprintln!("kill: invalid job id");
-            1
-        }
-    } else {
-        // PID
-        if let Ok(pid) = target.parse::<i32>() {
-            match kill(Pid::from_raw(pid), Signal::SIGTERM) {
-                Ok(_) => {
-                    println!("Sent SIGTERM to process {}", pid);
-                    0
+            match target.parse::<i32>() {
+                Ok(pid) => (pid, format!("process {}", pid)),
+                Err(_) => {
+                    eprintln!("kill: {}: arguments must be process or job IDs", target);
+                    status = 1;
+                    continue;
                 }
-                Err(e) => {
-                    eprintln!("kill: failed to kill process {}: {}", pid, e);
-                    1
+            }
+        };
+        match kill(Pid::from_raw(pid), signal) {
+            Ok(_) if signal.is_some() => outln!("Sent {} to {}", signal_name, what),
+            Ok(_) => {}
+            Err(e) => {
+                eprintln!("kill: failed to signal {}: {}", what, e);
+                status = 1;
+            }
+        }
+    }
+    status
+}
+
+// `kill -l`: every signal with its number; with arguments, the name of
+// each number (an exit status above 128 counts as its signal) or the
+// number of each name
+fn list_signals(args: &[String]) -> i32 {
+    if args.is_empty() {
+        for signal in Signal::iterator() {
+            outln!("{:2}) {}", signal as i32, signal.as_str());
+        }
+        return 0;
+    }
+    let mut status = 0;
+    for arg in args {
+        match arg.parse::<i32>() {
+            Ok(number) => {
+                let number = if number > 128 { number - 128 } else { number };
+                match Signal::try_from(number) {
+                    Ok(signal) => outln!("{}", &signal.as_str()[3..]),
+                    Err(_) => {
+                        eprintln!("kill: {}: invalid signal specification", arg);
+                        status = 1;
+                    }
                 }
             }
-        } else {
-            eprintln!("kill: invalid process id");
-            1
+            Err(_) => match signal_handler::parse_signal(arg) {
+                Some(signal) => outln!("{}", signal as i32),
+                None => {
+                    eprintln!("kill: {}: invalid signal specification", arg);
+                    status = 1;
+                }
+            },
         }
     }
-}
\ No newline at end of file
+    status
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn job_specs() {
+        let mut shell = Shell::new();
+        shell.add_job(1001, "sleep 100".to_string());
+        shell.add_job(1002, "vim notes".to_string());
+        shell.add_job(1003, "sleep 200".to_string());
+        shell.jobs[1].status = JobStatus::Stopped;
+
+        assert_eq!(find_job(&shell, "%+"), Ok(1));
+        assert_eq!(find_job(&shell, "%-"), Ok(2));
+        assert_eq!(find_job(&shell, "%1"), Ok(0));
+        assert_eq!(find_job(&shell, "3"), Ok(2));
+        assert_eq!(find_job(&shell, "%vim"), Ok(1));
+        assert_eq!(find_job(&shell, "%?200"), Ok(2));
+        assert!(find_job(&shell, "%sleep").is_err());
+        assert!(find_job(&shell, "%4").is_err());
+    }
+}
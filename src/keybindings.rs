@@ -0,0 +1,268 @@
+// src/keybindings.rs
+//
+// Key bindings, from the config's [keybindings] table and the `bind`
+// builtin. A key is written like "Ctrl-G", "Alt-b", "F5" or "Ctrl-X Ctrl-E"
+// for two in a row; it maps to an editor action by its readline name
+// ("beginning-of-line", "kill-word", ...) or else to a shell command. A
+// command runs as if typed, and the line being edited comes back after.
+// The prompt loop applies the table whenever it changes, so `bind` and
+// `config reload` take effect at the next prompt.
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use rustyline::{
+    At, Anchor, Cmd, ConditionalEventHandler, Editor, Event, EventContext, EventHandler, Helper,
+    KeyCode, KeyEvent, Modifiers, Movement, RepeatCount, Word,
+};
+use crate::history_search::SearchKey;
+use crate::line_editor::{ContinuationKey, MenuKeys};
+use crate::shell::Shell;
+use crate::outln;
+
+// Editor actions `bind -l` lists, by their readline names
+pub const ACTIONS: &[&str] = &[
+    "abort", "accept-line", "backward-char", "backward-delete-char", "backward-kill-word",
+    "backward-word", "beginning-of-history", "beginning-of-line", "capitalize-word",
+    "clear-screen", "complete", "delete-char", "downcase-word", "end-of-history", "end-of-line",
+    "forward-char", "forward-word", "history-search-backward", "history-search-forward",
+    "kill-line", "kill-whole-line", "kill-word", "next-history", "previous-history",
+    "quoted-insert", "reverse-search-history", "transpose-chars", "transpose-words", "undo",
+    "unix-line-discard", "unix-word-rubout", "upcase-word", "yank", "yank-pop",
+];
+
+// What a key does
+pub enum Binding {
+    Action(Cmd),
+    // The Ctrl-R search in history_search
+    Search,
+    Command(String),
+}
+
+impl Binding {
+    pub fn parse(action: &str) -> Self {
+        let action = action.trim();
+        let cmd = match action {
+            "abort" => Cmd::Abort,
+            "accept-line" => Cmd::AcceptLine,
+            "backward-char" => Cmd::Move(Movement::BackwardChar(1)),
+            "backward-delete-char" => Cmd::Kill(Movement::BackwardChar(1)),
+            "backward-kill-word" => Cmd::Kill(Movement::BackwardWord(1, Word::Emacs)),
+            "backward-word" => Cmd::Move(Movement::BackwardWord(1, Word::Emacs)),
+            "beginning-of-history" => Cmd::BeginningOfHistory,
+            "beginning-of-line" => Cmd::Move(Movement::BeginningOfLine),
+            "capitalize-word" => Cmd::CapitalizeWord,
+            "clear-screen" => Cmd::ClearScreen,
+            "complete" => Cmd::Complete,
+            "delete-char" => Cmd::Kill(Movement::ForwardChar(1)),
+            "downcase-word" => Cmd::DowncaseWord,
+            "end-of-history" => Cmd::EndOfHistory,
+            "end-of-line" => Cmd::Move(Movement::EndOfLine),
+            "forward-char" => Cmd::Move(Movement::ForwardChar(1)),
+            "forward-word" => Cmd::Move(Movement::ForwardWord(1, At::AfterEnd, Word::Emacs)),
+            "history-search-backward" => Cmd::HistorySearchBackward,
+            "history-search-forward" => Cmd::HistorySearchForward,
+            "kill-line" => Cmd::Kill(Movement::EndOfLine),
+            "kill-whole-line" => Cmd::Kill(Movement::WholeLine),
+            "kill-word" => Cmd::Kill(Movement::ForwardWord(1, At::AfterEnd, Word::Emacs)),
+            "next-history" => Cmd::NextHistory,
+            "previous-history" => Cmd::PreviousHistory,
+            "quoted-insert" => Cmd::QuotedInsert,
+            "reverse-search-history" => return Binding::Search,
+            "transpose-chars" => Cmd::TransposeChars,
+            "transpose-words" => Cmd::TransposeWords(1),
+            "undo" => Cmd::Undo(1),
+            "unix-line-discard" => Cmd::Kill(Movement::BeginningOfLine),
+            "unix-word-rubout" => Cmd::Kill(Movement::BackwardWord(1, Word::Big)),
+            "upcase-word" => Cmd::UpcaseWord,
+            "yank" => Cmd::Yank(1, Anchor::Before),
+            "yank-pop" => Cmd::YankPop,
+            command => return Binding::Command(command.to_string()),
+        };
+        Binding::Action(cmd)
+    }
+}
+
+// "Ctrl-X Ctrl-E" as the keys to press
+pub fn parse_keys(keys: &str) -> Result<Event, String> {
+    let keys = keys.split_whitespace().map(parse_key).collect::<Result<Vec<_>, _>>()?;
+    if keys.is_empty() {
+        return Err("no key given".to_string());
+    }
+    Ok(Event::KeySeq(keys))
+}
+
+// One key with its modifiers: "Ctrl-g", "C-g", "Alt-.", "M-b", "Shift-Tab", "F5"
+fn parse_key(chord: &str) -> Result<KeyEvent, String> {
+    let mut modifiers = Modifiers::NONE;
+    let mut rest = chord;
+    // The last part is the key, even if it is itself a `-`
+    while let Some((modifier, key)) = rest.split_once('-').filter(|(_, key)| !key.is_empty()) {
+        modifiers |= match modifier.to_ascii_lowercase().as_str() {
+            "ctrl" | "control" | "c" => Modifiers::CTRL,
+            "alt" | "meta" | "m" => Modifiers::ALT,
+            "shift" | "s" => Modifiers::SHIFT,
+            _ => return Err(format!("{}: unknown modifier `{}'", chord, modifier)),
+        };
+        rest = key;
+    }
+    let code = match rest.to_ascii_lowercase().as_str() {
+        "tab" => KeyCode::Tab,
+        "enter" | "return" => KeyCode::Enter,
+        "esc" | "escape" => KeyCode::Esc,
+        "backspace" => KeyCode::Backspace,
+        "delete" | "del" => KeyCode::Delete,
+        "insert" => KeyCode::Insert,
+        "home" => KeyCode::Home,
+        "end" => KeyCode::End,
+        "up" => KeyCode::Up,
+        "down" => KeyCode::Down,
+        "left" => KeyCode::Left,
+        "right" => KeyCode::Right,
+        "pageup" => KeyCode::PageUp,
+        "pagedown" => KeyCode::PageDown,
+        "space" => KeyCode::Char(' '),
+        name => match (name.strip_prefix('f').and_then(|n| n.parse::<u8>().ok()), rest.chars().count()) {
+            (Some(n @ 1..=24), _) => KeyCode::F(n),
+            (_, 1) => return Ok(KeyEvent::normalize(KeyEvent::new(rest.chars().next().unwrap_or(' '), modifiers))),
+            _ => return Err(format!("{}: unknown key `{}'", chord, rest)),
+        },
+    };
+    Ok(KeyEvent::normalize(KeyEvent(code, modifiers)))
+}
+
+// Bound to keys that run a command: keeps the command and the line being
+// edited, and ends `readline` (as an interrupt) so the prompt loop runs it
+#[derive(Clone, Default)]
+pub struct CommandKeys {
+    requested: Arc<Mutex<Option<(String, String)>>>,
+}
+
+impl CommandKeys {
+    // The command to run and the line to edit after it, once
+    pub fn take(&self) -> Option<(String, String)> {
+        self.requested.lock().unwrap().take()
+    }
+
+    fn handler(&self, command: String) -> CommandKey {
+        CommandKey { keys: self.clone(), command }
+    }
+}
+
+struct CommandKey {
+    keys: CommandKeys,
+    command: String,
+}
+
+impl ConditionalEventHandler for CommandKey {
+    fn handle(&self, _evt: &Event, _n: RepeatCount, _positive: bool, ctx: &EventContext) -> Option<Cmd> {
+        *self.keys.requested.lock().unwrap() = Some((self.command.clone(), ctx.line().to_string()));
+        Some(Cmd::Interrupt)
+    }
+}
+
+// Replaces the `previous` bindings on the editor with `bindings`; the
+// shell's own keys come back first, so a binding can still override them
+pub fn apply<H: Helper>(
+    rl: &mut Editor<H>,
+    previous: &HashMap<String, String>,
+    bindings: &HashMap<String, String>,
+    search: &SearchKey,
+    menu: &MenuKeys,
+    commands: &CommandKeys,
+    continuation: &ContinuationKey,
+) {
+    for keys in previous.keys() {
+        if let Ok(event) = parse_keys(keys) {
+            rl.unbind_sequence(event);
+        }
+    }
+    rl.bind_sequence(KeyEvent::ctrl('R'), EventHandler::Conditional(Box::new(search.clone())));
+    menu.bind(rl);
+    rl.bind_sequence(KeyEvent(KeyCode::Enter, Modifiers::NONE), EventHandler::Conditional(Box::new(continuation.clone())));
+    let mut sorted: Vec<_> = bindings.iter().collect();
+    sorted.sort();
+    for (keys, action) in sorted {
+        let event = match parse_keys(keys) {
+            Ok(event) => event,
+            Err(e) => {
+                eprintln!("rshell: keybindings: {}", e);
+                continue;
+            }
+        };
+        let handler = match Binding::parse(action) {
+            Binding::Action(cmd) => EventHandler::Simple(cmd),
+            Binding::Search => EventHandler::Conditional(Box::new(search.clone())),
+            Binding::Command(command) => EventHandler::Conditional(Box::new(commands.handler(command))),
+        };
+        rl.bind_sequence(event, handler);
+    }
+}
+
+// `bind` lists the bindings, `bind -l` the editor actions, `bind KEY
+// ACTION...` binds a key for this session and `bind -r KEY` unbinds it.
+// Bindings meant to stay go in the config's [keybindings] table.
+pub fn builtin_bind(shell: &mut Shell, args: &[String]) -> i32 {
+    match args.first().map(String::as_str) {
+        None | Some("-p") => {
+            let mut bindings: Vec<_> = shell.config.keybindings.iter().collect();
+            bindings.sort();
+            for (keys, action) in bindings {
+                outln!("{:<15} {}", keys, action);
+            }
+            0
+        }
+        Some("-l") => {
+            for action in ACTIONS {
+                outln!("{}", action);
+            }
+            0
+        }
+        Some("-r") => {
+            let Some(keys) = args.get(1) else {
+                eprintln!("bind: usage: bind -r KEY");
+                return 2;
+            };
+            if shell.config.keybindings.remove(keys).is_none() {
+                eprintln!("bind: {}: not bound", keys);
+                return 1;
+            }
+            0
+        }
+        Some(keys) => {
+            if args.len() < 2 {
+                eprintln!("bind: usage: bind [-l | -p | -r KEY | KEY ACTION...]");
+                return 2;
+            }
+            if let Err(e) = parse_keys(keys) {
+                eprintln!("bind: {}", e);
+                return 1;
+            }
+            shell.config.keybindings.insert(keys.to_string(), args[1..].join(" "));
+            0
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keys_and_actions() {
+        assert_eq!(parse_keys("Ctrl-g"), Ok(Event::from(KeyEvent::ctrl('G'))));
+        assert_eq!(parse_keys("M-b"), Ok(Event::from(KeyEvent::alt('b'))));
+        assert_eq!(parse_keys("Shift-Tab"), Ok(Event::from(KeyEvent(KeyCode::BackTab, Modifiers::NONE))));
+        assert_eq!(parse_keys("Alt--"), Ok(Event::from(KeyEvent::alt('-'))));
+        assert_eq!(
+            parse_keys("Ctrl-X Ctrl-E"),
+            Ok(Event::KeySeq(vec![KeyEvent::ctrl('X'), KeyEvent::ctrl('E')]))
+        );
+        assert_eq!(parse_keys("F5"), Ok(Event::from(KeyEvent(KeyCode::F(5), Modifiers::NONE))));
+        assert!(parse_keys("Hyper-x").is_err());
+        assert!(parse_keys("Ctrl-Nope").is_err());
+
+        for action in ACTIONS {
+            assert!(!matches!(Binding::parse(action), Binding::Command(_)), "{}", action);
+        }
+        assert!(matches!(Binding::parse("git status"), Binding::Command(c) if c == "git status"));
+    }
+}
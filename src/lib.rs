@@ -2,7 +2,10 @@ pub mod command;
 pub mod config;
 pub mod shell;
 pub mod utils;
+pub mod git_completion;
 pub mod history;
+pub mod history_expansion;
+pub mod history_search;
 pub mod scripting;
 
 // Add the new modules
@@ -15,8 +18,31 @@ pub mod async_io;
 pub mod parallel_exec;
 pub mod performance;
 pub mod memory_pool;
+pub mod task_runner;
+pub mod bench;
+pub mod metrics_export;
+pub mod sysinfo;
+pub mod terminal;
+pub mod structured;
+pub mod audit;
+pub mod safety;
+pub mod sandbox;
+pub mod plugin;
+pub mod prompt_command;
+pub mod envrc;
+pub mod bookmark;
+pub mod keybindings;
+pub mod multiplexer;
+pub mod login;
+pub mod invocation;
+pub mod compat;
+pub mod session;
+pub mod platform;
+pub mod option_completion;
+pub mod command_not_found;
 
 pub use command::{Command, CommandType};
+pub use session::{ExecResult, ShellSession};
 
 #[derive(Debug)]
 pub enum ShellError{
@@ -1,16 +1,25 @@
 // src/line_editor.rs
 use rustyline::{
-    completion::{Completer, FilenameCompleter, Pair},
+    completion::{unescape, Completer, FilenameCompleter, Pair},
     highlight::{Highlighter, MatchingBracketHighlighter},
     hint::{Hinter, HistoryHinter},
     validate::{Validator, ValidationResult, ValidationContext},
-    Helper, Context, Result,
+    Cmd, ConditionalEventHandler, Event, EventContext, EventHandler, KeyCode, KeyEvent, Modifiers,
+    RepeatCount, Helper, Context, Result,
 };
 use std::borrow::Cow::{self, Borrowed, Owned};
 use std::collections::HashSet;
 use std::path::Path;
+use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
 use std::env;
+use crate::alias::AliasMap;
+use crate::cache::CommandCache;
 use crate::config::Theme;
+use crate::plugin::PluginManager;
+use crate::terminal;
+use crate::utils::helpers::{display_width, pad_to_width};
+use crate::utils::string_utils::shell_quote_filename;
 
 pub struct ShellHelper {
     pub completer: FilenameCompleter,
@@ -19,28 +28,27 @@ pub struct ShellHelper {
     pub colored_prompt: String,
     pub theme: Theme,
     pub builtins: HashSet<String>,
-    pub aliases: std::collections::HashMap<String, String>,
+    pub aliases: AliasMap,
+    pub functions: HashSet<String>,
+    pub plugins: PluginManager,
+    // Shell variable names, for `$NAME` and `unset`
+    pub variables: Vec<String>,
+    // Job ids and commands, for `fg`, `bg` and `kill %`
+    pub jobs: Vec<(usize, String)>,
+    // Shared with the shell; holds what `git` completion asks git for
+    pub cache: CommandCache,
+    // general.completion_style = "Menu": names match fuzzily
+    pub fuzzy: bool,
+    // theme.continuation_prompt, shown where ContinuationKey left room
+    pub continuation_prompt: String,
 }
 
 impl ShellHelper {
-    pub fn new(theme: Theme, aliases: std::collections::HashMap<String, String>) -> Self {
-        let mut builtins = HashSet::new();
-        builtins.insert("cd".to_string());
-        builtins.insert("pwd".to_string());
-        builtins.insert("echo".to_string());
-        builtins.insert("export".to_string());
-        builtins.insert("unset".to_string());
-        builtins.insert("exit".to_string());
-        builtins.insert("history".to_string());
-        builtins.insert("jobs".to_string());
-        builtins.insert("help".to_string());
-        builtins.insert("fg".to_string());
-        builtins.insert("bg".to_string());
-        builtins.insert("kill".to_string());
-        builtins.insert("alias".to_string());
-        builtins.insert("unalias".to_string());
-        builtins.insert("theme".to_string());
-        builtins.insert("config".to_string());
+    pub fn new(theme: Theme, aliases: AliasMap) -> Self {
+        let builtins = crate::shell::builtins::BUILTINS.iter()
+            .chain(crate::structured::COMMANDS)
+            .map(|name| name.to_string())
+            .collect();
         
         Self {
             completer: FilenameCompleter::new(),
@@ -50,16 +58,65 @@ impl ShellHelper {
             theme,
             builtins,
             aliases,
+            functions: HashSet::new(),
+            plugins: PluginManager::new(),
+            variables: Vec::new(),
+            jobs: Vec::new(),
+            cache: CommandCache::new(),
+            fuzzy: false,
+            continuation_prompt: "> ".to_string(),
         }
     }
     
     pub fn set_prompt(&mut self, prompt: &str) {
-        self.colored_prompt = self.theme.colorize_prompt(prompt);
+        self.set_styled_prompt(self.theme.colorize_prompt(prompt));
     }
     
-    pub fn update_aliases(&mut self, aliases: std::collections::HashMap<String, String>) {
+    // A prompt that brings its own colors, e.g. from general.prompt_command
+    pub fn set_styled_prompt(&mut self, prompt: String) {
+        self.colored_prompt = prompt;
+        if self.theme.semantic_prompts {
+            self.colored_prompt = terminal::mark_prompt(&self.theme, &self.colored_prompt);
+        }
+    }
+    
+    pub fn update_aliases(&mut self, aliases: AliasMap) {
         self.aliases = aliases;
     }
+    
+    pub fn update_functions(&mut self, functions: HashSet<String>) {
+        self.functions = functions;
+    }
+    
+    pub fn set_plugins(&mut self, plugins: PluginManager) {
+        self.plugins = plugins;
+    }
+    
+    pub fn set_continuation_prompt(&mut self, prompt: &str) {
+        self.continuation_prompt = prompt.to_string();
+    }
+    
+    pub fn set_fuzzy(&mut self, fuzzy: bool) {
+        self.fuzzy = fuzzy;
+    }
+    
+    // Whether `name` completes `word`: starts with it, or with fuzzy
+    // matching has its characters in order
+    fn matches(&self, name: &str, word: &str) -> bool {
+        name.starts_with(word) || (self.fuzzy && crate::history_search::matches(name, word, true))
+    }
+    
+    pub fn set_cache(&mut self, cache: CommandCache) {
+        self.cache = cache;
+    }
+    
+    pub fn update_variables(&mut self, variables: Vec<String>) {
+        self.variables = variables;
+    }
+    
+    pub fn update_jobs(&mut self, jobs: Vec<(usize, String)>) {
+        self.jobs = jobs;
+    }
 }
 
 impl Completer for ShellHelper {
@@ -71,35 +128,72 @@ impl Completer for ShellHelper {
         pos: usize,
         ctx: &Context<'_>,
     ) -> Result<(usize, Vec<Pair>)> {
-        // First, try file completion
-        let (_start, mut candidates) = self.completer.complete(line, pos, ctx)?;
+        // `$NAME` or `${NAME`: variables wherever they are
+        let name_start = line[..pos]
+            .rfind(|ch: char| !(ch.is_ascii_alphanumeric() || ch == '_'))
+            .map_or(0, |i| i + 1);
+        let braced = line[..name_start].ends_with("${");
+        if braced || line[..name_start].ends_with('$') {
+            let prefix = &line[name_start..pos];
+            let candidates = self.variables.iter()
+                .filter(|name| self.matches(name, prefix))
+                .map(|name| Pair {
+                    display: name.clone(),
+                    replacement: if braced { format!("{}}}", name) } else { name.clone() },
+                })
+                .collect();
+            return Ok((name_start, sorted(candidates)));
+        }
+        
+        // First, try file completion. Its start accounts for quotes and
+        // escaped spaces, so it also marks the word being completed.
+        let (word_start, mut candidates) = self.completer.complete(line, pos, ctx)?;
+        let in_quotes = line[..word_start].ends_with(['"', '\'']);
+        if self.fuzzy && !in_quotes {
+            candidates = self.fuzzy_files(&line[word_start..pos]);
+        } else if !in_quotes {
+            for candidate in &mut candidates {
+                candidate.replacement = shell_quote_filename(&unescape(&candidate.replacement, Some('\\')));
+            }
+        }
+        let word = unescape(&line[word_start..pos], Some('\\'));
+        let word = word.as_ref();
         
-        // Find the word being completed
-        let word_start = line[..pos].rfind(' ').map(|i| i + 1).unwrap_or(0);
-        let word = &line[word_start..pos];
+        // Arguments whose kind the command tells: only those are offered
+        let segment = line[..word_start].rsplit(['|', ';', '&']).next().unwrap_or("");
+        let mut segment_words = segment.split_whitespace();
+        if let Some(command) = segment_words.next() {
+            let args: Vec<&str> = segment_words.collect();
+            if let Some(typed) = self.complete_argument(command, &args, word, &mut candidates) {
+                return Ok((word_start, sorted(typed)));
+            }
+        }
         
         // If we're at the beginning of the line or after a pipe/semicolon, add commands
-        if word_start == 0 || line[..word_start].trim_end().ends_with('|') 
+        if line[..word_start].trim().is_empty() || line[..word_start].trim_end().ends_with('|') 
             || line[..word_start].trim_end().ends_with(';') {
             
-            // Add builtins
-            for builtin in &self.builtins {
-                if builtin.starts_with(word) {
+            // Add builtins, including those registered by plugins
+            for builtin in self.builtins.iter().cloned().chain(self.plugins.builtin_names()) {
+                if self.matches(&builtin, word) {
                     candidates.push(Pair {
                         display: builtin.clone(),
-                        replacement: builtin.clone(),
+                        replacement: builtin,
                     });
                 }
             }
             
-            // Add aliases
-            for (alias, _) in &self.aliases {
-                if alias.starts_with(word) {
-                    candidates.push(Pair {
-                        display: format!("{} (alias)", alias),
-                        replacement: alias.clone(),
-                    });
-                }
+            // Add aliases and functions, with their tags lined up in the listing
+            let matching: Vec<_> = self.aliases.keys().map(|a| (&**a, "alias"))
+                .chain(self.functions.iter().map(|f| (f.as_str(), "function")))
+                .filter(|(name, _)| self.matches(name, word))
+                .collect();
+            let name_width = matching.iter().map(|(name, _)| display_width(name)).max().unwrap_or(0);
+            for (name, kind) in matching {
+                candidates.push(Pair {
+                    display: format!("{} ({})", pad_to_width(name, name_width), kind),
+                    replacement: shell_quote_filename(name),
+                });
             }
             
             // Add commands from PATH
@@ -108,10 +202,10 @@ impl Completer for ShellHelper {
                     if let Ok(entries) = std::fs::read_dir(dir) {
                         for entry in entries.flatten() {
                             if let Some(name) = entry.file_name().to_str() {
-                                if name.starts_with(word) && is_executable(&entry.path()) {
+                                if self.matches(name, word) && is_executable(&entry.path()) {
                                     candidates.push(Pair {
                                         display: name.to_string(),
-                                        replacement: name.to_string(),
+                                        replacement: shell_quote_filename(name),
                                     });
                                 }
                             }
@@ -119,14 +213,136 @@ impl Completer for ShellHelper {
                     }
                 }
             }
+        } else {
+            // Arguments of a command a plugin knows how to complete
+            let segment = line[..word_start].rsplit(['|', ';', '&']).next().unwrap_or("");
+            let mut words = segment.split_whitespace().map(String::from);
+            if let Some(command) = words.next() {
+                let args: Vec<String> = words.collect();
+                for completion in self.plugins.complete(&command, &args, word).unwrap_or_default() {
+                    candidates.push(Pair {
+                        replacement: shell_quote_filename(&completion),
+                        display: completion,
+                    });
+                }
+            }
         }
         
-        // Sort and deduplicate
-        candidates.sort_by(|a, b| a.display.cmp(&b.display));
-        candidates.dedup_by(|a, b| a.replacement == b.replacement);
-        
-        Ok((word_start, candidates))
+        Ok((word_start, sorted(candidates)))
+    }
+}
+
+// Arrow keys for `completion_style = "Menu"`: while Tab is cycling through
+// candidates, Down and Up move to the next and previous one; otherwise
+// they keep their usual meaning. Bound to Tab, Shift-Tab, Down, Up and,
+// to notice the cycling ending, any other key.
+#[derive(Clone, Default)]
+pub struct MenuKeys {
+    enabled: Arc<AtomicBool>,
+    // The line as it was when Tab was pressed, and whether a candidate has
+    // replaced it since; if none has, there was nothing to cycle through
+    tab_line: Arc<Mutex<Option<(String, bool)>>>,
+}
+
+impl MenuKeys {
+    pub fn bind<H: Helper>(&self, rl: &mut rustyline::Editor<H>) {
+        for event in [
+            Event::from(KeyEvent(KeyCode::Tab, Modifiers::NONE)),
+            Event::from(KeyEvent(KeyCode::BackTab, Modifiers::NONE)),
+            Event::from(KeyEvent(KeyCode::Down, Modifiers::NONE)),
+            Event::from(KeyEvent(KeyCode::Up, Modifiers::NONE)),
+            Event::Any,
+        ] {
+            rl.bind_sequence(event, EventHandler::Conditional(Box::new(self.clone())));
+        }
+    }
+    
+    pub fn set_enabled(&self, enabled: bool) {
+        self.enabled.store(enabled, Ordering::Relaxed);
+    }
+}
+
+impl ConditionalEventHandler for MenuKeys {
+    fn handle(&self, evt: &Event, _n: RepeatCount, _positive: bool, ctx: &EventContext) -> Option<Cmd> {
+        if !self.enabled.load(Ordering::Relaxed) {
+            return None;
+        }
+        let mut tab_line = self.tab_line.lock().unwrap();
+        if let Some((line, replaced)) = tab_line.as_mut() {
+            *replaced |= line != ctx.line();
+        }
+        let cycling = tab_line.as_ref().is_some_and(|(_, replaced)| *replaced);
+        match evt.get(0).map(|key| key.0) {
+            Some(KeyCode::Tab | KeyCode::BackTab) => {
+                tab_line.get_or_insert_with(|| (ctx.line().to_string(), false));
+                None
+            }
+            Some(KeyCode::Down) if cycling => Some(Cmd::Complete),
+            Some(KeyCode::Up) if cycling => Some(Cmd::CompleteBackward),
+            _ => {
+                *tab_line = None;
+                None
+            }
+        }
+    }
+}
+
+// Bound to Enter: a line that is not finished yet gets a newline and, for
+// the continuation prompt to be drawn in, as many spaces as it is wide.
+// The editor then keeps every line of the command in one buffer, so the
+// arrow keys go back up into earlier lines. Enter is passed on to MenuKeys
+// too, as the key it would otherwise see.
+#[derive(Clone)]
+pub struct ContinuationKey {
+    width: Arc<AtomicUsize>,
+    menu: MenuKeys,
+}
+
+impl ContinuationKey {
+    pub fn new(menu: &MenuKeys) -> Self {
+        Self { width: Arc::new(AtomicUsize::new(2)), menu: menu.clone() }
+    }
+    
+    pub fn set_prompt(&self, prompt: &str) {
+        self.width.store(display_width(prompt), Ordering::Relaxed);
+    }
+}
+
+impl ConditionalEventHandler for ContinuationKey {
+    fn handle(&self, evt: &Event, n: RepeatCount, positive: bool, ctx: &EventContext) -> Option<Cmd> {
+        self.menu.handle(evt, n, positive, ctx);
+        if !crate::shell::parser::needs_more_input(ctx.line()) {
+            return None;
+        }
+        Some(Cmd::Insert(1, format!("\n{}", " ".repeat(self.width.load(Ordering::Relaxed)))))
+    }
+}
+
+// A line read with ContinuationKey without the room it left for `prompt`
+pub fn strip_continuation(line: &str, prompt: &str) -> String {
+    let indent = " ".repeat(display_width(prompt));
+    if indent.is_empty() || !line.contains('\n') {
+        return line.to_string();
     }
+    let mut lines = line.split('\n');
+    let mut stripped = lines.next().unwrap_or_default().to_string();
+    for next in lines {
+        stripped.push('\n');
+        stripped.push_str(next.strip_prefix(indent.as_str()).unwrap_or(next));
+    }
+    stripped
+}
+
+// The other way round, for a command put back in the editor's history
+pub fn indent_continuation(line: &str, prompt: &str) -> String {
+    line.replace('\n', &format!("\n{}", " ".repeat(display_width(prompt))))
+}
+
+// Sorted by what is shown, without repeats
+fn sorted(mut candidates: Vec<Pair>) -> Vec<Pair> {
+    candidates.sort_by(|a, b| a.display.cmp(&b.display));
+    candidates.dedup_by(|a, b| a.replacement == b.replacement);
+    candidates
 }
 
 impl Hinter for ShellHelper {
@@ -190,11 +406,32 @@ impl Highlighter for ShellHelper {
                     }
                     is_first_word = false;
                 }
+                '\n' => {
+                    if !current_word.is_empty() {
+                        let colored = self.colorize_word(&current_word, is_first_word || after_pipe);
+                        highlighted.push_str(&colored);
+                        current_word.clear();
+                    }
+                    highlighted.push(ch);
+                    // The room ContinuationKey left shows the continuation prompt
+                    let indent = display_width(&self.continuation_prompt);
+                    if indent > 0 && chars.clone().take(indent).filter(|c| *c == ' ').count() == indent {
+                        chars.nth(indent - 1);
+                        highlighted.push_str(&self.theme.colorize_prompt(&self.continuation_prompt));
+                        if in_string {
+                            highlighted.push_str(&self.theme.string_color);
+                        }
+                    }
+                    if !in_string {
+                        is_first_word = true;
+                        after_pipe = false;
+                    }
+                }
                 '$' if !in_string => {
                     if chars.peek() == Some(&'(') || chars.peek() == Some(&'{') {
                         highlighted.push_str(&self.theme.variable_color);
                         highlighted.push(ch);
-                    } else if chars.peek().map_or(false, |c| c.is_alphabetic() || *c == '_') {
+                    } else if chars.peek().is_some_and(|c| c.is_alphabetic() || *c == '_') {
                         highlighted.push_str(&self.theme.variable_color);
                         highlighted.push(ch);
                         // Continue reading variable name
@@ -237,10 +474,11 @@ impl Highlighter for ShellHelper {
         prompt: &'p str,
         default: bool,
     ) -> Cow<'b, str> {
+        // `default` is false for rustyline's own prompts, e.g. reverse search
         if default {
-            Borrowed(prompt)
-        } else {
             Borrowed(&self.colored_prompt)
+        } else {
+            Borrowed(prompt)
         }
     }
     
@@ -255,28 +493,8 @@ impl Highlighter for ShellHelper {
 
 impl Validator for ShellHelper {
     fn validate(&self, ctx: &mut ValidationContext) -> Result<ValidationResult> {
-        let input = ctx.input();
-        
-        // Check for unclosed quotes
-        let mut in_single_quote = false;
-        let mut in_double_quote = false;
-        let mut escape_next = false;
-        
-        for ch in input.chars() {
-            if escape_next {
-                escape_next = false;
-                continue;
-            }
-            
-            match ch {
-                '\\' => escape_next = true,
-                '\'' if !in_double_quote => in_single_quote = !in_single_quote,
-                '"' if !in_single_quote => in_double_quote = !in_double_quote,
-                _ => {}
-            }
-        }
-        
-        if in_single_quote || in_double_quote {
+        // Open quotes and unfinished here-documents continue on the next line
+        if crate::shell::parser::needs_more_input(ctx.input()) {
             Ok(ValidationResult::Incomplete)
         } else {
             Ok(ValidationResult::Valid(None))
@@ -287,11 +505,91 @@ impl Validator for ShellHelper {
 impl Helper for ShellHelper {}
 
 impl ShellHelper {
+    // Candidates for `word`, an argument of `command` after `args`, when
+    // the command takes a known kind of argument there; `files` are the
+    // file name candidates
+    fn complete_argument(&self, command: &str, args: &[&str], word: &str, files: &mut Vec<Pair>) -> Option<Vec<Pair>> {
+        let names = |names: &mut dyn Iterator<Item = &str>| -> Vec<Pair> {
+            names.filter(|name| self.matches(name, word))
+                .map(|name| Pair { display: name.to_string(), replacement: name.to_string() })
+                .collect()
+        };
+        match command {
+            "cd" | "pushd" => Some(std::mem::take(files).into_iter()
+                .filter(|candidate| candidate.replacement.ends_with('/'))
+                .collect()),
+            "fg" | "bg" => Some(self.job_candidates(word)),
+            "kill" if word.starts_with('%') => Some(self.job_candidates(word)),
+            "unset" | "export" | "readonly" => Some(names(&mut self.variables.iter().map(String::as_str))),
+            "unalias" => Some(names(&mut self.aliases.keys().map(|name| &**name))),
+            "git" => {
+                let dir = env::current_dir().ok()?;
+                Some(names(&mut crate::git_completion::complete(&self.cache, &dir, args, word)?.iter().map(String::as_str)))
+            }
+            "theme" if args == ["set"] => Some(names(&mut crate::config::Config::THEMES.iter().copied())),
+            _ if word.starts_with('-') => {
+                let options = crate::option_completion::options(&self.cache, command)?;
+                let matching: Vec<_> = options.iter().filter(|(flag, _)| flag.starts_with(word)).collect();
+                let flag_width = matching.iter().map(|(flag, _)| display_width(flag)).max().unwrap_or(0);
+                Some(matching.into_iter()
+                    .map(|(flag, text)| Pair {
+                        display: format!("{}  {}", pad_to_width(flag, flag_width), text).trim_end().to_string(),
+                        replacement: flag.clone(),
+                    })
+                    .collect())
+            }
+            _ => None,
+        }
+    }
+    
+    // Files whose names match the last part of `word` fuzzily, in the
+    // directory its earlier parts name
+    fn fuzzy_files(&self, word: &str) -> Vec<Pair> {
+        let (dir, name) = match word.rfind('/') {
+            Some(slash) => word.split_at(slash + 1),
+            None => ("", word),
+        };
+        let name = unescape(name, Some('\\'));
+        let read = match dir {
+            "" => ".".to_string(),
+            dir => crate::utils::string_utils::expand_tilde(&unescape(dir, Some('\\'))),
+        };
+        let Ok(entries) = std::fs::read_dir(&read) else {
+            return Vec::new();
+        };
+        entries.flatten()
+            .filter_map(|entry| {
+                let file_name = entry.file_name().to_str()?.to_string();
+                // Hidden files only when asked for
+                if file_name.starts_with('.') && !name.starts_with('.') {
+                    return None;
+                }
+                if !self.matches(&file_name, &name) {
+                    return None;
+                }
+                let slash = if entry.path().is_dir() { "/" } else { "" };
+                Some(Pair {
+                    display: format!("{}{}", file_name, slash),
+                    replacement: format!("{}{}{}", dir, shell_quote_filename(&file_name), slash),
+                })
+            })
+            .collect()
+    }
+    
+    // `%N` for each job, shown with its command
+    fn job_candidates(&self, word: &str) -> Vec<Pair> {
+        self.jobs.iter()
+            .map(|(id, command)| (format!("%{}", id), command))
+            .filter(|(spec, _)| self.matches(spec, word))
+            .map(|(spec, command)| Pair { display: format!("{}  {}", spec, command), replacement: spec })
+            .collect()
+    }
+    
     fn colorize_word(&self, word: &str, is_command: bool) -> String {
         if is_command {
-            if self.builtins.contains(word) {
+            if self.builtins.contains(word) || self.plugins.has_builtin(word) {
                 format!("{}{}{}", self.theme.builtin_color, word, self.theme.reset_color)
-            } else if self.aliases.contains_key(word) {
+            } else if self.aliases.contains_key(word) || self.functions.contains(word) {
                 format!("{}{}{}", self.theme.alias_color, word, self.theme.reset_color)
             } else if is_command_in_path(word) {
                 format!("{}{}{}", self.theme.command_color, word, self.theme.reset_color)
@@ -325,4 +623,77 @@ fn is_command_in_path(cmd: &str) -> bool {
         }
     }
     false
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rustyline::history::History;
+
+    #[test]
+    fn completion_escapes_file_names() {
+        let helper = ShellHelper::new(Theme::default(), AliasMap::new());
+        let history = History::new();
+        let ctx = Context::new(&history);
+        let dir = std::env::temp_dir().join(format!("rshell-complete-{}", std::process::id()));
+        std::fs::create_dir_all(dir.join("My Documents")).unwrap();
+
+        let line = format!("ls {}/My", dir.display());
+        let (start, candidates) = helper.complete(&line, line.len(), &ctx).unwrap();
+        assert_eq!(start, 3);
+        assert_eq!(candidates[0].replacement, format!("{}/My\\ Documents/", dir.display()));
+
+        let line = format!("ls {}/My\\ D", dir.display());
+        let (start, candidates) = helper.complete(&line, line.len(), &ctx).unwrap();
+        assert_eq!(start, 3);
+        assert_eq!(candidates[0].replacement, format!("{}/My\\ Documents/", dir.display()));
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn completion_knows_argument_kinds() {
+        let mut helper = ShellHelper::new(Theme::default(), AliasMap::new());
+        helper.update_variables(vec!["HOME".to_string(), "HOSTNAME".to_string(), "PATH".to_string()]);
+        helper.update_jobs(vec![(1, "sleep 100".to_string()), (2, "vim".to_string())]);
+        let history = History::new();
+        let ctx = Context::new(&history);
+        let complete = |line: &str| {
+            let (start, candidates) = helper.complete(line, line.len(), &ctx).unwrap();
+            (start, candidates.into_iter().map(|c| c.replacement).collect::<Vec<_>>())
+        };
+        assert_eq!(complete("echo $HO"), (6, vec!["HOME".to_string(), "HOSTNAME".to_string()]));
+        assert_eq!(complete("echo ${PA"), (7, vec!["PATH}".to_string()]));
+        assert_eq!(complete("unset P"), (6, vec!["PATH".to_string()]));
+        assert_eq!(complete("fg "), (3, vec!["%1".to_string(), "%2".to_string()]));
+        assert_eq!(complete("kill %2"), (5, vec!["%2".to_string()]));
+        assert_eq!(complete("theme set d"), (10, vec!["default".to_string(), "dracula".to_string()]));
+
+        let dir = std::env::temp_dir().join(format!("rshell-complete-cd-{}", std::process::id()));
+        std::fs::create_dir_all(dir.join("sub")).unwrap();
+        std::fs::write(dir.join("sub.txt"), "").unwrap();
+        let line = format!("cd {}/su", dir.display());
+        assert_eq!(complete(&line).1, vec![format!("{}/sub/", dir.display())]);
+
+        helper.set_fuzzy(true);
+        let complete = |line: &str| {
+            let (start, candidates) = helper.complete(line, line.len(), &ctx).unwrap();
+            (start, candidates.into_iter().map(|c| c.replacement).collect::<Vec<_>>())
+        };
+        assert_eq!(complete("unset PH"), (6, vec!["PATH".to_string()]));
+        let line = format!("cat {}/stx", dir.display());
+        assert_eq!(complete(&line).1, vec![format!("{}/sub.txt", dir.display())]);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn continuation_lines() {
+        let typed = "for x in a b\n  do echo $x\n  done";
+        assert_eq!(strip_continuation(typed, "> "), "for x in a b\ndo echo $x\ndone");
+        assert_eq!(indent_continuation("for x in a b\ndo echo $x\ndone", "> "), typed);
+        assert_eq!(strip_continuation("echo \"a\nb\"", "> "), "echo \"a\nb\"");
+
+        let mut helper = ShellHelper::new(Theme::default(), AliasMap::new());
+        helper.set_continuation_prompt("~ ");
+        let highlighted = crate::utils::helpers::strip_ansi(&helper.highlight("ls |\n  wc", 0));
+        assert_eq!(highlighted, "ls |\n~ wc");
+    }
+}
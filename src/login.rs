@@ -0,0 +1,120 @@
+// src/login.rs
+use crate::scripting::ScriptEngine;
+use crate::shell::Shell;
+use crate::utils::string_utils::expand_tilde;
+use std::path::Path;
+use std::process::{Command, Stdio};
+
+pub const SYSTEM_PROFILE: &str = "/etc/rshell/profile";
+pub const USER_PROFILE: &str = "~/.rshell_profile";
+pub const USER_LOGOUT: &str = "~/.rshell_logout";
+pub const SYSTEM_RC: &str = "/etc/rshell/rc";
+pub const USER_RC: &str = "~/.config/rshell/rc.rsh";
+// Read instead of USER_RC when that does not exist
+pub const USER_RC_FALLBACK: &str = "~/.rshellrc";
+
+// Set for the `sh -l` the profile is imported from. A ~/.profile that
+// execs rshell -l would otherwise start the import again, forever.
+const IMPORTING_VAR: &str = "RSHELL_IMPORTING_PROFILE";
+
+// Variables that describe this process rather than the login session
+const NOT_IMPORTED: &[&str] = &["PWD", "OLDPWD", "SHLVL", "_", "SHELL", IMPORTING_VAR];
+
+// login(1) and sshd start login shells with a '-' in front of argv[0]
+pub fn is_login_invocation(argv0: &str, args: &[String]) -> bool {
+    Path::new(argv0).file_name().is_some_and(|name| name.to_string_lossy().starts_with('-'))
+        || args.iter().any(|a| a == "-l" || a == "--login")
+}
+
+// Runs `sh -l` once and takes over the environment it ends up with, so the
+// PATH and friends from /etc/profile and /etc/profile.d apply without rshell
+// having to understand their sh syntax
+fn import_sh_profile(shell: &mut Shell) {
+    if shell.environment.contains_key(IMPORTING_VAR) {
+        return;
+    }
+    let output = Command::new("/bin/sh")
+        .args(["-l", "-c", "env -0"])
+        .env(IMPORTING_VAR, "1")
+        .stdin(Stdio::null())
+        .stderr(Stdio::inherit())
+        .output();
+    let Ok(output) = output else {
+        return;
+    };
+    for entry in output.stdout.split(|&b| b == 0) {
+        let entry = String::from_utf8_lossy(entry);
+        if let Some((key, value)) = entry.split_once('=') {
+            if !key.is_empty() && !NOT_IMPORTED.contains(&key) {
+                shell.export_var(key, value.to_string());
+            }
+        }
+    }
+}
+
+// Runs an rshell script if it exists; errors are reported and startup goes on
+pub fn source_if_exists(shell: &mut Shell, path: &str) {
+    let path = expand_tilde(path);
+    let path = Path::new(&path);
+    if !path.is_file() {
+        return;
+    }
+    if let Err(e) = ScriptEngine::new().execute_script(shell, path) {
+        eprintln!("rshell: {}: {}", path.display(), e);
+    }
+}
+
+// Environment every rshell sets up; login shells also load the profiles
+pub fn setup(shell: &mut Shell, login: bool) {
+    let level = shell.environment.get("SHLVL").and_then(|l| l.parse::<u32>().ok()).unwrap_or(0);
+    shell.export_var("SHLVL", (level + 1).to_string());
+    if shell.environment.get("PATH").map_or("", String::as_str).is_empty() {
+        shell.export_var("PATH", crate::platform::DEFAULT_PATH.to_string());
+    }
+    if !shell.environment.contains_key("LOGNAME") {
+        let user = shell.username.clone();
+        shell.export_var("LOGNAME", user);
+    }
+    if !login {
+        return;
+    }
+    shell.login = true;
+    if let Ok(exe) = std::env::current_exe() {
+        shell.export_var("SHELL", exe.to_string_lossy().to_string());
+    }
+    if shell.config.general.import_sh_profile {
+        import_sh_profile(shell);
+    }
+    source_if_exists(shell, SYSTEM_PROFILE);
+    source_if_exists(shell, USER_PROFILE);
+}
+
+// Interactive shells read the rc files, after the profiles of a login
+// shell: the place for aliases, functions and exports in shell syntax
+pub fn source_rc_files(shell: &mut Shell) {
+    source_if_exists(shell, SYSTEM_RC);
+    if Path::new(&expand_tilde(USER_RC)).is_file() {
+        source_if_exists(shell, USER_RC);
+    } else {
+        source_if_exists(shell, USER_RC_FALLBACK);
+    }
+}
+
+// Runs ~/.rshell_logout when a login shell exits, however it exits
+pub fn logout(shell: &mut Shell) {
+    if std::mem::take(&mut shell.login) {
+        source_if_exists(shell, USER_LOGOUT);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn login_detection() {
+        assert!(is_login_invocation("-rshell", &[]));
+        assert!(is_login_invocation("/usr/bin/rshell", &["-l".to_string()]));
+        assert!(!is_login_invocation("/usr/bin/rshell", &["--profile-startup".to_string()]));
+    }
+}
@@ -1,55 +1,140 @@
 // main.rs
-mod shell;
-mod command;
-mod utils;
-mod signal_handler;
-mod job_control;
-mod line_editor;
-mod config;
-mod alias;
-
-use rustyline::{Editor, Config as RustylineConfig, EditMode, CompletionType};
-use shell::{Shell, parser::Parser, executor::Executor};
-use line_editor::ShellHelper;
-use config::Config;
+use std::collections::HashMap;
+use std::thread::{self, JoinHandle};
+use std::time::Instant;
+use bumpalo::Bump;
+use rustyline::{Editor, Config as RustylineConfig, config::Configurer, EditMode, CompletionType};
+use rustyline::history::History as RustylineHistory;
+use rust_shell::audit;
+use rust_shell::cache::CacheWarmer;
+use rust_shell::performance::{slow_command_hint, StartupProfiler};
+use rust_shell::shell::{Shell, builtins, trap, parser::{self, Parser}, executor::Executor};
+use rust_shell::line_editor::{indent_continuation, strip_continuation, ContinuationKey, MenuKeys, ShellHelper};
+use rust_shell::metrics_export::MetricsExporter;
+use rust_shell::config::{self, CompletionStyle, Config};
+use rust_shell::utils::helpers::strip_ansi;
+use rust_shell::history_expansion;
+use rust_shell::history_search::{self, Outcome, SearchKey};
+use rust_shell::invocation::Invocation;
+use rust_shell::scripting::ScriptEngine;
+use rust_shell::keybindings::{self, CommandKeys};
+use rust_shell::{alias, compat, envrc, login, prompt_command, signal_handler, terminal};
 
 fn main() -> Result<(), Box<dyn std::error::Error>> {
-    // Load or create configuration
+    let argv0 = std::env::args().next().unwrap_or_default();
+    let cli_args: Vec<String> = std::env::args().skip(1).collect();
+    if cli_args.first().is_some_and(|a| a == "--sh-compat") {
+        std::process::exit(compat::run_cli(&cli_args[1..]));
+    }
+    if cli_args.first().is_some_and(|a| a == "--ast" || a == "--parse-only") {
+        // Without a command line, all of stdin is parsed as one
+        let input = if cli_args.len() > 1 {
+            cli_args[1..].join(" ")
+        } else {
+            std::io::read_to_string(std::io::stdin())?
+        };
+        std::process::exit(builtins::print_ast(&input));
+    }
+    let invocation = match Invocation::parse(&argv0, &cli_args) {
+        Ok(invocation) => invocation,
+        Err(e) => {
+            eprintln!("rshell: {}", e);
+            eprintln!("usage: rshell [-il] [--norc] [-c COMMAND [NAME [ARG...]] | -s [ARG...] | SCRIPT [ARG...]]");
+            std::process::exit(2);
+        }
+    };
+    let mut startup = StartupProfiler::new(invocation.profile_startup);
+    
+    // Load or create configuration; scripts never stop to ask
     let config = match Config::load() {
         Ok(c) => c,
+        Err(_) if invocation.runs_script() => Config::default(),
         Err(_) => {
             println!("No configuration found. Let's set one up!");
             config::init_config_interactive()?
         }
     };
+    startup.mark("config load");
     
     // Setup signal handlers
     signal_handler::setup_signal_handlers()?;
+    startup.mark("signal setup");
     
     // Initialize shell with config
-    let mut shell = Shell::new();
-    shell.config = config.clone();
-    shell.aliases = config.aliases.clone();
+    let mut shell = Shell::with_config(config.clone());
+    let config_aliases = shell.aliases.clone();
+    startup.mark("shell init");
+    
+    shell.interactive = !invocation.runs_script();
+    login::setup(&mut shell, invocation.login);
+    startup.mark("login environment");
+    
+    shell.plugins.load_configured(&config.plugins);
+    startup.mark("plugins");
+    
+    if invocation.runs_script() {
+        let status = run_script(&mut shell, &invocation);
+        shell.exit(status);
+    }
+    
+    if !invocation.no_rc {
+        login::source_rc_files(&mut shell);
+        startup.mark("rc files");
+    }
+    let startup_aliases = shell.aliases.clone();
     
     // Create line editor with custom helper
     let rustyline_config = RustylineConfig::builder()
         .history_ignore_space(true)
-        .completion_type(CompletionType::List)
+        .completion_type(completion_type(config.general.completion_style))
         .edit_mode(match config.general.edit_mode {
             config::EditMode::Vi => EditMode::Vi,
             config::EditMode::Emacs => EditMode::Emacs,
         })
         .build();
     
-    let helper = ShellHelper::new(config.theme.clone(), shell.aliases.clone());
+    let mut helper = ShellHelper::new(config.theme.clone(), shell.aliases.clone());
+    helper.set_plugins(shell.plugins.clone());
+    helper.set_cache(shell.command_cache.clone());
+    helper.set_fuzzy(config.general.completion_style == CompletionStyle::Menu);
     let mut rl = Editor::with_config(rustyline_config)?;
     rl.set_helper(Some(helper));
+    let search_key = SearchKey::default();
+    let menu_keys = MenuKeys::default();
+    let command_keys = CommandKeys::default();
+    let continuation_key = ContinuationKey::new(&menu_keys);
+    // [keybindings] as last put on the editor
+    let mut applied_keybindings = config.keybindings.clone();
+    keybindings::apply(&mut rl, &HashMap::new(), &applied_keybindings, &search_key, &menu_keys, &command_keys,
+        &continuation_key);
+    // What a Ctrl-R search or a bound command left to edit on the next line
+    let mut initial_line: Option<String> = None;
+    startup.mark("theme and editor setup");
     
-    // Load history
+    // Load history in the background; it is installed into the editor once
+    // ready, and at the latest before the first entry is added.
     let history_file = config.general.history_file.replace('~', &dirs::home_dir()
         .unwrap_or_default()
         .to_string_lossy());
-    let _ = rl.load_history(&history_file);
+    let mut pending_history = Some({
+        let history_file = history_file.clone();
+        thread::spawn(move || {
+            let mut history = RustylineHistory::with_config(rustyline_config);
+            let _ = history.load(&history_file);
+            history
+        })
+    });
+    startup.mark("history load (background)");
+    
+    // Warm the PATH lookup cache off the main thread
+    CacheWarmer::new(shell.command_cache.clone()).warm_up();
+    startup.mark("PATH cache warmup (background)");
+    
+    // Periodic metrics export for monitoring, if configured
+    let metrics_exporter = MetricsExporter::new(
+        config.metrics.clone(), shell.perf.clone(), shell.command_cache.clone());
+    metrics_exporter.clone().start();
+    startup.mark("metrics export");
     
     // Create executor
     let mut executor = Executor::new();
@@ -60,10 +145,16 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
              shell.config.theme.reset_color);
     println!("Theme: {} | Type 'help' for commands", shell.config.theme.name);
     println!();
+    startup.report();
     
     loop {
+        if let Some(handle) = pending_history.take_if(|h| h.is_finished()) {
+            install_history(&mut rl, handle, &mut shell);
+        }
+        
         // Check for completed background jobs
-        executor.check_background_jobs(&mut shell);
+        shell.check_background_jobs();
+        trap::run_pending(&mut shell);
         
         // Check for signals
         let (sigint, sigtstp) = signal_handler::check_signals();
@@ -76,68 +167,169 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
             continue;
         }
         
+        // Load or unload the directory's .envrc
+        envrc::update(&mut shell);
+        
         // Update prompt
-        let prompt = shell.format_prompt();
+        terminal::report_prompt(&shell);
+        let styled_prompt = prompt_command::render(&shell);
+        let prompt = match &styled_prompt {
+            Some(styled) => strip_ansi(styled),
+            None => shell.format_prompt(),
+        };
         if let Some(helper) = rl.helper_mut() {
-            helper.set_prompt(&prompt);
+            match styled_prompt {
+                Some(styled) => helper.set_styled_prompt(styled),
+                None => helper.set_prompt(&prompt),
+            }
             helper.update_aliases(shell.aliases.clone());
+            helper.update_functions(shell.functions.keys().cloned().collect());
+            helper.update_variables(shell.environment.keys().map(|name| name.to_string()).collect());
+            helper.update_jobs(shell.jobs.iter().map(|job| (job.id, job.command.clone())).collect());
+            // `config reload` may have changed it
+            helper.set_fuzzy(shell.config.general.completion_style == CompletionStyle::Menu);
+            helper.set_continuation_prompt(&shell.config.theme.continuation_prompt);
         }
+        continuation_key.set_prompt(&shell.config.theme.continuation_prompt);
+        rl.set_completion_type(completion_type(shell.config.general.completion_style));
+        if shell.config.keybindings != applied_keybindings {
+            keybindings::apply(&mut rl, &applied_keybindings, &shell.config.keybindings,
+                &search_key, &menu_keys, &command_keys, &continuation_key);
+            applied_keybindings = shell.config.keybindings.clone();
+        }
+        menu_keys.set_enabled(shell.config.general.completion_style == CompletionStyle::Menu);
         
         // Read line
-        let readline = rl.readline(&prompt);
+        let readline = match initial_line.take() {
+            Some(initial) => rl.readline_with_initial(&prompt, (&initial, "")),
+            None => rl.readline(&prompt),
+        };
+        // Ctrl-R and keys bound to commands end the line as an interrupt;
+        // search, then run the match or go back to editing, or run the command
+        let readline = match readline {
+            Err(rustyline::error::ReadlineError::Interrupted) => match search_key.take() {
+                Some(editing) => {
+                    if let Some(handle) = pending_history.take() {
+                        install_history(&mut rl, handle, &mut shell);
+                    }
+                    let entries = shell.history.get_all();
+                    match history_search::run(&entries, shell.config.general.history_search_fuzzy) {
+                        Outcome::Run(line) => Ok(line),
+                        Outcome::Edit(line) => {
+                            initial_line = Some(line);
+                            continue;
+                        }
+                        Outcome::Cancel => {
+                            initial_line = Some(editing);
+                            continue;
+                        }
+                    }
+                }
+                None => match command_keys.take() {
+                    Some((command, editing)) => {
+                        initial_line = Some(editing);
+                        Ok(command)
+                    }
+                    None => Err(rustyline::error::ReadlineError::Interrupted),
+                },
+            },
+            other => other,
+        };
         
         match readline {
             Ok(line) => {
                 if line.trim().is_empty() {
                     continue;
                 }
+                let continuation_prompt = &shell.config.theme.continuation_prompt;
+                let mut line = strip_continuation(&line, continuation_prompt);
+                // Piped input skips the editor's validator, so a block like
+                // `if` is gathered here
+                while parser::needs_more_input(&line) {
+                    let Ok(next) = rl.readline(continuation_prompt) else { break };
+                    line.push('\n');
+                    line.push_str(&next);
+                }
                 
+                if let Some(handle) = pending_history.take() {
+                    install_history(&mut rl, handle, &mut shell);
+                }
+                // `!!` and the like, shown as they expand like other shells do
+                if shell.config.options.histexpand {
+                    match history_expansion::expand(&line, &shell.history.get_all()) {
+                        Ok(Some(expanded)) => {
+                            println!("{}", expanded);
+                            line = expanded;
+                        }
+                        Ok(None) => {}
+                        Err(e) => {
+                            eprintln!("rshell: {}", e);
+                            shell.last_exit_code = 1;
+                            continue;
+                        }
+                    }
+                }
+
                 // Add to history
-                rl.add_history_entry(&line);
-                shell.history.push(line.clone());
-                
-                // Expand aliases
-                let expanded = alias::expand_aliases(&line, &shell.aliases);
+                rl.add_history_entry(indent_continuation(&line, &shell.config.theme.continuation_prompt));
+                shell.history.add(line.clone());
                 
                 // Check for auto-cd
                 if shell.config.general.auto_cd {
-                    let trimmed = expanded.trim();
-                    if std::path::Path::new(trimmed).is_dir() {
-                        if let Err(e) = std::env::set_current_dir(trimmed) {
+                    let trimmed = line.trim();
+                    if !shell.aliases.contains_key(trimmed) && std::path::Path::new(trimmed).is_dir() {
+                        if let Err(e) = shell.change_dir(trimmed, false) {
                             eprintln!("cd: {}: {}", trimmed, e);
-                        } else {
-                            shell.current_dir = std::env::current_dir()
-                                .unwrap_or_default()
-                                .to_string_lossy()
-                                .to_string();
                         }
                         continue;
                     }
                 }
                 
                 // Parse and execute
-                match Parser::new(&expanded) {
+                let parse_start = Instant::now();
+                let arena = Bump::new();
+                let parser = Parser::with_aliases(&line, &shell.aliases, &arena);
+                match parser {
+                    Ok(parser) if parser.is_empty() => continue,
                     Ok(mut parser) => {
-                        match parser.parse() {
+                        let parsed = parser.parse();
+                        shell.perf.record_parsing(parse_start.elapsed());
+                        shell.perf.trace_span("parse", "parse", parse_start);
+                        match parsed {
                             Ok(command_type) => {
+                                terminal::report_command_start(&shell, &line);
+                                let start_dir = shell.current_dir.clone();
+                                shell.last_rusage = None;
+                                let exec_start = Instant::now();
                                 let exit_code = executor.execute(&mut shell, command_type);
+                                let elapsed = exec_start.elapsed();
+                                shell.perf.record_command_execution(elapsed);
+                                shell.history.record_result(elapsed, exit_code, shell.last_rusage);
+                                shell.last_duration = Some(elapsed);
                                 shell.last_exit_code = exit_code;
+                                terminal::report_command_end(&shell);
+                                audit::record_command(&shell, &start_dir, &line, elapsed, exit_code);
+                                shell.plugins.on_command(&line, exit_code);
+                                show_slow_command_hint(&shell, &line, elapsed);
+                                shell.enforce_memory_limits();
                             }
                             Err(e) => {
-                                eprintln!("{}Parse error: {}{}", 
+                                eprintln!("{}{}{}", 
                                     shell.config.theme.error_color,
-                                    e.message,
+                                    e.render(&line),
                                     shell.config.theme.reset_color);
                                 shell.last_exit_code = 1;
+                                terminal::report_command_end(&shell);
                             }
                         }
                     }
                     Err(e) => {
-                        eprintln!("{}Error: {}{}", 
+                        eprintln!("{}{}{}", 
                             shell.config.theme.error_color,
-                            e.message,
+                            e.render(&line),
                             shell.config.theme.reset_color);
                         shell.last_exit_code = 1;
+                        terminal::report_command_end(&shell);
                     }
                 }
             }
@@ -157,11 +349,79 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
     }
     
     // Save history
+    if let Some(handle) = pending_history.take() {
+        install_history(&mut rl, handle, &mut shell);
+    }
     let _ = rl.save_history(&history_file);
     
+    // Final metrics snapshot
+    if metrics_exporter.is_enabled() {
+        let _ = metrics_exporter.write_once();
+    }
+    
     // Save config with updated aliases
-    shell.config.aliases = shell.aliases.clone();
+    shell.config.aliases = alias::to_config(&alias::to_save(&shell.aliases, &config_aliases, &startup_aliases));
     let _ = shell.save_config();
     
-    Ok(())
-}
\ No newline at end of file
+    shell.exit(shell.last_exit_code);
+}
+
+// `-c COMMAND`, a script file or stdin, with the invocation's arguments as
+// $1..; the status is that of the last command run
+fn run_script(shell: &mut Shell, invocation: &Invocation) -> i32 {
+    let mut engine = ScriptEngine::new();
+    shell.positional = invocation.args.clone();
+    let result = match (&invocation.command, &invocation.script) {
+        (Some(command), name) => {
+            if let Some(name) = name {
+                shell.arg0 = name.clone();
+            }
+            engine.execute_str(shell, command)
+        }
+        (None, Some(script)) => {
+            let path = std::path::Path::new(script);
+            if !path.is_file() {
+                eprintln!("rshell: {}: No such file or directory", script);
+                return 127;
+            }
+            engine.execute_script_with_args(shell, path, &invocation.args)
+        }
+        (None, None) => match std::io::read_to_string(std::io::stdin()) {
+            Ok(input) => engine.execute_str(shell, &input),
+            Err(e) => Err(e.to_string()),
+        },
+    };
+    result.unwrap_or_else(|e| {
+        eprintln!("rshell: {}", e);
+        2
+    })
+}
+
+fn show_slow_command_hint(shell: &Shell, line: &str, elapsed: std::time::Duration) {
+    let general = &shell.config.general;
+    if !general.slow_command_hints || elapsed.as_millis() < general.slow_command_threshold_ms as u128 {
+        return;
+    }
+    if let Some(hint) = slow_command_hint(line, elapsed, shell.perf.get_average_execution_time()) {
+        eprintln!("{}hint: {}{}", shell.config.theme.hint_color, hint, shell.config.theme.reset_color);
+    }
+}
+
+fn completion_type(style: CompletionStyle) -> CompletionType {
+    match style {
+        CompletionStyle::List => CompletionType::List,
+        CompletionStyle::Menu => CompletionType::Circular,
+    }
+}
+
+// Hands the history file to the editor, and to `shell.history` ahead of
+// the commands run so far
+fn install_history(rl: &mut Editor<ShellHelper>, handle: JoinHandle<RustylineHistory>, shell: &mut Shell) {
+    if let Ok(history) = handle.join() {
+        // The editor keeps multi-line commands with room for the continuation prompt
+        let prompt = &shell.config.theme.continuation_prompt;
+        let commands: Vec<String> = history.iter().map(|entry| strip_continuation(entry, prompt)).collect();
+        shell.history.load_older(commands.iter().map(String::as_str));
+        *rl.history_mut() = history;
+    }
+}
@@ -1,6 +1,14 @@
 // src/memory_pool.rs
-use std::sync::{Arc, Mutex};
+use std::sync::{Arc, Mutex, OnceLock};
 use std::collections::VecDeque;
+use std::ffi::{c_char, CString};
+use std::os::unix::ffi::OsStrExt;
+use std::path::Path;
+use std::marker::PhantomData;
+use nix::errno::Errno;
+use crate::command::{Command, RedirectTarget};
+use crate::utils::helpers::format_bytes;
+use crate::outln;
 
 pub struct StringPool {
     pool: Arc<Mutex<VecDeque<String>>>,
@@ -86,6 +94,132 @@ impl<T> VecPool<T> {
     }
 }
 
+// Shared pools for the per-command hot paths (tokenizer words, argv)
+const WORD_POOL_SIZE: usize = 256;
+const WORD_CAPACITY: usize = 32;
+const ARGS_POOL_SIZE: usize = 16;
+const ARGS_CAPACITY: usize = 8;
+const ARGV_BUFFER_CAPACITY: usize = 256;
+
+pub fn word_pool() -> &'static StringPool {
+    static POOL: OnceLock<StringPool> = OnceLock::new();
+    POOL.get_or_init(|| StringPool::new(WORD_POOL_SIZE, WORD_CAPACITY))
+}
+
+pub fn args_pool() -> &'static VecPool<String> {
+    static POOL: OnceLock<VecPool<String>> = OnceLock::new();
+    POOL.get_or_init(|| VecPool::new(ARGS_POOL_SIZE, ARGS_CAPACITY))
+}
+
+// Process-wide interner for names that repeat across a session: environment
+// variable and alias names. Whoever drops names purges it.
+pub fn interner() -> &'static StringInterner {
+    static INTERNER: OnceLock<StringInterner> = OnceLock::new();
+    INTERNER.get_or_init(StringInterner::new)
+}
+
+fn argv_buffer_pool() -> &'static VecPool<u8> {
+    static POOL: OnceLock<VecPool<u8>> = OnceLock::new();
+    POOL.get_or_init(|| VecPool::new(ARGS_POOL_SIZE, ARGV_BUFFER_CAPACITY))
+}
+
+pub fn recycle_words(words: impl IntoIterator<Item = String>) {
+    let pool = word_pool();
+    for word in words {
+        pool.release(word);
+    }
+}
+
+// Hands a finished command's strings back to the pools
+pub fn recycle_command(mut command: Command) {
+    recycle_words(command.args.drain(..));
+    args_pool().release(command.args);
+    let files = command.redirects.into_iter().filter_map(|r| match r.target {
+        RedirectTarget::Read(file) | RedirectTarget::Write(file) | RedirectTarget::Append(file) => Some(file),
+        _ => None,
+    });
+    recycle_words(std::iter::once(command.program).chain(files));
+}
+
+// An execvp argv packed NUL-separated into one pooled buffer
+pub struct Argv {
+    buffer: Vec<u8>,
+}
+
+impl Argv {
+    pub fn build(program: &str, args: &[String]) -> Result<Self, &'static str> {
+        let mut argv = Self { buffer: argv_buffer_pool().acquire() };
+        for word in std::iter::once(program).chain(args.iter().map(|s| s.as_str())) {
+            if word.contains('\0') {
+                argv.recycle();
+                return Err("argument contains a NUL byte");
+            }
+            argv.buffer.extend_from_slice(word.as_bytes());
+            argv.buffer.push(0);
+        }
+        Ok(argv)
+    }
+    
+    // This process's environment as `NAME=value` words, the envp of a
+    // command that runs with something else than the shell's own
+    pub fn environment() -> Self {
+        let mut env = Self { buffer: argv_buffer_pool().acquire() };
+        for (name, value) in std::env::vars_os() {
+            env.buffer.extend_from_slice(name.as_bytes());
+            env.buffer.push(b'=');
+            env.buffer.extend_from_slice(value.as_bytes());
+            env.buffer.push(0);
+        }
+        env
+    }
+    
+    // Pointers to the words for exec, the program's path if it has been
+    // looked up already, and the environment unless it is this process's.
+    // This allocates, so build it before fork(): the child of a
+    // multithreaded process must not allocate.
+    pub fn exec_args<'a>(&'a self, path: Option<&Path>, env: Option<&'a Argv>) -> ExecArgs<'a> {
+        let path = path.and_then(|path| CString::new(path.as_os_str().as_bytes()).ok());
+        ExecArgs { path, pointers: self.pointers(), env: env.map(Argv::pointers), argv: PhantomData }
+    }
+    
+    fn pointers(&self) -> Vec<*const c_char> {
+        let mut pointers: Vec<*const c_char> = self.buffer
+            .split_inclusive(|&b| b == 0)
+            .map(|word| word.as_ptr().cast())
+            .collect();
+        pointers.push(std::ptr::null());
+        pointers
+    }
+    
+    pub fn recycle(self) {
+        argv_buffer_pool().release(self.buffer);
+    }
+}
+
+// An `Argv` ready for execvp, with nothing left to allocate
+pub struct ExecArgs<'a> {
+    path: Option<CString>,
+    pointers: Vec<*const c_char>,
+    env: Option<Vec<*const c_char>>,
+    argv: PhantomData<&'a Argv>,
+}
+
+impl ExecArgs<'_> {
+    // Runs the program in place of this process, from its path or else
+    // looked up along PATH; returns only if that fails, with the reason
+    pub fn exec(&self) -> Errno {
+        unsafe {
+            match (&self.path, &self.env) {
+                (Some(path), Some(env)) => libc::execve(path.as_ptr(), self.pointers.as_ptr(), env.as_ptr()),
+                (Some(path), None) => libc::execv(path.as_ptr(), self.pointers.as_ptr()),
+                (None, Some(env)) => libc::execvpe(self.pointers[0], self.pointers.as_ptr(), env.as_ptr()),
+                (None, None) => libc::execvp(self.pointers[0], self.pointers.as_ptr()),
+            };
+        }
+        Errno::last()
+    }
+}
+
 // Memory-efficient command buffer
 pub struct CommandBuffer {
     buffer: Vec<u8>,
@@ -132,13 +266,19 @@ impl CommandBuffer {
 
 // Optimized string interning for frequently used strings
 pub struct StringInterner {
-    interned: Arc<Mutex<HashMap<String, Arc<str>>>>,
+    interned: Arc<Mutex<HashSet<Arc<str>>>>,
+}
+
+impl Default for StringInterner {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl StringInterner {
     pub fn new() -> Self {
         Self {
-            interned: Arc::new(Mutex::new(HashMap::new())),
+            interned: Arc::new(Mutex::new(HashSet::new())),
         }
     }
     
@@ -149,14 +289,34 @@ impl StringInterner {
             existing.clone()
         } else {
             let arc: Arc<str> = Arc::from(s);
-            interned.insert(s.to_string(), arc.clone());
+            interned.insert(arc.clone());
             arc
         }
     }
     
+    // Drops strings nobody outside the interner refers to any more
+    pub fn purge_unused(&self) {
+        self.interned.lock().unwrap().retain(|s| Arc::strong_count(s) > 1);
+    }
+    
+    pub fn len(&self) -> usize {
+        self.interned.lock().unwrap().len()
+    }
+    
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+    
     pub fn clear(&self) {
         self.interned.lock().unwrap().clear();
     }
+    
+    // Estimated heap footprint: table slots plus each string and its refcounts
+    pub fn heap_bytes(&self) -> usize {
+        let interned = self.interned.lock().unwrap();
+        interned.capacity() * std::mem::size_of::<Arc<str>>()
+            + interned.iter().map(|s| s.len() + 2 * std::mem::size_of::<usize>()).sum::<usize>()
+    }
 }
 
 // Zero-copy parsing utilities
@@ -196,15 +356,33 @@ impl<'a> ZeroCopyParser<'a> {
     pub fn remaining(&self) -> &'a str {
         &self.input[self.position..]
     }
+    
+    pub fn position(&self) -> usize {
+        self.position
+    }
 }
 
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
+
+// How often the configured soft limits had to step in
+#[derive(Debug, Clone, Copy, Default)]
+pub struct LimitEnforcement {
+    pub history_compactions: usize,
+    pub history_entries_dropped: usize,
+    pub cache_evictions: usize,
+}
 
 // Memory usage tracker
 pub struct MemoryTracker {
     allocations: Arc<Mutex<HashMap<String, usize>>>,
 }
 
+impl Default for MemoryTracker {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl MemoryTracker {
     pub fn new() -> Self {
         Self {
@@ -217,6 +395,15 @@ impl MemoryTracker {
         *allocations.entry(name.to_string()).or_insert(0) += size;
     }
     
+    // Replaces the figure for consumers that are measured rather than counted
+    pub fn set(&self, name: &str, size: usize) {
+        self.allocations.lock().unwrap().insert(name.to_string(), size);
+    }
+    
+    pub fn get(&self, name: &str) -> usize {
+        self.allocations.lock().unwrap().get(name).copied().unwrap_or(0)
+    }
+    
     pub fn untrack(&self, name: &str, size: usize) {
         let mut allocations = self.allocations.lock().unwrap();
         if let Some(current) = allocations.get_mut(name) {
@@ -236,4 +423,111 @@ impl MemoryTracker {
     pub fn total_allocated(&self) -> usize {
         self.allocations.lock().unwrap().values().sum()
     }
-}
\ No newline at end of file
+}
+fn format_limit(limit_kb: usize) -> String {
+    if limit_kb == 0 {
+        "none".to_string()
+    } else {
+        format_bytes(limit_kb as u64 * 1024)
+    }
+}
+
+pub fn builtin_stats(shell: &mut crate::shell::Shell, args: &[String]) -> i32 {
+    match args.first().map(|s| s.as_str()) {
+        None | Some("memory") => {
+            shell.update_memory_usage();
+            let limits = &shell.config.memory;
+            let enforcement = shell.limit_enforcement;
+            outln!("Memory usage (estimated)");
+            outln!("========================");
+            for (name, bytes) in shell.memory.report() {
+                outln!("  {:<16} {:>10}", name, format_bytes(bytes as u64));
+            }
+            outln!("  {:<16} {:>10}", "total", format_bytes(shell.memory.total_allocated() as u64));
+            outln!("\nSoft limits");
+            outln!("  history:       {} ({} compactions, {} entries dropped)",
+                     format_limit(limits.history_soft_limit_kb),
+                     enforcement.history_compactions, enforcement.history_entries_dropped);
+            outln!("  command cache: {} ({} evictions)",
+                     format_limit(limits.cache_soft_limit_kb), enforcement.cache_evictions);
+            outln!("\nHistory: {} entries, {} distinct",
+                     shell.history.len(), shell.history.unique_commands());
+            0
+        }
+        Some(other) => {
+            eprintln!("stats: unknown subcommand '{}'", other);
+            eprintln!("Usage: stats [memory]");
+            1
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn argv_round_trip() {
+        let argv = Argv::build("ls", &["-l".to_string(), "".to_string(), "/tmp".to_string()]).unwrap();
+        let exec_args = argv.exec_args(None, None);
+        let (last, words) = exec_args.pointers.split_last().unwrap();
+        let words: Vec<_> = words.iter()
+            .map(|&word| unsafe { std::ffi::CStr::from_ptr(word) }.to_str().unwrap())
+            .collect();
+        assert_eq!(words, vec!["ls", "-l", "", "/tmp"]);
+        assert!(last.is_null());
+        drop(exec_args);
+        argv.recycle();
+        assert!(Argv::build("echo", &["a\0b".to_string()]).is_err());
+    }
+
+    #[test]
+    fn environment_block() {
+        std::env::set_var("RSHELL_POOL_ENV", "a=b");
+        let env = Argv::environment();
+        let argv = Argv::build("env", &[]).unwrap();
+        let exec_args = argv.exec_args(None, Some(&env));
+        let words: Vec<_> = exec_args.env.as_ref().unwrap().iter()
+            .take_while(|word| !word.is_null())
+            .map(|&word| unsafe { std::ffi::CStr::from_ptr(word) }.to_str().unwrap())
+            .collect();
+        assert!(words.contains(&"RSHELL_POOL_ENV=a=b"));
+        drop(exec_args);
+        argv.recycle();
+        env.recycle();
+    }
+
+    #[test]
+    fn tracker_set_replaces_measurement() {
+        let tracker = MemoryTracker::new();
+        tracker.track("history", 100);
+        tracker.set("history", 40);
+        tracker.set("jobs", 60);
+        assert_eq!(tracker.get("history"), 40);
+        assert_eq!(tracker.total_allocated(), 100);
+        assert_eq!(tracker.report()[0], ("jobs".to_string(), 60));
+    }
+
+    #[test]
+    fn interner_shares_and_purges() {
+        let interner = StringInterner::new();
+        let a = interner.intern("PATH");
+        let b = interner.intern("PATH");
+        assert!(Arc::ptr_eq(&a, &b));
+        drop(interner.intern("HOME"));
+        interner.purge_unused();
+        assert_eq!(interner.len(), 1);
+    }
+
+    #[test]
+    fn string_pool_reuses_buffers() {
+        let pool = StringPool::new(2, 16);
+        let mut s = pool.acquire();
+        s.push_str("hello");
+        let ptr = s.as_ptr();
+        pool.release(s);
+        let pool_len = pool.pool.lock().unwrap().len();
+        let reused = (0..pool_len).map(|_| pool.acquire()).any(|s| s.as_ptr() == ptr && s.is_empty());
+        assert!(reused);
+    }
+}
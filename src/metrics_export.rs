@@ -0,0 +1,171 @@
+// src/metrics_export.rs
+use crate::cache::{CacheStats, CommandCache};
+use crate::config::{MetricsConfig, MetricsFormat};
+use crate::performance::{PerformanceMetrics, PerformanceMonitor};
+use serde_json::json;
+use std::fmt::Write as _;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::thread;
+use std::time::Duration;
+
+fn average_secs(samples: &std::collections::VecDeque<Duration>) -> f64 {
+    if samples.is_empty() {
+        0.0
+    } else {
+        samples.iter().sum::<Duration>().as_secs_f64() / samples.len() as f64
+    }
+}
+
+// Prometheus text exposition format, suitable for node_exporter's textfile collector
+pub fn render_prometheus(metrics: &PerformanceMetrics, cache: &CacheStats, uptime: Duration) -> String {
+    let mut out = String::new();
+    let mut metric = |name: &str, kind: &str, help: &str, samples: &[(&str, f64)]| {
+        let _ = writeln!(out, "# HELP {} {}", name, help);
+        let _ = writeln!(out, "# TYPE {} {}", name, kind);
+        for (labels, value) in samples {
+            if labels.is_empty() {
+                let _ = writeln!(out, "{} {}", name, value);
+            } else {
+                let _ = writeln!(out, "{}{{{}}} {}", name, labels, value);
+            }
+        }
+    };
+
+    metric("rshell_uptime_seconds", "gauge", "Time since the shell started.",
+          &[("", uptime.as_secs_f64())]);
+    metric("rshell_commands_total", "counter", "Command lines executed.",
+          &[("", metrics.total_commands as f64)]);
+    metric("rshell_command_duration_avg_seconds", "gauge",
+          "Average execution time of recent command lines.",
+          &[("", average_secs(&metrics.command_execution_times))]);
+    metric("rshell_parse_duration_avg_seconds", "gauge",
+          "Average parse time of recent command lines.",
+          &[("", average_secs(&metrics.parsing_times))]);
+    metric("rshell_child_cpu_seconds_total", "counter", "CPU time used by child processes.",
+          &[("mode=\"user\"", metrics.child_user_time.as_secs_f64()),
+            ("mode=\"system\"", metrics.child_system_time.as_secs_f64())]);
+    metric("rshell_child_max_rss_kilobytes", "gauge", "Largest resident set size of any child.",
+          &[("", metrics.child_max_rss_kb as f64)]);
+    metric("rshell_child_page_faults_total", "counter", "Page faults in child processes.",
+          &[("", metrics.child_page_faults as f64)]);
+    metric("rshell_cache_hits_total", "counter", "Command cache hits.",
+          &[("cache=\"path\"", cache.path_hits as f64),
+            ("cache=\"dir\"", cache.dir_hits as f64),
+            ("cache=\"completion\"", cache.completion_hits as f64)]);
+    metric("rshell_cache_misses_total", "counter", "Command cache misses.",
+          &[("cache=\"path\"", cache.path_misses as f64),
+            ("cache=\"dir\"", cache.dir_misses as f64),
+            ("cache=\"completion\"", cache.completion_misses as f64)]);
+    out
+}
+
+pub fn render_json(metrics: &PerformanceMetrics, cache: &CacheStats, uptime: Duration) -> String {
+    let status = json!({
+        "pid": std::process::id(),
+        "uptime_seconds": uptime.as_secs_f64(),
+        "commands_total": metrics.total_commands,
+        "command_duration_avg_seconds": average_secs(&metrics.command_execution_times),
+        "parse_duration_avg_seconds": average_secs(&metrics.parsing_times),
+        "child": {
+            "user_seconds": metrics.child_user_time.as_secs_f64(),
+            "system_seconds": metrics.child_system_time.as_secs_f64(),
+            "max_rss_kb": metrics.child_max_rss_kb,
+            "page_faults": metrics.child_page_faults,
+        },
+        "cache": {
+            "path_hits": cache.path_hits,
+            "path_misses": cache.path_misses,
+            "dir_hits": cache.dir_hits,
+            "dir_misses": cache.dir_misses,
+            "completion_hits": cache.completion_hits,
+            "completion_misses": cache.completion_misses,
+        },
+    });
+    format!("{:#}\n", status)
+}
+
+// `{pid}` in the configured path lets several shells export side by side
+pub fn resolve_metrics_path(path: &str) -> PathBuf {
+    let home = dirs::home_dir().unwrap_or_default();
+    PathBuf::from(path
+        .replace('~', &home.to_string_lossy())
+        .replace("{pid}", &std::process::id().to_string()))
+}
+
+// Write to a temporary file first so collectors never see a partial file
+fn write_atomically(path: &Path, contents: &str) -> io::Result<()> {
+    let tmp = path.with_extension(format!("tmp.{}", std::process::id()));
+    fs::write(&tmp, contents)?;
+    fs::rename(&tmp, path)
+}
+
+// Background thread that snapshots metrics every `interval_secs`
+#[derive(Clone)]
+pub struct MetricsExporter {
+    config: MetricsConfig,
+    perf: PerformanceMonitor,
+    cache: CommandCache,
+}
+
+impl MetricsExporter {
+    pub fn new(config: MetricsConfig, perf: PerformanceMonitor, cache: CommandCache) -> Self {
+        Self { config, perf, cache }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        !self.config.path.is_empty()
+    }
+
+    pub fn write_once(&self) -> io::Result<()> {
+        let path = resolve_metrics_path(&self.config.path);
+        let metrics = self.perf.get_metrics();
+        let cache = self.cache.get_stats();
+        let uptime = self.perf.get_uptime();
+        let contents = match self.config.format {
+            MetricsFormat::Prometheus => render_prometheus(&metrics, &cache, uptime),
+            MetricsFormat::Json => render_json(&metrics, &cache, uptime),
+        };
+        write_atomically(&path, &contents)
+    }
+
+    // Does nothing unless a path is configured
+    pub fn start(self) {
+        if !self.is_enabled() {
+            return;
+        }
+        let interval = Duration::from_secs(self.config.interval_secs.max(1));
+        thread::spawn(move || loop {
+            if let Err(e) = self.write_once() {
+                eprintln!("rshell: metrics export to {} failed: {}", self.config.path, e);
+                return;
+            }
+            thread::sleep(interval);
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn prometheus_output() {
+        let metrics = PerformanceMetrics { total_commands: 3, ..Default::default() };
+        let cache = CacheStats { path_hits: 5, ..Default::default() };
+        let text = render_prometheus(&metrics, &cache, Duration::from_secs(2));
+        assert!(text.contains("# TYPE rshell_commands_total counter\nrshell_commands_total 3\n"));
+        assert!(text.contains("rshell_cache_hits_total{cache=\"path\"} 5\n"));
+        assert!(text.contains("rshell_uptime_seconds 2\n"));
+    }
+
+    #[test]
+    fn json_output() {
+        let metrics = PerformanceMetrics { total_commands: 7, ..Default::default() };
+        let text = render_json(&metrics, &CacheStats::default(), Duration::ZERO);
+        let value: serde_json::Value = serde_json::from_str(&text).unwrap();
+        assert_eq!(value["commands_total"], 7);
+        assert_eq!(value["cache"]["path_hits"], 0);
+    }
+}
@@ -0,0 +1,92 @@
+// src/multiplexer.rs
+use crate::shell::Shell;
+use std::env;
+use std::process::Command;
+use std::sync::OnceLock;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Multiplexer {
+    Tmux,
+    Screen,
+}
+
+impl Multiplexer {
+    // Decided once: the variables are inherited from the multiplexer at startup
+    pub fn detect() -> Option<Self> {
+        static DETECTED: OnceLock<Option<Multiplexer>> = OnceLock::new();
+        *DETECTED.get_or_init(|| {
+            if env::var_os("TMUX").is_some_and(|v| !v.is_empty()) {
+                Some(Multiplexer::Tmux)
+            } else if env::var_os("STY").is_some_and(|v| !v.is_empty()) {
+                Some(Multiplexer::Screen)
+            } else {
+                None
+            }
+        })
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Multiplexer::Tmux => "tmux",
+            Multiplexer::Screen => "screen",
+        }
+    }
+
+    // Wraps a sequence in DCS so the multiplexer hands it to the outer
+    // terminal untouched. tmux wants inner ESCs doubled (and
+    // `set -g allow-passthrough on` since 3.3).
+    pub fn passthrough(self, sequence: &str) -> String {
+        match self {
+            Multiplexer::Tmux => format!("\x1bPtmux;{}\x1b\\", sequence.replace('\x1b', "\x1b\x1b")),
+            Multiplexer::Screen => format!("\x1bP{}\x1b\\", sequence),
+        }
+    }
+}
+
+// Screen's window-name sequence, which tmux also uses to rename the window
+pub fn window_name(name: &str) -> String {
+    let name: String = name.chars().filter(|c| !c.is_control()).collect();
+    format!("\x1bk{}\x1b\\", name)
+}
+
+// `tsplit [-h] [cmd...]`: opens a tmux pane in the current directory running
+// cmd, or a new shell; -h splits side by side
+pub fn builtin_tsplit(shell: &mut Shell, args: &[String]) -> i32 {
+    if Multiplexer::detect() != Some(Multiplexer::Tmux) {
+        eprintln!("tsplit: not running inside tmux");
+        return 1;
+    }
+    let (horizontal, command) = match args.first().map(String::as_str) {
+        Some("-h") => (true, &args[1..]),
+        Some("-v") => (false, &args[1..]),
+        _ => (false, args),
+    };
+    let mut tmux = Command::new("tmux");
+    tmux.arg("split-window").arg("-c").arg(&shell.current_dir);
+    if horizontal {
+        tmux.arg("-h");
+    }
+    if !command.is_empty() {
+        tmux.arg("--").args(command);
+    }
+    match tmux.status() {
+        Ok(status) => status.code().unwrap_or(1),
+        Err(e) => {
+            eprintln!("tsplit: tmux: {}", e);
+            1
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn passthrough_wrapping() {
+        assert_eq!(Multiplexer::Tmux.passthrough("\x1b]7;file://h/\x1b\\"),
+                   "\x1bPtmux;\x1b\x1b]7;file://h/\x1b\x1b\\\x1b\\");
+        assert_eq!(Multiplexer::Screen.passthrough("\x1b]133;A\x07"), "\x1bP\x1b]133;A\x07\x1b\\");
+        assert_eq!(window_name("vim\nx"), "\x1bkvimx\x1b\\");
+    }
+}
@@ -0,0 +1,160 @@
+// src/option_completion.rs
+//
+// Options for a word starting with `-`, read from the command's own
+// `--help`. The first time a command's options are wanted, `CMD --help` runs
+// on a thread; the flags and the first line of what they do are kept in
+// `CommandCache`'s completion cache under "CMD --help", one "FLAG\tTEXT"
+// per option. If it takes longer than a moment, that Tab gets file names and
+// a later one the options.
+use std::io::Read;
+use std::process::{Command, Stdio};
+use std::sync::mpsc;
+use std::thread;
+use std::time::{Duration, Instant};
+use crate::cache::CommandCache;
+
+// How long Tab waits for --help before falling back to files
+const WAIT: Duration = Duration::from_millis(300);
+// After this, a --help that still runs is killed
+const TIMEOUT: Duration = Duration::from_secs(2);
+
+// The options `command` lists in its --help, with their descriptions; None
+// for builtins, unknown commands, or while --help is still running
+pub fn options(cache: &CommandCache, command: &str) -> Option<Vec<(String, String)>> {
+    let key = format!("{} --help", command);
+    if let Some(lines) = cache.cached_completions(&key) {
+        return Some(decode(&lines));
+    }
+    if cache.is_builtin(command) {
+        return None;
+    }
+    let path = cache.find_command(command)?;
+    // Nothing yet, so the help runs only once even if Tab is pressed again
+    cache.store_completions(&key, Vec::new());
+    let (sender, receiver) = mpsc::channel();
+    let background = cache.clone();
+    thread::spawn(move || {
+        let help = run_help(&path).unwrap_or_default();
+        let lines: Vec<String> = parse(&help).into_iter()
+            .map(|(flag, text)| format!("{}\t{}", flag, text))
+            .collect();
+        background.store_completions(&key, lines.clone());
+        let _ = sender.send(lines);
+    });
+    receiver.recv_timeout(WAIT).ok().map(|lines| decode(&lines))
+}
+
+fn decode(lines: &[String]) -> Vec<(String, String)> {
+    lines.iter()
+        .map(|line| match line.split_once('\t') {
+            Some((flag, text)) => (flag.to_string(), text.to_string()),
+            None => (line.clone(), String::new()),
+        })
+        .collect()
+}
+
+// What `path --help` prints, on stdout or else stderr
+fn run_help(path: &std::path::Path) -> Option<String> {
+    let mut child = Command::new(path)
+        .arg("--help")
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .env("PAGER", "cat")
+        .env("MANPAGER", "cat")
+        .spawn()
+        .ok()?;
+    let mut stdout = child.stdout.take()?;
+    let mut stderr = child.stderr.take()?;
+    let err_reader = thread::spawn(move || {
+        let mut text = String::new();
+        let _ = stderr.read_to_string(&mut text);
+        text
+    });
+    let out_reader = thread::spawn(move || {
+        let mut text = String::new();
+        let _ = stdout.read_to_string(&mut text);
+        text
+    });
+    let started = Instant::now();
+    loop {
+        match child.try_wait() {
+            Ok(Some(_)) => break,
+            Ok(None) if started.elapsed() < TIMEOUT => thread::sleep(Duration::from_millis(20)),
+            _ => {
+                let _ = child.kill();
+                let _ = child.wait();
+                break;
+            }
+        }
+    }
+    let out = out_reader.join().unwrap_or_default();
+    let err = err_reader.join().unwrap_or_default();
+    Some(if out.trim().is_empty() { err } else { out })
+}
+
+// The flags in a --help text and the first line of each one's description:
+// lines like `  -a, --all      do not ignore entries starting with .` or
+// `      --block-size=SIZE` with the description on the next line
+pub fn parse(help: &str) -> Vec<(String, String)> {
+    let mut options: Vec<(String, String)> = Vec::new();
+    let mut lines = help.lines().peekable();
+    while let Some(line) = lines.next() {
+        let trimmed = line.trim_start();
+        if !trimmed.starts_with('-') || trimmed.starts_with("--- ") {
+            continue;
+        }
+        // Flags, then two or more blanks, then what they do
+        let (flags, mut text) = match trimmed.find("  ").or_else(|| trimmed.find('\t')) {
+            Some(gap) => (&trimmed[..gap], trimmed[gap..].trim().to_string()),
+            None => (trimmed, String::new()),
+        };
+        if text.is_empty() {
+            if let Some(next) = lines.peek().filter(|next| {
+                let next = next.trim_start();
+                !next.is_empty() && !next.starts_with('-')
+            }) {
+                text = next.trim().to_string();
+            }
+        }
+        for flag in flags.split([',', ' ', '|']) {
+            let flag = flag.split(['=', '[', '<']).next().unwrap_or("");
+            let well_formed = flag.len() > 1 && flag != "--"
+                && flag.trim_start_matches('-').chars().all(|ch| ch.is_ascii_alphanumeric() || matches!(ch, '-' | '_' | '?' | '.'));
+            if flag.starts_with('-') && well_formed && !options.iter().any(|(known, _)| known == flag) {
+                options.push((flag.to_string(), text.clone()));
+            }
+        }
+    }
+    options
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_gnu_and_clap_help() {
+        let help = "\
+Usage: ls [OPTION]... [FILE]...
+  -a, --all                  do not ignore entries starting with .
+      --block-size=SIZE      with -l, scale sizes by SIZE
+  -C                         list entries by columns
+      --color[=WHEN]         color the output WHEN
+  -o FILE, --output <FILE>   write to FILE
+      --an-option-long-enough-to-push-the-text-down
+                             on the next line
+Options:
+  -v, --verbose  Use verbose output
+";
+        let options = parse(help);
+        let flags: Vec<&str> = options.iter().map(|(flag, _)| flag.as_str()).collect();
+        assert_eq!(flags, [
+            "-a", "--all", "--block-size", "-C", "--color", "-o", "--output",
+            "--an-option-long-enough-to-push-the-text-down", "-v", "--verbose",
+        ]);
+        assert_eq!(options[1].1, "do not ignore entries starting with .");
+        assert_eq!(options[7].1, "on the next line");
+        assert_eq!(options[9].1, "Use verbose output");
+    }
+}
@@ -1,15 +1,20 @@
 // src/parallel_exec.rs
 use rayon::prelude::*;
-use crossbeam::channel::{bounded, unbounded, Sender};
-use std::thread;
-use std::time::Duration;
+use crossbeam::channel::bounded;
+use std::fmt;
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
 use std::os::unix::process::ExitStatusExt;
+use serde::Serialize;
 use crate::command::Command;
 
 pub struct ParallelExecutor {
     thread_pool: rayon::ThreadPool,
-    #[allow(dead_code)]
-    worker_threads: Vec<WorkerThread>,
 }
 
 impl ParallelExecutor {
@@ -17,17 +22,9 @@ impl ParallelExecutor {
         let thread_pool = rayon::ThreadPoolBuilder::new()
             .num_threads(num_threads)
             .build()
-            .map_err(|e| std::io::Error::new(std::io::ErrorKind::Other, e))?;
+            .map_err(std::io::Error::other)?;
         
-        let mut worker_threads = Vec::new();
-        for i in 0..num_threads {
-            worker_threads.push(WorkerThread::new(format!("worker-{}", i)));
-        }
-        
-        Ok(Self {
-            thread_pool,
-            worker_threads,
-        })
+        Ok(Self { thread_pool })
     }
     
     pub fn execute_parallel_commands(&self, commands: Vec<Command>) -> Vec<CommandResult> {
@@ -62,14 +59,14 @@ impl ParallelExecutor {
         
         match output {
             Ok(output) => CommandResult {
-                command: command.clone(),
+                command: command_line(command),
                 exit_code: output.status.code().unwrap_or(-1),
                 stdout: String::from_utf8_lossy(&output.stdout).to_string(),
                 stderr: String::from_utf8_lossy(&output.stderr).to_string(),
                 duration,
             },
             Err(e) => CommandResult {
-                command: command.clone(),
+                command: command_line(command),
                 exit_code: -1,
                 stdout: String::new(),
                 stderr: e.to_string(),
@@ -102,7 +99,7 @@ impl ParallelExecutor {
                 Ok(child) => child,
                 Err(e) => {
                     results.push(CommandResult {
-                        command: command.clone(),
+                        command: command_line(command),
                         exit_code: -1,
                         stdout: String::new(),
                         stderr: e.to_string(),
@@ -131,7 +128,7 @@ impl ParallelExecutor {
             last_output = output.stdout.clone();
             
             results.push(CommandResult {
-                command: command.clone(),
+                command: command_line(command),
                 exit_code: output.status.code().unwrap_or(-1),
                 stdout: String::from_utf8_lossy(&output.stdout).to_string(),
                 stderr: String::from_utf8_lossy(&output.stderr).to_string(),
@@ -182,7 +179,7 @@ impl ParallelExecutor {
         match rx.recv_timeout(timeout) {
             Ok(result) => result,
             Err(_) => CommandResult {
-                command,
+                command: command_line(&command),
                 exit_code: -1,
                 stdout: String::new(),
                 stderr: "Command timed out".to_string(),
@@ -205,14 +202,14 @@ impl ParallelExecutor {
         
         match output {
             Ok(output) => CommandResult {
-                command: command.clone(),
+                command: command_line(command),
                 exit_code: output.status.code().unwrap_or(-1),
                 stdout: String::from_utf8_lossy(&output.stdout).to_string(),
                 stderr: String::from_utf8_lossy(&output.stderr).to_string(),
                 duration,
             },
             Err(e) => CommandResult {
-                command: command.clone(),
+                command: command_line(command),
                 exit_code: -1,
                 stdout: String::new(),
                 stderr: e.to_string(),
@@ -224,13 +221,22 @@ impl ParallelExecutor {
 
 #[derive(Debug, Clone)]
 pub struct CommandResult {
-    pub command: Command,
+    // The command line that ran
+    pub command: String,
     pub exit_code: i32,
     pub stdout: String,
     pub stderr: String,
     pub duration: Duration,
 }
 
+fn command_line(command: &Command) -> String {
+    if command.args.is_empty() {
+        command.program.clone()
+    } else {
+        format!("{} {}", command.program, command.args.join(" "))
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct PipelineResult {
     pub commands: Vec<Command>,
@@ -238,79 +244,126 @@ pub struct PipelineResult {
     pub total_duration: Duration,
 }
 
-struct WorkerThread {
-    sender: Sender<WorkItem>,
-    handle: Option<thread::JoinHandle<()>>,
+// Structured output for parallel runs (`--output json|tsv`)
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ResultFormat {
+    Json,
+    Tsv,
 }
 
-enum WorkItem {
-    Command(Command, Sender<CommandResult>),
-    Shutdown,
+impl FromStr for ResultFormat {
+    type Err = String;
+    
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "json" => Ok(ResultFormat::Json),
+            "tsv" => Ok(ResultFormat::Tsv),
+            _ => Err(format!("unknown output format '{}' (expected json or tsv)", s)),
+        }
+    }
 }
 
-impl WorkerThread {
-    fn new(name: String) -> Self {
-        let (tx, rx) = unbounded();
-        
-        let handle = thread::Builder::new()
-            .name(name)
-            .spawn(move || {
-                while let Ok(item) = rx.recv() {
-                    match item {
-                        WorkItem::Command(cmd, result_tx) => {
-                            let result = Self::execute_command(&cmd);
-                            let _ = result_tx.send(result);
-                        }
-                        WorkItem::Shutdown => break,
-                    }
-                }
-            })
-            .unwrap();
-        
-        Self {
-            sender: tx,
-            handle: Some(handle),
+impl fmt::Display for ResultFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ResultFormat::Json => write!(f, "json"),
+            ResultFormat::Tsv => write!(f, "tsv"),
         }
     }
+}
+
+// One record per command, under the name it was run as. stdout/stderr are
+// either the captured text or, when an output directory is given, the path
+// of the file holding it.
+#[derive(Debug, Clone, Serialize)]
+pub struct ResultRecord {
+    pub name: String,
+    pub command: String,
+    pub exit_code: i32,
+    pub duration_ms: f64,
+    pub stdout: String,
+    pub stderr: String,
+}
+
+// Output files are `<name>.stdout` and `<name>.stderr`
+pub fn build_records(
+    results: &[(String, CommandResult)],
+    output_dir: Option<&Path>,
+) -> std::io::Result<Vec<ResultRecord>> {
+    if let Some(dir) = output_dir {
+        fs::create_dir_all(dir)?;
+    }
     
-    fn execute_command(command: &Command) -> CommandResult {
-        use std::process::Command as StdCommand;
-        use std::time::Instant;
-        
-        let start = Instant::now();
-        
-        let output = StdCommand::new(&command.program)
-            .args(&command.args)
-            .output();
-        
-        let duration = start.elapsed();
-        
-        match output {
-            Ok(output) => CommandResult {
-                command: command.clone(),
-                exit_code: output.status.code().unwrap_or(-1),
-                stdout: String::from_utf8_lossy(&output.stdout).to_string(),
-                stderr: String::from_utf8_lossy(&output.stderr).to_string(),
-                duration,
-            },
-            Err(e) => CommandResult {
-                command: command.clone(),
-                exit_code: -1,
-                stdout: String::new(),
-                stderr: e.to_string(),
-                duration,
-            },
+    results
+        .iter()
+        .map(|(name, result)| {
+            let (stdout, stderr) = match output_dir {
+                Some(dir) => {
+                    let file = name.replace('/', "_");
+                    let stdout_path = dir.join(format!("{}.stdout", file));
+                    let stderr_path = dir.join(format!("{}.stderr", file));
+                    fs::write(&stdout_path, &result.stdout)?;
+                    fs::write(&stderr_path, &result.stderr)?;
+                    (path_ref(stdout_path), path_ref(stderr_path))
+                }
+                None => (result.stdout.clone(), result.stderr.clone()),
+            };
+            Ok(ResultRecord {
+                name: name.clone(),
+                command: result.command.clone(),
+                exit_code: result.exit_code,
+                duration_ms: result.duration.as_secs_f64() * 1000.0,
+                stdout,
+                stderr,
+            })
+        })
+        .collect()
+}
+
+fn path_ref(path: PathBuf) -> String {
+    path.to_string_lossy().to_string()
+}
+
+// JSON output is one object per line so it can be streamed into jq.
+pub fn format_records(records: &[ResultRecord], format: ResultFormat) -> String {
+    let mut out = String::new();
+    match format {
+        ResultFormat::Json => {
+            for record in records {
+                out.push_str(&serde_json::to_string(record).unwrap_or_default());
+                out.push('\n');
+            }
+        }
+        ResultFormat::Tsv => {
+            out.push_str("name\tcommand\texit_code\tduration_ms\tstdout\tstderr\n");
+            for record in records {
+                out.push_str(&format!(
+                    "{}\t{}\t{}\t{:.3}\t{}\t{}\n",
+                    escape_tsv(&record.name),
+                    escape_tsv(&record.command),
+                    record.exit_code,
+                    record.duration_ms,
+                    escape_tsv(&record.stdout),
+                    escape_tsv(&record.stderr),
+                ));
+            }
         }
     }
+    out
 }
 
-impl Drop for WorkerThread {
-    fn drop(&mut self) {
-        let _ = self.sender.send(WorkItem::Shutdown);
-        if let Some(handle) = self.handle.take() {
-            let _ = handle.join();
+fn escape_tsv(field: &str) -> String {
+    let mut escaped = String::with_capacity(field.len());
+    for ch in field.chars() {
+        match ch {
+            '\\' => escaped.push_str("\\\\"),
+            '\t' => escaped.push_str("\\t"),
+            '\n' => escaped.push_str("\\n"),
+            '\r' => escaped.push_str("\\r"),
+            _ => escaped.push(ch),
         }
     }
+    escaped
 }
 
 // Batch command executor for efficient bulk operations
@@ -335,4 +388,190 @@ impl BatchExecutor {
             })
             .collect()
     }
-}
\ No newline at end of file
+}
+
+// Progress reporting for parallel batches, i.e. the tasks of `run`. On a
+// terminal the status line is redrawn in place; otherwise a plain line is
+// printed every few seconds.
+pub struct BatchProgress {
+    total: usize,
+    completed: AtomicUsize,
+    failed: AtomicUsize,
+    start: Instant,
+    interactive: bool,
+    last_report: Mutex<Instant>,
+}
+
+const PROGRESS_BAR_WIDTH: usize = 30;
+const PLAIN_REPORT_INTERVAL: Duration = Duration::from_secs(2);
+
+impl BatchProgress {
+    pub fn new(total: usize) -> Self {
+        let interactive = unsafe { libc::isatty(libc::STDERR_FILENO) == 1 };
+        Self::with_mode(total, interactive)
+    }
+    
+    pub fn with_mode(total: usize, interactive: bool) -> Self {
+        let now = Instant::now();
+        Self {
+            total,
+            completed: AtomicUsize::new(0),
+            failed: AtomicUsize::new(0),
+            start: now,
+            interactive,
+            last_report: Mutex::new(now),
+        }
+    }
+    
+    pub fn record(&self, result: &CommandResult) {
+        if result.exit_code != 0 {
+            self.failed.fetch_add(1, Ordering::SeqCst);
+        }
+        let completed = self.completed.fetch_add(1, Ordering::SeqCst) + 1;
+        
+        let mut last_report = self.last_report.lock().unwrap();
+        if self.interactive {
+            self.render_status_line();
+        } else if last_report.elapsed() >= PLAIN_REPORT_INTERVAL || completed == self.total {
+            eprintln!("{}", self.summary());
+        } else {
+            return;
+        }
+        *last_report = Instant::now();
+    }
+    
+    // Takes the status line away so other output can be printed in its place;
+    // the next `record` draws it again
+    pub fn clear(&self) {
+        if self.interactive {
+            let _guard = self.last_report.lock().unwrap();
+            eprint!("\r\x1b[K");
+        }
+    }
+    
+    pub fn finish(&self) {
+        let _guard = self.last_report.lock().unwrap();
+        if self.interactive {
+            self.render_status_line();
+            eprintln!();
+        }
+    }
+    
+    pub fn completed(&self) -> usize {
+        self.completed.load(Ordering::SeqCst)
+    }
+    
+    pub fn failed(&self) -> usize {
+        self.failed.load(Ordering::SeqCst)
+    }
+    
+    pub fn eta(&self) -> Option<Duration> {
+        let completed = self.completed();
+        if completed == 0 || completed >= self.total {
+            return None;
+        }
+        let per_item = self.start.elapsed() / completed as u32;
+        Some(per_item * (self.total - completed) as u32)
+    }
+    
+    pub fn summary(&self) -> String {
+        let mut line = format!(
+            "{}/{} done, {} failed",
+            self.completed(),
+            self.total,
+            self.failed()
+        );
+        if let Some(eta) = self.eta() {
+            line.push_str(&format!(", ETA {}s", eta.as_secs()));
+        }
+        line
+    }
+    
+    fn render_status_line(&self) {
+        let filled = (self.completed() * PROGRESS_BAR_WIDTH)
+            .checked_div(self.total)
+            .unwrap_or(PROGRESS_BAR_WIDTH)
+            .min(PROGRESS_BAR_WIDTH);
+        let bar = format!(
+            "{}{}",
+            "#".repeat(filled),
+            ".".repeat(PROGRESS_BAR_WIDTH - filled)
+        );
+        let mut stderr = std::io::stderr();
+        let _ = write!(stderr, "\r\x1b[K[{}] {}", bar, self.summary());
+        let _ = stderr.flush();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    fn result_with_code(exit_code: i32) -> CommandResult {
+        CommandResult {
+            command: "true".to_string(),
+            exit_code,
+            stdout: String::new(),
+            stderr: String::new(),
+            duration: Duration::from_millis(1),
+        }
+    }
+    
+    #[test]
+    fn test_batch_progress_counts() {
+        let progress = BatchProgress::with_mode(3, false);
+        progress.record(&result_with_code(0));
+        progress.record(&result_with_code(2));
+        assert_eq!(progress.completed(), 2);
+        assert_eq!(progress.failed(), 1);
+        assert!(progress.summary().starts_with("2/3 done, 1 failed"));
+        
+        progress.record(&result_with_code(0));
+        assert_eq!(progress.eta(), None);
+        assert_eq!(progress.summary(), "3/3 done, 1 failed");
+    }
+    
+    #[test]
+    fn test_format_records() {
+        let mut result = result_with_code(1);
+        result.stdout = "a\tb\n".to_string();
+        let records = build_records(&[("check".to_string(), result)], None).unwrap();
+        
+        let tsv = format_records(&records, ResultFormat::Tsv);
+        assert_eq!(tsv.lines().nth(1), Some("check\ttrue\t1\t1.000\ta\\tb\\n\t"));
+        
+        let json = format_records(&records, ResultFormat::Json);
+        assert!(json.starts_with("{\"name\":\"check\",\"command\":\"true\",\"exit_code\":1,"));
+        assert_eq!("json".parse::<ResultFormat>(), Ok(ResultFormat::Json));
+        assert!("xml".parse::<ResultFormat>().is_err());
+    }
+    
+    #[test]
+    fn test_records_with_output_dir() {
+        let dir = std::env::temp_dir().join(format!("rshell-records-{}", std::process::id()));
+        let mut result = result_with_code(0);
+        result.stdout = "out\n".to_string();
+        result.stderr = "err\n".to_string();
+        let records = build_records(&[("lint".to_string(), result)], Some(&dir)).unwrap();
+        
+        let stdout_path = dir.join("lint.stdout");
+        assert_eq!(records[0].stdout, stdout_path.to_string_lossy());
+        assert_eq!(fs::read_to_string(&stdout_path).unwrap(), "out\n");
+        assert_eq!(fs::read_to_string(dir.join("lint.stderr")).unwrap(), "err\n");
+        
+        let json: serde_json::Value = serde_json::from_str(format_records(&records, ResultFormat::Json).trim()).unwrap();
+        assert_eq!(json["name"], "lint");
+        assert_eq!(json["command"], "true");
+        assert_eq!(json["exit_code"], 0);
+        assert_eq!(json["stdout"], stdout_path.to_string_lossy().as_ref());
+        
+        let tsv = format_records(&records, ResultFormat::Tsv);
+        let fields: Vec<&str> = tsv.lines().nth(1).unwrap().split('\t').collect();
+        assert_eq!(fields[0], "lint");
+        assert_eq!(fields[1], "true");
+        assert_eq!(fields[2], "0");
+        assert_eq!(fields[4], stdout_path.to_string_lossy());
+        assert_eq!(fields[5], dir.join("lint.stderr").to_string_lossy());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
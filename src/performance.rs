@@ -1,8 +1,12 @@
 // src/performance.rs
 use std::time::{Duration, Instant};
 use std::sync::Arc;
-use parking_lot::RwLock;
+use parking_lot::{Mutex, RwLock};
 use std::collections::VecDeque;
+use std::path::PathBuf;
+use serde::Serialize;
+use crate::utils::helpers::{format_bytes, format_duration};
+use crate::outln;
 
 #[derive(Clone, Debug)]
 pub struct PerformanceMetrics {
@@ -12,6 +16,10 @@ pub struct PerformanceMetrics {
     pub memory_usage: usize,
     pub active_threads: usize,
     pub total_commands: usize,
+    pub child_user_time: Duration,
+    pub child_system_time: Duration,
+    pub child_max_rss_kb: u64,
+    pub child_page_faults: u64,
 }
 
 impl Default for PerformanceMetrics {
@@ -23,13 +31,109 @@ impl Default for PerformanceMetrics {
             memory_usage: 0,
             active_threads: 0,
             total_commands: 0,
+            child_user_time: Duration::ZERO,
+            child_system_time: Duration::ZERO,
+            child_max_rss_kb: 0,
+            child_page_faults: 0,
         }
     }
 }
 
+// Resource usage of reaped child processes, as reported by wait4(2)
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct ResourceUsage {
+    pub user_time: Duration,
+    pub system_time: Duration,
+    pub max_rss_kb: u64,
+    pub minor_faults: u64,
+    pub major_faults: u64,
+}
+
+impl ResourceUsage {
+    fn from_rusage(usage: &libc::rusage) -> Self {
+        let timeval = |tv: libc::timeval| {
+            Duration::from_secs(tv.tv_sec.max(0) as u64)
+                + Duration::from_micros(tv.tv_usec.max(0) as u64)
+        };
+        Self {
+            user_time: timeval(usage.ru_utime),
+            system_time: timeval(usage.ru_stime),
+            max_rss_kb: crate::platform::max_rss_kb(usage.ru_maxrss),
+            minor_faults: usage.ru_minflt.max(0) as u64,
+            major_faults: usage.ru_majflt.max(0) as u64,
+        }
+    }
+    
+    // getrusage(2) for RUSAGE_SELF or RUSAGE_CHILDREN: the shell's own usage
+    // or that of every child it has waited for
+    pub fn current(who: libc::c_int) -> Self {
+        let mut usage: libc::rusage = unsafe { std::mem::zeroed() };
+        unsafe { libc::getrusage(who, &mut usage) };
+        Self::from_rusage(&usage)
+    }
+    
+    pub fn cpu_time(&self) -> Duration {
+        self.user_time + self.system_time
+    }
+    
+    // Combines usage of several children (e.g. pipeline stages): times add up,
+    // peak RSS is the largest single process
+    pub fn merge(&mut self, other: &ResourceUsage) {
+        self.user_time += other.user_time;
+        self.system_time += other.system_time;
+        self.max_rss_kb = self.max_rss_kb.max(other.max_rss_kb);
+        self.minor_faults += other.minor_faults;
+        self.major_faults += other.major_faults;
+    }
+}
+
+// Blocking waitpid() that also returns the child's rusage via wait4(2)
+pub fn wait_with_rusage(pid: nix::unistd::Pid) -> nix::Result<(nix::sys::wait::WaitStatus, ResourceUsage)> {
+    let mut status: libc::c_int = 0;
+    let mut usage: libc::rusage = unsafe { std::mem::zeroed() };
+    let res = unsafe { libc::wait4(pid.as_raw(), &mut status, 0, &mut usage) };
+    let pid = nix::errno::Errno::result(res)?;
+    let status = nix::sys::wait::WaitStatus::from_raw(nix::unistd::Pid::from_raw(pid), status)?;
+    Ok((status, ResourceUsage::from_rusage(&usage)))
+}
+
+// Clones share the same underlying metrics
+#[derive(Clone)]
 pub struct PerformanceMonitor {
     metrics: Arc<RwLock<PerformanceMetrics>>,
     start_time: Instant,
+    trace: Arc<Mutex<Option<TraceSession>>>,
+}
+
+// A chrome://tracing "complete" event (ph = "X"), timestamps in microseconds
+#[derive(Clone, Debug, Serialize)]
+pub struct TraceEvent {
+    pub name: String,
+    pub cat: String,
+    pub ph: &'static str,
+    pub ts: u64,
+    pub dur: u64,
+    pub pid: u32,
+    pub tid: u32,
+}
+
+struct TraceSession {
+    path: PathBuf,
+    origin: Instant,
+    events: Vec<TraceEvent>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct TraceFile<'a> {
+    trace_events: &'a [TraceEvent],
+    display_time_unit: &'static str,
+}
+
+impl Default for PerformanceMonitor {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl PerformanceMonitor {
@@ -37,6 +141,7 @@ impl PerformanceMonitor {
         Self {
             metrics: Arc::new(RwLock::new(PerformanceMetrics::default())),
             start_time: Instant::now(),
+            trace: Arc::new(Mutex::new(None)),
         }
     }
     
@@ -57,6 +162,14 @@ impl PerformanceMonitor {
         metrics.parsing_times.push_back(duration);
     }
     
+    pub fn record_child_usage(&self, usage: &ResourceUsage) {
+        let mut metrics = self.metrics.write();
+        metrics.child_user_time += usage.user_time;
+        metrics.child_system_time += usage.system_time;
+        metrics.child_max_rss_kb = metrics.child_max_rss_kb.max(usage.max_rss_kb);
+        metrics.child_page_faults += usage.minor_faults + usage.major_faults;
+    }
+    
     pub fn update_cache_hit_rate(&self, hits: usize, total: usize) {
         if total > 0 {
             let mut metrics = self.metrics.write();
@@ -69,6 +182,53 @@ impl PerformanceMonitor {
         metrics.memory_usage = bytes;
     }
     
+    pub fn start_trace(&self, path: PathBuf) {
+        *self.trace.lock() = Some(TraceSession {
+            path,
+            origin: Instant::now(),
+            events: Vec::new(),
+        });
+    }
+    
+    pub fn is_tracing(&self) -> bool {
+        self.trace.lock().is_some()
+    }
+    
+    // Records a span from `start` until now; a no-op unless tracing
+    pub fn trace_span(&self, name: &str, category: &str, start: Instant) {
+        let mut trace = self.trace.lock();
+        if let Some(session) = trace.as_mut() {
+            let ts = start.saturating_duration_since(session.origin);
+            session.events.push(TraceEvent {
+                name: name.to_string(),
+                cat: category.to_string(),
+                ph: "X",
+                ts: ts.as_micros() as u64,
+                dur: start.elapsed().as_micros() as u64,
+                pid: std::process::id(),
+                tid: 1,
+            });
+        }
+    }
+    
+    // Stops tracing and writes the chrome://tracing JSON file
+    pub fn stop_trace(&self) -> Option<std::io::Result<(PathBuf, usize)>> {
+        let session = self.trace.lock().take()?;
+        let file = TraceFile {
+            trace_events: &session.events,
+            display_time_unit: "ms",
+        };
+        let result = serde_json::to_string(&file)
+            .map_err(std::io::Error::other)
+            .and_then(|json| std::fs::write(&session.path, json))
+            .map(|_| (session.path, session.events.len()));
+        Some(result)
+    }
+    
+    pub fn reset(&self) {
+        *self.metrics.write() = PerformanceMetrics::default();
+    }
+    
     pub fn get_metrics(&self) -> PerformanceMetrics {
         self.metrics.read().clone()
     }
@@ -91,23 +251,225 @@ impl PerformanceMonitor {
         let metrics = self.get_metrics();
         let uptime = self.get_uptime();
         
-        println!("\n=== Performance Report ===");
-        println!("Uptime: {:?}", uptime);
-        println!("Total commands executed: {}", metrics.total_commands);
+        outln!("\n=== Performance Report ===");
+        outln!("Uptime: {}", format_duration(uptime));
+        outln!("Total commands executed: {}", metrics.total_commands);
         
         if let Some(avg_exec) = self.get_average_execution_time() {
-            println!("Average execution time: {:?}", avg_exec);
+            outln!("Average execution time: {}", format_duration(avg_exec));
         }
         
         if !metrics.parsing_times.is_empty() {
             let avg_parse: Duration = metrics.parsing_times.iter().sum::<Duration>() 
                 / metrics.parsing_times.len() as u32;
-            println!("Average parsing time: {:?}", avg_parse);
+            outln!("Average parsing time: {}", format_duration(avg_parse));
         }
         
-        println!("Cache hit rate: {:.2}%", metrics.cache_hit_rate * 100.0);
-        println!("Memory usage: {} KB", metrics.memory_usage / 1024);
-        println!("Active threads: {}", metrics.active_threads);
+        outln!("Child CPU time: {} user, {} system",
+                 format_duration(metrics.child_user_time), format_duration(metrics.child_system_time));
+        outln!("Child peak RSS: {}", format_bytes(metrics.child_max_rss_kb * 1024));
+        outln!("Child page faults: {}", metrics.child_page_faults);
+        outln!("Cache hit rate: {:.2}%", metrics.cache_hit_rate * 100.0);
+        outln!("Memory usage: {}", format_bytes(metrics.memory_usage as u64));
+        outln!("Active threads: {}", metrics.active_threads);
+    }
+}
+
+// `1m2.345s`, the way `times` and `time` show durations
+fn format_minutes(duration: Duration) -> String {
+    format!("{}m{}.{:03}s", duration.as_secs() / 60, duration.as_secs() % 60, duration.subsec_millis())
+}
+
+// `times`: user and system CPU time of the shell, then of its children
+pub fn builtin_times(_shell: &mut crate::shell::Shell, _args: &[String]) -> i32 {
+    for who in [libc::RUSAGE_SELF, libc::RUSAGE_CHILDREN] {
+        let usage = ResourceUsage::current(who);
+        outln!("{} {}", format_minutes(usage.user_time), format_minutes(usage.system_time));
+    }
+    0
+}
+
+// `time CMD [ARG...]` runs the command and reports on stderr how long it
+// took and the CPU time it, and any children it waited for, used
+pub fn builtin_time(shell: &mut crate::shell::Shell, args: &[String]) -> i32 {
+    use crate::shell::{executor::Executor, parser::Parser};
+    use crate::utils::string_utils::shell_quote;
+    
+    let start = Instant::now();
+    let before = [libc::RUSAGE_SELF, libc::RUSAGE_CHILDREN].map(ResourceUsage::current);
+    let status = if args.is_empty() {
+        0
+    } else {
+        // The words are already expanded; quoting keeps them as they are
+        let line = args.iter().map(|arg| shell_quote(arg)).collect::<Vec<_>>().join(" ");
+        let arena = bumpalo::Bump::new();
+        match Parser::with_aliases(&line, &shell.aliases, &arena).and_then(|mut parser| parser.parse()) {
+            Ok(parsed) => Executor::new().execute(shell, parsed),
+            Err(e) => {
+                eprintln!("time: {}", e.render(&line));
+                return 2;
+            }
+        }
+    };
+    let real = start.elapsed();
+    let after = [libc::RUSAGE_SELF, libc::RUSAGE_CHILDREN].map(ResourceUsage::current);
+    let used = |pick: fn(&ResourceUsage) -> Duration| {
+        before.iter().zip(&after).map(|(b, a)| pick(a).saturating_sub(pick(b))).sum::<Duration>()
+    };
+    eprintln!();
+    eprintln!("real\t{}", format_minutes(real));
+    eprintln!("user\t{}", format_minutes(used(|usage| usage.user_time)));
+    eprintln!("sys\t{}", format_minutes(used(|usage| usage.system_time)));
+    status
+}
+
+pub fn builtin_perf(shell: &mut crate::shell::Shell, args: &[String]) -> i32 {
+    match args.first().map(|s| s.as_str()) {
+        None | Some("report") => {
+            shell.perf.print_report();
+            0
+        }
+        Some("reset") => {
+            shell.perf.reset();
+            outln!("Performance metrics reset");
+            0
+        }
+        Some("trace") => builtin_perf_trace(shell, &args[1..]),
+        Some("hints") => {
+            match args.get(1).map(|s| s.as_str()) {
+                Some("on") => shell.config.general.slow_command_hints = true,
+                Some("off") => shell.config.general.slow_command_hints = false,
+                None => {}
+                Some(other) => {
+                    eprintln!("perf: hints: expected 'on' or 'off', got '{}'", other);
+                    return 1;
+                }
+            }
+            outln!("Slow-command hints: {} (threshold {}ms)",
+                     if shell.config.general.slow_command_hints { "on" } else { "off" },
+                     shell.config.general.slow_command_threshold_ms);
+            0
+        }
+        Some(other) => {
+            eprintln!("perf: unknown subcommand '{}'", other);
+            eprintln!("Usage: perf [report|reset|hints [on|off]|trace start [file]|trace stop]");
+            1
+        }
+    }
+}
+
+fn builtin_perf_trace(shell: &mut crate::shell::Shell, args: &[String]) -> i32 {
+    match args.first().map(|s| s.as_str()) {
+        Some("start") => {
+            let path = args.get(1)
+                .map(PathBuf::from)
+                .unwrap_or_else(|| PathBuf::from("rshell-trace.json"));
+            let path = crate::utils::path_utils::resolve_path(&path.to_string_lossy())
+                .unwrap_or(path);
+            outln!("Tracing to {} (stop with 'perf trace stop')", path.display());
+            shell.perf.start_trace(path);
+            0
+        }
+        Some("stop") => match shell.perf.stop_trace() {
+            Some(Ok((path, count))) => {
+                outln!("Wrote {} events to {} (open in chrome://tracing or ui.perfetto.dev)",
+                         count, path.display());
+                0
+            }
+            Some(Err(e)) => {
+                eprintln!("perf: failed to write trace: {}", e);
+                1
+            }
+            None => {
+                eprintln!("perf: no trace in progress");
+                1
+            }
+        },
+        _ => {
+            eprintln!("Usage: perf trace start [file] | perf trace stop");
+            1
+        }
+    }
+}
+
+// Programs whose run time is dominated by the user, not worth a hint
+const INTERACTIVE_PROGRAMS: &[&str] = &[
+    "vim", "vi", "nvim", "nano", "emacs", "less", "more", "man", "ssh",
+    "top", "htop", "watch", "tmux", "screen", "sleep", "rshell", "bash", "zsh", "sh",
+];
+
+// Builds the post-run hint shown when a command exceeded the slow threshold.
+// `average` is the monitor's average execution time, used for the comparison.
+pub fn slow_command_hint(command_line: &str, duration: Duration, average: Option<Duration>) -> Option<String> {
+    let trimmed = command_line.trim();
+    if trimmed.ends_with('&') {
+        return None;
+    }
+    let words: Vec<&str> = trimmed.split_whitespace().collect();
+    let program = words.first()?.rsplit('/').next().unwrap_or_default();
+    if INTERACTIVE_PROGRAMS.contains(&program) && !trimmed.contains('|') {
+        return None;
+    }
+    
+    let file_args = words[1..].iter()
+        .filter(|w| !w.starts_with('-') && std::path::Path::new(w).is_file())
+        .count();
+    let suggestion = if file_args > 1 {
+        "process the files concurrently (e.g. xargs -P) instead of one by one"
+    } else {
+        "add & to run it in the background"
+    };
+    
+    let mut hint = format!("took {}; {}",
+                           format_duration(duration), suggestion);
+    if let Some(avg) = average.filter(|avg| !avg.is_zero()) {
+        let ratio = duration.as_secs_f64() / avg.as_secs_f64();
+        if ratio >= 5.0 {
+            hint.push_str(&format!(" ({:.0}x your average command)", ratio));
+        }
+    }
+    Some(hint)
+}
+
+// Startup phase timings for `rshell --profile-startup`
+pub struct StartupProfiler {
+    enabled: bool,
+    start: Instant,
+    last_mark: Instant,
+    phases: Vec<(String, Duration)>,
+}
+
+impl StartupProfiler {
+    pub fn new(enabled: bool) -> Self {
+        let now = Instant::now();
+        Self {
+            enabled,
+            start: now,
+            last_mark: now,
+            phases: Vec::new(),
+        }
+    }
+    
+    // Records the time since the previous mark under `phase`
+    pub fn mark(&mut self, phase: &str) {
+        let now = Instant::now();
+        self.phases.push((phase.to_string(), now - self.last_mark));
+        self.last_mark = now;
+    }
+    
+    pub fn phases(&self) -> &[(String, Duration)] {
+        &self.phases
+    }
+    
+    pub fn report(&self) {
+        if !self.enabled {
+            return;
+        }
+        eprintln!("Startup profile:");
+        for (phase, duration) in &self.phases {
+            eprintln!("  {:<32} {:>10}", phase, format_duration(*duration));
+        }
+        eprintln!("  {:<32} {:>10}", "total", format_duration(self.start.elapsed()));
     }
 }
 
@@ -144,4 +506,27 @@ macro_rules! profile {
     ($name:expr) => {
         let _profiler = $crate::performance::Profiler::new($name);
     };
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn slow_hint_suggests_background() {
+        let hint = slow_command_hint("cargo build", Duration::from_secs(12), None).unwrap();
+        assert!(hint.contains("took 12.0s"));
+        assert!(hint.contains("add &"));
+    }
+
+    #[test]
+    fn slow_hint_compares_to_average() {
+        let hint = slow_command_hint("make", Duration::from_secs(20), Some(Duration::from_secs(1))).unwrap();
+        assert!(hint.contains("20x your average"));
+    }
+
+    #[test]
+    fn slow_hint_skips_interactive_and_background() {
+        assert!(slow_command_hint("vim notes.txt", Duration::from_secs(60), None).is_none());
+        assert!(slow_command_hint("make &", Duration::from_secs(60), None).is_none());
+    }
+}
@@ -0,0 +1,120 @@
+// src/platform.rs
+//
+// The few things rshell needs that POSIX leaves to each OS. Linux answers
+// from /proc, macOS from sysctl(3); other Unixes get the portable fallback
+// (or None, and the caller leaves the field out).
+use std::time::Duration;
+
+// PATH for a shell started with an empty environment (`env -i rshell`).
+// Homebrew installs to /opt/homebrew on Apple silicon.
+#[cfg(target_os = "macos")]
+pub const DEFAULT_PATH: &str = "/opt/homebrew/bin:/usr/local/bin:/usr/bin:/bin:/usr/sbin:/sbin";
+#[cfg(not(target_os = "macos"))]
+pub const DEFAULT_PATH: &str = "/usr/local/bin:/usr/bin:/bin:/usr/local/sbin:/usr/sbin:/sbin";
+
+// User, mount and network namespaces, which `sandbox` is built on
+pub const HAS_NAMESPACES: bool = cfg!(target_os = "linux");
+
+#[cfg(target_os = "macos")]
+fn sysctl<T: Copy>(name: &str) -> Option<T> {
+    let name = std::ffi::CString::new(name).ok()?;
+    let mut value: T = unsafe { std::mem::zeroed() };
+    let mut size = std::mem::size_of::<T>();
+    let rc = unsafe {
+        libc::sysctlbyname(name.as_ptr(), &mut value as *mut T as *mut libc::c_void,
+                           &mut size, std::ptr::null_mut(), 0)
+    };
+    (rc == 0 && size == std::mem::size_of::<T>()).then_some(value)
+}
+
+// PRETTY_NAME from os-release, e.g. "Debian GNU/Linux 12 (bookworm)"
+#[cfg(not(target_os = "macos"))]
+pub fn os_name() -> Option<String> {
+    let contents = std::fs::read_to_string("/etc/os-release")
+        .or_else(|_| std::fs::read_to_string("/usr/lib/os-release")).ok()?;
+    contents.lines()
+        .find_map(|line| line.strip_prefix("PRETTY_NAME="))
+        .map(|value| value.trim_matches('"').to_string())
+}
+
+// Product name and version from SystemVersion.plist, e.g. "macOS 14.4"
+#[cfg(target_os = "macos")]
+pub fn os_name() -> Option<String> {
+    let plist = std::fs::read_to_string("/System/Library/CoreServices/SystemVersion.plist").ok()?;
+    let value = |key: &str| {
+        let rest = &plist[plist.find(&format!("<key>{}</key>", key))?..];
+        let start = rest.find("<string>")? + "<string>".len();
+        let end = rest[start..].find("</string>")?;
+        Some(rest[start..start + end].to_string())
+    };
+    match (value("ProductName"), value("ProductVersion")) {
+        (Some(name), Some(version)) => Some(format!("{} {}", name, version)),
+        (name, _) => name,
+    }
+}
+
+#[cfg(target_os = "linux")]
+pub fn system_uptime() -> Option<Duration> {
+    let contents = std::fs::read_to_string("/proc/uptime").ok()?;
+    let seconds: f64 = contents.split_whitespace().next()?.parse().ok()?;
+    Some(Duration::from_secs_f64(seconds))
+}
+
+#[cfg(target_os = "macos")]
+pub fn system_uptime() -> Option<Duration> {
+    let boot: libc::timeval = sysctl("kern.boottime")?;
+    let now = std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).ok()?;
+    now.checked_sub(Duration::from_secs(boot.tv_sec as u64))
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "macos")))]
+pub fn system_uptime() -> Option<Duration> {
+    None
+}
+
+// (total, available) in kilobytes. MemAvailable accounts for reclaimable
+// caches; without /proc fall back to sysconf, where everything free counts.
+#[cfg(target_os = "linux")]
+pub fn memory_info() -> Option<(u64, u64)> {
+    if let Ok(contents) = std::fs::read_to_string("/proc/meminfo") {
+        let field = |name: &str| contents.lines()
+            .find_map(|line| line.strip_prefix(name))
+            .and_then(|rest| rest.trim_start_matches(':').split_whitespace().next())
+            .and_then(|kb| kb.parse::<u64>().ok());
+        if let (Some(total), Some(available)) = (field("MemTotal"), field("MemAvailable")) {
+            return Some((total, available));
+        }
+    }
+    let page_kb = unsafe { libc::sysconf(libc::_SC_PAGESIZE) } as u64 / 1024;
+    let total = unsafe { libc::sysconf(libc::_SC_PHYS_PAGES) };
+    let available = unsafe { libc::sysconf(libc::_SC_AVPHYS_PAGES) };
+    if total <= 0 || available < 0 {
+        return None;
+    }
+    Some((total as u64 * page_kb, available as u64 * page_kb))
+}
+
+// Free, speculative and purgeable pages are what the kernel hands out
+// without paging anything, the closest macOS has to MemAvailable
+#[cfg(target_os = "macos")]
+pub fn memory_info() -> Option<(u64, u64)> {
+    let total: u64 = sysctl("hw.memsize")?;
+    let page_kb = unsafe { libc::sysconf(libc::_SC_PAGESIZE) } as u64 / 1024;
+    let pages: u64 = ["vm.page_free_count", "vm.page_speculative_count", "vm.page_purgeable_count"]
+        .iter()
+        .filter_map(|name| sysctl::<u32>(name))
+        .map(u64::from)
+        .sum();
+    Some((total / 1024, pages * page_kb))
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "macos")))]
+pub fn memory_info() -> Option<(u64, u64)> {
+    None
+}
+
+// ru_maxrss is in kilobytes on Linux but in bytes on macOS
+pub fn max_rss_kb(ru_maxrss: libc::c_long) -> u64 {
+    let value = ru_maxrss.max(0) as u64;
+    if cfg!(target_os = "macos") { value / 1024 } else { value }
+}
@@ -0,0 +1,397 @@
+// src/plugin.rs
+//
+// Plugins are cdylib crates that depend on rust_shell and export their
+// declaration with `declare_plugin!`. Trait objects cross the library
+// boundary, so a plugin must be built by the same compiler against the same
+// shell version. The declaration carries both (the compiler's comes from
+// build.rs) and they are checked before anything else is touched.
+use crate::shell::Shell;
+use crate::outln;
+use libloading::Library;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, RwLock};
+
+pub const ABI_VERSION: u32 = 2;
+pub const SHELL_VERSION: &str = env!("CARGO_PKG_VERSION");
+pub const RUSTC_VERSION: &str = env!("RSHELL_RUSTC_VERSION");
+const DECLARATION_SYMBOL: &[u8] = b"RSHELL_PLUGIN\0";
+
+pub type BuiltinFn = Box<dyn Fn(&[String]) -> i32 + Send + Sync>;
+// Called with the arguments before the cursor and the word being completed
+pub type CompleterFn = Box<dyn Fn(&[String], &str) -> Vec<String> + Send + Sync>;
+pub type SegmentFn = Box<dyn Fn() -> String + Send + Sync>;
+
+pub struct Registry<T> {
+    entries: Vec<(String, T)>,
+}
+
+impl<T> Default for Registry<T> {
+    fn default() -> Self {
+        Self { entries: Vec::new() }
+    }
+}
+
+impl<T> Registry<T> {
+    pub fn register(&mut self, name: &str, entry: T) {
+        self.entries.retain(|(existing, _)| existing != name);
+        self.entries.push((name.to_string(), entry));
+    }
+
+    pub fn get(&self, name: &str) -> Option<&T> {
+        self.entries.iter().find(|(existing, _)| existing == name).map(|(_, entry)| entry)
+    }
+
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.entries.iter().map(|(name, _)| name.as_str())
+    }
+}
+
+pub trait ShellPlugin: Send + Sync {
+    fn name(&self) -> &str;
+    fn version(&self) -> &str;
+    fn register_builtins(&self, _builtins: &mut Registry<BuiltinFn>) {}
+    // Keyed by the command whose arguments they complete
+    fn register_completers(&self, _completers: &mut Registry<CompleterFn>) {}
+    // Each segment `name` fills `{name}` in the prompt format
+    fn prompt_segments(&self, _segments: &mut Registry<SegmentFn>) {}
+    fn on_command(&self, _line: &str, _exit_code: i32) {}
+}
+
+#[repr(C)]
+pub struct PluginDeclaration {
+    pub abi_version: u32,
+    pub shell_version: &'static str,
+    pub rustc_version: &'static str,
+    pub create: fn() -> Box<dyn ShellPlugin>,
+}
+
+// Exports the declaration the shell looks up, e.g.
+// `rust_shell::declare_plugin!(GitPlugin::new());`
+#[macro_export]
+macro_rules! declare_plugin {
+    ($constructor:expr) => {
+        #[no_mangle]
+        pub static RSHELL_PLUGIN: $crate::plugin::PluginDeclaration = $crate::plugin::PluginDeclaration {
+            abi_version: $crate::plugin::ABI_VERSION,
+            shell_version: $crate::plugin::SHELL_VERSION,
+            rustc_version: $crate::plugin::RUSTC_VERSION,
+            create: || Box::new($constructor),
+        };
+    };
+}
+
+struct HostPlugin;
+
+impl ShellPlugin for HostPlugin {
+    fn name(&self) -> &str {
+        "host"
+    }
+
+    fn version(&self) -> &str {
+        SHELL_VERSION
+    }
+}
+
+struct LoadedPlugin {
+    path: Option<PathBuf>,
+    builtins: Registry<BuiltinFn>,
+    completers: Registry<CompleterFn>,
+    segments: Registry<SegmentFn>,
+    plugin: Box<dyn ShellPlugin>,
+    // Dropped last: the code behind everything above lives in it
+    _library: Option<Library>,
+}
+
+// Shared between the shell and the line editor
+#[derive(Clone, Default)]
+pub struct PluginManager {
+    plugins: Arc<RwLock<Vec<LoadedPlugin>>>,
+}
+
+pub fn plugins_dir() -> PathBuf {
+    dirs::home_dir().unwrap_or_default().join(".config").join("rshell").join("plugins")
+}
+
+// A bare name is looked up in the plugins directory as lib<name>.so or <name>.so
+pub fn resolve(name: &str) -> PathBuf {
+    if name.contains('/') {
+        return PathBuf::from(crate::utils::string_utils::expand_tilde(name));
+    }
+    let dir = plugins_dir();
+    let lib = dir.join(format!("lib{}.so", name));
+    if lib.exists() {
+        lib
+    } else {
+        dir.join(format!("{}.so", name))
+    }
+}
+
+impl PluginManager {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn read(&self) -> std::sync::RwLockReadGuard<'_, Vec<LoadedPlugin>> {
+        self.plugins.read().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn write(&self) -> std::sync::RwLockWriteGuard<'_, Vec<LoadedPlugin>> {
+        self.plugins.write().unwrap_or_else(|e| e.into_inner())
+    }
+
+    // Registers an in-process plugin, or one whose library is kept alive with it
+    pub fn add(&self, plugin: Box<dyn ShellPlugin>, path: Option<PathBuf>, library: Option<Library>) -> Result<String, String> {
+        let name = plugin.name().to_string();
+        if self.read().iter().any(|p| p.plugin.name() == name) {
+            return Err(format!("a plugin named '{}' is already loaded", name));
+        }
+        let mut loaded = LoadedPlugin {
+            path,
+            builtins: Registry::default(),
+            completers: Registry::default(),
+            segments: Registry::default(),
+            plugin,
+            _library: library,
+        };
+        loaded.plugin.register_builtins(&mut loaded.builtins);
+        loaded.plugin.register_completers(&mut loaded.completers);
+        loaded.plugin.prompt_segments(&mut loaded.segments);
+        self.write().push(loaded);
+        Ok(name)
+    }
+
+    // Builtins registered by an embedding application (see ShellSession) are
+    // kept under a built-in "host" plugin
+    pub fn register_builtin(&self, name: &str, builtin: BuiltinFn) {
+        let mut plugins = self.write();
+        if !plugins.iter().any(|p| p.plugin.name() == HostPlugin.name()) {
+            plugins.push(LoadedPlugin {
+                path: None,
+                builtins: Registry::default(),
+                completers: Registry::default(),
+                segments: Registry::default(),
+                plugin: Box::new(HostPlugin),
+                _library: None,
+            });
+        }
+        if let Some(host) = plugins.iter_mut().find(|p| p.plugin.name() == HostPlugin.name()) {
+            host.builtins.register(name, builtin);
+        }
+    }
+
+    pub fn load(&self, path: &Path) -> Result<String, String> {
+        let library = unsafe { Library::new(path) }.map_err(|e| e.to_string())?;
+        // The symbol's address is the address of the exported static
+        let declaration: *const PluginDeclaration = unsafe {
+            *library.get::<*const PluginDeclaration>(DECLARATION_SYMBOL)
+                .map_err(|_| "not an rshell plugin (no RSHELL_PLUGIN symbol)".to_string())?
+        };
+        let declaration = unsafe { &*declaration };
+        if declaration.abi_version != ABI_VERSION {
+            return Err(format!("plugin ABI {} does not match the shell's ABI {}",
+                               declaration.abi_version, ABI_VERSION));
+        }
+        if declaration.shell_version != SHELL_VERSION {
+            return Err(format!("built for rshell {}, this is {}; rebuild the plugin",
+                               declaration.shell_version, SHELL_VERSION));
+        }
+        if declaration.rustc_version != RUSTC_VERSION {
+            return Err(format!("built with {}, the shell with {}; rebuild the plugin",
+                               declaration.rustc_version, RUSTC_VERSION));
+        }
+        let plugin = (declaration.create)();
+        self.add(plugin, Some(path.to_path_buf()), Some(library))
+    }
+
+    // Loads the plugins named in the config; failures are reported, not fatal
+    pub fn load_configured(&self, names: &[String]) {
+        for name in names {
+            let path = resolve(name);
+            if let Err(e) = self.load(&path) {
+                eprintln!("rshell: plugin {}: {}", path.display(), e);
+            }
+        }
+    }
+
+    pub fn unload(&self, name: &str) -> bool {
+        let mut plugins = self.write();
+        let before = plugins.len();
+        plugins.retain(|p| p.plugin.name() != name);
+        plugins.len() != before
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.read().is_empty()
+    }
+
+    pub fn has_builtin(&self, name: &str) -> bool {
+        self.read().iter().any(|p| p.builtins.get(name).is_some())
+    }
+
+    pub fn builtin_names(&self) -> Vec<String> {
+        self.read().iter().flat_map(|p| p.builtins.names().map(String::from).collect::<Vec<_>>()).collect()
+    }
+
+    pub fn run_builtin(&self, name: &str, args: &[String]) -> Option<i32> {
+        let plugins = self.read();
+        plugins.iter().find_map(|p| p.builtins.get(name)).map(|builtin| builtin(args))
+    }
+
+    pub fn complete(&self, command: &str, args: &[String], word: &str) -> Option<Vec<String>> {
+        let plugins = self.read();
+        plugins.iter().find_map(|p| p.completers.get(command)).map(|completer| completer(args, word))
+    }
+
+    pub fn expand_prompt_segments(&self, prompt: &str) -> String {
+        let mut prompt = prompt.to_string();
+        for plugin in self.read().iter() {
+            for (name, segment) in &plugin.segments.entries {
+                let placeholder = format!("{{{}}}", name);
+                if prompt.contains(&placeholder) {
+                    prompt = prompt.replace(&placeholder, &segment());
+                }
+            }
+        }
+        prompt
+    }
+
+    pub fn on_command(&self, line: &str, exit_code: i32) {
+        for plugin in self.read().iter() {
+            plugin.plugin.on_command(line, exit_code);
+        }
+    }
+}
+
+pub fn builtin_plugin(shell: &mut Shell, args: &[String]) -> i32 {
+    match args.first().map(String::as_str) {
+        None | Some("list") => {
+            let plugins = shell.plugins.read();
+            if plugins.is_empty() {
+                outln!("No plugins loaded (plugin directory: {})", plugins_dir().display());
+            }
+            for loaded in plugins.iter() {
+                let path = loaded.path.as_ref().map(|p| p.display().to_string()).unwrap_or_default();
+                outln!("{} {}  {}", loaded.plugin.name(), loaded.plugin.version(), path);
+                let builtins: Vec<&str> = loaded.builtins.names().collect();
+                if !builtins.is_empty() {
+                    outln!("  builtins:   {}", builtins.join(" "));
+                }
+                let completers: Vec<&str> = loaded.completers.names().collect();
+                if !completers.is_empty() {
+                    outln!("  completes:  {}", completers.join(" "));
+                }
+                let segments: Vec<String> = loaded.segments.names().map(|s| format!("{{{}}}", s)).collect();
+                if !segments.is_empty() {
+                    outln!("  prompt:     {}", segments.join(" "));
+                }
+            }
+            0
+        }
+        Some("available") => {
+            let Ok(entries) = std::fs::read_dir(plugins_dir()) else {
+                outln!("No plugin directory at {}", plugins_dir().display());
+                return 0;
+            };
+            let mut names: Vec<String> = entries.flatten()
+                .map(|e| e.path())
+                .filter(|p| p.extension().is_some_and(|ext| ext == "so"))
+                .filter_map(|p| p.file_stem().map(|s| s.to_string_lossy().trim_start_matches("lib").to_string()))
+                .collect();
+            names.sort();
+            for name in names {
+                outln!("{}", name);
+            }
+            0
+        }
+        Some("load") if args.len() == 2 => {
+            let path = resolve(&args[1]);
+            match shell.plugins.load(&path) {
+                Ok(name) => {
+                    outln!("Loaded plugin {}", name);
+                    0
+                }
+                Err(e) => {
+                    eprintln!("plugin: {}: {}", path.display(), e);
+                    1
+                }
+            }
+        }
+        Some("unload") if args.len() == 2 => {
+            if shell.plugins.unload(&args[1]) {
+                0
+            } else {
+                eprintln!("plugin: {}: not loaded", args[1]);
+                1
+            }
+        }
+        _ => {
+            eprintln!("usage: plugin [list | available | load <name|path> | unload <name>]");
+            2
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicI32, Ordering};
+
+    static LAST_EXIT: AtomicI32 = AtomicI32::new(-1);
+
+    struct Greeter;
+
+    impl ShellPlugin for Greeter {
+        fn name(&self) -> &str {
+            "greeter"
+        }
+
+        fn version(&self) -> &str {
+            "1.0.0"
+        }
+
+        fn register_builtins(&self, builtins: &mut Registry<BuiltinFn>) {
+            builtins.register("greet", Box::new(|args| args.len() as i32));
+        }
+
+        fn register_completers(&self, completers: &mut Registry<CompleterFn>) {
+            completers.register("greet", Box::new(|_, word| {
+                ["alice", "bob"].iter().filter(|n| n.starts_with(word)).map(|n| n.to_string()).collect()
+            }));
+        }
+
+        fn prompt_segments(&self, segments: &mut Registry<SegmentFn>) {
+            segments.register("greeting", Box::new(|| "hi".to_string()));
+        }
+
+        fn on_command(&self, _line: &str, exit_code: i32) {
+            LAST_EXIT.store(exit_code, Ordering::SeqCst);
+        }
+    }
+
+    #[test]
+    fn registration() {
+        let manager = PluginManager::new();
+        assert_eq!(manager.add(Box::new(Greeter), None, None).unwrap(), "greeter");
+        assert!(manager.add(Box::new(Greeter), None, None).is_err());
+
+        assert_eq!(manager.run_builtin("greet", &["a".to_string(), "b".to_string()]), Some(2));
+        assert_eq!(manager.run_builtin("nope", &[]), None);
+        assert_eq!(manager.complete("greet", &[], "a"), Some(vec!["alice".to_string()]));
+        assert_eq!(manager.expand_prompt_segments("{greeting} {user}"), "hi {user}");
+        manager.on_command("greet", 3);
+        assert_eq!(LAST_EXIT.load(Ordering::SeqCst), 3);
+
+        assert!(manager.unload("greeter"));
+        assert!(!manager.has_builtin("greet"));
+    }
+
+    #[test]
+    fn rejects_non_plugins() {
+        assert!(PluginManager::new().load(Path::new("/nonexistent/libnothing.so")).is_err());
+    }
+
+    #[test]
+    fn compiler_version_is_recorded() {
+        assert!(RUSTC_VERSION.starts_with("rustc "), "{}", RUSTC_VERSION);
+    }
+}
@@ -0,0 +1,58 @@
+// src/prompt_command.rs
+use crate::shell::Shell;
+use std::process::{Command, Stdio};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc;
+use std::thread;
+use std::time::Duration;
+
+static TIMEOUT_REPORTED: AtomicBool = AtomicBool::new(false);
+
+// Runs `general.prompt_command` through sh and returns its output, styling
+// included. The command sees the last status, duration and job count as
+// RSHELL_STATUS, RSHELL_DURATION_MS and RSHELL_JOBS, e.g. for starship:
+//   starship prompt --status=$RSHELL_STATUS --cmd-duration=$RSHELL_DURATION_MS --jobs=$RSHELL_JOBS
+// None (use the built-in prompt) when unset, failing or slower than the timeout.
+pub fn render(shell: &Shell) -> Option<String> {
+    let general = &shell.config.general;
+    if general.prompt_command.trim().is_empty() {
+        return None;
+    }
+    let duration_ms = shell.last_duration.map(|d| d.as_millis()).unwrap_or(0);
+    let mut command = Command::new("sh");
+    command.arg("-c").arg(&general.prompt_command)
+        .current_dir(&shell.current_dir)
+        .env("RSHELL_STATUS", shell.last_exit_code.to_string())
+        .env("RSHELL_DURATION_MS", duration_ms.to_string())
+        .env("RSHELL_JOBS", shell.jobs.len().to_string())
+        .env("STARSHIP_SHELL", "rshell")
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null());
+    if let Some(columns) = crate::terminal::columns() {
+        command.env("COLUMNS", columns.to_string());
+    }
+    let child = command.spawn().ok()?;
+    let pid = child.id() as libc::pid_t;
+
+    // Wait off-thread so a hung prompt program cannot hang the shell
+    let (tx, rx) = mpsc::channel();
+    thread::spawn(move || {
+        let _ = tx.send(child.wait_with_output());
+    });
+    match rx.recv_timeout(Duration::from_millis(general.prompt_command_timeout_ms.max(1))) {
+        Ok(Ok(output)) if output.status.success() => {
+            let prompt = String::from_utf8_lossy(&output.stdout);
+            Some(prompt.trim_end_matches(['\n', '\r']).to_string())
+        }
+        Ok(_) => None,
+        Err(_) => {
+            unsafe { libc::kill(pid, libc::SIGKILL) };
+            if !TIMEOUT_REPORTED.swap(true, Ordering::Relaxed) {
+                eprintln!("rshell: prompt_command took longer than {}ms, using the built-in prompt",
+                          general.prompt_command_timeout_ms);
+            }
+            None
+        }
+    }
+}
@@ -0,0 +1,163 @@
+// src/safety.rs
+use crate::command::Command;
+use crate::config::{DangerPattern, Severity};
+use crate::shell::Shell;
+use glob::{MatchOptions, Pattern};
+use std::io::{self, BufRead, Write};
+
+// Exported (to any value) to run dangerous commands without being asked
+pub const OVERRIDE_VAR: &str = "RSHELL_ALLOW_DANGEROUS";
+
+const WRAPPERS: &[&str] = &["sudo", "doas", "nice", "nohup", "time"];
+
+fn glob_matches(pattern: &str, text: &str) -> bool {
+    let options = MatchOptions { require_literal_separator: true, ..MatchOptions::new() };
+    match Pattern::new(pattern) {
+        Ok(glob) => glob.matches_with(text, options),
+        Err(_) => pattern == text,
+    }
+}
+
+fn is_flag_cluster(word: &str) -> bool {
+    word.len() > 1 && word.starts_with('-') && !word.starts_with("--")
+}
+
+// Skips privilege and scheduling wrappers so `sudo rm -rf /` is caught too
+fn unwrap_command<'a>(program: &'a str, args: &'a [String]) -> (&'a str, &'a [String]) {
+    let mut program = program;
+    let mut args = args;
+    while WRAPPERS.contains(&program.rsplit('/').next().unwrap_or(program)) {
+        let skip = args.iter().take_while(|a| a.starts_with('-')).count();
+        match args.get(skip) {
+            Some(next) => {
+                program = next;
+                args = &args[skip + 1..];
+            }
+            None => break,
+        }
+    }
+    (program, args)
+}
+
+// The first pattern word is a glob over the program name, the rest must all
+// occur among the arguments in any order. Short flag clusters compare by
+// letter, so `rm -rf /` also matches `rm -fr /` and `rm -r -f /`.
+pub fn pattern_matches(pattern: &str, program: &str, args: &[String]) -> bool {
+    let (program, args) = unwrap_command(program, args);
+    let mut words = pattern.split_whitespace();
+    let Some(first) = words.next() else {
+        return false;
+    };
+    let name = program.rsplit('/').next().unwrap_or(program);
+    if !glob_matches(first, name) {
+        return false;
+    }
+    let flags: String = args.iter()
+        .filter(|a| is_flag_cluster(a))
+        .flat_map(|a| a.chars().skip(1))
+        .collect();
+    words.all(|word| {
+        if is_flag_cluster(word) {
+            word.chars().skip(1).all(|c| flags.contains(c))
+        } else {
+            args.iter().any(|arg| glob_matches(word, arg))
+        }
+    })
+}
+
+// The most severe pattern the command matches
+pub fn check<'a>(patterns: &'a [DangerPattern], command: &Command) -> Option<&'a DangerPattern> {
+    patterns.iter()
+        .filter(|p| pattern_matches(&p.pattern, &command.program, &command.args))
+        .max_by_key(|p| p.severity)
+}
+
+fn stdin_is_terminal() -> bool {
+    unsafe { libc::isatty(libc::STDIN_FILENO) == 1 }
+}
+
+fn ask(question: &str) -> Option<String> {
+    eprint!("{}", question);
+    let _ = io::stderr().flush();
+    let mut answer = String::new();
+    io::stdin().lock().read_line(&mut answer).ok()?;
+    Some(answer.trim().to_string())
+}
+
+// A shell variable of the name does not count, it has to be exported
+fn overridden(shell: &Shell) -> bool {
+    shell.is_exported(OVERRIDE_VAR) && shell.environment.contains_key(OVERRIDE_VAR)
+}
+
+// Returns false if the user declined (or could not be asked) and the
+// commands must not run
+pub fn confirm<'a>(shell: &Shell, commands: impl IntoIterator<Item = &'a Command>) -> bool {
+    let safety = &shell.config.safety;
+    if !safety.confirm_dangerous || overridden(shell) {
+        return true;
+    }
+    let theme = &shell.config.theme;
+    for command in commands {
+        let Some(danger) = check(&safety.patterns, command) else {
+            continue;
+        };
+        let line = format!("{} {}", command.program, command.args.join(" "));
+        let reason = if danger.reason.is_empty() { danger.pattern.as_str() } else { danger.reason.as_str() };
+        eprintln!("{}rshell: dangerous command: {} ({}){}",
+                  theme.error_color, line.trim_end(), reason, theme.reset_color);
+        let allowed = match danger.severity {
+            Severity::Warn => true,
+            _ if !stdin_is_terminal() => {
+                eprintln!("rshell: not running it without a terminal to confirm on; export {}=1 to override",
+                          OVERRIDE_VAR);
+                false
+            }
+            Severity::Confirm => ask("Run it anyway? [y/N] ")
+                .is_some_and(|a| a.eq_ignore_ascii_case("y") || a.eq_ignore_ascii_case("yes")),
+            Severity::Critical => ask("Type 'yes' to run it: ").is_some_and(|a| a == "yes"),
+        };
+        if !allowed {
+            eprintln!("rshell: cancelled");
+            return false;
+        }
+    }
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::SafetyConfig;
+
+    fn command(line: &str) -> Command {
+        let mut words = line.split_whitespace().map(String::from);
+        let mut command = Command::new(words.next().unwrap());
+        command.args = words.collect();
+        command
+    }
+
+    #[test]
+    fn default_patterns() {
+        let patterns = SafetyConfig::default().patterns;
+        for line in ["rm -rf /", "rm -fr /", "sudo rm -r -f /", "mkfs.ext4 /dev/sdb1",
+                     "dd if=img.iso of=/dev/sda bs=4M", "chmod -R 777 /"] {
+            assert!(check(&patterns, &command(line)).is_some(), "{}", line);
+        }
+        for line in ["rm -rf /tmp/build", "rm -r /", "dd if=/dev/zero of=disk.img", "chmod 777 /", "ls /"] {
+            assert!(check(&patterns, &command(line)).is_none(), "{}", line);
+        }
+    }
+
+    #[test]
+    fn override_has_to_be_exported() {
+        let mut shell = Shell::with_config(crate::config::Config::default());
+        shell.unset_var(OVERRIDE_VAR);
+        shell.set_var(OVERRIDE_VAR, "1".to_string());
+        assert!(!overridden(&shell));
+        shell.set_exported(OVERRIDE_VAR, true);
+        assert!(overridden(&shell));
+        shell.unset_var(OVERRIDE_VAR);
+        shell.set_exported(OVERRIDE_VAR, true);
+        assert!(!overridden(&shell));
+    }
+}
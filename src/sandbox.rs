@@ -0,0 +1,200 @@
+// src/sandbox.rs
+use crate::memory_pool::Argv;
+use crate::platform;
+use crate::shell::Shell;
+use nix::sys::wait::{waitpid, WaitStatus};
+use nix::unistd::{fork, ForkResult};
+use std::path::PathBuf;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum HomeMode {
+    #[default]
+    Shared,
+    ReadOnly,
+    Tmpfs,
+}
+
+#[derive(Debug, Default)]
+pub struct SandboxOptions {
+    pub no_net: bool,
+    pub home: HomeMode,
+}
+
+const USAGE: &str = "usage: sandbox [--no-net] [--ro-home | --tmp-home] [--] command [args...]";
+
+// Splits leading options from the command to run
+pub fn parse_args(args: &[String]) -> Result<(SandboxOptions, &[String]), String> {
+    let mut options = SandboxOptions::default();
+    let mut rest = args;
+    while let Some(arg) = rest.first() {
+        match arg.as_str() {
+            "--no-net" => options.no_net = true,
+            "--ro-home" => options.home = HomeMode::ReadOnly,
+            "--tmp-home" => options.home = HomeMode::Tmpfs,
+            "--" => {
+                rest = &rest[1..];
+                break;
+            }
+            flag if flag.starts_with("--") => return Err(format!("unknown option '{}'", flag)),
+            _ => break,
+        }
+        rest = &rest[1..];
+    }
+    if rest.is_empty() {
+        return Err(USAGE.to_string());
+    }
+    Ok((options, rest))
+}
+
+#[cfg(target_os = "linux")]
+mod namespaces {
+    use super::{HomeMode, SandboxOptions};
+    use nix::mount::{mount, MsFlags};
+    use nix::sched::{unshare, CloneFlags};
+    use std::ffi::CString;
+    use std::fs;
+    use std::path::Path;
+
+    fn io_err(what: &str, e: impl std::fmt::Display) -> String {
+        format!("{}: {}", what, e)
+    }
+
+    // Maps our own uid/gid into the new user namespace so files keep their owner
+    fn write_id_maps(uid: u32, gid: u32) -> Result<(), String> {
+        fs::write("/proc/self/setgroups", "deny").map_err(|e| io_err("setgroups", e))?;
+        fs::write("/proc/self/uid_map", format!("{} {} 1", uid, uid)).map_err(|e| io_err("uid_map", e))?;
+        fs::write("/proc/self/gid_map", format!("{} {} 1", gid, gid)).map_err(|e| io_err("gid_map", e))
+    }
+
+    // Flags an unprivileged remount has to keep, or the kernel refuses it
+    fn locked_flags(path: &Path) -> MsFlags {
+        let Ok(c_path) = CString::new(path.as_os_str().as_encoded_bytes()) else {
+            return MsFlags::empty();
+        };
+        let mut stat: libc::statvfs = unsafe { std::mem::zeroed() };
+        if unsafe { libc::statvfs(c_path.as_ptr(), &mut stat) } != 0 {
+            return MsFlags::empty();
+        }
+        let mut flags = MsFlags::empty();
+        for (st, ms) in [(libc::ST_NOSUID, MsFlags::MS_NOSUID), (libc::ST_NODEV, MsFlags::MS_NODEV),
+                         (libc::ST_NOEXEC, MsFlags::MS_NOEXEC), (libc::ST_NOATIME, MsFlags::MS_NOATIME),
+                         (libc::ST_RELATIME, MsFlags::MS_RELATIME)] {
+            if stat.f_flag & st != 0 {
+                flags |= ms;
+            }
+        }
+        flags
+    }
+
+    fn setup_home(home: &Path, mode: HomeMode) -> Result<(), String> {
+        let none: Option<&str> = None;
+        match mode {
+            HomeMode::Shared => Ok(()),
+            HomeMode::ReadOnly => {
+                mount(Some(home), home, none, MsFlags::MS_BIND | MsFlags::MS_REC, none)
+                    .map_err(|e| io_err("bind home", e))?;
+                let flags = MsFlags::MS_BIND | MsFlags::MS_REMOUNT | MsFlags::MS_RDONLY | locked_flags(home);
+                mount(none, home, none, flags, none).map_err(|e| io_err("read-only home", e))
+            }
+            HomeMode::Tmpfs => {
+                mount(Some("tmpfs"), home, Some("tmpfs"), MsFlags::MS_NOSUID | MsFlags::MS_NODEV, Some("mode=0700"))
+                    .map_err(|e| io_err("tmpfs home", e))
+            }
+        }
+    }
+
+    // Runs in the forked child: new namespaces, then the mounts, all before exec
+    pub fn enter_sandbox(options: &SandboxOptions, home: &Path, uid: u32, gid: u32) -> Result<(), String> {
+        let mut flags = CloneFlags::CLONE_NEWUSER | CloneFlags::CLONE_NEWNS;
+        if options.no_net {
+            flags |= CloneFlags::CLONE_NEWNET;
+        }
+        unshare(flags).map_err(|e| io_err("unshare", e))?;
+        write_id_maps(uid, gid)?;
+        // Keep our mounts from propagating back to the host
+        let none: Option<&str> = None;
+        mount(none, "/", none, MsFlags::MS_REC | MsFlags::MS_PRIVATE, none)
+            .map_err(|e| io_err("private mounts", e))?;
+        setup_home(home, options.home)
+    }
+}
+
+#[cfg(target_os = "linux")]
+use namespaces::enter_sandbox;
+
+#[cfg(not(target_os = "linux"))]
+fn enter_sandbox(_options: &SandboxOptions, _home: &std::path::Path, _uid: u32, _gid: u32) -> Result<(), String> {
+    Err("namespaces are only available on Linux".to_string())
+}
+
+pub fn builtin_sandbox(shell: &mut Shell, args: &[String]) -> i32 {
+    let (options, command) = match parse_args(args) {
+        Ok(parsed) => parsed,
+        Err(e) => {
+            eprintln!("sandbox: {}", e);
+            return 2;
+        }
+    };
+    if !platform::HAS_NAMESPACES {
+        eprintln!("sandbox: namespaces are only available on Linux");
+        return 1;
+    }
+    let home = shell.environment.get("HOME")
+        .map(PathBuf::from)
+        .or_else(dirs::home_dir)
+        .unwrap_or_else(|| PathBuf::from("/"));
+    let argv = match Argv::build(&command[0], &command[1..]) {
+        Ok(argv) => argv,
+        Err(e) => {
+            eprintln!("sandbox: {}: {}", command[0], e);
+            return 1;
+        }
+    };
+    let (uid, gid) = unsafe { (libc::getuid(), libc::getgid()) };
+    let exec_args = argv.exec_args(None, None);
+
+    match unsafe { fork() } {
+        Ok(ForkResult::Parent { child }) => {
+            drop(exec_args);
+            argv.recycle();
+            match waitpid(child, None) {
+                Ok(WaitStatus::Exited(_, code)) => code,
+                Ok(WaitStatus::Signaled(_, sig, _)) => 128 + sig as i32,
+                _ => 1,
+            }
+        }
+        Ok(ForkResult::Child) => {
+            if let Err(e) = enter_sandbox(&options, &home, uid, gid) {
+                eprintln!("sandbox: {}", e);
+                std::process::exit(126);
+            }
+            std::env::set_var("RSHELL_SANDBOX", "1");
+            let e = exec_args.exec();
+            eprintln!("sandbox: {}: {}", command[0], e);
+            std::process::exit(127);
+        }
+        Err(e) => {
+            drop(exec_args);
+            argv.recycle();
+            eprintln!("sandbox: fork failed: {}", e);
+            1
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn option_parsing() {
+        let args: Vec<String> = ["--no-net", "--ro-home", "sh", "-c", "--no-net"]
+            .iter().map(|s| s.to_string()).collect();
+        let (options, command) = parse_args(&args).unwrap();
+        assert!(options.no_net);
+        assert_eq!(options.home, HomeMode::ReadOnly);
+        assert_eq!(command, &args[2..]);
+        assert!(parse_args(&args[..2]).is_err());
+        assert!(parse_args(&["--bogus".to_string(), "ls".to_string()]).is_err());
+    }
+}
@@ -1,17 +1,18 @@
+use crate::audit;
 use crate::shell::Shell;
 use std::fs::File;
 use std::io::{BufRead, BufReader};
+use std::borrow::Cow;
 use std::path::Path;
+use std::time::Instant;
 
-pub struct ScriptEngine {
-    variables: std::collections::HashMap<String, String>,
-}
+// Runs scripts a line at a time in the shell, so variables, functions and
+// options they set are the shell's own
+pub struct ScriptEngine;
 
 impl ScriptEngine {
     pub fn new() -> Self {
-        Self {
-            variables: std::collections::HashMap::new(),
-        }
+        Self
     }
     
     pub fn execute_script(&mut self, shell: &mut Shell, script_path: &Path) -> Result<i32, String> {
@@ -19,76 +20,97 @@ impl ScriptEngine {
             .map_err(|e| format!("Failed to open script: {}", e))?;
         
         let reader = BufReader::new(file);
+        let lines: Vec<String> = reader.lines()
+            .enumerate()
+            .map(|(line_num, line)| line.map_err(|e| format!("Error reading line {}: {}", line_num + 1, e)))
+            .collect::<Result<_, _>>()?;
+        self.execute_lines(shell, lines.iter().map(String::as_str))
+    }
+    
+    // Runs a script with `$0` set to its path and `$1`.. to `args`; the
+    // caller's positional parameters are back afterwards
+    pub fn execute_script_with_args(&mut self, shell: &mut Shell, script_path: &Path, args: &[String]) -> Result<i32, String> {
+        let arg0 = std::mem::replace(&mut shell.arg0, script_path.display().to_string());
+        let positional = std::mem::replace(&mut shell.positional, args.to_vec());
+        let result = self.execute_script(shell, script_path);
+        shell.arg0 = arg0;
+        shell.positional = positional;
+        result
+    }
+    
+    // Runs script text that is already in memory
+    pub fn execute_str(&mut self, shell: &mut Shell, script: &str) -> Result<i32, String> {
+        self.execute_lines(shell, script.lines())
+    }
+    
+    // Each command runs with `$LINENO` at the line it starts on; the caller's
+    // is back afterwards, for scripts that source others
+    fn execute_lines<'a>(&mut self, shell: &mut Shell, lines: impl Iterator<Item = &'a str>) -> Result<i32, String> {
+        let lineno = shell.lineno;
+        let result = self.run_lines(shell, lines);
+        shell.lineno = lineno;
+        result
+    }
+    
+    // `set -e` is the shell's own option: the executor ends the shell at the
+    // first failing command, wherever in the script that is. A parse error
+    // ends the script; the caller reports it, caret diagnostic and all.
+    fn run_lines<'a>(&mut self, shell: &mut Shell, lines: impl Iterator<Item = &'a str>) -> Result<i32, String> {
         let mut last_exit_code = 0;
         
-        for (line_num, line) in reader.lines().enumerate() {
-            let line = line.map_err(|e| format!("Error reading line {}: {}", line_num + 1, e))?;
-            let line = line.trim();
+        let mut lines = lines.enumerate();
+        while let Some((line_num, line)) = lines.next() {
+            // The end stays: it may be inside a quote that goes on below
+            let mut line = Cow::Borrowed(line.trim_start());
             
             // Skip empty lines and comments
             if line.is_empty() || line.starts_with('#') {
                 continue;
             }
             
-            // Process variable assignments
-            if let Some((var, value)) = line.split_once('=') {
-                let var = var.trim();
-                let value = self.expand_variables(value.trim());
-                self.variables.insert(var.to_string(), value);
-                continue;
+            // Open quotes and here-documents continue on the following lines
+            while crate::shell::parser::needs_more_input(&line) {
+                let Some((_, next)) = lines.next() else { break };
+                let joined = line.to_mut();
+                joined.push('\n');
+                joined.push_str(next);
             }
             
-           
-            let expanded_line = self.expand_variables(line);
+            shell.lineno = line_num + 1;
             
-            match crate::shell::parser::Parser::new(&expanded_line) {
+            let parse_start = Instant::now();
+            let arena = bumpalo::Bump::new();
+            match crate::shell::parser::Parser::new(&line, &arena) {
                 Ok(mut parser) => {
-                    match parser.parse() {
+                    let parsed = parser.parse();
+                    shell.perf.record_parsing(parse_start.elapsed());
+                    match parsed {
                         Ok(command_type) => {
                             let mut executor = crate::shell::executor::Executor::new();
+                            // The prompt records what is typed at it; this is
+                            // for `-c` and scripts
+                            let start_dir = (!shell.interactive).then(|| shell.current_dir.clone());
+                            let exec_start = Instant::now();
                             last_exit_code = executor.execute(shell, command_type);
+                            shell.last_exit_code = last_exit_code;
+                            shell.perf.record_command_execution(exec_start.elapsed());
+                            if let Some(start_dir) = start_dir {
+                                audit::record_command(shell, &start_dir, &line, exec_start.elapsed(), last_exit_code);
+                            }
                         }
                         Err(e) => {
-                            eprintln!("Script error at line {}: {:?}", line_num + 1, e);
-                            return Err(format!("Parse error at line {}: {:?}", line_num + 1, e));
+                            return Err(format!("line {}:\n{}", line_num + 1, e.render(&line)));
                         }
                     }
                 }
                 Err(e) => {
-                    eprintln!("Script error at line {}: {:?}", line_num + 1, e);
-                    return Err(format!("Parse error at line {}: {:?}", line_num + 1, e));
+                    return Err(format!("line {}:\n{}", line_num + 1, e.render(&line)));
                 }
-            }
-            
-            if last_exit_code != 0 && self.variables.get("errexit").is_some() {
-                return Ok(last_exit_code);
-            }
+            };
         }
         
         Ok(last_exit_code)
     }
-    
-    pub fn set_variable(&mut self, name: String, value: String) {
-        self.variables.insert(name, value);
-    }
-    
-    pub fn get_variable(&self, name: &str) -> Option<&String> {
-        self.variables.get(name)
-    }
-    
-    fn expand_variables(&self, input: &str) -> String {
-        let mut result = input.to_string();
-        
-        // Simple variable expansion: $VAR or ${VAR}
-        for (var, value) in &self.variables {
-            let patterns = [format!("${}", var), format!("${{{}}}", var)];
-            for pattern in &patterns {
-                result = result.replace(pattern, value);
-            }
-        }
-        
-        result
-    }
 }
 
 impl Default for ScriptEngine {
@@ -0,0 +1,154 @@
+// src/session.rs
+use crate::config::Config;
+use crate::plugin::ShellPlugin;
+use crate::shell::executor::Executor;
+use crate::shell::parser::Parser;
+use crate::shell::Shell;
+use crate::{ShellError, ShellResult};
+use bumpalo::Bump;
+use nix::unistd::{close, dup, dup2, pipe};
+use std::fs::File;
+use std::io::{self, Read, Write};
+use std::os::unix::io::FromRawFd;
+use std::thread;
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ExecResult {
+    pub stdout: String,
+    pub exit_code: i32,
+}
+
+// An rshell that runs inside another program: state (variables, aliases,
+// cwd, jobs) carries over from one line to the next, as in the REPL.
+//
+//     let mut session = ShellSession::new_with_config(Config::default());
+//     session.register_builtin("greet", |args| { println!("hi {}", args.join(" ")); 0 });
+//     let result = session.eval("greet you | tr a-z A-Z")?;
+//
+// `eval` captures stdout by pointing fd 1 at a pipe, which is process-wide:
+// run one eval at a time, and note that background jobs started by it keep
+// the capture open until they exit.
+pub struct ShellSession {
+    shell: Shell,
+    executor: Executor,
+}
+
+impl Default for ShellSession {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ShellSession {
+    // Uses the default configuration, not the user's config file
+    pub fn new() -> Self {
+        Self::new_with_config(Config::default())
+    }
+
+    pub fn new_with_config(config: Config) -> Self {
+        Self {
+            shell: Shell::with_config(config),
+            executor: Executor::new(),
+        }
+    }
+
+    pub fn shell(&self) -> &Shell {
+        &self.shell
+    }
+
+    pub fn shell_mut(&mut self) -> &mut Shell {
+        &mut self.shell
+    }
+
+    // Builtins registered here take effect in pipelines too
+    pub fn register_builtin<F>(&mut self, name: &str, builtin: F)
+    where
+        F: Fn(&[String]) -> i32 + Send + Sync + 'static,
+    {
+        self.shell.plugins.register_builtin(name, Box::new(builtin));
+    }
+
+    pub fn add_plugin(&mut self, plugin: Box<dyn ShellPlugin>) -> ShellResult<()> {
+        self.shell.plugins.add(plugin, None, None)
+            .map(|_| ())
+            .map_err(ShellError::SystemError)
+    }
+
+    // Runs a line with output going wherever stdout points
+    pub fn run(&mut self, line: &str) -> ShellResult<i32> {
+        if line.trim().is_empty() {
+            return Ok(self.shell.last_exit_code);
+        }
+        let arena = Bump::new();
+        let parsed = Parser::with_aliases(line, &self.shell.aliases, &arena).and_then(|mut parser| parser.parse());
+        let command = match parsed {
+            Ok(command) => command,
+            Err(e) => {
+                self.shell.last_exit_code = 1;
+                return Err(ShellError::ParseError(e.message));
+            }
+        };
+        let exit_code = self.executor.execute(&mut self.shell, command);
+        self.shell.check_background_jobs();
+        self.shell.last_exit_code = exit_code;
+        self.shell.plugins.on_command(line, exit_code);
+        Ok(exit_code)
+    }
+
+    // Runs a line and returns what it printed to stdout
+    pub fn eval(&mut self, line: &str) -> ShellResult<ExecResult> {
+        io::stdout().flush()?;
+        let (read_fd, write_fd) = pipe().map_err(io::Error::from)?;
+        let saved_stdout = dup(1).map_err(io::Error::from)?;
+        dup2(write_fd, 1).map_err(io::Error::from)?;
+        let _ = close(write_fd);
+
+        // Drain concurrently so a chatty command cannot fill the pipe
+        let reader = thread::spawn(move || {
+            let mut output = Vec::new();
+            let _ = unsafe { File::from_raw_fd(read_fd) }.read_to_end(&mut output);
+            output
+        });
+        let result = self.run(line);
+        let _ = io::stdout().flush();
+        let restored = dup2(saved_stdout, 1);
+        let _ = close(saved_stdout);
+        let stdout = reader.join().unwrap_or_default();
+        restored.map_err(io::Error::from)?;
+
+        Ok(ExecResult {
+            stdout: String::from_utf8_lossy(&stdout).into_owned(),
+            exit_code: result?,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn eval_captures_output_and_status() {
+        let mut session = ShellSession::new();
+        session.register_builtin("seven", |args| 7 + args.len() as i32);
+
+        let result = session.eval("printf 'a\\nb\\n' | sort -r").unwrap();
+        assert_eq!(result, ExecResult { stdout: "b\na\n".to_string(), exit_code: 0 });
+        assert_eq!(session.eval("echo hi").unwrap().stdout, "hi\n");
+        assert_eq!(session.eval("sh -c 'exit 3'").unwrap().exit_code, 3);
+        assert_eq!(session.eval("seven x y").unwrap().exit_code, 9);
+        assert_eq!(session.shell().last_exit_code, 9);
+        assert!(matches!(session.eval("echo x |"), Err(ShellError::ParseError(_))));
+    }
+
+    #[test]
+    fn only_exported_variables_reach_commands() {
+        let mut session = ShellSession::new();
+        let mut child = |line: &str| session.eval(line).unwrap().stdout;
+        assert_eq!(child("RSHELL_SESSION_V=1; sh -c 'echo \"[$RSHELL_SESSION_V]\"'"), "[]\n");
+        assert_eq!(child("RSHELL_SESSION_V=2 sh -c 'echo \"[$RSHELL_SESSION_V]\"'"), "[2]\n");
+        assert_eq!(child("export RSHELL_SESSION_V; sh -c 'echo \"[$RSHELL_SESSION_V]\"'"), "[1]\n");
+        assert_eq!(child("export -n RSHELL_SESSION_V; sh -c 'echo \"[$RSHELL_SESSION_V]\"'"), "[]\n");
+        assert_eq!(session.shell().environment["RSHELL_SESSION_V"], "1");
+    }
+}
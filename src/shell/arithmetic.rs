@@ -0,0 +1,432 @@
+// shell/arithmetic.rs
+//
+// Shell arithmetic, for `$(( expr ))`, the `(( expr ))` command and `let`.
+// Values are 64-bit signed integers that wrap on overflow. The operators
+// and their precedence are C's, plus `**`; `&&`, `||` and `?:` only
+// evaluate the side they need. A variable's value is read as a number, or
+// failing that as an expression of its own; unset or empty it is 0.
+use crate::shell::expand;
+use crate::shell::Shell;
+
+// How deep variables may refer to expressions in other variables
+const MAX_DEPTH: usize = 32;
+
+// Binary operators from loosest to tightest, above `?:` and below `**`
+const LEVELS: &[&[&str]] = &[
+    &["||"],
+    &["&&"],
+    &["|"],
+    &["^"],
+    &["&"],
+    &["==", "!="],
+    &["<", ">", "<=", ">="],
+    &["<<", ">>"],
+    &["+", "-"],
+    &["*", "/", "%"],
+];
+
+// Longest first, so `<<=` is not read as `<<` then `=`
+const OPERATORS: &[&str] = &[
+    "<<=", ">>=",
+    "**", "++", "--", "<<", ">>", "<=", ">=", "==", "!=", "&&", "||",
+    "+=", "-=", "*=", "/=", "%=", "&=", "^=", "|=",
+    "+", "-", "*", "/", "%", "<", ">", "&", "|", "^", "!", "~", "?", ":", "=", ",", "(", ")",
+];
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Number(i64),
+    Name(String),
+    Op(&'static str),
+}
+
+#[derive(Debug)]
+enum Expr {
+    Number(i64),
+    Var(String),
+    Unary(&'static str, Box<Expr>),
+    Binary(&'static str, Box<Expr>, Box<Expr>),
+    Conditional(Box<Expr>, Box<Expr>, Box<Expr>),
+    // `name = value`, or with the operator of `+=` and the like
+    Assign(String, Option<&'static str>, Box<Expr>),
+    // `++name`, `name--`: the change and whether the old value is the result
+    Step(String, i64, bool),
+}
+
+// Expands `$(( expr ))`'s or `(( expr ))`'s text like a double-quoted
+// word, then evaluates it. Errors are reported as `rshell: expr: error`.
+pub fn expand(shell: &mut Shell, text: &str) -> Option<i64> {
+    let expr = expand::expand_single(shell, text)?;
+    match evaluate(shell, &expr) {
+        Ok(value) => Some(value),
+        Err(e) => {
+            eprintln!("rshell: {}: {}", expr.trim(), e);
+            None
+        }
+    }
+}
+
+// `(( expr ))`: succeeds when the value is not zero
+pub fn run(shell: &mut Shell, text: &str) -> i32 {
+    match expand(shell, text) {
+        Some(value) => i32::from(value == 0),
+        None => 1,
+    }
+}
+
+// `let expr...`: each argument is an expression; the status is that of the
+// last, as with `(( ))`
+pub fn builtin_let(shell: &mut Shell, args: &[String]) -> i32 {
+    if args.is_empty() {
+        eprintln!("let: expression expected");
+        return 1;
+    }
+    let mut value = 0;
+    for arg in args {
+        match evaluate(shell, arg) {
+            Ok(result) => value = result,
+            Err(e) => {
+                eprintln!("let: {}: {}", arg, e);
+                return 1;
+            }
+        }
+    }
+    i32::from(value == 0)
+}
+
+pub fn evaluate(shell: &mut Shell, expr: &str) -> Result<i64, String> {
+    Evaluator { shell, depth: 0 }.evaluate(expr)
+}
+
+fn tokenize(expr: &str) -> Result<Vec<Token>, String> {
+    let mut tokens = Vec::new();
+    let mut rest = expr.trim_start();
+    while let Some(ch) = rest.chars().next() {
+        let len = if ch.is_ascii_digit() {
+            let len = rest.find(|c: char| !c.is_ascii_alphanumeric() && c != '#').unwrap_or(rest.len());
+            tokens.push(Token::Number(parse_number(&rest[..len])?));
+            len
+        } else if ch.is_ascii_alphabetic() || ch == '_' {
+            let len = rest.find(|c: char| !c.is_ascii_alphanumeric() && c != '_').unwrap_or(rest.len());
+            tokens.push(Token::Name(rest[..len].to_string()));
+            len
+        } else {
+            let Some(op) = OPERATORS.iter().find(|op| rest.starts_with(**op)) else {
+                return Err(format!("syntax error: invalid arithmetic operator (error token is \"{}\")", rest));
+            };
+            tokens.push(Token::Op(op));
+            op.len()
+        };
+        rest = rest[len..].trim_start();
+    }
+    Ok(tokens)
+}
+
+// `42`, `0x2a`, `052` or `BASE#DIGITS` (`2#101010`), bases 2 to 36
+fn parse_number(text: &str) -> Result<i64, String> {
+    let (base, digits) = if let Some((base, digits)) = text.split_once('#') {
+        match base.parse::<u32>() {
+            Ok(base @ 2..=36) => (base, digits),
+            _ => return Err(format!("invalid arithmetic base (error token is \"{}\")", text)),
+        }
+    } else if let Some(hex) = text.strip_prefix("0x").or_else(|| text.strip_prefix("0X")) {
+        (16, hex)
+    } else if text.len() > 1 && text.starts_with('0') {
+        (8, &text[1..])
+    } else {
+        (10, text)
+    };
+    let mut value: i64 = 0;
+    if digits.is_empty() {
+        return Err(format!("invalid number (error token is \"{}\")", text));
+    }
+    for ch in digits.chars() {
+        match ch.to_digit(base) {
+            Some(digit) => value = value.wrapping_mul(base as i64).wrapping_add(digit as i64),
+            None => return Err(format!("value too great for base (error token is \"{}\")", text)),
+        }
+    }
+    Ok(value)
+}
+
+struct ExprParser {
+    tokens: Vec<Token>,
+    position: usize,
+}
+
+impl ExprParser {
+    fn peek_op(&self) -> Option<&'static str> {
+        match self.tokens.get(self.position) {
+            Some(Token::Op(op)) => Some(op),
+            _ => None,
+        }
+    }
+
+    fn eat(&mut self, op: &str) -> bool {
+        let found = self.peek_op() == Some(op);
+        self.position += usize::from(found);
+        found
+    }
+
+    fn error(&self) -> String {
+        match self.tokens.get(self.position) {
+            None => "syntax error: operand expected".to_string(),
+            Some(token) => format!("syntax error in expression (error token is \"{}\")", describe(token)),
+        }
+    }
+
+    // `a, b`: both are evaluated, the value is b's
+    fn comma(&mut self) -> Result<Expr, String> {
+        let mut left = self.assignment()?;
+        while self.eat(",") {
+            let right = self.assignment()?;
+            left = Expr::Binary(",", Box::new(left), Box::new(right));
+        }
+        Ok(left)
+    }
+
+    fn assignment(&mut self) -> Result<Expr, String> {
+        if let (Some(Token::Name(name)), Some(Token::Op(op))) = (self.tokens.get(self.position), self.tokens.get(self.position + 1)) {
+            if op.ends_with('=') && !matches!(*op, "==" | "!=" | "<=" | ">=") {
+                let name = name.clone();
+                let op = op.strip_suffix('=').filter(|op| !op.is_empty())
+                    .and_then(|op| OPERATORS.iter().copied().find(|known| *known == op));
+                self.position += 2;
+                let value = self.assignment()?;
+                return Ok(Expr::Assign(name, op, Box::new(value)));
+            }
+        }
+        self.conditional()
+    }
+
+    fn conditional(&mut self) -> Result<Expr, String> {
+        let condition = self.binary(0)?;
+        if !self.eat("?") {
+            return Ok(condition);
+        }
+        let then = self.comma()?;
+        if !self.eat(":") {
+            return Err(self.error());
+        }
+        let otherwise = self.assignment()?;
+        Ok(Expr::Conditional(Box::new(condition), Box::new(then), Box::new(otherwise)))
+    }
+
+    fn binary(&mut self, level: usize) -> Result<Expr, String> {
+        let Some(ops) = LEVELS.get(level) else {
+            return self.power();
+        };
+        let mut left = self.binary(level + 1)?;
+        while let Some(op) = self.peek_op().filter(|op| ops.contains(op)) {
+            self.position += 1;
+            let right = self.binary(level + 1)?;
+            left = Expr::Binary(op, Box::new(left), Box::new(right));
+        }
+        Ok(left)
+    }
+
+    // `**` groups to the right: `2**3**2` is `2**9`
+    fn power(&mut self) -> Result<Expr, String> {
+        let base = self.unary()?;
+        if self.eat("**") {
+            let exponent = self.power()?;
+            return Ok(Expr::Binary("**", Box::new(base), Box::new(exponent)));
+        }
+        Ok(base)
+    }
+
+    fn unary(&mut self) -> Result<Expr, String> {
+        match self.peek_op() {
+            Some(op @ ("-" | "+" | "!" | "~")) => {
+                self.position += 1;
+                Ok(Expr::Unary(op, Box::new(self.unary()?)))
+            }
+            Some(op @ ("++" | "--")) => {
+                self.position += 1;
+                let Some(Token::Name(name)) = self.tokens.get(self.position).cloned() else {
+                    return Err(self.error());
+                };
+                self.position += 1;
+                Ok(Expr::Step(name, if op == "++" { 1 } else { -1 }, false))
+            }
+            _ => self.postfix(),
+        }
+    }
+
+    fn postfix(&mut self) -> Result<Expr, String> {
+        let token = self.tokens.get(self.position).cloned();
+        self.position += 1;
+        match token {
+            Some(Token::Number(value)) => Ok(Expr::Number(value)),
+            Some(Token::Name(name)) => match self.peek_op() {
+                Some(op @ ("++" | "--")) => {
+                    self.position += 1;
+                    Ok(Expr::Step(name, if op == "++" { 1 } else { -1 }, true))
+                }
+                _ => Ok(Expr::Var(name)),
+            },
+            Some(Token::Op("(")) => {
+                let inner = self.comma()?;
+                if !self.eat(")") {
+                    return Err(self.error());
+                }
+                Ok(inner)
+            }
+            _ => {
+                self.position -= 1;
+                Err(self.error())
+            }
+        }
+    }
+}
+
+fn describe(token: &Token) -> String {
+    match token {
+        Token::Number(value) => value.to_string(),
+        Token::Name(name) => name.clone(),
+        Token::Op(op) => op.to_string(),
+    }
+}
+
+struct Evaluator<'s> {
+    shell: &'s mut Shell,
+    depth: usize,
+}
+
+impl Evaluator<'_> {
+    fn evaluate(&mut self, expr: &str) -> Result<i64, String> {
+        let mut parser = ExprParser { tokens: tokenize(expr)?, position: 0 };
+        if parser.tokens.is_empty() {
+            return Ok(0);
+        }
+        let parsed = parser.comma()?;
+        if parser.position < parser.tokens.len() {
+            return Err(parser.error());
+        }
+        self.eval(&parsed)
+    }
+
+    fn variable(&mut self, name: &str) -> Result<i64, String> {
+        let value = match self.shell.environment.get(name) {
+            Some(value) => value.trim().to_string(),
+            None if self.shell.config.options.nounset => return Err(self.shell.unbound_variable(name)),
+            None => return Ok(0),
+        };
+        if value.is_empty() {
+            return Ok(0);
+        }
+        if let Ok(number) = value.parse::<i64>() {
+            return Ok(number);
+        }
+        if self.depth >= MAX_DEPTH {
+            return Err("expression recursion level exceeded".to_string());
+        }
+        self.depth += 1;
+        let result = self.evaluate(&value);
+        self.depth -= 1;
+        result
+    }
+
+    fn assign(&mut self, name: &str, value: i64) -> Result<i64, String> {
+        self.shell.try_set_var(name, value.to_string())?;
+        Ok(value)
+    }
+
+    fn eval(&mut self, expr: &Expr) -> Result<i64, String> {
+        Ok(match expr {
+            Expr::Number(value) => *value,
+            Expr::Var(name) => self.variable(name)?,
+            Expr::Unary(op, inner) => {
+                let value = self.eval(inner)?;
+                match *op {
+                    "-" => value.wrapping_neg(),
+                    "!" => i64::from(value == 0),
+                    "~" => !value,
+                    _ => value,
+                }
+            }
+            Expr::Binary("&&", left, right) => i64::from(self.eval(left)? != 0 && self.eval(right)? != 0),
+            Expr::Binary("||", left, right) => i64::from(self.eval(left)? != 0 || self.eval(right)? != 0),
+            Expr::Binary(op, left, right) => {
+                let left = self.eval(left)?;
+                let right = self.eval(right)?;
+                apply(op, left, right)?
+            }
+            Expr::Conditional(condition, then, otherwise) => {
+                if self.eval(condition)? != 0 { self.eval(then)? } else { self.eval(otherwise)? }
+            }
+            Expr::Assign(name, op, value) => {
+                let value = self.eval(value)?;
+                let value = match op {
+                    Some(op) => apply(op, self.variable(name)?, value)?,
+                    None => value,
+                };
+                self.assign(name, value)?
+            }
+            Expr::Step(name, delta, postfix) => {
+                let old = self.variable(name)?;
+                let new = self.assign(name, old.wrapping_add(*delta))?;
+                if *postfix { old } else { new }
+            }
+        })
+    }
+}
+
+fn apply(op: &str, left: i64, right: i64) -> Result<i64, String> {
+    Ok(match op {
+        "," => right,
+        "|" => left | right,
+        "^" => left ^ right,
+        "&" => left & right,
+        "==" => i64::from(left == right),
+        "!=" => i64::from(left != right),
+        "<" => i64::from(left < right),
+        ">" => i64::from(left > right),
+        "<=" => i64::from(left <= right),
+        ">=" => i64::from(left >= right),
+        "<<" => left.wrapping_shl(right as u32),
+        ">>" => left.wrapping_shr(right as u32),
+        "+" => left.wrapping_add(right),
+        "-" => left.wrapping_sub(right),
+        "*" => left.wrapping_mul(right),
+        "/" | "%" if right == 0 => return Err("division by 0".to_string()),
+        "/" => left.wrapping_div(right),
+        "%" => left.wrapping_rem(right),
+        "**" if right < 0 => return Err("exponent less than 0".to_string()),
+        "**" => left.wrapping_pow(u32::try_from(right).unwrap_or(u32::MAX)),
+        _ => unreachable!("'{}' is not a binary operator", op),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+
+    #[test]
+    fn precedence_and_assignment() {
+        let mut shell = Shell::with_config(Config::default());
+        let mut eval = |expr: &str| evaluate(&mut shell, expr);
+        assert_eq!(eval("2 + 3 * 4"), Ok(14));
+        assert_eq!(eval("(2 + 3) * 4"), Ok(20));
+        assert_eq!(eval("2 ** 3 ** 2"), Ok(512));
+        assert_eq!(eval("-2 ** 2"), Ok(4));
+        assert_eq!(eval("7 % 3 == 1 && 1 << 4 > 010"), Ok(1));
+        assert_eq!(eval("0x1f + 2#101"), Ok(36));
+        assert_eq!(eval("x = 5, y = x++ * 2, x += y"), Ok(16));
+        assert_eq!(eval("x > 10 ? x-- : 0"), Ok(16));
+        assert_eq!(eval("x"), Ok(15));
+        assert_eq!(eval("0 && ++x"), Ok(0));
+        assert_eq!(eval("x"), Ok(15));
+        assert_eq!(eval(""), Ok(0));
+        assert_eq!(eval("1 / 0"), Err("division by 0".to_string()));
+        assert!(eval("1 +").is_err());
+        assert!(eval("3 = 4").is_err());
+        assert!(eval("09").is_err());
+        shell.set_var("expr", "x * 2".to_string());
+        shell.set_var("self", "self".to_string());
+        assert_eq!(evaluate(&mut shell, "expr + 1"), Ok(31));
+        assert!(evaluate(&mut shell, "self").is_err());
+        assert_eq!(shell.environment["y"], "10");
+    }
+}
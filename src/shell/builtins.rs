@@ -1,26 +1,37 @@
 // src/shell/builtins.rs
-use crate::shell::Shell;
+use crate::job_control;
+use crate::shell::{output, Control, JobStatus, Shell};
+use crate::shell::parser::{self, Parser};
 use crate::config::Config;
+use crate::memory_pool::Argv;
+use crate::signal_handler;
+use crate::utils::helpers::is_valid_var_name;
+use crate::utils::path_utils;
+use crate::utils::string_utils::shell_quote;
+use crate::{out, outln};
+use bumpalo::Bump;
 use std::env;
+use std::io::{self, Write};
+use std::path::Path;
 
 pub fn builtin_theme(shell: &mut Shell, args: &[String]) -> i32 {
     if args.is_empty() {
-        println!("Current theme: {}", shell.config.theme.name);
-        println!("\nAvailable themes:");
-        println!("  default - Classic terminal colors");
-        println!("  ocean   - Blue and cyan theme");
-        println!("  forest  - Green nature theme");
-        println!("  dracula - Dark purple theme");
+        outln!("Current theme: {}", shell.config.theme.name);
+        outln!("\nAvailable themes:");
+        outln!("  default - Classic terminal colors");
+        outln!("  ocean   - Blue and cyan theme");
+        outln!("  forest  - Green nature theme");
+        outln!("  dracula - Dark purple theme");
         return 0;
     }
     
     match args[0].as_str() {
         "list" => {
-            println!("Available themes:");
-            println!("  default - Classic terminal colors");
-            println!("  ocean   - Blue and cyan theme 🌊");
-            println!("  forest  - Green nature theme 🌲");
-            println!("  dracula - Dark purple theme 🦇");
+            outln!("Available themes:");
+            outln!("  default - Classic terminal colors");
+            outln!("  ocean   - Blue and cyan theme 🌊");
+            outln!("  forest  - Green nature theme 🌲");
+            outln!("  dracula - Dark purple theme 🦇");
         }
         "set" => {
             if args.len() < 2 {
@@ -36,7 +47,7 @@ pub fn builtin_theme(shell: &mut Shell, args: &[String]) -> i32 {
                 return 1;
             }
             
-            println!("Theme changed to: {}", args[1]);
+            outln!("Theme changed to: {}", args[1]);
         }
         "preview" => {
             if args.len() < 2 {
@@ -45,14 +56,14 @@ pub fn builtin_theme(shell: &mut Shell, args: &[String]) -> i32 {
             }
             
             let theme = shell.config.get_theme_by_name(&args[1]);
-            println!("Preview of '{}' theme:", args[1]);
-            println!("{}Prompt color{}", theme.prompt_color, theme.reset_color);
-            println!("{}Command color{}", theme.command_color, theme.reset_color);
-            println!("{}Builtin color{}", theme.builtin_color, theme.reset_color);
-            println!("{}String color{}", theme.string_color, theme.reset_color);
-            println!("{}Variable color{}", theme.variable_color, theme.reset_color);
-            println!("{}Path color{}", theme.path_color, theme.reset_color);
-            println!("{}Error color{}", theme.error_color, theme.reset_color);
+            outln!("Preview of '{}' theme:", args[1]);
+            outln!("{}Prompt color{}", theme.prompt_color, theme.reset_color);
+            outln!("{}Command color{}", theme.command_color, theme.reset_color);
+            outln!("{}Builtin color{}", theme.builtin_color, theme.reset_color);
+            outln!("{}String color{}", theme.string_color, theme.reset_color);
+            outln!("{}Variable color{}", theme.variable_color, theme.reset_color);
+            outln!("{}Path color{}", theme.path_color, theme.reset_color);
+            outln!("{}Error color{}", theme.error_color, theme.reset_color);
         }
         _ => {
             eprintln!("Unknown theme command: {}", args[0]);
@@ -65,14 +76,17 @@ pub fn builtin_theme(shell: &mut Shell, args: &[String]) -> i32 {
 
 pub fn builtin_config(shell: &mut Shell, args: &[String]) -> i32 {
     if args.is_empty() {
-        println!("RShell Configuration");
-        println!("====================");
-        println!("Theme: {}", shell.config.theme.name);
-        println!("History size: {}", shell.config.general.history_size);
-        println!("Enable hints: {}", shell.config.general.enable_hints);
-        println!("Enable completion: {}", shell.config.general.enable_completion);
-        println!("Auto-cd: {}", shell.config.general.auto_cd);
-        println!("\nConfig file: ~/.config/rshell/config.toml");
+        outln!("RShell Configuration");
+        outln!("====================");
+        outln!("Theme: {}", shell.config.theme.name);
+        outln!("History size: {}", shell.config.general.history_size);
+        outln!("Enable hints: {}", shell.config.general.enable_hints);
+        outln!("Enable completion: {}", shell.config.general.enable_completion);
+        outln!("Auto-cd: {}", shell.config.general.auto_cd);
+        outln!("Slow-command hints: {} (>= {}ms)",
+                 shell.config.general.slow_command_hints,
+                 shell.config.general.slow_command_threshold_ms);
+        outln!("\nConfig file: ~/.config/rshell/config.toml");
         return 0;
     }
     
@@ -81,8 +95,8 @@ pub fn builtin_config(shell: &mut Shell, args: &[String]) -> i32 {
             match Config::load() {
                 Ok(config) => {
                     shell.config = config;
-                    shell.aliases = shell.config.aliases.clone();
-                    println!("Configuration reloaded");
+                    shell.aliases = crate::alias::from_config(&shell.config.aliases);
+                    outln!("Configuration reloaded");
                     0
                 }
                 Err(e) => {
@@ -107,7 +121,7 @@ pub fn builtin_config(shell: &mut Shell, args: &[String]) -> i32 {
             match crate::config::init_config_interactive() {
                 Ok(config) => {
                     shell.config = config;
-                    shell.aliases = shell.config.aliases.clone();
+                    shell.aliases = crate::alias::from_config(&shell.config.aliases);
                     0
                 }
                 Err(e) => {
@@ -123,47 +137,130 @@ pub fn builtin_config(shell: &mut Shell, args: &[String]) -> i32 {
     }
 }
 
+// Runs the builtin `program`, None if there is none by that name. Output it
+// could not write makes it fail, as a full disk or a closed pipe would for
+// an external command.
 pub fn execute_builtin(shell: &mut Shell, program: &str, args: &[String]) -> Option<i32> {
+    let status = run_builtin(shell, program, args)?;
+    match output::take_error() {
+        Some(e) => {
+            eprintln!("rshell: {}: write error: {}", program, e);
+            Some(1)
+        }
+        None => Some(status),
+    }
+}
+
+fn run_builtin(shell: &mut Shell, program: &str, args: &[String]) -> Option<i32> {
     match program {
         "cd" => Some(builtin_cd(shell, args)),
-        "pwd" => Some(builtin_pwd()),
+        "pwd" => Some(builtin_pwd(shell, args)),
         "echo" => Some(builtin_echo(args)),
         "export" => Some(builtin_export(shell, args)),
         "unset" => Some(builtin_unset(shell, args)),
-        "exit" => Some(builtin_exit(args)),
-        "history" => Some(builtin_history(shell)),
-        "jobs" => Some(builtin_jobs(shell)),
+        "set" => Some(builtin_set(shell, args)),
+        "exit" => Some(builtin_exit(shell, args)),
+        "exec" => Some(builtin_exec(shell, args)),
+        "break" | "continue" => Some(builtin_break(shell, program, args)),
+        "local" => Some(builtin_local(shell, args)),
+        "readonly" => Some(crate::shell::declare::builtin_readonly(shell, args)),
+        "declare" => Some(crate::shell::declare::builtin_declare(shell, args)),
+        "trap" => Some(crate::shell::trap::builtin_trap(shell, args)),
+        "read" => Some(crate::shell::read::builtin_read(shell, args)),
+        "getopts" => Some(crate::shell::getopts::builtin_getopts(shell, args)),
+        "let" => Some(crate::shell::arithmetic::builtin_let(shell, args)),
+        "calc" => Some(crate::shell::calc::builtin_calc(shell, args)),
+        "shift" => Some(builtin_shift(shell, args)),
+        "return" => Some(builtin_return(shell, args)),
+        "type" => Some(builtin_type(shell, args)),
+        "which" => Some(builtin_which(shell, args)),
+        "hash" => Some(builtin_hash(shell, args)),
+        "rehash" => Some(builtin_hash(shell, &["-r".to_string()])),
+        "pushd" => Some(crate::shell::dirstack::builtin_pushd(shell, args)),
+        "popd" => Some(crate::shell::dirstack::builtin_popd(shell, args)),
+        "dirs" => Some(crate::shell::dirstack::builtin_dirs(shell, args)),
+        "history" => Some(builtin_history(shell, args)),
+        "jobs" => Some(builtin_jobs(shell, args)),
         "help" => Some(builtin_help()),
-        "fg" => Some(crate::job_control::fg_command(shell, args)),
-        "bg" => Some(crate::job_control::bg_command(shell, args)),
-        "kill" => Some(crate::job_control::kill_job(shell, args)),
+        "fg" => Some(job_control::fg_command(shell, args)),
+        "bg" => Some(job_control::bg_command(shell, args)),
+        "kill" => Some(job_control::kill_job(shell, args)),
         "alias" => Some(crate::alias::builtin_alias(shell, args)),
         "unalias" => Some(crate::alias::builtin_unalias(shell, args)),
         "theme" => Some(builtin_theme(shell, args)),
         "config" => Some(builtin_config(shell, args)),
-        _ => None,
+        "run" => Some(crate::task_runner::builtin_run(shell, args)),
+        "perf" => Some(crate::performance::builtin_perf(shell, args)),
+        "times" => Some(crate::performance::builtin_times(shell, args)),
+        "time" => Some(crate::performance::builtin_time(shell, args)),
+        "bench" => Some(crate::bench::builtin_bench(shell, args)),
+        "stats" => Some(crate::memory_pool::builtin_stats(shell, args)),
+        "sysinfo" => Some(crate::sysinfo::builtin_sysinfo(shell, args)),
+        "audit" => Some(crate::audit::builtin_audit(shell, args)),
+        "sandbox" => Some(crate::sandbox::builtin_sandbox(shell, args)),
+        "plugin" => Some(crate::plugin::builtin_plugin(shell, args)),
+        "envrc" => Some(crate::envrc::builtin_envrc(shell, args)),
+        "bookmark" => Some(crate::bookmark::builtin_bookmark(shell, args)),
+        "bind" => Some(crate::keybindings::builtin_bind(shell, args)),
+        "tsplit" => Some(crate::multiplexer::builtin_tsplit(shell, args)),
+        "debug" => Some(builtin_debug(args)),
+        "from-json" | "from-csv" | "select" | "where" | "sort-by" | "to-table" | "to-json" => {
+            Some(crate::structured::builtin_structured(program, args))
+        }
+        _ => shell.plugins.run_builtin(program, args),
     }
 }
 
 fn builtin_cd(shell: &mut Shell, args: &[String]) -> i32 {
-    let new_dir = if args.is_empty() {
-        env::var("HOME").unwrap_or_else(|_| "/".to_string())
-    } else {
-        args[0].clone()
+    let mut physical = false;
+    let mut operands = Vec::new();
+    for arg in args {
+        match arg.as_str() {
+            "-P" => physical = true,
+            "-L" => physical = false,
+            _ => operands.push(arg),
+        }
+    }
+    
+    // `cd -` goes back to $OLDPWD and says where it went
+    let mut announce = false;
+    let new_dir = match operands.first().map(|s| s.as_str()) {
+        Some("-") => match shell.environment.get("OLDPWD") {
+            Some(dir) if !dir.is_empty() => {
+                announce = true;
+                dir.clone()
+            }
+            _ => {
+                eprintln!("cd: OLDPWD not set");
+                return 1;
+            }
+        },
+        Some(dir) => dir.to_string(),
+        None => env::var("HOME").unwrap_or_else(|_| "/".to_string()),
     };
     
-    let path = if new_dir.starts_with('~') {
+    let mut path = if new_dir.starts_with('~') {
         new_dir.replacen('~', &env::var("HOME").unwrap_or_else(|_| "/".to_string()), 1)
+    } else if let Some(dir) = crate::bookmark::resolve(shell, &new_dir) {
+        dir
     } else {
         new_dir
     };
     
-    match env::set_current_dir(&path) {
+    // A directory found through a CDPATH entry other than the current one
+    // is announced too, since the user did not spell it out
+    if let Some(cdpath) = shell.environment.get("CDPATH").filter(|cdpath| !cdpath.is_empty()) {
+        if let Some(found) = path_utils::find_in_cdpath(cdpath, &path) {
+            announce |= !found.starts_with(".");
+            path = found.to_string_lossy().to_string();
+        }
+    }
+    
+    match shell.change_dir(&path, physical) {
         Ok(_) => {
-            shell.current_dir = env::current_dir()
-                .unwrap_or_default()
-                .to_string_lossy()
-                .to_string();
+            if announce {
+                outln!("{}", shell.current_dir);
+            }
             0
         }
         Err(e) => {
@@ -173,10 +270,27 @@ fn builtin_cd(shell: &mut Shell, args: &[String]) -> i32 {
     }
 }
 
-fn builtin_pwd() -> i32 {
+fn builtin_pwd(shell: &Shell, args: &[String]) -> i32 {
+    let physical = match args.last().map(|s| s.as_str()) {
+        None | Some("-L") => false,
+        Some("-P") => true,
+        Some(other) => {
+            eprintln!("pwd: {}: invalid option", other);
+            eprintln!("Usage: pwd [-L|-P]");
+            return 1;
+        }
+    };
+    
+    // The logical path is only trusted while it still leads to the real cwd
+    let logical = Path::new(&shell.current_dir);
+    if !physical && path_utils::same_file(logical, Path::new(".")) {
+        outln!("{}", shell.current_dir);
+        return 0;
+    }
+    
     match env::current_dir() {
         Ok(path) => {
-            println!("{}", path.display());
+            outln!("{}", path.display());
             0
         }
         Err(e) => {
@@ -188,93 +302,678 @@ fn builtin_pwd() -> i32 {
 
 fn builtin_echo(args: &[String]) -> i32 {
     let output = args.join(" ");
-    println!("{}", output);
+    outln!("{}", output);
     0
 }
 
+// `export NAME[=value]...` passes variables on to the commands the shell
+// runs; `-n` stops passing them but keeps their values. Alone, or with
+// `-p`, it lists the exported variables as commands that would recreate them.
 fn builtin_export(shell: &mut Shell, args: &[String]) -> i32 {
-    if args.is_empty() {
-        for (key, value) in &shell.environment {
-            println!("{}={}", key, value);
+    let (export, names) = match args.first().map(String::as_str) {
+        Some("-n") => (false, &args[1..]),
+        Some("-p") => (true, &args[..0]),
+        _ => (true, args),
+    };
+    if export && names.is_empty() {
+        let mut exported: Vec<&str> = shell.exported.iter().map(|name| &**name).collect();
+        exported.sort_unstable();
+        for name in exported {
+            match shell.environment.get(name) {
+                Some(value) => outln!("export {}={}", name, shell_quote(value)),
+                None => outln!("export {}", name),
+            }
         }
         return 0;
     }
     
-    for arg in args {
-        if let Some(eq_pos) = arg.find('=') {
-            let key = arg[..eq_pos].to_string();
-            let value = arg[eq_pos + 1..].to_string();
-            shell.environment.insert(key.clone(), value.clone());
-            env::set_var(key, value);
-        } else {
-            eprintln!("export: '{}': not a valid identifier", arg);
-            return 1;
+    let mut status = 0;
+    for arg in names {
+        let (name, value) = match arg.split_once('=') {
+            Some((name, value)) => (name, Some(value)),
+            None => (arg.as_str(), None),
+        };
+        if !is_valid_var_name(name) {
+            eprintln!("export: `{}': not a valid identifier", arg);
+            status = 1;
+            continue;
         }
+        if let Some(value) = value {
+            if !shell.set_var(name, value.to_string()) {
+                status = 1;
+                continue;
+            }
+        }
+        shell.set_exported(name, export);
     }
-    0
+    status
 }
 
+// `unset [-fv] NAME...`: -f removes functions, -v variables; without
+// either, a name that is not a variable is tried as a function
 fn builtin_unset(shell: &mut Shell, args: &[String]) -> i32 {
-    for var in args {
-        shell.environment.remove(var);
-        env::remove_var(var);
+    let (mut functions, mut variables) = (false, false);
+    let mut names = args;
+    while let Some(flag) = names.first().filter(|arg| arg.starts_with('-') && arg.len() > 1) {
+        names = &names[1..];
+        if flag == "--" {
+            break;
+        }
+        for letter in flag[1..].chars() {
+            match letter {
+                'f' => functions = true,
+                'v' => variables = true,
+                _ => {
+                    eprintln!("unset: -{}: invalid option", letter);
+                    eprintln!("unset: usage: unset [-f] [-v] [name ...]");
+                    return 2;
+                }
+            }
+        }
+    }
+    let mut status = 0;
+    for name in names {
+        if functions && !variables {
+            shell.functions.remove(name.as_str());
+            continue;
+        }
+        if !variables && !shell.environment.contains_key(name.as_str()) && shell.functions.remove(name.as_str()).is_some() {
+            continue;
+        }
+        if !shell.unset_var(name) {
+            eprintln!("unset: {}: cannot unset: readonly variable", name);
+            status = 1;
+        }
+    }
+    status
+}
+
+// `set -o NAME` / `set +o NAME` turn an option on or off; `set -o` lists
+// them and `set +o` prints the commands that would restore them
+// `set -eux -o pipefail`, with `+` to turn options off. The first word
+// that is not an option, or everything after `--`, replaces $1..
+fn builtin_set(shell: &mut Shell, args: &[String]) -> i32 {
+    if args.is_empty() {
+        return builtin_set(shell, &["-o".to_string()]);
+    }
+    let mut i = 0;
+    while let Some(arg) = args.get(i) {
+        i += 1;
+        if arg == "--" {
+            shell.positional = args[i..].to_vec();
+            break;
+        }
+        let enable = match arg.chars().next() {
+            Some('-') if arg.len() > 1 => true,
+            Some('+') if arg.len() > 1 => false,
+            _ => {
+                shell.positional = args[i - 1..].to_vec();
+                break;
+            }
+        };
+        for flag in arg[1..].chars() {
+            let name = match flag {
+                'e' => "errexit",
+                'u' => "nounset",
+                'x' => "xtrace",
+                'H' => "histexpand",
+                'o' => match args.get(i) {
+                    Some(name) => {
+                        i += 1;
+                        name.as_str()
+                    }
+                    None => {
+                        print_options(shell, enable);
+                        continue;
+                    }
+                },
+                _ => {
+                    eprintln!("set: {}{}: invalid option", &arg[..1], flag);
+                    eprintln!("set: usage: set [-euxH] [-o|+o name] [--] [arg...]");
+                    return 2;
+                }
+            };
+            match shell.config.options.get_mut(name) {
+                Some(option) => *option = enable,
+                None => {
+                    eprintln!("set: {}: invalid option name", name);
+                    return 1;
+                }
+            }
+        }
     }
     0
 }
 
-fn builtin_exit(args: &[String]) -> i32 {
+// `set -o` lists the options, `set +o` as commands that restore them
+fn print_options(shell: &Shell, enable: bool) {
+    for (name, on) in shell.config.options.list() {
+        if enable {
+            outln!("{:<15}{}", name, if on { "on" } else { "off" });
+        } else {
+            outln!("set {}o {}", if on { '-' } else { '+' }, name);
+        }
+    }
+}
+
+fn builtin_exit(shell: &mut Shell, args: &[String]) -> i32 {
     let exit_code = if args.is_empty() {
         0
     } else {
         args[0].parse().unwrap_or(0)
     };
-    std::process::exit(exit_code);
+    shell.exit(exit_code);
 }
 
-fn builtin_history(shell: &mut Shell) -> i32 {
-    for (i, cmd) in shell.history.iter().enumerate() {
-        println!("{:5} {}", i + 1, cmd);
+// `exec cmd args` runs cmd in place of the shell, with the redirections
+// and assignments on the line already applied. Returns only on failure,
+// and then only to an interactive shell: a script ends there, as in POSIX.
+fn builtin_exec(shell: &mut Shell, args: &[String]) -> i32 {
+    let Some((program, rest)) = args.split_first() else {
+        return 0;
+    };
+    let argv = match Argv::build(program, rest) {
+        Ok(argv) => argv,
+        Err(e) => {
+            eprintln!("exec: {}: {}", program, e);
+            return 1;
+        }
+    };
+    let _ = io::stdout().flush();
+    let _ = signal_handler::restore_default_signals();
+    let e = argv.exec_args(None, None).exec();
+    let _ = signal_handler::setup_signal_handlers();
+    argv.recycle();
+    eprintln!("exec: {}: {}", program, e.desc());
+    let code = if e == nix::errno::Errno::ENOENT { 127 } else { 126 };
+    if !shell.interactive {
+        shell.exit(code);
+    }
+    code
+}
+
+// `history [-v]` lists the commands run; `-c` clears the list, `-d N`
+// deletes entry N, `-w`/`-a [FILE]` write all of it / the new entries to
+// FILE (the configured history file by default) and `search REGEX` lists
+// the entries matching an extended regular expression
+fn builtin_history(shell: &mut Shell, args: &[String]) -> i32 {
+    match args.first().map(String::as_str) {
+        Some("-c") => {
+            shell.history.clear();
+            return 0;
+        }
+        Some("-d") => {
+            let index = args.get(1).and_then(|n| n.parse::<usize>().ok()).filter(|&n| n > 0);
+            return match index {
+                Some(n) if shell.history.remove(n - 1) => 0,
+                _ => {
+                    eprintln!("history: {}: history position out of range", args.get(1).map_or("", |n| n.as_str()));
+                    1
+                }
+            };
+        }
+        Some(flag @ ("-w" | "-a")) => {
+            let file = match args.get(1) {
+                Some(file) => file.clone(),
+                None => crate::utils::string_utils::expand_tilde(&shell.config.general.history_file),
+            };
+            let written = if flag == "-w" {
+                shell.history.write_to(Path::new(&file))
+            } else {
+                shell.history.append_to(Path::new(&file))
+            };
+            return match written {
+                Ok(()) => 0,
+                Err(e) => {
+                    eprintln!("history: {}: {}", file, e);
+                    1
+                }
+            };
+        }
+        Some("search") => {
+            let Some(pattern) = args.get(1) else {
+                eprintln!("history: usage: history search REGEX");
+                return 2;
+            };
+            return match shell.history.search(pattern) {
+                Ok(found) if found.is_empty() => 1,
+                Ok(found) => {
+                    for (i, command) in found {
+                        outln!("{:5} {}", i + 1, command);
+                    }
+                    0
+                }
+                Err(e) => {
+                    eprintln!("history: {}", e);
+                    2
+                }
+            };
+        }
+        None | Some("-v") => {}
+        Some(other) => {
+            eprintln!("history: {}: invalid option", other);
+            eprintln!("history: usage: history [-v] | -c | -d N | -w [FILE] | -a [FILE] | search REGEX");
+            return 2;
+        }
+    }
+    let verbose = args.iter().any(|a| a == "-v");
+    for (i, entry) in shell.history.entries().enumerate() {
+        if verbose {
+            let duration = entry.duration
+                .map(crate::utils::helpers::format_duration)
+                .unwrap_or_else(|| "-".to_string());
+            let status = entry.exit_code
+                .map(|code| code.to_string())
+                .unwrap_or_else(|| "-".to_string());
+            let (cpu, rss) = entry.rusage
+                .map(|usage| (crate::utils::helpers::format_duration(usage.cpu_time()),
+                              crate::utils::helpers::format_bytes(usage.max_rss_kb * 1024)))
+                .unwrap_or_else(|| ("-".to_string(), "-".to_string()));
+            outln!("{:5} {:>8} {:>4} {:>8} {:>9}  {}", i + 1, duration, status, cpu, rss, entry.command);
+        } else {
+            outln!("{:5} {}", i + 1, entry.command);
+        }
     }
     0
 }
 
-fn builtin_jobs(shell: &mut Shell) -> i32 {
-    if shell.jobs.is_empty() {
-        println!("No active jobs");
+// `jobs [-lprs] [JOB...]`: -l adds the pid, -p prints only pids, -r and -s
+// only running or stopped jobs. `+` marks the current job and `-` the
+// previous one, which `%+` and `%-` name.
+fn builtin_jobs(shell: &mut Shell, args: &[String]) -> i32 {
+    let (mut long, mut pids, mut running, mut stopped) = (false, false, false, false);
+    let mut specs = args;
+    while let Some(flag) = specs.first().filter(|arg| arg.starts_with('-') && arg.len() > 1) {
+        specs = &specs[1..];
+        if flag == "--" {
+            break;
+        }
+        for letter in flag[1..].chars() {
+            match letter {
+                'l' => long = true,
+                'p' => pids = true,
+                'r' => running = true,
+                's' => stopped = true,
+                _ => {
+                    eprintln!("jobs: -{}: invalid option", letter);
+                    eprintln!("jobs: usage: jobs [-lprs] [jobspec ...]");
+                    return 2;
+                }
+            }
+        }
+    }
+    shell.reap_jobs();
+    let mut status = 0;
+    let selected: Vec<usize> = if specs.is_empty() {
+        (0..shell.jobs.len()).collect()
     } else {
-        for job in &shell.jobs {
-            println!("[{}] {:?} {}", job.id, job.status, job.command);
+        specs.iter()
+            .filter_map(|spec| match job_control::find_job(shell, spec) {
+                Ok(idx) => Some(idx),
+                Err(e) => {
+                    eprintln!("jobs: {}", e);
+                    status = 1;
+                    None
+                }
+            })
+            .collect()
+    };
+    let selected: Vec<usize> = selected.into_iter()
+        .filter(|&idx| match shell.jobs[idx].status {
+            JobStatus::Running => running || !stopped,
+            JobStatus::Stopped => stopped || !running,
+            JobStatus::Done => !running && !stopped,
+        })
+        .collect();
+    if shell.jobs.is_empty() && args.is_empty() {
+        outln!("No active jobs");
+    }
+    let order = job_control::job_order(shell);
+    for idx in selected {
+        let job = &shell.jobs[idx];
+        if pids {
+            outln!("{}", job.pid);
+            continue;
+        }
+        let mark = match order.iter().position(|&i| i == idx) {
+            Some(0) => '+',
+            Some(1) => '-',
+            _ => ' ',
+        };
+        if long {
+            outln!("[{}]{} {:>7} {:?} {}", job.id, mark, job.pid, job.status, job.command);
+        } else {
+            outln!("[{}]{} {:?} {}", job.id, mark, job.status, job.command);
+        }
+    }
+    status
+}
+
+// Prints the tree `input` parses to without running anything
+pub fn print_ast(input: &str) -> i32 {
+    let arena = Bump::new();
+    match Parser::new(input, &arena).and_then(|mut parser| parser.parse()) {
+        Ok(parsed) => {
+            out!("{}", crate::command::ast::render(&parsed));
+            0
+        }
+        Err(e) => {
+            eprintln!("{}", e.render(input));
+            2
         }
     }
+}
+
+// `debug ast 'cmd | other'`: the arguments are joined into one line, so
+// quote it as a whole to keep the quoting under test
+fn builtin_debug(args: &[String]) -> i32 {
+    match args.split_first() {
+        Some((sub, line)) if sub == "ast" && !line.is_empty() => print_ast(&line.join(" ")),
+        _ => {
+            eprintln!("debug: usage: debug ast <command>");
+            2
+        }
+    }
+}
+
+// `break`/`continue` only leave a note on the shell; the loops it passes
+// through act on it once the current command returns
+fn builtin_break(shell: &mut Shell, name: &str, args: &[String]) -> i32 {
+    if shell.loop_depth == 0 {
+        eprintln!("{}: only meaningful in a loop", name);
+        return 0;
+    }
+    let count = match args.first().map(|n| n.parse::<usize>()) {
+        None => 1,
+        Some(Ok(n)) if n >= 1 => n.min(shell.loop_depth),
+        Some(_) => {
+            eprintln!("{}: {}: loop count out of range", name, args[0]);
+            return 1;
+        }
+    };
+    shell.control = Some(if name == "break" { Control::Break(count) } else { Control::Continue(count) });
+    0
+}
+
+// `local NAME[=value]`: the variable gets back the value it had (or is
+// unset again) when the function returns. Without a value it starts unset.
+fn builtin_local(shell: &mut Shell, args: &[String]) -> i32 {
+    if shell.locals.is_empty() {
+        eprintln!("local: can only be used in a function");
+        return 1;
+    }
+    if args.is_empty() {
+        for (name, _) in shell.locals.last().into_iter().flatten() {
+            if let Some(value) = shell.environment.get(name.as_str()) {
+                outln!("{}={}", name, value);
+            }
+        }
+        return 0;
+    }
+    let mut status = 0;
+    for arg in args {
+        let (name, value) = match arg.split_once('=') {
+            Some((name, value)) => (name, Some(value)),
+            None => (arg.as_str(), None),
+        };
+        if !is_valid_var_name(name) {
+            eprintln!("local: `{}': not a valid identifier", arg);
+            status = 1;
+            continue;
+        }
+        if shell.attributes(name).readonly {
+            eprintln!("local: {}: readonly variable", name);
+            status = 1;
+            continue;
+        }
+        let previous = shell.environment.get(name).cloned();
+        let frame = shell.locals.last_mut().expect("checked above");
+        if !frame.iter().any(|(saved, _)| saved == name) {
+            frame.push((name.to_string(), previous));
+        }
+        let set = match value {
+            Some(value) => shell.set_var(name, value.to_string()),
+            None => shell.unset_var(name),
+        };
+        if !set {
+            status = 1;
+        }
+    }
+    status
+}
+
+// Moves $2.. down to $1..; a count larger than $# changes nothing
+fn builtin_shift(shell: &mut Shell, args: &[String]) -> i32 {
+    let count = match args.first() {
+        None => 1,
+        Some(n) => match n.parse::<usize>() {
+            Ok(n) => n,
+            Err(_) => {
+                eprintln!("shift: {}: numeric argument required", n);
+                return 1;
+            }
+        },
+    };
+    if count > shell.positional.len() {
+        return 1;
+    }
+    shell.positional.drain(..count);
     0
 }
 
+fn builtin_return(shell: &mut Shell, args: &[String]) -> i32 {
+    if shell.locals.is_empty() {
+        eprintln!("return: can only be used in a function");
+        return 1;
+    }
+    let code = match args.first() {
+        None => shell.last_exit_code,
+        Some(n) => match n.parse::<i32>() {
+            Ok(n) => n & 0xff,
+            Err(_) => {
+                eprintln!("return: {}: numeric argument required", n);
+                2
+            }
+        },
+    };
+    shell.control = Some(Control::Return(code));
+    code
+}
+
+// What running NAME would run, in the order the shell looks
+fn builtin_type(shell: &Shell, args: &[String]) -> i32 {
+    if args.is_empty() {
+        eprintln!("type: usage: type NAME...");
+        return 1;
+    }
+    let mut status = 0;
+    for name in args {
+        if let Some(value) = shell.aliases.get(name.as_str()) {
+            outln!("{} is aliased to `{}'", name, value);
+        } else if parser::is_keyword(name) {
+            outln!("{} is a shell keyword", name);
+        } else if let Some(body) = shell.functions.get(name) {
+            outln!("{} is a function", name);
+            outln!("{}() {}", name, body);
+        } else if is_builtin(name) || shell.plugins.has_builtin(name) {
+            outln!("{} is a shell builtin", name);
+        } else if let Some(path) = shell.command_cache.find_command(name) {
+            outln!("{} is {}", name, path.display());
+        } else {
+            eprintln!("type: {}: not found", name);
+            status = 1;
+        }
+    }
+    status
+}
+
+// `which [-a] NAME...`: the alias, function, builtin or file NAME runs,
+// looked up through the command cache without forking. With -a, all of
+// them, including files further along PATH that the first one shadows.
+fn builtin_which(shell: &Shell, args: &[String]) -> i32 {
+    let mut all = false;
+    let mut names = args;
+    while let Some(flag) = names.first().filter(|arg| arg.starts_with('-') && arg.len() > 1) {
+        names = &names[1..];
+        match flag.as_str() {
+            "--" => break,
+            "-a" => all = true,
+            _ => {
+                eprintln!("which: {}: invalid option", flag);
+                eprintln!("which: usage: which [-a] NAME...");
+                return 2;
+            }
+        }
+    }
+    if names.is_empty() {
+        eprintln!("which: usage: which [-a] NAME...");
+        return 2;
+    }
+    let mut status = 0;
+    for name in names {
+        let mut found = Vec::new();
+        if let Some(value) = shell.aliases.get(name.as_str()) {
+            found.push(format!("{}: aliased to {}", name, value));
+        }
+        if shell.functions.contains_key(name) {
+            found.push(format!("{}: shell function", name));
+        }
+        if is_builtin(name) || shell.plugins.has_builtin(name) {
+            found.push(format!("{}: shell built-in command", name));
+        }
+        if all {
+            found.extend(shell.command_cache.find_all(name).iter().map(|path| path.display().to_string()));
+        } else if found.is_empty() {
+            found.extend(shell.command_cache.find_command(name).map(|path| path.display().to_string()));
+        }
+        if found.is_empty() {
+            eprintln!("which: {}: not found", name);
+            status = 1;
+        }
+        for line in found.iter().take(if all { found.len() } else { 1 }) {
+            outln!("{}", line);
+        }
+    }
+    status
+}
+
+// `hash` lists the commands whose PATH lookup is cached, `hash NAME...`
+// looks them up now and `hash -r` (or `rehash`) forgets every lookup, for
+// when a new binary shadows a cached one
+fn builtin_hash(shell: &mut Shell, args: &[String]) -> i32 {
+    let mut names = args;
+    if let Some(flag) = names.first().filter(|arg| arg.starts_with('-') && arg.len() > 1) {
+        names = &names[1..];
+        match flag.as_str() {
+            "--" => {}
+            "-r" => shell.command_cache.invalidate(),
+            _ => {
+                eprintln!("hash: {}: invalid option", flag);
+                eprintln!("hash: usage: hash [-r] [NAME...]");
+                return 2;
+            }
+        }
+        if names.is_empty() {
+            return 0;
+        }
+    }
+    if names.is_empty() {
+        let paths = shell.command_cache.cached_paths();
+        if paths.is_empty() {
+            outln!("hash: hash table empty");
+        }
+        for (name, path) in paths {
+            outln!("{}\t{}", name, path.display());
+        }
+        return 0;
+    }
+    let mut status = 0;
+    for name in names {
+        if is_builtin(name) || name.contains('/') {
+            continue;
+        }
+        if shell.command_cache.find_command(name).is_none() {
+            eprintln!("hash: {}: not found", name);
+            status = 1;
+        }
+    }
+    status
+}
+
 fn builtin_help() -> i32 {
-    println!("RShell Built-in Commands:");
-    println!("  cd [dir]         - Change directory");
-    println!("  pwd              - Print working directory");
-    println!("  echo [text]      - Display text");
-    println!("  export VAR=val   - Set environment variable");
-    println!("  unset VAR        - Unset environment variable");
-    println!("  history          - Show command history");
-    println!("  jobs             - List active jobs");
-    println!("  fg [job]         - Bring job to foreground");
-    println!("  bg [job]         - Resume job in background");
-    println!("  kill <pid|%job>  - Kill a process or job");
-    println!("  alias            - Set command alias");
-    println!("  unalias          - Remove alias");
-    println!("  theme            - Manage shell themes");
-    println!("  config           - Manage configuration");
-    println!("  help             - Show this help message");
-    println!("  exit [code]      - Exit the shell");
+    outln!("RShell Built-in Commands:");
+    outln!("  cd [-L|-P] [dir] - Change directory (-P resolves symlinks, -: previous, @name: bookmark, searches $CDPATH)");
+    outln!("  pushd [DIR|+N]   - Save the current directory and change to DIR (+N: rotate the stack)");
+    outln!("  popd [+N]        - Drop the top of the directory stack and change to the next");
+    outln!("  dirs [-clpv]     - Show the directory stack (-v numbered, -c clear)");
+    outln!("  pwd [-L|-P]      - Print working directory (-P: physical)");
+    outln!("  echo [text]      - Display text");
+    outln!("  export [-n] VAR[=val] - Pass a variable on to commands (-n: stop passing it)");
+    outln!("  unset [-fv] NAME - Remove a variable (-v) or function (-f)");
+    outln!("  set [-euxH] [-o|+o name] [--] [arg...] - Turn shell options on/off, list them, or set $1..");
+    outln!("  break [n], continue [n] - Leave or restart the n-th enclosing loop");
+    outln!("  exec [cmd [args...]] - Replace the shell with cmd, or redirect the shell's own fds");
+    outln!("  local NAME[=value]... - Make variables local to the running function");
+    outln!("  readonly NAME[=value]... - Make variables unchangeable; alone, list them");
+    outln!("  declare [-ilprux] [+ilux] NAME[=value]... - Set variable attributes (-i integer, -u/-l case)");
+    outln!("  declare -f [NAME...], declare -F - Print function definitions, or just their names");
+    outln!("  read [-rs] [-p prompt] [NAME...] - Read a line from stdin into variables, split at $IFS");
+    outln!("  getopts OPTSTRING NAME [ARG...] - Parse the next option from $1.. into NAME and OPTARG");
+    outln!("  let EXPR...      - Evaluate arithmetic; fails if the last value is 0");
+    outln!("  calc EXPR        - Floating-point calculator: calc '3.5*1.2+sqrt(2)'");
+    outln!("  trap [ACTION] SIGNAL... - Run ACTION on a signal or on EXIT ('' ignores, - resets)");
+    outln!("  shift [n]        - Drop the first n positional parameters (default 1)");
+    outln!("  return [n]       - Leave the running function with status n");
+    outln!("  type NAME...     - Tell whether NAME is an alias, keyword, function, builtin or file");
+    outln!("  which [-a] NAME... - Show what NAME runs (-a: everything it could, shadowed files too)");
+    outln!("  hash [-r] [NAME...] - List or add cached command paths (-r, rehash: forget them)");
+    outln!("  history [-v]     - Show command history (-v adds duration, status, CPU, RSS)");
+    outln!("  history -c | -d N | -w|-a [file] | search REGEX - Clear, delete, save or search history");
+    outln!("  jobs [-lprs]     - List jobs (-l pids, -p only pids, -r running, -s stopped)");
+    outln!("  fg [job]         - Bring job to foreground (%N, %+, %-, %name, %?text)");
+    outln!("  bg [job]         - Resume job in background");
+    outln!("  kill [-SIG] <pid|%job>... - Send SIGTERM or SIG to processes or jobs (-l: list signals)");
+    outln!("  alias            - Set command alias");
+    outln!("  unalias          - Remove alias");
+    outln!("  theme            - Manage shell themes");
+    outln!("  config           - Manage configuration");
+    outln!("  run [--output json|tsv [--output-dir DIR]] [task...] - Run tasks and their dependencies");
+    outln!("  time cmd [args]  - Run cmd and report real, user and system time");
+    outln!("  times            - CPU time used by the shell and its children");
+    outln!("  bench [-w N] [-r N] <cmd>... - Benchmark and compare commands");
+    outln!("  stats [memory]   - Memory usage and soft-limit enforcement");
+    outln!("  sysinfo [field]  - Show OS, kernel, uptime, load, memory and terminal");
+    outln!("  audit [search [--failed] [--cwd DIR] [-n N] [--json] [text]] - Query the command audit log");
+    outln!("  sandbox [--no-net] [--ro-home|--tmp-home] cmd - Run cmd in new user/mount/net namespaces");
+    outln!("  plugin [list|available|load <name>|unload <name>] - Manage dynamic plugins");
+    outln!("  envrc [status|allow|deny|reload] - Trust and load the directory's .envrc/.env");
+    outln!("  bookmark [list|add NAME [DIR]|rm NAME] - Name directories for `cd @NAME`");
+    outln!("  bind [-l|-p|-r KEY|KEY ACTION...] - Bind keys to editor actions or commands");
+    outln!("  tsplit [-h] [cmd] - Run cmd (or a shell) in a new tmux pane here");
+    outln!("  from-json, from-csv [file]  - Read JSON/CSV into a table");
+    outln!("  select <field>..., where <field> <op> <value>, sort-by <field> [-r]");
+    outln!("                   - Transform a table (ops: eq ne lt le gt ge contains)");
+    outln!("  to-table, to-json - Print a table as a grid or as JSON");
+    outln!("  perf [report|reset|hints|trace start [file]|trace stop] - Metrics and tracing");
+    outln!("  debug ast '<command>' - Show how a command line parses, without running it");
+    outln!("  help             - Show this help message");
+    outln!("  exit [code]      - Exit the shell");
     0
 }
 
+// Every builtin besides the table commands in structured::COMMANDS. The
+// command cache and the line editor's completion and highlighting read
+// their lists from here.
+pub const BUILTINS: &[&str] = &[
+    "cd", "pwd", "echo", "export", "unset", "set", "exit", "exec", "break", "continue",
+    "local", "readonly", "declare", "trap", "read", "getopts", "let", "calc", "shift",
+    "return", "type", "which", "hash", "rehash", "pushd", "popd", "dirs",
+    "history", "jobs", "help", "fg", "bg", "kill",
+    "alias", "unalias", "theme", "config", "run", "perf", "time", "times", "bench",
+    "stats", "sysinfo", "audit", "sandbox", "plugin", "envrc", "bookmark", "bind", "tsplit",
+    "debug",
+];
+
 pub fn is_builtin(program: &str) -> bool {
-    matches!(
-        program,
-        "cd" | "pwd" | "echo" | "export" | "unset" | "exit" | 
-        "history" | "jobs" | "help" | "fg" | "bg" | "kill" |
-        "alias" | "unalias" | "theme" | "config"
-    )
+    BUILTINS.contains(&program) || crate::structured::COMMANDS.contains(&program)
 }
\ No newline at end of file
@@ -0,0 +1,262 @@
+// shell/calc.rs
+//
+// `calc EXPR...`: a floating-point calculator, for what `$(( ))` can't do
+// with integers. `+ - * / %`, `^` or `**` for powers (grouping to the
+// right), parentheses, the constants `pi` and `e`, functions like
+// `sqrt(2)` or `max(a, b)`, and shell variables holding numbers.
+use crate::shell::Shell;
+use crate::outln;
+
+// Functions by name and how many arguments they take
+const FUNCTIONS: &[(&str, usize)] = &[
+    ("sqrt", 1), ("cbrt", 1), ("abs", 1), ("exp", 1), ("ln", 1), ("log", 1), ("log2", 1),
+    ("sin", 1), ("cos", 1), ("tan", 1), ("asin", 1), ("acos", 1), ("atan", 1),
+    ("floor", 1), ("ceil", 1), ("round", 1), ("trunc", 1),
+    ("min", 2), ("max", 2), ("pow", 2), ("atan2", 2), ("hypot", 2),
+];
+
+pub fn builtin_calc(shell: &mut Shell, args: &[String]) -> i32 {
+    if args.is_empty() {
+        eprintln!("calc: usage: calc EXPRESSION");
+        return 2;
+    }
+    let expr = args.join(" ");
+    match evaluate(shell, &expr) {
+        Ok(value) => {
+            outln!("{}", format_number(value));
+            0
+        }
+        Err(e) => {
+            eprintln!("calc: {}", e);
+            1
+        }
+    }
+}
+
+pub fn evaluate(shell: &Shell, expr: &str) -> Result<f64, String> {
+    let mut parser = Calc { shell, chars: expr.chars().collect(), position: 0 };
+    let value = parser.sum()?;
+    parser.skip_blanks();
+    match parser.chars.get(parser.position) {
+        None => Ok(value),
+        Some(ch) => Err(format!("syntax error near `{}'", ch)),
+    }
+}
+
+// Up to 10 decimals, without trailing zeros: `4.2` rather than `4.200000000000001`
+fn format_number(value: f64) -> String {
+    if !value.is_finite() {
+        return value.to_string();
+    }
+    let text = format!("{:.10}", value);
+    let text = text.trim_end_matches('0').trim_end_matches('.');
+    if text == "-0" { "0".to_string() } else { text.to_string() }
+}
+
+struct Calc<'s> {
+    shell: &'s Shell,
+    chars: Vec<char>,
+    position: usize,
+}
+
+impl Calc<'_> {
+    fn skip_blanks(&mut self) {
+        while self.chars.get(self.position).is_some_and(|ch| ch.is_whitespace()) {
+            self.position += 1;
+        }
+    }
+
+    // The next character after blanks, taken if it is `ch`
+    fn eat(&mut self, ch: char) -> bool {
+        self.skip_blanks();
+        let found = self.chars.get(self.position) == Some(&ch);
+        self.position += usize::from(found);
+        found
+    }
+
+    fn sum(&mut self) -> Result<f64, String> {
+        let mut value = self.product()?;
+        loop {
+            if self.eat('+') {
+                value += self.product()?;
+            } else if self.eat('-') {
+                value -= self.product()?;
+            } else {
+                return Ok(value);
+            }
+        }
+    }
+
+    fn product(&mut self) -> Result<f64, String> {
+        let mut value = self.unary()?;
+        loop {
+            self.skip_blanks();
+            // `**` is a power, not two multiplications
+            if self.chars.get(self.position..self.position + 2) == Some(&['*', '*']) {
+                return Ok(value);
+            }
+            if self.eat('*') {
+                value *= self.unary()?;
+            } else if self.eat('/') {
+                let divisor = self.unary()?;
+                if divisor == 0.0 {
+                    return Err("division by zero".to_string());
+                }
+                value /= divisor;
+            } else if self.eat('%') {
+                let divisor = self.unary()?;
+                if divisor == 0.0 {
+                    return Err("division by zero".to_string());
+                }
+                value %= divisor;
+            } else {
+                return Ok(value);
+            }
+        }
+    }
+
+    fn unary(&mut self) -> Result<f64, String> {
+        if self.eat('-') {
+            return Ok(-self.unary()?);
+        }
+        if self.eat('+') {
+            return self.unary();
+        }
+        self.power()
+    }
+
+    // `2^3^2` is `2^9`; `-2^2` is `-4`
+    fn power(&mut self) -> Result<f64, String> {
+        let base = self.atom()?;
+        self.skip_blanks();
+        if self.chars.get(self.position..self.position + 2) == Some(&['*', '*']) {
+            self.position += 2;
+            return Ok(base.powf(self.unary()?));
+        }
+        if self.eat('^') {
+            return Ok(base.powf(self.unary()?));
+        }
+        Ok(base)
+    }
+
+    fn atom(&mut self) -> Result<f64, String> {
+        self.skip_blanks();
+        let start = self.position;
+        match self.chars.get(start) {
+            Some('(') => {
+                self.position += 1;
+                let value = self.sum()?;
+                if !self.eat(')') {
+                    return Err("missing `)'".to_string());
+                }
+                Ok(value)
+            }
+            Some(ch) if ch.is_ascii_digit() || *ch == '.' => {
+                while self.chars.get(self.position).is_some_and(|ch| ch.is_ascii_digit() || *ch == '.') {
+                    self.position += 1;
+                }
+                // An exponent: `1.5e3`, `2E-4`
+                if matches!(self.chars.get(self.position), Some('e' | 'E')) {
+                    let mut end = self.position + 1;
+                    if matches!(self.chars.get(end), Some('+' | '-')) {
+                        end += 1;
+                    }
+                    if self.chars.get(end).is_some_and(|ch| ch.is_ascii_digit()) {
+                        self.position = end;
+                        while self.chars.get(self.position).is_some_and(|ch| ch.is_ascii_digit()) {
+                            self.position += 1;
+                        }
+                    }
+                }
+                let text: String = self.chars[start..self.position].iter().collect();
+                text.parse().map_err(|_| format!("invalid number `{}'", text))
+            }
+            Some(ch) if ch.is_ascii_alphabetic() || *ch == '_' => {
+                while self.chars.get(self.position).is_some_and(|ch| ch.is_ascii_alphanumeric() || *ch == '_') {
+                    self.position += 1;
+                }
+                let name: String = self.chars[start..self.position].iter().collect();
+                if self.eat('(') {
+                    return self.call(&name);
+                }
+                self.variable(&name)
+            }
+            Some(ch) => Err(format!("syntax error near `{}'", ch)),
+            None => Err("operand expected".to_string()),
+        }
+    }
+
+    fn call(&mut self, name: &str) -> Result<f64, String> {
+        let Some(&(_, arity)) = FUNCTIONS.iter().find(|(known, _)| *known == name) else {
+            return Err(format!("{}: unknown function", name));
+        };
+        let mut args = vec![self.sum()?];
+        while self.eat(',') {
+            args.push(self.sum()?);
+        }
+        if !self.eat(')') {
+            return Err("missing `)'".to_string());
+        }
+        if args.len() != arity {
+            return Err(format!("{}: expects {} argument{}", name, arity, if arity == 1 { "" } else { "s" }));
+        }
+        let x = args[0];
+        Ok(match name {
+            "sqrt" => x.sqrt(),
+            "cbrt" => x.cbrt(),
+            "abs" => x.abs(),
+            "exp" => x.exp(),
+            "ln" => x.ln(),
+            "log" => x.log10(),
+            "log2" => x.log2(),
+            "sin" => x.sin(),
+            "cos" => x.cos(),
+            "tan" => x.tan(),
+            "asin" => x.asin(),
+            "acos" => x.acos(),
+            "atan" => x.atan(),
+            "floor" => x.floor(),
+            "ceil" => x.ceil(),
+            "round" => x.round(),
+            "trunc" => x.trunc(),
+            "min" => x.min(args[1]),
+            "max" => x.max(args[1]),
+            "pow" => x.powf(args[1]),
+            "atan2" => x.atan2(args[1]),
+            "hypot" => x.hypot(args[1]),
+            _ => unreachable!("listed in FUNCTIONS"),
+        })
+    }
+
+    fn variable(&self, name: &str) -> Result<f64, String> {
+        match name {
+            "pi" => Ok(std::f64::consts::PI),
+            "e" => Ok(std::f64::consts::E),
+            _ => match self.shell.environment.get(name) {
+                Some(value) => value.trim().parse().map_err(|_| format!("{}: not a number: {}", name, value)),
+                None => Err(format!("{}: unknown variable", name)),
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn expressions() {
+        let mut shell = Shell::new();
+        shell.set_var("RSHELL_CALC", "2.5".to_string());
+        let calc = |expr: &str| evaluate(&shell, expr).map(format_number);
+        assert_eq!(calc("3.5*1.2+sqrt(4)"), Ok("6.2".to_string()));
+        assert_eq!(calc("2^3^2"), Ok("512".to_string()));
+        assert_eq!(calc("-2**2 + max(1, 3) % 2"), Ok("-3".to_string()));
+        assert_eq!(calc("1.5e3 / RSHELL_CALC"), Ok("600".to_string()));
+        assert_eq!(calc("round(pi * 100) / 100"), Ok("3.14".to_string()));
+        assert!(calc("1 / 0").is_err());
+        assert!(calc("sqrt(1, 2)").is_err());
+        assert!(calc("(1 + 2").is_err());
+        assert!(calc("2 3").is_err());
+    }
+}
@@ -0,0 +1,177 @@
+// shell/conditional.rs
+//
+// Evaluates `[[ ... ]]`. Operands expand without field splitting or
+// globbing. The right side of `==` and `!=` is a pattern, that of `=~` a
+// POSIX extended regex; quoted parts of either match literally.
+use crate::command::CondExpr;
+use crate::shell::{expand, globbing, Shell};
+use nix::unistd::{access, AccessFlags};
+use std::ffi::CString;
+use std::path::Path;
+
+// Upper bound on the capture groups `=~` reports
+const MAX_GROUPS: usize = 32;
+
+// 0 if the expression is true, 1 if false, 2 after an error
+pub fn run(shell: &mut Shell, expr: &CondExpr) -> i32 {
+    match evaluate(shell, expr) {
+        Ok(true) => 0,
+        Ok(false) => 1,
+        Err(message) => {
+            // Expansion errors were already reported
+            if !message.is_empty() {
+                eprintln!("rshell: [[: {}", message);
+            }
+            2
+        }
+    }
+}
+
+fn evaluate(shell: &mut Shell, expr: &CondExpr) -> Result<bool, String> {
+    match expr {
+        CondExpr::Not(inner) => Ok(!evaluate(shell, inner)?),
+        CondExpr::And(left, right) => Ok(evaluate(shell, left)? && evaluate(shell, right)?),
+        CondExpr::Or(left, right) => Ok(evaluate(shell, left)? || evaluate(shell, right)?),
+        CondExpr::Word(word) => Ok(!operand(shell, word)?.is_empty()),
+        CondExpr::Unary(op, word) => {
+            let value = operand(shell, word)?;
+            unary(op, &value)
+        }
+        CondExpr::Binary(left, op, right) => {
+            let left = operand(shell, left)?;
+            match *op {
+                "==" | "=" | "!=" => {
+                    let pattern = expand::expand_pattern(shell, right, false).ok_or_else(String::new)?;
+                    let matched = globbing::Pattern::new(&pattern, true).matches(&left);
+                    Ok(matched != (*op == "!="))
+                }
+                "=~" => {
+                    let regex = expand::expand_pattern(shell, right, true).ok_or_else(String::new)?;
+                    regex_match(shell, &left, &regex)
+                }
+                _ => {
+                    let right = operand(shell, right)?;
+                    compare(&left, op, &right)
+                }
+            }
+        }
+    }
+}
+
+fn operand(shell: &mut Shell, word: &str) -> Result<String, String> {
+    if !expand::needs_expansion(word) {
+        return Ok(word.to_string());
+    }
+    expand::expand_single(shell, word).ok_or_else(String::new)
+}
+
+fn unary(op: &str, value: &str) -> Result<bool, String> {
+    let path = Path::new(value);
+    Ok(match op {
+        "-z" => value.is_empty(),
+        "-n" => !value.is_empty(),
+        "-e" => path.exists(),
+        "-f" => path.is_file(),
+        "-d" => path.is_dir(),
+        "-s" => path.metadata().is_ok_and(|meta| meta.len() > 0),
+        "-L" | "-h" => path.symlink_metadata().is_ok_and(|meta| meta.file_type().is_symlink()),
+        "-r" => access(path, AccessFlags::R_OK).is_ok(),
+        "-w" => access(path, AccessFlags::W_OK).is_ok(),
+        "-x" => access(path, AccessFlags::X_OK).is_ok(),
+        _ => return Err(format!("{}: unary operator expected", op)),
+    })
+}
+
+// `<` and `>` compare strings; the rest compare integers
+fn compare(left: &str, op: &str, right: &str) -> Result<bool, String> {
+    match op {
+        "<" => return Ok(left < right),
+        ">" => return Ok(left > right),
+        _ => {}
+    }
+    let number = |text: &str| text.trim().parse::<i64>()
+        .map_err(|_| format!("{}: integer expression expected", text));
+    let (left, right) = (number(left)?, number(right)?);
+    Ok(match op {
+        "-eq" => left == right,
+        "-ne" => left != right,
+        "-lt" => left < right,
+        "-le" => left <= right,
+        "-gt" => left > right,
+        "-ge" => left >= right,
+        _ => return Err(format!("{}: binary operator expected", op)),
+    })
+}
+
+// Matches with regcomp(3), so the dialect is the system's ERE like in bash.
+// On a match BASH_REMATCH holds the matched text and BASH_REMATCH_1, _2, ...
+// the groups; a failed match clears them.
+fn regex_match(shell: &mut Shell, text: &str, regex: &str) -> Result<bool, String> {
+    let pattern = CString::new(regex).map_err(|_| format!("{}: invalid regular expression", regex))?;
+    let subject = CString::new(text).map_err(|_| format!("{}: invalid operand", text))?;
+    let mut matches = [libc::regmatch_t { rm_so: -1, rm_eo: -1 }; MAX_GROUPS];
+    let found = unsafe {
+        let mut compiled: libc::regex_t = std::mem::zeroed();
+        if libc::regcomp(&mut compiled, pattern.as_ptr(), libc::REG_EXTENDED) != 0 {
+            return Err(format!("{}: invalid regular expression", regex));
+        }
+        let found = libc::regexec(&compiled, subject.as_ptr(), MAX_GROUPS, matches.as_mut_ptr(), 0) == 0;
+        libc::regfree(&mut compiled);
+        found
+    };
+
+    shell.unset_var("BASH_REMATCH");
+    let mut stale = 1;
+    while shell.environment.contains_key(format!("BASH_REMATCH_{}", stale).as_str()) {
+        shell.unset_var(&format!("BASH_REMATCH_{}", stale));
+        stale += 1;
+    }
+    if !found {
+        return Ok(false);
+    }
+    // Groups past the last one that took part in the match aren't told
+    // apart from ones the regex doesn't have
+    let last = matches.iter().rposition(|m| m.rm_so >= 0).unwrap_or(0);
+    for (i, m) in matches[..=last].iter().enumerate() {
+        // Offsets are in bytes and may split a character outside a UTF-8 locale
+        let group = match m.rm_so {
+            -1 => String::new(),
+            _ => String::from_utf8_lossy(&text.as_bytes()[m.rm_so as usize..m.rm_eo as usize]).into_owned(),
+        };
+        let name = if i == 0 { "BASH_REMATCH".to_string() } else { format!("BASH_REMATCH_{}", i) };
+        shell.set_var(&name, group);
+    }
+    Ok(true)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::command::CommandType;
+    use crate::config::Config;
+    use crate::shell::parser::Parser;
+    use bumpalo::Bump;
+
+    fn test(shell: &mut Shell, line: &str) -> i32 {
+        let arena = Bump::new();
+        let Ok(CommandType::Conditional(expr)) = Parser::new(line, &arena).unwrap().parse() else { panic!("{}", line) };
+        run(shell, expr)
+    }
+
+    #[test]
+    fn patterns_and_regexes() {
+        let mut shell = Shell::with_config(Config::default());
+        shell.environment.insert("V".into(), "key=value".to_string());
+        assert_eq!(test(&mut shell, "[[ $V == key=* && $V != *=\"*\" ]]"), 0);
+        assert_eq!(test(&mut shell, "[[ 10 -gt 9 && 10 < 9 ]]"), 0);
+        assert_eq!(test(&mut shell, "[[ ten -gt 9 ]]"), 2);
+        assert_eq!(test(&mut shell, "[[ $V =~ ^([a-z]+)=(x)?(v.*)$ ]]"), 0);
+        let group = |shell: &Shell, name: &str| shell.environment.get(name).cloned();
+        assert_eq!(group(&shell, "BASH_REMATCH").as_deref(), Some("key=value"));
+        assert_eq!(group(&shell, "BASH_REMATCH_1").as_deref(), Some("key"));
+        assert_eq!(group(&shell, "BASH_REMATCH_2").as_deref(), Some(""));
+        assert_eq!(group(&shell, "BASH_REMATCH_3").as_deref(), Some("value"));
+        assert_eq!(test(&mut shell, "[[ key.value =~ \"y=v\" ]]"), 1);
+        assert_eq!(group(&shell, "BASH_REMATCH_1"), None);
+    }
+}
@@ -0,0 +1,284 @@
+// shell/declare.rs
+//
+// Variable attributes and the builtins that set them: `readonly` and
+// `declare [-ilrux] [+ilux]`. Attributes are kept on the shell next to
+// `exported`, by name, and applied whenever the variable is assigned: an
+// integer variable stores its value evaluated as arithmetic, -u and -l
+// change case, and a readonly one refuses the assignment.
+use crate::memory_pool::interner;
+use crate::shell::Shell;
+use crate::shell::arithmetic;
+use crate::utils::helpers::is_valid_var_name;
+use crate::utils::string_utils::shell_quote;
+use crate::outln;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct VarAttributes {
+    pub readonly: bool,
+    pub integer: bool,
+    pub uppercase: bool,
+    pub lowercase: bool,
+}
+
+impl VarAttributes {
+    pub fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+
+    // The value to store for `value` assigned to `name`
+    pub fn apply(&self, shell: &mut Shell, name: &str, value: String) -> Result<String, String> {
+        if self.readonly {
+            return Err(format!("{}: readonly variable", name));
+        }
+        let value = if self.integer {
+            arithmetic::evaluate(shell, &value)
+                .map_err(|e| format!("{}: {}", value.trim(), e))?
+                .to_string()
+        } else {
+            value
+        };
+        Ok(if self.uppercase {
+            value.to_uppercase()
+        } else if self.lowercase {
+            value.to_lowercase()
+        } else {
+            value
+        })
+    }
+}
+
+// The flags `declare -p` shows for `name`, `--` when there are none
+fn flags(shell: &Shell, name: &str) -> String {
+    let attributes = shell.attributes(name);
+    let mut flags = String::from("-");
+    for (set, letter) in [
+        (attributes.integer, 'i'),
+        (attributes.lowercase, 'l'),
+        (attributes.readonly, 'r'),
+        (attributes.uppercase, 'u'),
+        (shell.is_exported(name), 'x'),
+    ] {
+        if set {
+            flags.push(letter);
+        }
+    }
+    if flags.len() == 1 {
+        flags.push('-');
+    }
+    flags
+}
+
+fn print_declaration(shell: &Shell, command: &str, name: &str) {
+    let flags = if command == "declare" { format!(" {}", flags(shell, name)) } else { String::new() };
+    match shell.environment.get(name) {
+        Some(value) => outln!("{}{} {}={}", command, flags, name, shell_quote(value)),
+        None => outln!("{}{} {}", command, flags, name),
+    }
+}
+
+// `declare -f [NAME...]` prints the definitions, in a form that can be read
+// back in, and `declare -F` just `declare -f NAME`; all functions without
+// names, sorted
+fn print_functions(shell: &Shell, names: &[String], names_only: bool) -> i32 {
+    let mut listed: Vec<&str> = if names.is_empty() {
+        shell.functions.keys().map(String::as_str).collect()
+    } else {
+        names.iter().map(String::as_str).collect()
+    };
+    if names.is_empty() {
+        listed.sort_unstable();
+    }
+    let mut status = 0;
+    for name in listed {
+        match shell.functions.get(name) {
+            Some(_) if names_only => outln!("declare -f {}", name),
+            Some(body) => outln!("{}() {}", name, body),
+            None => status = 1,
+        }
+    }
+    status
+}
+
+// `readonly NAME[=value]...` makes variables unchangeable for the rest of
+// the shell's life; alone or with -p, lists them
+pub fn builtin_readonly(shell: &mut Shell, args: &[String]) -> i32 {
+    let names = match args.first().map(String::as_str) {
+        Some("-p") => &args[1..],
+        Some("--") => &args[1..],
+        _ => args,
+    };
+    if names.is_empty() {
+        let mut readonly: Vec<&str> = shell.attributes.iter()
+            .filter(|(_, attributes)| attributes.readonly)
+            .map(|(name, _)| &**name)
+            .collect();
+        readonly.sort_unstable();
+        for name in readonly {
+            print_declaration(shell, "readonly", name);
+        }
+        return 0;
+    }
+    let mut status = 0;
+    for arg in names {
+        let (name, value) = match arg.split_once('=') {
+            Some((name, value)) => (name, Some(value)),
+            None => (arg.as_str(), None),
+        };
+        if !is_valid_var_name(name) {
+            eprintln!("readonly: `{}': not a valid identifier", arg);
+            status = 1;
+            continue;
+        }
+        if let Some(value) = value {
+            if !shell.set_var(name, value.to_string()) {
+                status = 1;
+                continue;
+            }
+        }
+        shell.attributes.entry(interner().intern(name)).or_default().readonly = true;
+    }
+    status
+}
+
+// `declare [-ilrux] [+ilux] [-p] [NAME[=value]...]`: -i integer, -l/-u
+// lower/upper case, -r readonly, -x export; `+` takes an attribute off.
+// Without names, lists the variables with the given attributes, or all.
+// -f and -F are about functions instead.
+pub fn builtin_declare(shell: &mut Shell, args: &[String]) -> i32 {
+    let mut add = VarAttributes::default();
+    let mut remove = VarAttributes::default();
+    let (mut export, mut unexport, mut print) = (false, false, false);
+    // -f prints function definitions, -F only their names
+    let (mut functions, mut function_names) = (false, false);
+    let mut names = args;
+    while let Some(flag) = names.first().filter(|arg| arg.len() > 1 && arg.starts_with(['-', '+'])) {
+        names = &names[1..];
+        if flag == "--" {
+            break;
+        }
+        let on = flag.starts_with('-');
+        let target = if on { &mut add } else { &mut remove };
+        for letter in flag[1..].chars() {
+            match letter {
+                'i' => target.integer = true,
+                'l' => target.lowercase = true,
+                'u' => target.uppercase = true,
+                'r' if on => target.readonly = true,
+                'x' if on => export = true,
+                'x' => unexport = true,
+                'p' if on => print = true,
+                'f' if on => functions = true,
+                'F' if on => function_names = true,
+                _ => {
+                    eprintln!("declare: {}{}: invalid option", &flag[..1], letter);
+                    eprintln!("declare: usage: declare [-fFilprux] [+ilux] [name[=value] ...]");
+                    return 2;
+                }
+            }
+        }
+    }
+
+    if functions || function_names {
+        return print_functions(shell, names, function_names);
+    }
+
+    if names.is_empty() {
+        let filtered = !add.is_empty() || export;
+        let mut listed: Vec<&str> = shell.environment.keys()
+            .map(|name| &**name)
+            .filter(|name| {
+                let attributes = shell.attributes(name);
+                !filtered
+                    || (add.integer && attributes.integer)
+                    || (add.lowercase && attributes.lowercase)
+                    || (add.uppercase && attributes.uppercase)
+                    || (add.readonly && attributes.readonly)
+                    || (export && shell.is_exported(name))
+            })
+            .collect();
+        listed.sort_unstable();
+        for name in listed {
+            if print || filtered {
+                print_declaration(shell, "declare", name);
+            } else {
+                outln!("{}={}", name, shell_quote(&shell.environment[name]));
+            }
+        }
+        return 0;
+    }
+
+    let mut status = 0;
+    for arg in names {
+        let (name, value) = match arg.split_once('=') {
+            Some((name, value)) => (name, Some(value)),
+            None => (arg.as_str(), None),
+        };
+        if !is_valid_var_name(name) {
+            eprintln!("declare: `{}': not a valid identifier", arg);
+            status = 1;
+            continue;
+        }
+        if print {
+            if shell.environment.contains_key(name) {
+                print_declaration(shell, "declare", name);
+            } else {
+                eprintln!("declare: {}: not found", name);
+                status = 1;
+            }
+            continue;
+        }
+        let mut attributes = shell.attributes(name);
+        if attributes.readonly && (value.is_some() || !remove.is_empty()) {
+            eprintln!("declare: {}: readonly variable", name);
+            status = 1;
+            continue;
+        }
+        // -u and -l each replace the other
+        if add.uppercase || add.lowercase {
+            attributes.uppercase = add.uppercase;
+            attributes.lowercase = add.lowercase;
+        }
+        attributes.integer = (attributes.integer || add.integer) && !remove.integer;
+        attributes.uppercase &= !remove.uppercase;
+        attributes.lowercase &= !remove.lowercase;
+        shell.set_attributes(name, attributes);
+        if let Some(value) = value {
+            if !shell.set_var(name, value.to_string()) {
+                status = 1;
+                continue;
+            }
+        }
+        if export || unexport {
+            shell.set_exported(name, export);
+        }
+        if add.readonly {
+            attributes.readonly = true;
+            shell.set_attributes(name, attributes);
+        }
+    }
+    status
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(words: &[&str]) -> Vec<String> {
+        words.iter().map(|word| word.to_string()).collect()
+    }
+
+    #[test]
+    fn attributes_apply_on_assignment() {
+        let mut shell = Shell::new();
+        assert_eq!(builtin_declare(&mut shell, &args(&["-i", "RSHELL_N=2+3"])), 0);
+        assert_eq!(shell.environment.get("RSHELL_N").map(String::as_str), Some("5"));
+        assert_eq!(builtin_declare(&mut shell, &args(&["-u", "RSHELL_U=abc"])), 0);
+        assert_eq!(shell.environment.get("RSHELL_U").map(String::as_str), Some("ABC"));
+
+        assert_eq!(builtin_readonly(&mut shell, &args(&["RSHELL_R=1"])), 0);
+        assert!(!shell.set_var("RSHELL_R", "2".to_string()));
+        assert!(!shell.unset_var("RSHELL_R"));
+        assert_eq!(shell.environment.get("RSHELL_R").map(String::as_str), Some("1"));
+        assert_eq!(builtin_declare(&mut shell, &args(&["+i", "RSHELL_R"])), 1);
+    }
+}
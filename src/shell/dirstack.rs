@@ -0,0 +1,211 @@
+// shell/dirstack.rs
+//
+// The directory stack: `pushd`, `popd` and `dirs`. Entry 0 is always the
+// current directory, the rest are kept on the shell most recent first.
+// `+N` counts entries from the left of the `dirs` listing, `-N` from the
+// right. pushd and popd print the stack after changing it.
+use crate::shell::Shell;
+use crate::outln;
+use std::env;
+
+// The current directory followed by the stack
+fn listing(shell: &Shell) -> Vec<String> {
+    std::iter::once(shell.current_dir.clone()).chain(shell.dir_stack.iter().cloned()).collect()
+}
+
+// `+N` or `-N` as an index into a listing of `len` entries
+fn parse_index(arg: &str, len: usize) -> Option<usize> {
+    let n: usize = arg.get(1..)?.parse().ok()?;
+    match arg.as_bytes()[0] {
+        b'+' if n < len => Some(n),
+        b'-' if n < len => Some(len - 1 - n),
+        _ => None,
+    }
+}
+
+fn is_index(arg: &str) -> bool {
+    arg.len() > 1 && arg.starts_with(['+', '-']) && arg[1..].bytes().all(|b| b.is_ascii_digit())
+}
+
+// `~` for the home directory unless `long`
+fn display(dir: &str, long: bool) -> String {
+    match env::var("HOME") {
+        Ok(home) if !long && !home.is_empty() && (dir == home || dir.starts_with(&format!("{}/", home))) => {
+            format!("~{}", &dir[home.len()..])
+        }
+        _ => dir.to_string(),
+    }
+}
+
+fn print_stack(shell: &Shell) {
+    let dirs: Vec<String> = listing(shell).iter().map(|dir| display(dir, false)).collect();
+    outln!("{}", dirs.join(" "));
+}
+
+// Changes to `dir`, reporting a failure as `name: dir: error`
+fn change_to(shell: &mut Shell, name: &str, dir: &str) -> bool {
+    match shell.change_dir(dir, false) {
+        Ok(()) => true,
+        Err(e) => {
+            eprintln!("{}: {}: {}", name, dir, e);
+            false
+        }
+    }
+}
+
+// `pushd DIR` saves the current directory and changes to DIR; `pushd`
+// alone swaps the top two entries and `pushd +N` rotates entry N to the top
+pub fn builtin_pushd(shell: &mut Shell, args: &[String]) -> i32 {
+    let arg = match args {
+        [] => None,
+        [arg] => Some(arg.as_str()),
+        _ => {
+            eprintln!("pushd: too many arguments");
+            return 2;
+        }
+    };
+    let mut dirs = listing(shell);
+    match arg {
+        None => {
+            if dirs.len() < 2 {
+                eprintln!("pushd: no other directory");
+                return 1;
+            }
+            dirs.swap(0, 1);
+        }
+        Some(arg) if is_index(arg) => {
+            let Some(n) = parse_index(arg, dirs.len()) else {
+                eprintln!("pushd: {}: directory stack index out of range", arg);
+                return 1;
+            };
+            dirs.rotate_left(n);
+        }
+        Some(dir) => {
+            let previous = shell.current_dir.clone();
+            if !change_to(shell, "pushd", dir) {
+                return 1;
+            }
+            shell.dir_stack.insert(0, previous);
+            print_stack(shell);
+            return 0;
+        }
+    }
+    if !change_to(shell, "pushd", &dirs[0]) {
+        return 1;
+    }
+    shell.dir_stack = dirs.split_off(1);
+    print_stack(shell);
+    0
+}
+
+// `popd` drops the top entry and changes to the next; `popd +N` drops
+// entry N, changing directory only if that is the current one
+pub fn builtin_popd(shell: &mut Shell, args: &[String]) -> i32 {
+    let n = match args {
+        [] => 0,
+        [arg] if is_index(arg) => match parse_index(arg, shell.dir_stack.len() + 1) {
+            Some(n) => n,
+            None => {
+                eprintln!("popd: {}: directory stack index out of range", arg);
+                return 1;
+            }
+        },
+        [arg] => {
+            eprintln!("popd: {}: invalid argument", arg);
+            eprintln!("popd: usage: popd [+N | -N]");
+            return 2;
+        }
+        _ => {
+            eprintln!("popd: too many arguments");
+            return 2;
+        }
+    };
+    if shell.dir_stack.is_empty() {
+        eprintln!("popd: directory stack empty");
+        return 1;
+    }
+    if n == 0 {
+        let dir = shell.dir_stack[0].clone();
+        if !change_to(shell, "popd", &dir) {
+            return 1;
+        }
+        shell.dir_stack.remove(0);
+    } else {
+        shell.dir_stack.remove(n - 1);
+    }
+    print_stack(shell);
+    0
+}
+
+// `dirs [-clpv] [+N | -N]`: -c empties the stack, -l shows full paths
+// instead of `~`, -p one entry per line and -v numbered like that
+pub fn builtin_dirs(shell: &mut Shell, args: &[String]) -> i32 {
+    let (mut clear, mut long, mut per_line, mut numbered) = (false, false, false, false);
+    let mut index = None;
+    for arg in args {
+        if is_index(arg) {
+            match parse_index(arg, shell.dir_stack.len() + 1) {
+                Some(n) => index = Some(n),
+                None => {
+                    eprintln!("dirs: {}: directory stack index out of range", arg);
+                    return 1;
+                }
+            }
+            continue;
+        }
+        let Some(flags) = arg.strip_prefix('-').filter(|flags| !flags.is_empty()) else {
+            eprintln!("dirs: {}: invalid argument", arg);
+            eprintln!("dirs: usage: dirs [-clpv] [+N | -N]");
+            return 2;
+        };
+        for flag in flags.chars() {
+            match flag {
+                'c' => clear = true,
+                'l' => long = true,
+                'p' => per_line = true,
+                'v' => numbered = true,
+                _ => {
+                    eprintln!("dirs: -{}: invalid option", flag);
+                    eprintln!("dirs: usage: dirs [-clpv] [+N | -N]");
+                    return 2;
+                }
+            }
+        }
+    }
+    if clear {
+        shell.dir_stack.clear();
+        return 0;
+    }
+    let dirs: Vec<String> = listing(shell).iter().map(|dir| display(dir, long)).collect();
+    if let Some(n) = index {
+        outln!("{}", dirs[n]);
+    } else if numbered {
+        for (i, dir) in dirs.iter().enumerate() {
+            outln!("{:2}  {}", i, dir);
+        }
+    } else if per_line {
+        for dir in &dirs {
+            outln!("{}", dir);
+        }
+    } else {
+        outln!("{}", dirs.join(" "));
+    }
+    0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn indexes_count_from_either_end() {
+        assert_eq!(parse_index("+0", 3), Some(0));
+        assert_eq!(parse_index("+2", 3), Some(2));
+        assert_eq!(parse_index("-0", 3), Some(2));
+        assert_eq!(parse_index("-2", 3), Some(0));
+        assert_eq!(parse_index("+3", 3), None);
+        assert_eq!(parse_index("-3", 3), None);
+        assert!(is_index("+1") && is_index("-10"));
+        assert!(!is_index("-") && !is_index("-v") && !is_index("dir"));
+    }
+}
@@ -1,11 +1,22 @@
 // shell/executor.rs
-use crate::command::{Command, CommandType};
-use crate::shell::{Shell, builtins, JobStatus};
+use crate::command::{Command, CommandType, FunctionBody, HereDoc, PipeStage, RedirectTarget, Redirection, SimpleCommand};
+use crate::shell::{Shell, Control, arithmetic, builtins, conditional, expand, read, trap};
+use crate::signal_handler;
+use crate::memory_pool::{self, Argv, ExecArgs};
+use crate::performance::wait_with_rusage;
+use crate::safety;
+use crate::command_not_found;
+use crate::utils::env_utils::expand_variables;
+use crate::utils::string_utils::shell_quote;
+use std::borrow::Cow;
 use std::fs::{File, OpenOptions};
-use std::os::unix::io::AsRawFd;
-use nix::sys::wait::{waitpid, WaitStatus, WaitPidFlag};
-use nix::unistd::{fork, ForkResult, dup2, close, execvp, Pid};
-use std::ffi::CString;
+use std::os::unix::io::{AsRawFd, IntoRawFd};
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+use nix::errno::Errno;
+use nix::sys::wait::WaitStatus;
+use nix::unistd::{fork, ForkResult, dup2, close};
+use std::time::Instant;
 
 // Simplified OptimizedExecutor without complex dependencies
 pub struct OptimizedExecutor {
@@ -13,16 +24,16 @@ pub struct OptimizedExecutor {
 }
 
 
-pub struct Executor {
-    pub background_jobs: Vec<i32>,
-}
+// Runs parsed commands against a shell; all state, jobs included, lives in
+// the shell, so a fresh executor can run each line
+pub struct Executor;
 
 impl OptimizedExecutor {
     pub fn new() -> std::io::Result<Self> {
         Ok(Self {})
     }
     
-    pub fn execute_optimized(&mut self, shell: &mut Shell, cmd_type: CommandType) -> i32 {
+    pub fn execute_optimized(&mut self, shell: &mut Shell, cmd_type: CommandType<'_>) -> i32 {
         // Simplified implementation - delegate to standard executor
         let mut executor = Executor::new();
         executor.execute(shell, cmd_type)
@@ -33,135 +44,571 @@ impl OptimizedExecutor {
     }
 }
 
+impl Default for Executor {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl Executor {
     pub fn new() -> Self {
-        Self {
-            background_jobs: Vec::new(),
-        }
+        Self
     }
     
-    pub fn execute(&mut self, shell: &mut Shell, cmd_type: CommandType) -> i32 {
+    pub fn execute(&mut self, shell: &mut Shell, cmd_type: CommandType<'_>) -> i32 {
+        if signal_handler::has_pending_traps() {
+            trap::run_pending(shell);
+        }
         match cmd_type {
-            CommandType::Simple(command) => self.execute_simple(shell, command),
-            CommandType::Pipeline(commands) => self.execute_pipeline(shell, commands),
+            CommandType::Simple(command) => {
+                let status = self.execute_simple(shell, &command);
+                errexit(shell, status)
+            }
+            CommandType::Pipeline(stages) => {
+                let status = self.execute_pipeline(shell, stages);
+                errexit(shell, status)
+            }
             CommandType::And(left, right) => {
-                let left_result = self.execute(shell, *left);
-                if left_result == 0 {
+                let left_result = self.test(shell, *left);
+                shell.last_exit_code = left_result;
+                if left_result == 0 && shell.control.is_none() {
                     self.execute(shell, *right)
                 } else {
                     left_result
                 }
             }
             CommandType::Or(left, right) => {
-                let left_result = self.execute(shell, *left);
-                if left_result != 0 {
+                let left_result = self.test(shell, *left);
+                shell.last_exit_code = left_result;
+                if left_result != 0 && shell.control.is_none() {
                     self.execute(shell, *right)
                 } else {
                     left_result
                 }
             }
+            CommandType::Sequence(commands) => {
+                let mut exit_code = 0;
+                for &command in commands {
+                    exit_code = self.execute(shell, command);
+                    shell.last_exit_code = exit_code;
+                    // The rest is skipped on the way out to the loop
+                    if shell.control.is_some() {
+                        break;
+                    }
+                }
+                exit_code
+            }
+            CommandType::Subshell(inner) => {
+                let status = self.execute_subshell(shell, *inner);
+                errexit(shell, status)
+            }
+            CommandType::Not(inner) => i32::from(self.test(shell, *inner) == 0),
+            CommandType::Background(inner) => self.execute_background(shell, *inner),
+            CommandType::Conditional(expr) => {
+                let status = conditional::run(shell, expr);
+                errexit(shell, status)
+            }
+            CommandType::Arithmetic(expr) => {
+                let status = arithmetic::run(shell, expr);
+                errexit(shell, status)
+            }
+            CommandType::Coproc(name, inner) => self.execute_coproc(shell, name, *inner),
+            CommandType::If { branches, else_body } => {
+                for &(condition, body) in branches {
+                    let status = self.test(shell, condition);
+                    shell.last_exit_code = status;
+                    if shell.control.is_some() {
+                        return status;
+                    }
+                    if status == 0 {
+                        return self.execute(shell, body);
+                    }
+                }
+                // No branch taken and no else: success, as in sh
+                else_body.map_or(0, |body| self.execute(shell, *body))
+            }
+            CommandType::Group(inner) => self.execute(shell, *inner),
+            CommandType::Function { name, body } => {
+                shell.functions.insert(name.to_string(), Rc::new(FunctionBody::copy_of(body)));
+                0
+            }
+            CommandType::For { name, words, body } => {
+                let Some(words) = loop_words(shell, words) else { return 1 };
+                shell.loop_depth += 1;
+                let status = self.execute_for(shell, name, words, *body);
+                shell.loop_depth -= 1;
+                status
+            }
+            CommandType::Select { name, words, body } => {
+                let Some(words) = loop_words(shell, words) else { return 1 };
+                shell.loop_depth += 1;
+                let status = self.execute_select(shell, name, &words, *body);
+                shell.loop_depth -= 1;
+                status
+            }
+            CommandType::While { condition, body, until } => {
+                shell.loop_depth += 1;
+                let status = self.execute_while(shell, *condition, *body, until);
+                shell.loop_depth -= 1;
+                status
+            }
+        }
+    }
+    
+    // Runs a command whose status is tested, so `set -e` leaves it alone
+    fn test(&mut self, shell: &mut Shell, command: CommandType<'_>) -> i32 {
+        shell.condition_depth += 1;
+        let status = self.execute(shell, command);
+        shell.condition_depth -= 1;
+        status
+    }
+    
+    // Runs a function body with `args` as $1..$n. Variables made `local`
+    // and the loops around the call are restored when it returns.
+    fn call_function(&mut self, shell: &mut Shell, name: &str, function: &FunctionBody, args: &[String]) -> i32 {
+        if shell.locals.len() >= MAX_FUNCTION_DEPTH {
+            eprintln!("{}: maximum function nesting level exceeded ({})", name, MAX_FUNCTION_DEPTH);
+            return 1;
+        }
+        let positional = std::mem::replace(&mut shell.positional, args.to_vec());
+        // `break` inside the function can't reach a loop outside it
+        let loop_depth = std::mem::take(&mut shell.loop_depth);
+        shell.locals.push(Vec::new());
+        let mut status = self.execute(shell, *function.borrow_dependent());
+        if let Some(Control::Return(code)) = shell.control {
+            shell.control = None;
+            status = code;
+        }
+        for (name, value) in shell.locals.pop().unwrap_or_default().into_iter().rev() {
+            match value {
+                Some(value) => shell.set_var(&name, value),
+                None => shell.unset_var(&name),
+            };
+        }
+        shell.loop_depth = loop_depth;
+        shell.positional = positional;
+        status
+    }
+    
+    // Same status and Ctrl+C handling as `while`
+    fn execute_for(&mut self, shell: &mut Shell, name: &str, words: Vec<String>, body: CommandType<'_>) -> i32 {
+        let mut status = 0;
+        for word in words {
+            shell.set_var(name, word);
+            status = self.execute(shell, body);
+            shell.last_exit_code = status;
+            if leave_loop(shell) {
+                break;
+            }
+            if interrupted(shell, status) {
+                return 130;
+            }
+        }
+        status
+    }
+    
+    // Shows the words as a numbered menu on stderr and reads choices after
+    // $PS3 until end of input or `break`. Each line read goes in REPLY and
+    // the word it numbers in NAME (empty if none); an empty line shows the
+    // menu again.
+    fn execute_select(&mut self, shell: &mut Shell, name: &str, words: &[String], body: CommandType<'_>) -> i32 {
+        use std::io::Write;
+        let mut status = 0;
+        let mut show_menu = true;
+        while !words.is_empty() {
+            if show_menu {
+                let width = words.len().to_string().len();
+                for (i, word) in words.iter().enumerate() {
+                    eprintln!("{:>width$}) {}", i + 1, word);
+                }
+            }
+            eprint!("{}", shell.environment.get("PS3").map_or("#? ", String::as_str));
+            let _ = std::io::stderr().flush();
+            let (line, complete) = match read::read_line(true) {
+                Ok(line) => line,
+                Err(_) => return 130,
+            };
+            if line.is_empty() && !complete {
+                eprintln!();
+                break;
+            }
+            let reply = String::from_utf8_lossy(&line).trim().to_string();
+            show_menu = reply.is_empty();
+            if show_menu {
+                continue;
+            }
+            let choice = reply.parse::<usize>().ok().and_then(|n| words.get(n.checked_sub(1)?));
+            shell.set_var(name, choice.cloned().unwrap_or_default());
+            shell.set_var("REPLY", reply);
+            status = self.execute(shell, body);
+            shell.last_exit_code = status;
+            if leave_loop(shell) {
+                break;
+            }
+            if interrupted(shell, status) {
+                return 130;
+            }
+        }
+        status
+    }
+    
+    // The status is the body's last, or 0 if it never ran. Ctrl+C, whether
+    // the shell or a command in the loop got it, stops the loop.
+    fn execute_while(&mut self, shell: &mut Shell, condition: CommandType<'_>, body: CommandType<'_>, until: bool) -> i32 {
+        let mut status = 0;
+        loop {
+            let tested = self.test(shell, condition);
+            shell.last_exit_code = tested;
+            if shell.control.is_some() {
+                if leave_loop(shell) {
+                    break;
+                }
+                continue;
+            }
+            if interrupted(shell, tested) {
+                return 130;
+            }
+            if (tested == 0) == until {
+                break;
+            }
+            status = self.execute(shell, body);
+            shell.last_exit_code = status;
+            if leave_loop(shell) {
+                break;
+            }
+            if interrupted(shell, status) {
+                return 130;
+            }
+        }
+        status
+    }
+    
+    // Forks once for the whole list and returns at once; the child is a
+    // single job however many processes it goes on to start
+    fn execute_background(&mut self, shell: &mut Shell, inner: CommandType<'_>) -> i32 {
+        use std::io::Write;
+        // Asking happens here, since the job cannot read the terminal
+        let mut commands = Vec::new();
+        collect_simple(&inner, &mut commands);
+        if !safety::confirm(shell, &commands) {
+            return 1;
+        }
+        let _ = std::io::stdout().flush();
+        match unsafe { fork() } {
+            Ok(ForkResult::Parent { child }) => {
+                let pid = child.as_raw();
+                shell.add_job(pid, inner.to_string());
+                println!("[{}] {}", shell.jobs.len(), pid);
+                0
+            }
+            Ok(ForkResult::Child) => {
+                shell.config.safety.confirm_dangerous = false;
+                trap::reset_for_subshell(shell);
+                // The job already is a separate process
+                let inner = match inner {
+                    CommandType::Subshell(inner) => *inner,
+                    other => other,
+                };
+                let code = Executor::new().execute(shell, inner);
+                let _ = std::io::stdout().flush();
+                std::process::exit(code);
+            }
+            Err(e) => {
+                eprintln!("Fork failed: {}", e);
+                1
+            }
+        }
+    }
+    
+    // Starts `inner` as a background job reading from one pipe and writing to
+    // another. NAME holds the fd to read its output from, NAME_1 the fd to
+    // write its input to and NAME_PID its pid; other commands reach them
+    // with `<&$NAME` and `>&$NAME_1`.
+    fn execute_coproc(&mut self, shell: &mut Shell, name: &str, inner: CommandType<'_>) -> i32 {
+        use std::io::Write;
+        let mut commands = Vec::new();
+        collect_simple(&inner, &mut commands);
+        if !safety::confirm(shell, &commands) {
+            return 1;
+        }
+        let (to_shell, to_coproc) = match (nix::unistd::pipe(), nix::unistd::pipe()) {
+            (Ok(to_shell), Ok(to_coproc)) => (to_shell, to_coproc),
+            (result, other) => {
+                for (read_fd, write_fd) in [result, other].into_iter().flatten() {
+                    close(read_fd).ok();
+                    close(write_fd).ok();
+                }
+                eprintln!("coproc: cannot create pipes");
+                return 1;
+            }
+        };
+        let _ = std::io::stdout().flush();
+        match unsafe { fork() } {
+            Ok(ForkResult::Parent { child }) => {
+                close(to_shell.1).ok();
+                close(to_coproc.0).ok();
+                // A coproc of the same name is replaced, and its pipes closed
+                if shell.environment.contains_key(format!("{}_PID", name).as_str()) {
+                    for var in [name.to_string(), format!("{}_1", name)] {
+                        let fd = shell.environment.get(var.as_str()).and_then(|fd| fd.parse::<i32>().ok());
+                        if let Some(fd) = fd.filter(|&fd| fd >= COPROC_MIN_FD) {
+                            close(fd).ok();
+                        }
+                    }
+                }
+                let pid = child.as_raw();
+                shell.set_var(name, high_fd(to_shell.0).to_string());
+                shell.set_var(&format!("{}_1", name), high_fd(to_coproc.1).to_string());
+                shell.set_var(&format!("{}_PID", name), pid.to_string());
+                shell.add_job(pid, format!("coproc {}", inner));
+                println!("[{}] {}", shell.jobs.len(), pid);
+                0
+            }
+            Ok(ForkResult::Child) => {
+                dup2(to_coproc.0, 0).ok();
+                dup2(to_shell.1, 1).ok();
+                for fd in [to_shell.0, to_shell.1, to_coproc.0, to_coproc.1] {
+                    close(fd).ok();
+                }
+                shell.config.safety.confirm_dangerous = false;
+                trap::reset_for_subshell(shell);
+                let inner = match inner {
+                    CommandType::Subshell(inner) => *inner,
+                    other => other,
+                };
+                let code = Executor::new().execute(shell, inner);
+                let _ = std::io::stdout().flush();
+                std::process::exit(code);
+            }
+            Err(e) => {
+                for fd in [to_shell.0, to_shell.1, to_coproc.0, to_coproc.1] {
+                    close(fd).ok();
+                }
+                eprintln!("Fork failed: {}", e);
+                1
+            }
         }
     }
     
-    fn execute_simple(&mut self, shell: &mut Shell, command: Command) -> i32 {
+    // Runs `inner` in a forked copy of the shell, so cd, exports and aliases
+    // inside it leave the parent untouched
+    fn execute_subshell(&mut self, shell: &mut Shell, inner: CommandType<'_>) -> i32 {
+        use std::io::Write;
+        let _ = std::io::stdout().flush();
+        match unsafe { fork() } {
+            Ok(ForkResult::Parent { child }) => match wait_with_rusage(child) {
+                Ok((status, usage)) => {
+                    shell.record_child_usage(&usage);
+                    match status {
+                        WaitStatus::Exited(_, code) => code,
+                        WaitStatus::Signaled(_, sig, _) => 128 + sig as i32,
+                        _ => 1,
+                    }
+                }
+                Err(_) => 1,
+            },
+            Ok(ForkResult::Child) => {
+                trap::reset_for_subshell(shell);
+                let code = Executor::new().execute(shell, inner);
+                let _ = std::io::stdout().flush();
+                std::process::exit(code);
+            }
+            Err(e) => {
+                eprintln!("Fork failed: {}", e);
+                1
+            }
+        }
+    }
+    
+    fn execute_simple(&mut self, shell: &mut Shell, command: &SimpleCommand<'_>) -> i32 {
+        let Some(command) = expand::expand_command(shell, command) else { return 1 };
+        if shell.config.options.xtrace {
+            trace(shell, &command);
+        }
+        if !safety::confirm(shell, [&command]) {
+            memory_pool::recycle_command(command);
+            return 1;
+        }
+        
+        // `A=1` on its own sets a shell variable
+        if command.program.is_empty() {
+            let mut status = 0;
+            for (name, value) in &command.env_overrides {
+                if !shell.set_var(name, value.clone()) {
+                    status = 1;
+                }
+            }
+            memory_pool::recycle_command(command);
+            return status;
+        }
+        
+        // Functions come before builtins, so they can wrap them
+        let start = Instant::now();
+        if let Some(function) = shell.functions.get(&command.program).cloned() {
+            let exit_code = match SavedFds::redirect(shell, &command.redirects) {
+                Ok(redirected) => {
+                    let saved = override_env(shell, &command.env_overrides);
+                    let exit_code = self.call_function(shell, &command.program, &function, &command.args);
+                    restore_env(shell, saved);
+                    drop(redirected);
+                    exit_code
+                }
+                Err(e) => {
+                    eprintln!("rshell: {}", e);
+                    1
+                }
+            };
+            memory_pool::recycle_command(command);
+            return exit_code;
+        }
+        
         // Check if it's a builtin command
-        if let Some(exit_code) = builtins::execute_builtin(
-            shell, &command.program, &command.args
-        ) {
+        if builtins::is_builtin(&command.program) || shell.plugins.has_builtin(&command.program) {
+            let exit_code = execute_builtin(shell, &command);
+            if shell.perf.is_tracing() {
+                shell.perf.trace_span(&format!("builtin {}", command.program), "builtin", start);
+            }
+            memory_pool::recycle_command(command);
             return exit_code;
         }
         
         // Fork and execute external command
-        self.execute_external(shell, command)
+        let exit_code = self.execute_external(shell, &command);
+        if shell.perf.is_tracing() {
+            shell.perf.trace_span(&format!("exec {}", command.program), "exec", start);
+        }
+        memory_pool::recycle_command(command);
+        exit_code
     }
     
-    fn execute_external(&mut self, shell: &mut Shell, command: Command) -> i32 {
+    fn execute_external(&mut self, shell: &mut Shell, command: &Command) -> i32 {
+        // Everything the child needs is ready before forking, and the
+        // buffers go back to the pool after
+        let launch = match Launch::prepare(shell, command) {
+            Ok(launch) => launch,
+            Err(e) => {
+                eprintln!("{}", e);
+                return 1;
+            }
+        };
+        if let Program::NotFound = launch.program {
+            launch.recycle();
+            command_not_found::report(shell, &command.program);
+            return 127;
+        }
+        let exec_args = launch.exec_args();
+        let fork_start = Instant::now();
         unsafe {
             match fork() {
                 Ok(ForkResult::Parent { child }) => {
-                    let pid = child.as_raw();
+                    shell.perf.trace_span("fork", "process", fork_start);
+                    drop(exec_args);
+                    launch.recycle();
                     
-                    if command.background {
-                        // Add to background jobs
-                        shell.add_job(pid, format!("{} {}", command.program, command.args.join(" ")));
-                        self.background_jobs.push(pid);
-                        println!("[{}] {}", shell.jobs.len(), pid);
-                        0
-                    } else {
-                        // Wait for foreground process
-                        match waitpid(child, None) {
-                            Ok(WaitStatus::Exited(_, code)) => code as i32,
-                            Ok(WaitStatus::Signaled(_, sig, _)) => 128 + sig as i32,
-                            _ => 1,
+                    // Wait for foreground process
+                    let wait_start = Instant::now();
+                    let status = match wait_with_rusage(child) {
+                        Ok((status, usage)) => {
+                            shell.record_child_usage(&usage);
+                            match status {
+                                WaitStatus::Exited(_, code) => code,
+                                WaitStatus::Signaled(_, sig, _) => 128 + sig as i32,
+                                _ => 1,
+                            }
                         }
-                    }
+                        Err(_) => 1,
+                    };
+                    shell.perf.trace_span("wait", "process", wait_start);
+                    status
                 }
                 Ok(ForkResult::Child) => {
-                    // Setup redirections
-                    if let Some(stdin_file) = command.stdin_redirect {
-                        let file = File::open(&stdin_file).expect("Failed to open input file");
-                        dup2(file.as_raw_fd(), 0).expect("Failed to redirect stdin");
-                    }
-                    
-                    if let Some(stdout_file) = command.stdout_redirect {
-                        let file = if command.append_stdout {
-                            OpenOptions::new()
-                                .create(true)
-                                .append(true)
-                                .open(&stdout_file)
-                                .expect("Failed to open output file")
-                        } else {
-                            File::create(&stdout_file).expect("Failed to create output file")
-                        };
-                        dup2(file.as_raw_fd(), 1).expect("Failed to redirect stdout");
-                    }
-                    
-                    if let Some(stderr_file) = command.stderr_redirect {
-                        let file = File::create(&stderr_file).expect("Failed to create error file");
-                        dup2(file.as_raw_fd(), 2).expect("Failed to redirect stderr");
-                    }
-                    
-                    // Prepare arguments for execvp
-                    let prog = CString::new(command.program.clone()).unwrap();
-                    let mut args: Vec<CString> = vec![prog.clone()];
-                    for arg in command.args {
-                        args.push(CString::new(arg).unwrap());
-                    }
-                    
-                    // Execute the command
-                    execvp(&prog, &args).expect("Failed to execute command");
-                    std::process::exit(127); // Command not found
+                    launch.redirects.apply();
+                    let e = exec_args.exec();
+                    command_not_found::exec_failed(&command.program, e);
                 }
                 Err(e) => {
+                    drop(exec_args);
+                    launch.recycle();
                     eprintln!("Fork failed: {}", e);
-                    return 1;
+                    1
                 }
             }
         }
     }
     
-    pub fn execute_pipeline(&mut self, shell: &mut Shell, commands: Vec<Command>) -> i32 {
-        if commands.is_empty() {
-            return 0;
+    // Each stage runs in its own child. Simple commands exec directly;
+    // compound stages run through a fresh Executor in the child.
+    pub fn execute_pipeline(&mut self, shell: &mut Shell, stages: &[PipeStage<'_>]) -> i32 {
+        if stages.len() <= 1 {
+            return stages.first().map_or(0, |stage| self.execute(shell, stage.command));
         }
         
-        if commands.len() == 1 {
-            return self.execute_simple(shell, commands.into_iter().next().unwrap());
+        // Simple stages are expanded up front, so an error stops the whole
+        // pipeline before anything starts
+        let mut commands = Vec::with_capacity(stages.len());
+        for stage in stages {
+            let CommandType::Simple(command) = &stage.command else {
+                commands.push(None);
+                continue;
+            };
+            let Some(command) = expand::expand_command(shell, command) else {
+                commands.into_iter().flatten().for_each(memory_pool::recycle_command);
+                return 1;
+            };
+            if shell.config.options.xtrace {
+                trace(shell, &command);
+            }
+            commands.push(Some(command));
+        }
+        let status = self.run_pipeline(shell, stages, &commands);
+        commands.into_iter().flatten().for_each(memory_pool::recycle_command);
+        status
+    }
+    
+    fn run_pipeline(&mut self, shell: &mut Shell, stages: &[PipeStage<'_>], commands: &[Option<Command>]) -> i32 {
+        if !safety::confirm(shell, commands.iter().flatten()) {
+            return 1;
+        }
+        
+        let mut launches = Vec::with_capacity(stages.len());
+        for command in commands.iter().map(Option::as_ref) {
+            let Some(command) = command else {
+                launches.push(None);
+                continue;
+            };
+            match Launch::prepare(shell, command) {
+                Ok(launch) => launches.push(Some(launch)),
+                Err(e) => {
+                    eprintln!("{}", e);
+                    launches.into_iter().flatten().for_each(Launch::recycle);
+                    return 1;
+                }
+            }
         }
         
         let mut pids = Vec::new();
         let mut pipes = Vec::new();
         
         // Create pipes for all but the last command
-        for _ in 0..commands.len() - 1 {
+        for _ in 0..stages.len() - 1 {
             let (read_fd, write_fd) = nix::unistd::pipe().expect("Failed to create pipe");
             pipes.push((read_fd, write_fd));
         }
         
-        for (i, command) in commands.iter().enumerate() {
+        let pipeline_start = Instant::now();
+        for (i, ((stage, command), launch)) in stages.iter().zip(commands).zip(&launches).enumerate() {
+            if let (Some(Launch { program: Program::NotFound, .. }), Some(command)) = (launch, command) {
+                command_not_found::report(shell, &command.program);
+            }
+            let exec_args = launch.as_ref().map(Launch::exec_args);
+            let fork_start = Instant::now();
             unsafe {
                 match fork() {
                     Ok(ForkResult::Parent { child }) => {
+                        if shell.perf.is_tracing() {
+                            let name = command.as_ref().map_or("compound command", |c| c.program.as_str());
+                            shell.perf.trace_span(&format!("fork {}", name), "process", fork_start);
+                        }
                         pids.push(child);
                         
                         // Close pipe ends we don't need in parent
@@ -173,6 +620,7 @@ impl Executor {
                         }
                     }
                     Ok(ForkResult::Child) => {
+                        trap::reset_for_subshell(shell);
                         // Setup pipes
                         if i > 0 {
                             // Not first command, redirect stdin from previous pipe
@@ -184,6 +632,9 @@ impl Executor {
                         if i < pipes.len() {
                             // Not last command, redirect stdout to next pipe
                             dup2(pipes[i].1, 1).expect("Failed to redirect stdout");
+                            if stage.pipe_stderr {
+                                dup2(pipes[i].1, 2).expect("Failed to redirect stderr");
+                            }
                             close(pipes[i].0).ok();
                             close(pipes[i].1).ok();
                         }
@@ -194,30 +645,59 @@ impl Executor {
                             close(*write_fd).ok();
                         }
                         
-                        // Check if it's a builtin
-                        if builtins::is_builtin(&command.program) {
-                            // For builtins in pipeline, we need to handle them specially
-                            // For now, just exit with error
-                            std::process::exit(1);
+                        let (Some(command), Some(launch), Some(exec_args)) = (command, launch, &exec_args) else {
+                            // A subshell stage already has its own process
+                            let stage = match stage.command {
+                                CommandType::Subshell(inner) => *inner,
+                                other => other,
+                            };
+                            let code = Executor::new().execute(shell, stage);
+                            use std::io::Write;
+                            let _ = std::io::stdout().flush();
+                            std::process::exit(code);
+                        };
+                        
+                        // Explicit redirections override the pipe
+                        launch.redirects.apply();
+                        if command.program.is_empty() {
+                            std::process::exit(0);
                         }
                         
-                        // Execute the command
-                        let prog = CString::new(command.program.clone()).unwrap();
-                        let mut args: Vec<CString> = vec![prog.clone()];
-                        for arg in &command.args {
-                            args.push(CString::new(arg.clone()).unwrap());
+                        // Functions and builtins run in the forked child, like
+                        // a subshell: their output feeds the pipe, but changes
+                        // to shell state (cd, export) do not outlive the pipeline
+                        if let Some(function) = shell.functions.get(&command.program).cloned() {
+                            override_env(shell, &command.env_overrides);
+                            let code = self.call_function(shell, &command.program, &function, &command.args);
+                            use std::io::Write;
+                            let _ = std::io::stdout().flush();
+                            std::process::exit(code);
+                        }
+                        if builtins::is_builtin(&command.program) || shell.plugins.has_builtin(&command.program) {
+                            override_env(shell, &command.env_overrides);
+                            let code = builtins::execute_builtin(shell, &command.program, &command.args).unwrap_or(127);
+                            use std::io::Write;
+                            let _ = std::io::stdout().flush();
+                            std::process::exit(code);
                         }
                         
-                        execvp(&prog, &args).expect("Failed to execute command");
-                        std::process::exit(127);
+                        // Execute the command
+                        if let Program::NotFound = launch.program {
+                            libc::_exit(127);
+                        }
+                        let e = exec_args.exec();
+                        command_not_found::exec_failed(&command.program, e);
                     }
                     Err(e) => {
                         eprintln!("Fork failed: {}", e);
+                        drop(exec_args);
+                        launches.into_iter().flatten().for_each(Launch::recycle);
                         return 1;
                     }
                 }
             }
         }
+        launches.into_iter().flatten().for_each(Launch::recycle);
         
         // Close all pipes in parent
         for (read_fd, write_fd) in pipes {
@@ -226,45 +706,478 @@ impl Executor {
         }
         
         // Wait for all children
+        let wait_start = Instant::now();
         let mut last_status = 0;
+        let mut failed_status = 0;
         for pid in pids {
-            match waitpid(pid, None) {
-                Ok(WaitStatus::Exited(_, code)) => last_status = code as i32,
-                Ok(WaitStatus::Signaled(_, sig, _)) => last_status = 128 + sig as i32,
-                _ => last_status = 1,
+            last_status = match wait_with_rusage(pid) {
+                Ok((status, usage)) => {
+                    shell.record_child_usage(&usage);
+                    match status {
+                        WaitStatus::Exited(_, code) => code,
+                        WaitStatus::Signaled(_, sig, _) => 128 + sig as i32,
+                        _ => 1,
+                    }
+                }
+                Err(_) => 1,
+            };
+            if last_status != 0 {
+                failed_status = last_status;
             }
         }
+        shell.perf.trace_span("wait", "process", wait_start);
+        shell.perf.trace_span("pipeline", "exec", pipeline_start);
         
-        last_status
+        // With pipefail, the last command that failed decides, as in bash
+        if shell.config.options.pipefail {
+            failed_status
+        } else {
+            last_status
+        }
     }
-    
-    pub fn check_background_jobs(&mut self, shell: &mut Shell) {
-        let mut completed = Vec::new();
-        
-        for &pid in &self.background_jobs {
-            match waitpid(Pid::from_raw(pid), Some(WaitPidFlag::WNOHANG)) {
-                Ok(WaitStatus::Exited(_, code)) => {
-                    println!("[Done] {} exited with code {}", pid, code);
-                    completed.push(pid);
-                    
-                    // Update job status
-                    if let Some(job) = shell.jobs.iter_mut().find(|j| j.pid == pid) {
-                        job.status = JobStatus::Done;
-                    }
+}
+
+// Every simple command in `command`, as parsed
+fn collect_simple(command: &CommandType<'_>, found: &mut Vec<Command>) {
+    match command {
+        CommandType::Simple(command) => found.push(command.to_command()),
+        CommandType::Pipeline(stages) => stages.iter().for_each(|stage| collect_simple(&stage.command, found)),
+        CommandType::And(left, right) | CommandType::Or(left, right) => {
+            collect_simple(left, found);
+            collect_simple(right, found);
+        }
+        CommandType::Sequence(commands) => commands.iter().for_each(|command| collect_simple(command, found)),
+        CommandType::Subshell(inner) | CommandType::Not(inner) | CommandType::Background(inner) => {
+            collect_simple(inner, found)
+        }
+        CommandType::Coproc(_, inner) => collect_simple(inner, found),
+        CommandType::For { body, .. } | CommandType::Select { body, .. } | CommandType::Group(body) => {
+            collect_simple(body, found)
+        }
+        // The body is checked when the function is called
+        CommandType::Function { .. } => {}
+        CommandType::While { condition, body, .. } => {
+            collect_simple(condition, found);
+            collect_simple(body, found);
+        }
+        CommandType::If { branches, else_body } => {
+            for (condition, body) in branches.iter() {
+                collect_simple(condition, found);
+                collect_simple(body, found);
+            }
+            if let Some(body) = else_body {
+                collect_simple(body, found);
+            }
+        }
+        CommandType::Conditional(_) | CommandType::Arithmetic(_) => {}
+    }
+}
+
+// The words of `for`/`select`, expanded and globbed when the loop starts;
+// without `in`, the positional parameters. None after an expansion error.
+fn loop_words(shell: &mut Shell, words: Option<&[&str]>) -> Option<Vec<String>> {
+    let Some(words) = words else {
+        return Some(shell.positional.clone());
+    };
+    let mut expanded = Vec::new();
+    for word in words {
+        expanded.extend(expand::expand_word(shell, word)?);
+    }
+    Some(expanded)
+}
+
+// Takes a `break`/`continue` that has reached a loop; true if the loop has
+// to stop, because it was a `break` or is meant for a loop further out
+fn leave_loop(shell: &mut Shell) -> bool {
+    match shell.control.take() {
+        None | Some(Control::Continue(1)) => false,
+        Some(Control::Return(code)) => {
+            shell.control = Some(Control::Return(code));
+            true
+        }
+        Some(Control::Break(1)) => true,
+        Some(Control::Break(n)) => {
+            shell.control = Some(Control::Break(n - 1));
+            true
+        }
+        Some(Control::Continue(n)) => {
+            shell.control = Some(Control::Continue(n - 1));
+            true
+        }
+    }
+}
+
+// `set -e`: a command failing outside a condition ends the shell, saying
+// where when it is running a script. `break`/`return` on their way out are
+// not failures.
+fn errexit(shell: &mut Shell, status: i32) -> i32 {
+    if status != 0 && shell.config.options.errexit && shell.condition_depth == 0 && shell.control.is_none() {
+        if shell.lineno > 0 {
+            eprintln!("{}: line {}: exiting with status {} (set -e)", shell.arg0, shell.lineno, status);
+        }
+        shell.exit(status);
+    }
+    status
+}
+
+// `set -x`: the command as it will run, after $PS4 ("+ " if unset)
+fn trace(shell: &Shell, command: &Command) {
+    let mut words: Vec<String> = command.env_overrides.iter()
+        .map(|(name, value)| format!("{}={}", name, shell_quote(value)))
+        .collect();
+    if !command.program.is_empty() {
+        words.extend(std::iter::once(&command.program).chain(&command.args).map(|word| shell_quote(word)));
+    }
+    let ps4 = shell.environment.get("PS4").map_or("+ ", String::as_str);
+    eprintln!("{}{}", ps4, words.join(" "));
+}
+
+// Unless INT is trapped: then its action runs and the loop goes on
+fn interrupted(shell: &Shell, status: i32) -> bool {
+    if shell.traps.contains_key(&libc::SIGINT) {
+        return false;
+    }
+    status == 128 + libc::SIGINT || signal_handler::take_interrupt()
+}
+
+// Deeper calls are refused rather than overflowing the stack
+const MAX_FUNCTION_DEPTH: usize = 200;
+
+// Coproc fds are kept at or above this, out of the way of redirections
+const COPROC_MIN_FD: i32 = 10;
+
+// Moves `fd` to COPROC_MIN_FD or above, closed on exec so commands only
+// get it through an explicit redirection
+fn high_fd(fd: i32) -> i32 {
+    let moved = unsafe { libc::fcntl(fd, libc::F_DUPFD_CLOEXEC, COPROC_MIN_FD) };
+    if moved < 0 {
+        unsafe { libc::fcntl(fd, libc::F_SETFD, libc::FD_CLOEXEC) };
+        return fd;
+    }
+    close(fd).ok();
+    moved
+}
+
+// Where an external command runs from
+enum Program {
+    // Looked up in the command cache before fork, so `hash` lists it
+    Found(PathBuf),
+    // Left to execvp in the child: paths, and the empty program; functions
+    // and builtins in a pipeline never get that far
+    Search,
+    NotFound,
+}
+
+impl Program {
+    fn path(&self) -> Option<&Path> {
+        match self {
+            Program::Found(path) => Some(path),
+            _ => None,
+        }
+    }
+}
+
+fn resolve(shell: &Shell, command: &Command) -> Program {
+    let program = command.program.as_str();
+    if program.is_empty() || program.contains('/') || shell.command_cache.is_builtin(program)
+        || shell.functions.contains_key(program)
+    {
+        return Program::Search;
+    }
+    match shell.command_cache.find_command(program) {
+        Some(path) => Program::Found(path),
+        None => Program::NotFound,
+    }
+}
+
+// What a forked child needs to become `command`, made ready before fork:
+// the child of a multithreaded process must not allocate, so all it does
+// is dup2 the redirections into place and exec
+struct Launch {
+    argv: Argv,
+    program: Program,
+    // Only when `NAME=value` prefixes make it differ from the shell's
+    env: Option<Argv>,
+    redirects: ChildRedirects,
+}
+
+impl Launch {
+    fn prepare(shell: &mut Shell, command: &Command) -> Result<Self, String> {
+        let argv = Argv::build(&command.program, &command.args)
+            .map_err(|e| format!("{}: {}", command.program, e))?;
+        let redirects = match ChildRedirects::open(shell, &command.redirects) {
+            Ok(redirects) => redirects,
+            Err(e) => {
+                argv.recycle();
+                return Err(format!("rshell: {}", e));
+            }
+        };
+        if command.env_overrides.is_empty() {
+            let program = resolve(shell, command);
+            return Ok(Launch { argv, program, env: None, redirects });
+        }
+        // The prefixes are set just long enough to look the program up,
+        // maybe along a PATH of its own, and to copy the environment
+        let saved = override_env(shell, &command.env_overrides);
+        let program = resolve(shell, command);
+        let env = Argv::environment();
+        restore_env(shell, saved);
+        Ok(Launch { argv, program, env: Some(env), redirects })
+    }
+
+    fn exec_args(&self) -> ExecArgs<'_> {
+        self.argv.exec_args(self.program.path(), self.env.as_ref())
+    }
+
+    // Gives the buffers back; the redirections' files close as they drop
+    fn recycle(self) {
+        self.argv.recycle();
+        if let Some(env) = self.env {
+            env.recycle();
+        }
+    }
+}
+
+// Applies `NAME=value` prefixes, exported for the one command, and returns
+// the values they replaced and whether those were exported. A readonly
+// variable keeps its value and is left out.
+fn override_env(shell: &mut Shell, overrides: &[(String, String)]) -> Vec<(String, Option<String>, bool)> {
+    overrides.iter()
+        .filter_map(|(name, value)| {
+            let previous = (name.clone(), shell.environment.get(name.as_str()).cloned(), shell.is_exported(name));
+            shell.set_var(name, value.clone()).then(|| {
+                shell.set_exported(name, true);
+                previous
+            })
+        })
+        .collect()
+}
+
+fn restore_env(shell: &mut Shell, saved: Vec<(String, Option<String>, bool)>) {
+    for (name, previous, exported) in saved.into_iter().rev() {
+        match previous {
+            Some(value) => shell.set_var(&name, value),
+            None => shell.unset_var(&name),
+        };
+        if !exported {
+            shell.set_exported(&name, false);
+        }
+    }
+}
+
+// Builtins run in the shell process itself, so their redirections point
+// this process's fds at the files for the length of the call
+fn execute_builtin(shell: &mut Shell, command: &Command) -> i32 {
+    // `exec` with only redirections applies them to the shell for good
+    if command.program == "exec" && command.args.is_empty() {
+        use std::io::Write;
+        let _ = std::io::stdout().flush();
+        return match apply_redirections(shell, &command.redirects) {
+            Ok(()) => 0,
+            Err(e) => {
+                eprintln!("rshell: {}", e);
+                1
+            }
+        };
+    }
+    let redirected = match SavedFds::redirect(shell, &command.redirects) {
+        Ok(saved) => saved,
+        Err(e) => {
+            eprintln!("rshell: {}", e);
+            return 1;
+        }
+    };
+    let saved = override_env(shell, &command.env_overrides);
+    let exit_code = builtins::execute_builtin(shell, &command.program, &command.args).unwrap_or(127);
+    restore_env(shell, saved);
+    drop(redirected);
+    exit_code
+}
+
+// Copies of the fds a builtin's redirections replace, put back on drop.
+// None for an fd that was closed to begin with.
+struct SavedFds(Vec<(i32, Option<i32>)>);
+
+impl SavedFds {
+    fn redirect(shell: &Shell, redirects: &[Redirection]) -> Result<SavedFds, String> {
+        use std::io::Write;
+        let _ = std::io::stdout().flush();
+        let mut saved = SavedFds(Vec::new());
+        for fd in redirects.iter().map(|redirection| redirection.fd) {
+            if !saved.0.iter().any(|&(saved_fd, _)| saved_fd == fd) {
+                // Above 10, where the redirections themselves won't land
+                let copy = unsafe { libc::fcntl(fd, libc::F_DUPFD_CLOEXEC, 10) };
+                saved.0.push((fd, (copy >= 0).then_some(copy)));
+            }
+        }
+        apply_redirections(shell, redirects)?;
+        Ok(saved)
+    }
+}
+
+impl Drop for SavedFds {
+    fn drop(&mut self) {
+        use std::io::Write;
+        let _ = std::io::stdout().flush();
+        let _ = std::io::stderr().flush();
+        for (fd, copy) in self.0.drain(..) {
+            match copy {
+                Some(copy) => {
+                    dup2(copy, fd).ok();
+                    close(copy).ok();
                 }
-                Ok(WaitStatus::Signaled(_, sig, _)) => {
-                    println!("[Done] {} terminated by signal {}", pid, sig);
-                    completed.push(pid);
-                    
-                    if let Some(job) = shell.jobs.iter_mut().find(|j| j.pid == pid) {
-                        job.status = JobStatus::Done;
+                None => {
+                    close(fd).ok();
+                }
+            }
+        }
+    }
+}
+
+// A command's redirections, their files opened by the shell before fork
+// on fds above any the redirections name, closed on exec. In the child they
+// are moved into place in order.
+struct ChildRedirects(Vec<(i32, ChildFd)>);
+
+#[derive(Clone, Copy)]
+enum ChildFd {
+    // A file opened for the child; the shell closes its copy
+    Opened(i32),
+    Dup(i32),
+    Close,
+}
+
+impl ChildRedirects {
+    fn open(shell: &Shell, redirects: &[Redirection]) -> Result<Self, String> {
+        let lowest = redirects.iter().map(|redirection| redirection.fd + 1).fold(10, i32::max);
+        let mut prepared = ChildRedirects(Vec::with_capacity(redirects.len()));
+        for redirection in redirects {
+            let target = match &redirection.target {
+                RedirectTarget::Dup(source) => ChildFd::Dup(*source),
+                RedirectTarget::Close => ChildFd::Close,
+                RedirectTarget::DupWord(word) => return Err(format!("{}: bad file descriptor", word)),
+                target => {
+                    let file = open_file(shell, target)?;
+                    let fd = unsafe { libc::fcntl(file.as_raw_fd(), libc::F_DUPFD_CLOEXEC, lowest) };
+                    if fd < 0 {
+                        return Err(format!("{}: {}", redirection.fd, std::io::Error::last_os_error()));
                     }
+                    ChildFd::Opened(fd)
+                }
+            };
+            prepared.0.push((redirection.fd, target));
+        }
+        Ok(prepared)
+    }
+
+    // In the child. A failure is reported without allocating, and the
+    // child exits 1.
+    fn apply(&self) {
+        for &(fd, target) in &self.0 {
+            let source = match target {
+                ChildFd::Opened(source) | ChildFd::Dup(source) => source,
+                ChildFd::Close => {
+                    unsafe { libc::close(fd) };
+                    continue;
                 }
-                _ => {}
+            };
+            if unsafe { libc::dup2(source, fd) } < 0 {
+                child_failed(source, Errno::last());
             }
         }
-        
-        // Remove completed jobs
-        self.background_jobs.retain(|pid| !completed.contains(pid));
     }
 }
+
+impl Drop for ChildRedirects {
+    fn drop(&mut self) {
+        for &(_, target) in &self.0 {
+            if let ChildFd::Opened(fd) = target {
+                close(fd).ok();
+            }
+        }
+    }
+}
+
+// `rshell: FD: reason` and exit, for a forked child that cannot allocate
+fn child_failed(fd: i32, error: Errno) -> ! {
+    let mut digits = [0u8; 10];
+    let mut start = digits.len();
+    let mut n = fd.unsigned_abs();
+    loop {
+        start -= 1;
+        digits[start] = b'0' + (n % 10) as u8;
+        n /= 10;
+        if n == 0 {
+            break;
+        }
+    }
+    for part in [b"rshell: ".as_slice(), &digits[start..], b": ", error.desc().as_bytes(), b"\n"] {
+        unsafe {
+            libc::write(libc::STDERR_FILENO, part.as_ptr().cast(), part.len());
+        }
+    }
+    unsafe { libc::_exit(1) }
+}
+
+fn apply_redirections(shell: &Shell, redirects: &[Redirection]) -> Result<(), String> {
+    for redirection in redirects {
+        let fd = redirection.fd;
+        let opened = match &redirection.target {
+            RedirectTarget::Dup(source) => {
+                dup2(*source, fd).map_err(|e| format!("{}: {}", source, e))?;
+                continue;
+            }
+            // Replaced when the command was expanded
+            RedirectTarget::DupWord(word) => return Err(format!("{}: bad file descriptor", word)),
+            RedirectTarget::Close => {
+                close(fd).ok();
+                continue;
+            }
+            target => open_file(shell, target)?,
+        };
+        // The file may already have landed on `fd`; then it must stay open,
+        // and across exec too
+        if opened.as_raw_fd() == fd {
+            let fd = opened.into_raw_fd();
+            unsafe { libc::fcntl(fd, libc::F_SETFD, 0) };
+        } else {
+            dup2(opened.as_raw_fd(), fd).map_err(|e| format!("{}: {}", fd, e))?;
+        }
+    }
+    Ok(())
+}
+
+// Opens the file a `<`, `>`, `>>` or here-document redirection reads or
+// writes
+fn open_file(shell: &Shell, target: &RedirectTarget) -> Result<File, String> {
+    match target {
+        RedirectTarget::Read(path) => File::open(path).map_err(|e| format!("{}: {}", path, e)),
+        RedirectTarget::Write(path) => File::create(path).map_err(|e| format!("{}: {}", path, e)),
+        RedirectTarget::Append(path) => OpenOptions::new().create(true).append(true).open(path)
+            .map_err(|e| format!("{}: {}", path, e)),
+        RedirectTarget::HereDoc(here_doc) => here_doc_file(shell, here_doc)
+            .map_err(|e| format!("here-document: {}", e)),
+        RedirectTarget::Dup(_) | RedirectTarget::DupWord(_) | RedirectTarget::Close => {
+            unreachable!("{:?} names no file", target)
+        }
+    }
+}
+
+// The body goes into an already-unlinked temporary file, as sh does, so it
+// can be any size and needs no writer process
+fn here_doc_file(shell: &Shell, here_doc: &HereDoc) -> std::io::Result<File> {
+    use std::io::{Seek, SeekFrom, Write};
+    use std::os::unix::fs::OpenOptionsExt;
+    let body = if here_doc.expand {
+        Cow::Owned(expand_variables(&here_doc.body, &shell.environment))
+    } else {
+        Cow::Borrowed(&here_doc.body)
+    };
+    let path = std::env::temp_dir().join(format!("rshell-heredoc-{}", std::process::id()));
+    let _ = std::fs::remove_file(&path);
+    let mut file = OpenOptions::new().read(true).write(true).create_new(true).mode(0o600).open(&path)?;
+    let _ = std::fs::remove_file(&path);
+    file.write_all(body.as_bytes())?;
+    file.seek(SeekFrom::Start(0))?;
+    Ok(file)
+}
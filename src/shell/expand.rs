@@ -0,0 +1,672 @@
+// shell/expand.rs
+//
+// Word expansion, done by the executor right before a command runs. The
+// parser keeps each word as typed, and its quoting decides what happens to
+// each part: nothing inside '...'; variables and command substitution, but
+// no field splitting or globbing, inside "..."; all of it outside quotes.
+use crate::command::{Command, RedirectTarget, Redirection, SimpleCommand};
+use crate::memory_pool::{args_pool, word_pool};
+use crate::performance::wait_with_rusage;
+use crate::shell::executor::Executor;
+use crate::shell::globbing;
+use crate::shell::arithmetic;
+use crate::shell::parser::{arithmetic_len, quoted_len, Parser};
+use crate::shell::Shell;
+use crate::utils::env_utils::{apply_parameter, parse_parameter, ParamOp, ParamValue};
+use crate::utils::helpers::is_valid_var_name;
+use bumpalo::Bump;
+use nix::sys::wait::WaitStatus;
+use nix::unistd::{close, dup2, fork, pipe, ForkResult};
+use std::fs::File;
+use std::io::{Read, Write};
+use std::os::unix::io::FromRawFd;
+
+// Whether a word can change when expanded at all
+pub fn needs_expansion(word: &str) -> bool {
+    word.starts_with('~') || word.contains(['\'', '"', '\\', '$', '`']) || globbing::has_pattern_chars(word)
+}
+
+// One resulting word, with the glob pattern it stands for alongside: quoted
+// characters are escaped in the pattern so they only match themselves
+#[derive(Default)]
+struct Field {
+    text: String,
+    pattern: String,
+    glob: bool,
+    // `""` is a word even though it is empty, as is the field before a
+    // non-whitespace IFS character, like the middle one in `a::b`
+    quoted: bool,
+    // Quoted text is escaped for a regex rather than a glob, for `=~`
+    regex: bool,
+}
+
+impl Field {
+    fn push(&mut self, text: &str, quoted: bool) {
+        self.text.push_str(text);
+        if quoted {
+            let escaped = if self.regex { escape_regex(text) } else { globbing::escape(text) };
+            self.pattern.push_str(&escaped);
+            self.quoted = true;
+        } else {
+            self.pattern.push_str(text);
+            self.glob |= globbing::has_pattern_chars(text);
+        }
+    }
+}
+
+struct Expansion<'s> {
+    shell: &'s mut Shell,
+    // Off for assignment values and redirection targets, which stay one word
+    split: bool,
+    fields: Vec<Field>,
+    current: Field,
+    // An error was reported, so the command must not run
+    failed: bool,
+}
+
+impl Expansion<'_> {
+    fn new(shell: &mut Shell, split: bool) -> Expansion<'_> {
+        Expansion { shell, split, fields: Vec::new(), current: Field::default(), failed: false }
+    }
+
+    fn word(&mut self, word: &str) {
+        let mut i = if word.starts_with('~') { self.tilde(word) } else { 0 };
+        while i < word.len() {
+            let rest = &word[i..];
+            let Some(len) = quoted_len(rest) else {
+                self.current.push(rest, true);
+                break;
+            };
+            match rest.as_bytes()[0] {
+                b'\'' => self.current.push(&rest[1..len - 1], true),
+                b'"' => self.double_quoted(&rest[1..len - 1]),
+                // A trailing backslash stands for itself
+                b'\\' if len == 1 => self.current.push("\\", true),
+                b'\\' => self.current.push(&rest[1..len], true),
+                b'$' | b'`' => {
+                    let len = dollar_len(rest, len);
+                    self.substitution(&rest[..len], false);
+                    i += len;
+                    continue;
+                }
+                _ => {
+                    let end = rest.find(['\'', '"', '\\', '$', '`']).unwrap_or(rest.len());
+                    self.current.push(&rest[..end], false);
+                    i += end;
+                    continue;
+                }
+            }
+            i += len;
+        }
+    }
+
+    // The inside of "...": backslash only escapes $ ` " \ and newline
+    fn double_quoted(&mut self, inner: &str) {
+        self.current.push("", true);
+        let mut i = 0;
+        while i < inner.len() {
+            let rest = &inner[i..];
+            match rest.as_bytes()[0] {
+                b'\\' => match rest[1..].chars().next() {
+                    Some(ch @ ('$' | '`' | '"' | '\\')) => {
+                        self.current.push(ch.encode_utf8(&mut [0; 4]), true);
+                        i += 2;
+                    }
+                    Some('\n') => i += 2,
+                    _ => {
+                        self.current.push("\\", true);
+                        i += 1;
+                    }
+                },
+                b'$' | b'`' => {
+                    let len = dollar_len(rest, quoted_len(rest).unwrap_or(rest.len()));
+                    self.substitution(&rest[..len], true);
+                    i += len;
+                }
+                _ => {
+                    let end = rest.find(['\\', '$', '`']).unwrap_or(rest.len());
+                    self.current.push(&rest[..end], true);
+                    i += end;
+                }
+            }
+        }
+    }
+
+    // `$NAME`, `${NAME}`, `$(cmd)`, `` `cmd` `` or `$((expr))`
+    fn substitution(&mut self, text: &str, quoted: bool) {
+        if matches!(text, "$@" | "${@}" | "$*" | "${*}") {
+            self.all_positional(text.contains('*'), quoted);
+            return;
+        }
+        let arithmetic = text.strip_prefix('$').filter(|t| arithmetic_len(t) == Some(t.len()));
+        let value = if let Some(expr) = arithmetic {
+            match arithmetic::expand(self.shell, &expr[2..expr.len() - 2]) {
+                Some(value) => value.to_string(),
+                None => {
+                    self.failed = true;
+                    return;
+                }
+            }
+        } else if let Some(command) = text.strip_prefix("$(").and_then(|t| t.strip_suffix(')')) {
+            command_substitution(self.shell, command)
+        } else if let Some(command) = text.strip_prefix('`').and_then(|t| t.strip_suffix('`')) {
+            command_substitution(self.shell, &unescape_backticks(command))
+        } else if let Some(expr) = text.strip_prefix("${").and_then(|t| t.strip_suffix('}')) {
+            match self.parameter(expr) {
+                Some(value) => value,
+                None => return,
+            }
+        } else if text.len() > 1 {
+            match self.lookup(&text[1..]) {
+                Some(value) => value,
+                None if self.unbound(&text[1..]) => return,
+                None => String::new(),
+            }
+        } else {
+            // A `$` that starts nothing is literal
+            self.current.push(text, quoted);
+            return;
+        };
+        self.result(&value, quoted);
+    }
+
+    // `$@` and `$*`. Quoted, `$@` is one word per parameter (none at all if
+    // there are none) and `$*` a single word; unquoted, each parameter is
+    // split and globbed on its own.
+    fn all_positional(&mut self, star: bool, quoted: bool) {
+        if (star && quoted) || !self.split {
+            let value = self.lookup(if star { "*" } else { "@" }).unwrap_or_default();
+            self.result(&value, quoted);
+            return;
+        }
+        let params = self.shell.positional.clone();
+        if params.is_empty() && self.current.text.is_empty() {
+            self.current.quoted = false;
+        }
+        for (i, param) in params.iter().enumerate() {
+            if i > 0 {
+                self.end_field();
+            }
+            self.result(param, quoted);
+        }
+    }
+
+    // With `set -u`, reports `name` as an error and returns true
+    fn unbound(&mut self, name: &str) -> bool {
+        if !self.shell.config.options.nounset {
+            return false;
+        }
+        let message = self.shell.unbound_variable(name);
+        eprintln!("rshell: {}", message);
+        self.failed = true;
+        true
+    }
+
+    // A parameter's value; None when it is unset
+    fn lookup(&self, name: &str) -> Option<String> {
+        let shell = &*self.shell;
+        match name {
+            "?" => Some(shell.last_exit_code.to_string()),
+            "$" => Some(shell.pid.to_string()),
+            "!" => shell.last_background_pid.map(|pid| pid.to_string()),
+            "#" => Some(shell.positional.len().to_string()),
+            "@" => Some(shell.positional.join(" ")),
+            // Joined by the first character of IFS: none if it is empty
+            "*" => {
+                let ifs = shell.environment.get("IFS").map_or(" ", String::as_str);
+                Some(shell.positional.join(ifs.get(..1).unwrap_or("")))
+            }
+            "0" => Some(shell.arg0.clone()),
+            "LINENO" => Some(shell.lineno.to_string()),
+            // The `set` flags that are on
+            "-" => {
+                let options = &shell.config.options;
+                let flags = [('e', options.errexit), ('u', options.nounset), ('x', options.xtrace)];
+                Some(flags.iter().filter(|(_, on)| *on).map(|(flag, _)| flag).collect())
+            }
+            _ if name.bytes().all(|b| b.is_ascii_digit()) => {
+                let n: usize = name.parse().ok()?;
+                shell.positional.get(n.checked_sub(1)?).cloned()
+            }
+            _ => shell.environment.get(name).cloned(),
+        }
+    }
+
+    // `${...}`, with its operators; None after reporting an error
+    fn parameter(&mut self, expr: &str) -> Option<String> {
+        let Some((name, op)) = parse_parameter(expr) else {
+            eprintln!("rshell: ${{{}}}: bad substitution", expr);
+            self.failed = true;
+            return None;
+        };
+        let value = self.lookup(name);
+        let has_default = matches!(op, ParamOp::Default { .. } | ParamOp::Assign { .. }
+            | ParamOp::Alternate { .. } | ParamOp::Error { .. });
+        if value.is_none() && !has_default && self.unbound(name) {
+            return None;
+        }
+        // Operands get tilde, parameter and command expansion, as one word
+        let shell = &mut *self.shell;
+        let expand_operand = |word: &str| {
+            let mut expansion = Expansion::new(shell, false);
+            expansion.word(word);
+            expansion.finish().unwrap_or_default().concat()
+        };
+        match apply_parameter(name, value.as_deref(), op, expand_operand) {
+            ParamValue::Value(value) => Some(value),
+            ParamValue::Assign(value) if is_valid_var_name(name) => {
+                self.shell.set_var(name, value.clone());
+                Some(value)
+            }
+            ParamValue::Assign(_) => {
+                eprintln!("rshell: ${}: cannot assign in this way", name);
+                self.failed = true;
+                None
+            }
+            ParamValue::Error(message) => {
+                let message = self.shell.expansion_error(message);
+                eprintln!("rshell: {}", message);
+                self.failed = true;
+                None
+            }
+        }
+    }
+
+    // Unquoted results are split into words at the characters in $IFS
+    // (space, tab and newline if unset, no splitting if empty), and globbed.
+    // Runs of IFS whitespace are one delimiter and never make empty fields;
+    // each other IFS character, with any whitespace around it, ends a field.
+    fn result(&mut self, value: &str, quoted: bool) {
+        let ifs = self.shell.environment.get("IFS").map_or(" \t\n", String::as_str);
+        if quoted || !self.split || ifs.is_empty() {
+            self.current.push(value, quoted);
+            return;
+        }
+        let ifs = ifs.to_string();
+        let is_space = |ch: char| matches!(ch, ' ' | '\t' | '\n') && ifs.contains(ch);
+        let mut start = 0;
+        let mut chars = value.char_indices().peekable();
+        while let Some((i, ch)) = chars.next() {
+            if !ifs.contains(ch) {
+                continue;
+            }
+            self.current.push(&value[start..i], false);
+            let mut hard = !is_space(ch);
+            while let Some(&(_, next)) = chars.peek() {
+                if !is_space(next) && (hard || !ifs.contains(next)) {
+                    break;
+                }
+                hard |= !is_space(next);
+                chars.next();
+            }
+            self.current.quoted |= hard;
+            self.end_field();
+            start = chars.peek().map_or(value.len(), |&(j, _)| j);
+        }
+        self.current.push(&value[start..], false);
+    }
+
+    // A leading `~` alone or before `/` is the home directory; returns how
+    // much of the word it used
+    fn tilde(&mut self, word: &str) -> usize {
+        if !matches!(word.as_bytes().get(1), None | Some(b'/')) {
+            return 0;
+        }
+        let home = match self.shell.environment.get("HOME") {
+            Some(home) => home.clone(),
+            None => match dirs::home_dir() {
+                Some(home) => home.to_string_lossy().into_owned(),
+                None => return 0,
+            },
+        };
+        self.current.push(&home, true);
+        1
+    }
+
+    fn end_field(&mut self) {
+        let field = std::mem::take(&mut self.current);
+        if !field.text.is_empty() || field.quoted {
+            self.fields.push(field);
+        }
+    }
+
+    // Globs each field with unquoted pattern characters. A pattern that
+    // matches nothing is kept as it was written, dropped with nullglob or an
+    // error with failglob. None if any error was reported.
+    fn finish(mut self) -> Option<Vec<String>> {
+        self.end_field();
+        let options = &self.shell.config.options;
+        let mut words = Vec::with_capacity(self.fields.len());
+        for field in self.fields {
+            if field.glob && self.split {
+                let matches = globbing::expand(&field.pattern, options);
+                if !matches.is_empty() {
+                    words.extend(matches);
+                    continue;
+                }
+                if options.failglob {
+                    eprintln!("rshell: no match: {}", field.text);
+                    return None;
+                }
+                if options.nullglob {
+                    continue;
+                }
+            }
+            words.push(field.text);
+        }
+        (!self.failed).then_some(words)
+    }
+}
+
+// `$` plus the name after it: quoted_len only covers `$(..)` and `${..}`.
+// Special and positional parameters are one character: `$12` is `${1}2`.
+fn dollar_len(text: &str, len: usize) -> usize {
+    if !text.starts_with('$') || len > 1 {
+        return len;
+    }
+    let name = match text.as_bytes().get(1) {
+        Some(b) if b.is_ascii_digit() || matches!(b, b'?' | b'$' | b'!' | b'#' | b'@' | b'*' | b'-') => 1,
+        _ => text[1..].bytes().take_while(|b| b.is_ascii_alphanumeric() || *b == b'_').count(),
+    };
+    1 + name
+}
+
+// Inside backticks a backslash only escapes $ ` and \
+fn unescape_backticks(command: &str) -> String {
+    let mut result = String::with_capacity(command.len());
+    let mut chars = command.chars().peekable();
+    while let Some(ch) = chars.next() {
+        match (ch, chars.peek()) {
+            ('\\', Some(&next @ ('$' | '`' | '\\'))) => {
+                result.push(next);
+                chars.next();
+            }
+            _ => result.push(ch),
+        }
+    }
+    result
+}
+
+// Output of `command` without its trailing newlines. It runs in a forked
+// copy of the shell, so aliases, builtins and variables all work inside it
+// and nothing it changes leaks out; its status becomes `$?`.
+fn command_substitution(shell: &mut Shell, command: &str) -> String {
+    if command.trim().is_empty() {
+        return String::new();
+    }
+    let arena = Bump::new();
+    let parsed = match Parser::with_aliases(command, &shell.aliases, &arena).and_then(|mut parser| parser.parse()) {
+        Ok(parsed) => parsed,
+        Err(e) => {
+            eprintln!("rshell: command substitution: {}", e.message);
+            shell.last_exit_code = 2;
+            return String::new();
+        }
+    };
+    let (read_fd, write_fd) = match pipe() {
+        Ok(fds) => fds,
+        Err(e) => {
+            eprintln!("rshell: command substitution: {}", e);
+            return String::new();
+        }
+    };
+    let _ = std::io::stdout().flush();
+    match unsafe { fork() } {
+        Ok(ForkResult::Child) => {
+            let _ = close(read_fd);
+            let _ = dup2(write_fd, 1);
+            let _ = close(write_fd);
+            crate::shell::trap::reset_for_subshell(shell);
+            let code = Executor::new().execute(shell, parsed);
+            let _ = std::io::stdout().flush();
+            std::process::exit(code);
+        }
+        Ok(ForkResult::Parent { child }) => {
+            let _ = close(write_fd);
+            let mut output = Vec::new();
+            let _ = unsafe { File::from_raw_fd(read_fd) }.read_to_end(&mut output);
+            shell.last_exit_code = match wait_with_rusage(child) {
+                Ok((status, usage)) => {
+                    shell.record_child_usage(&usage);
+                    match status {
+                        WaitStatus::Exited(_, code) => code,
+                        WaitStatus::Signaled(_, sig, _) => 128 + sig as i32,
+                        _ => 1,
+                    }
+                }
+                Err(_) => 1,
+            };
+            String::from_utf8_lossy(&output).trim_end_matches('\n').to_string()
+        }
+        Err(e) => {
+            let _ = close(read_fd);
+            let _ = close(write_fd);
+            eprintln!("rshell: command substitution: {}", e);
+            String::new()
+        }
+    }
+}
+
+// All the words `word` expands to; none for e.g. an unset, unquoted `$VAR`.
+// None if an error (like `${X:?}`) was reported.
+pub fn expand_word(shell: &mut Shell, word: &str) -> Option<Vec<String>> {
+    let mut expansion = Expansion::new(shell, true);
+    expansion.word(word);
+    expansion.finish()
+}
+
+// Expands `word` into exactly one word, without splitting or globbing
+pub fn expand_single(shell: &mut Shell, word: &str) -> Option<String> {
+    let mut expansion = Expansion::new(shell, false);
+    expansion.word(word);
+    expansion.finish().map(|words| words.concat())
+}
+
+// Expands `word` into one pattern, for the right side of `[[ x == y ]]`:
+// quoted parts are escaped so they match literally, for a glob or, with
+// `regex`, for an extended regular expression
+pub fn expand_pattern(shell: &mut Shell, word: &str, regex: bool) -> Option<String> {
+    let mut expansion = Expansion::new(shell, false);
+    expansion.current.regex = regex;
+    expansion.word(word);
+    (!expansion.failed).then_some(expansion.current.pattern)
+}
+
+fn escape_regex(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for ch in text.chars() {
+        if "\\.^$*+?()[]{}|".contains(ch) {
+            escaped.push('\\');
+        }
+        escaped.push(ch);
+    }
+    escaped
+}
+
+// Expands a parsed command into one ready to run: program and arguments,
+// assignment values and redirection targets. Here-document bodies expand
+// when written. None if an expansion error was reported and the command
+// must not run.
+pub fn expand_command(shell: &mut Shell, parsed: &SimpleCommand<'_>) -> Option<Command> {
+    let mut env_overrides = Vec::with_capacity(parsed.env_overrides.len());
+    for &(name, value) in parsed.env_overrides {
+        env_overrides.push((name.to_string(), expand_target(shell, value)?));
+    }
+    let mut redirects = Vec::with_capacity(parsed.redirects.len());
+    for redirection in parsed.redirects {
+        let target = match redirection.target {
+            RedirectTarget::Read(path) => RedirectTarget::Read(expand_target(shell, path)?),
+            RedirectTarget::Write(path) => RedirectTarget::Write(expand_target(shell, path)?),
+            RedirectTarget::Append(path) => RedirectTarget::Append(expand_target(shell, path)?),
+            RedirectTarget::DupWord(word) => {
+                let expanded = expand_single(shell, word)?;
+                match expanded.as_str() {
+                    "-" => RedirectTarget::Close,
+                    n => match n.parse::<i32>() {
+                        Ok(n) if n >= 0 => RedirectTarget::Dup(n),
+                        _ => {
+                            eprintln!("rshell: {}: bad file descriptor", expanded);
+                            return None;
+                        }
+                    },
+                }
+            }
+            _ => redirection.owned().target,
+        };
+        redirects.push(Redirection::new(redirection.fd, target));
+    }
+    let mut args = args_pool().acquire();
+    let program = if !needs_expansion(parsed.program) && !parsed.args.iter().any(|arg| needs_expansion(arg)) {
+        args.extend(parsed.args.iter().map(|arg| pooled(arg)));
+        pooled(parsed.program)
+    } else {
+        let mut words = expand_word(shell, parsed.program)?;
+        for arg in parsed.args {
+            words.extend(expand_word(shell, arg)?);
+        }
+        let mut words = words.into_iter();
+        // An empty program is left, like `A=1`, if every word expanded to nothing
+        let program = words.next().unwrap_or_default();
+        args.extend(words);
+        program
+    };
+    Some(Command { program, args, redirects, env_overrides })
+}
+
+// An assignment value or file name: one word, never split or globbed
+fn expand_target(shell: &mut Shell, word: &str) -> Option<String> {
+    if needs_expansion(word) {
+        expand_single(shell, word)
+    } else {
+        Some(pooled(word))
+    }
+}
+
+// A word copied into a pooled String, to go back to the pool with the command
+fn pooled(word: &str) -> String {
+    let mut owned = word_pool().acquire();
+    owned.push_str(word);
+    owned
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+
+    #[test]
+    fn quoting_controls_each_expansion() {
+        let mut shell = Shell::with_config(Config::default());
+        shell.set_var("X", "a  b".to_string());
+        let mut expand = |word: &str| expand_word(&mut shell, word).unwrap();
+        assert_eq!(expand("'$X'"), ["$X"]);
+        assert_eq!(expand("\"$X\""), ["a  b"]);
+        assert_eq!(expand("$X"), ["a", "b"]);
+        assert_eq!(expand("pre${X}post"), ["prea", "bpost"]);
+        assert_eq!(expand("\"\\$X \\q\""), ["$X \\q"]);
+        assert_eq!(expand("$UNSET_RSHELL_VAR"), Vec::<String>::new());
+        assert_eq!(expand("\"\"''"), [""]);
+        assert_eq!(expand("\"$(printf 'x  y\n\n')\""), ["x  y"]);
+        assert_eq!(expand("$(echo a; echo b)"), ["a", "b"]);
+        assert_eq!(expand("`printf \\$X`"), ["a"]);
+        assert_eq!(expand("$(X=in; printf $X; cd /)"), ["in"]);
+        assert_eq!(expand("$PWD"), [std::env::var("PWD").unwrap_or_default()]);
+        assert_eq!(expand("'/*'"), ["/*"]);
+        assert_eq!(expand("/\"*\""), ["/*"]);
+        assert!(expand("/*").len() > 1);
+        assert_eq!(expand_single(&mut shell, "$X*").unwrap(), "a  b*");
+    }
+
+    #[test]
+    fn splitting_follows_ifs() {
+        let mut shell = Shell::with_config(Config::default());
+        // Only in the shell's own table: IFS in the process environment
+        // would reach shells made by tests running alongside
+        let mut split = |ifs: Option<&str>, value: &str, word: &str| {
+            match ifs {
+                Some(ifs) => shell.environment.insert("IFS".into(), ifs.to_string()),
+                None => shell.environment.remove("IFS"),
+            };
+            shell.environment.insert("X".into(), value.to_string());
+            expand_word(&mut shell, word).unwrap()
+        };
+        assert_eq!(split(None, " \t a  b\n", "$X"), ["a", "b"]);
+        assert_eq!(split(Some(":"), "a::b:", "$X"), ["a", "", "b"]);
+        assert_eq!(split(Some(":"), ":a b", "pre$X"), ["pre", "a b"]);
+        assert_eq!(split(Some(": "), " a : b  c:", "$X"), ["a", "b", "c"]);
+        assert_eq!(split(Some(": "), "a :: b", "$X"), ["a", "", "b"]);
+        assert_eq!(split(Some(""), "a b", "$X"), ["a b"]);
+        assert_eq!(split(Some(":"), "a:b", "\"$X\""), ["a:b"]);
+    }
+
+    #[test]
+    fn unmatched_patterns() {
+        let mut shell = Shell::with_config(Config::default());
+        let pattern = "/nonexistent-rshell-dir/*.rs";
+        assert_eq!(expand_word(&mut shell, pattern).unwrap(), [pattern]);
+        shell.config.options.nullglob = true;
+        assert!(expand_word(&mut shell, pattern).unwrap().is_empty());
+        assert_eq!(expand_word(&mut shell, "'*'.rs").unwrap(), ["*.rs"]);
+        shell.config.options.failglob = true;
+        assert!(expand_word(&mut shell, pattern).is_none());
+    }
+
+    #[test]
+    fn special_parameters() {
+        let mut shell = Shell::with_config(Config::default());
+        shell.last_exit_code = 3;
+        shell.positional = vec!["one".to_string(), "two".to_string()];
+        shell.arg0 = "script.rsh".to_string();
+        let mut expand = |word: &str| expand_single(&mut shell, word).unwrap();
+        assert_eq!(expand("$?:$#:$0:$1:${2}:$3:$!"), "3:2:script.rsh:one:two::");
+        assert_eq!(expand("$$"), std::process::id().to_string());
+        assert_eq!(expand("$12"), "one2");
+    }
+
+    #[test]
+    fn all_positional_parameters() {
+        let mut shell = Shell::with_config(Config::default());
+        shell.positional = vec!["a b".to_string(), String::new(), "c".to_string()];
+        assert_eq!(expand_word(&mut shell, "\"$@\"").unwrap(), ["a b", "", "c"]);
+        assert_eq!(expand_word(&mut shell, "\"x$@y\"").unwrap(), ["xa b", "", "cy"]);
+        assert_eq!(expand_word(&mut shell, "$@").unwrap(), ["a", "b", "c"]);
+        assert_eq!(expand_word(&mut shell, "\"$*\"").unwrap(), ["a b  c"]);
+        assert_eq!(expand_word(&mut shell, "${*}").unwrap(), ["a", "b", "c"]);
+        assert_eq!(expand_single(&mut shell, "$@").unwrap(), "a b  c");
+        shell.positional.clear();
+        assert!(expand_word(&mut shell, "\"$@\"").unwrap().is_empty());
+        assert_eq!(expand_word(&mut shell, "\"$*\"").unwrap(), [""]);
+    }
+
+    #[test]
+    fn nounset_makes_unset_variables_errors() {
+        let mut shell = Shell::with_config(Config::default());
+        shell.interactive = true;
+        shell.unset_var("RSHELL_T");
+        shell.config.options.nounset = true;
+        shell.config.options.xtrace = true;
+        assert_eq!(expand_single(&mut shell, "$-"), Some("ux".to_string()));
+        assert_eq!(expand_single(&mut shell, "$RSHELL_T"), None);
+        assert_eq!(expand_single(&mut shell, "${#RSHELL_T}"), None);
+        assert_eq!(expand_single(&mut shell, "$1"), None);
+        assert_eq!(expand_single(&mut shell, "${RSHELL_T-x}${RSHELL_T:+y}"), Some("x".to_string()));
+        assert_eq!(expand_word(&mut shell, "\"$@\"").unwrap().len(), 0);
+        shell.config.options.nounset = false;
+        assert_eq!(expand_single(&mut shell, "$RSHELL_T"), Some(String::new()));
+    }
+
+    #[test]
+    fn parameter_operators_see_shell_state() {
+        let mut shell = Shell::with_config(Config::default());
+        shell.last_exit_code = 7;
+        shell.unset_var("RSHELL_T");
+        assert_eq!(expand_word(&mut shell, "\"${RSHELL_T:-a  b}\"").unwrap(), ["a  b"]);
+        assert_eq!(expand_word(&mut shell, "${RSHELL_T:-~/a b}${#?}").unwrap().len(), 2);
+        assert_eq!(expand_single(&mut shell, "${RSHELL_T:=$?x}").unwrap(), "7x");
+        assert_eq!(shell.environment.get("RSHELL_T").map(String::as_str), Some("7x"));
+        shell.interactive = true;
+        assert_eq!(expand_single(&mut shell, "${RSHELL_U:?}"), None);
+        assert_eq!(expand_single(&mut shell, "${1:=x}"), None);
+        shell.unset_var("RSHELL_T");
+    }
+}
@@ -0,0 +1,125 @@
+// shell/getopts.rs
+//
+// POSIX `getopts OPTSTRING NAME [ARG...]`, for `while getopts` loops over
+// $1.. (or the ARGs). Each call puts the next option letter in NAME and its
+// argument in OPTARG, and leaves OPTIND at the next word to look at. Where
+// it is inside a group like `-abc` is kept on the shell, and starts over
+// whenever OPTIND is changed from outside.
+use crate::shell::Shell;
+use crate::utils::helpers::is_valid_var_name;
+
+pub fn builtin_getopts(shell: &mut Shell, args: &[String]) -> i32 {
+    let [optstring, name, rest @ ..] = args else {
+        eprintln!("getopts: usage: getopts optstring name [arg ...]");
+        return 2;
+    };
+    if !is_valid_var_name(name) {
+        eprintln!("getopts: `{}': not a valid identifier", name);
+        return 2;
+    }
+    let words = if rest.is_empty() { shell.positional.clone() } else { rest.to_vec() };
+    // A leading `:` reports errors through NAME and OPTARG instead of stderr
+    let (silent, optstring) = match optstring.strip_prefix(':') {
+        Some(optstring) => (true, optstring),
+        None => (false, optstring.as_str()),
+    };
+
+    let mut optind = shell.environment.get("OPTIND").and_then(|i| i.parse::<usize>().ok()).unwrap_or(1).max(1);
+    let mut pos = match shell.getopts_position {
+        (index, pos) if index == optind => pos,
+        _ => 1,
+    };
+    let word = words.get(optind - 1).map(String::as_str);
+    let option = match word {
+        Some("--") if pos == 1 => {
+            optind += 1;
+            None
+        }
+        Some(word) if pos > 1 || (word.starts_with('-') && word.len() > 1) => word[pos..].chars().next(),
+        _ => None,
+    };
+    let Some(option) = option else {
+        finish(shell, name, "?", None, optind, 1);
+        return 1;
+    };
+    let word = word.unwrap_or_default();
+    pos += option.len_utf8();
+    let at_end = pos >= word.len();
+
+    let spec = optstring.find(option).filter(|_| option != ':');
+    let (letter, optarg) = match spec {
+        None => {
+            if !silent {
+                eprintln!("{}: illegal option -- {}", shell.arg0, option);
+            }
+            ("?".to_string(), silent.then(|| option.to_string()))
+        }
+        Some(i) if optstring[i + option.len_utf8()..].starts_with(':') => {
+            if !at_end {
+                // `-ofile`
+                let value = word[pos..].to_string();
+                pos = word.len();
+                (option.to_string(), Some(value))
+            } else if let Some(value) = words.get(optind) {
+                // `-o file`
+                optind += 1;
+                (option.to_string(), Some(value.clone()))
+            } else if silent {
+                (":".to_string(), Some(option.to_string()))
+            } else {
+                eprintln!("{}: option requires an argument -- {}", shell.arg0, option);
+                ("?".to_string(), None)
+            }
+        }
+        Some(_) => (option.to_string(), None),
+    };
+    if pos >= word.len() {
+        optind += 1;
+        pos = 1;
+    }
+    finish(shell, name, &letter, optarg, optind, pos);
+    0
+}
+
+fn finish(shell: &mut Shell, name: &str, letter: &str, optarg: Option<String>, optind: usize, pos: usize) {
+    shell.set_var(name, letter.to_string());
+    match optarg {
+        Some(optarg) => shell.set_var("OPTARG", optarg),
+        None => shell.unset_var("OPTARG"),
+    };
+    shell.set_var("OPTIND", optind.to_string());
+    shell.getopts_position = (optind, pos);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+
+    // Runs getopts until it is done, collecting NAME:OPTARG for each option
+    fn parse_all(shell: &mut Shell, optstring: &str, words: &[&str]) -> Vec<String> {
+        shell.set_var("OPTIND", "1".to_string());
+        let mut args = vec![optstring.to_string(), "opt".to_string()];
+        args.extend(words.iter().map(|w| w.to_string()));
+        let mut found = Vec::new();
+        while builtin_getopts(shell, &args) == 0 {
+            let optarg = shell.environment.get("OPTARG").cloned().unwrap_or_default();
+            found.push(format!("{}:{}", shell.environment["opt"], optarg));
+        }
+        found
+    }
+
+    #[test]
+    fn options_and_arguments() {
+        let mut shell = Shell::with_config(Config::default());
+        assert_eq!(parse_all(&mut shell, "abo:", &["-ab", "-ofile", "-o", "x y", "rest", "-a"]),
+                   ["a:", "b:", "o:file", "o:x y"]);
+        assert_eq!(shell.environment["OPTIND"], "5");
+        assert_eq!(parse_all(&mut shell, "a", &["-a", "--", "-a"]), ["a:"]);
+        assert_eq!(shell.environment["OPTIND"], "3");
+        assert_eq!(parse_all(&mut shell, ":ab:", &["-x", "-b"]), ["?:x", "::b"]);
+        shell.positional = vec!["-v".to_string(), "file".to_string()];
+        assert_eq!(parse_all(&mut shell, "v", &[]), ["v:"]);
+        assert_eq!(shell.environment["OPTIND"], "2");
+    }
+}
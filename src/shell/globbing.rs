@@ -0,0 +1,390 @@
+// shell/globbing.rs
+//
+// Pathname expansion. A pattern is matched one path component at a time
+// against directory listings: `*`, `?` and bracket classes (including POSIX
+// names like `[[:alpha:]]`), `**` for any depth of directories (globstar),
+// and the extglob groups `?(a|b)`, `*(..)`, `+(..)`, `@(..)` and `!(..)`.
+// A backslash makes the next character literal. As in sh, a leading `.` in
+// a file name is only matched by a literal `.` unless dotglob is set.
+use crate::config::ShellOptions;
+use std::fs;
+use std::path::Path;
+
+// Characters with a meaning in patterns; `escape` protects them
+const SPECIAL: &[char] = &['*', '?', '[', ']', '\\', '(', ')', '|', '+', '@', '!'];
+
+pub fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for ch in text.chars() {
+        if SPECIAL.contains(&ch) {
+            escaped.push('\\');
+        }
+        escaped.push(ch);
+    }
+    escaped
+}
+
+// Whether unquoted `text` could make a word a pattern
+pub fn has_pattern_chars(text: &str) -> bool {
+    text.contains(['*', '?', '[']) || ["+(", "@(", "!("].iter().any(|group| text.contains(group))
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum GroupKind {
+    ZeroOrOne,
+    ZeroOrMore,
+    OneOrMore,
+    ExactlyOne,
+    Not,
+}
+
+#[derive(Debug, Clone)]
+enum ClassItem {
+    Char(char),
+    Range(char, char),
+    Named(fn(char) -> bool),
+}
+
+#[derive(Debug, Clone)]
+enum Token {
+    Literal(char),
+    AnyChar,
+    AnyString,
+    Class { negated: bool, items: Vec<ClassItem> },
+    Group { kind: GroupKind, alternatives: Vec<Vec<Token>> },
+}
+
+// One compiled path component
+#[derive(Debug, Clone)]
+pub struct Pattern {
+    tokens: Vec<Token>,
+}
+
+impl Pattern {
+    pub fn new(pattern: &str, extglob: bool) -> Self {
+        let chars: Vec<char> = pattern.chars().collect();
+        let mut pos = 0;
+        let tokens = parse_sequence(&chars, &mut pos, extglob, false);
+        Self { tokens }
+    }
+
+    // The text the pattern stands for when it has no special parts
+    fn literal(&self) -> Option<String> {
+        self.tokens.iter().map(|token| match token {
+            Token::Literal(ch) => Some(*ch),
+            _ => None,
+        }).collect()
+    }
+
+    pub fn matches(&self, name: &str) -> bool {
+        let text: Vec<char> = name.chars().collect();
+        match_tokens(&self.tokens, &text)
+    }
+
+    // Like `matches`, but without dotglob hidden names need the pattern to
+    // start with `.`
+    fn matches_file_name(&self, name: &str, dotglob: bool) -> bool {
+        if !dotglob && name.starts_with('.') && !matches!(self.tokens.first(), Some(Token::Literal('.'))) {
+            return false;
+        }
+        self.matches(name)
+    }
+}
+
+fn parse_sequence(chars: &[char], pos: &mut usize, extglob: bool, in_group: bool) -> Vec<Token> {
+    let mut tokens = Vec::new();
+    while let Some(&ch) = chars.get(*pos) {
+        if in_group && (ch == '|' || ch == ')') {
+            break;
+        }
+        *pos += 1;
+        let kind = match ch {
+            '?' => Some(GroupKind::ZeroOrOne),
+            '*' => Some(GroupKind::ZeroOrMore),
+            '+' => Some(GroupKind::OneOrMore),
+            '@' => Some(GroupKind::ExactlyOne),
+            '!' => Some(GroupKind::Not),
+            _ => None,
+        };
+        if let Some(kind) = kind.filter(|_| extglob && chars.get(*pos) == Some(&'(')) {
+            if let Some(group) = parse_group(chars, pos, kind) {
+                tokens.push(group);
+                continue;
+            }
+        }
+        let token = match ch {
+            '?' => Token::AnyChar,
+            // Runs of `*` are one `*`
+            '*' if matches!(tokens.last(), Some(Token::AnyString)) => continue,
+            '*' => Token::AnyString,
+            '[' => match parse_class(chars, *pos) {
+                Some((class, end)) => {
+                    *pos = end;
+                    class
+                }
+                None => Token::Literal('['),
+            },
+            '\\' => match chars.get(*pos) {
+                Some(&next) => {
+                    *pos += 1;
+                    Token::Literal(next)
+                }
+                None => Token::Literal('\\'),
+            },
+            _ => Token::Literal(ch),
+        };
+        tokens.push(token);
+    }
+    tokens
+}
+
+// `(a|b)` after an extglob operator; `pos` is at the `(`. None, with `pos`
+// unchanged, if the group is not closed.
+fn parse_group(chars: &[char], pos: &mut usize, kind: GroupKind) -> Option<Token> {
+    let mut end = *pos + 1;
+    let mut alternatives = Vec::new();
+    loop {
+        alternatives.push(parse_sequence(chars, &mut end, true, true));
+        match chars.get(end) {
+            Some('|') => end += 1,
+            Some(')') => break,
+            _ => return None,
+        }
+    }
+    *pos = end + 1;
+    Some(Token::Group { kind, alternatives })
+}
+
+// A bracket expression whose contents start at `start` (just after `[`);
+// returns it and the position after its `]`
+fn parse_class(chars: &[char], start: usize) -> Option<(Token, usize)> {
+    let mut pos = start;
+    let negated = matches!(chars.get(pos), Some('!' | '^'));
+    if negated {
+        pos += 1;
+    }
+    let mut items = Vec::new();
+    let first = pos;
+    loop {
+        let ch = *chars.get(pos)?;
+        // `]` right after the opening bracket is a member, not the end
+        if ch == ']' && pos > first {
+            return Some((Token::Class { negated, items }, pos + 1));
+        }
+        if ch == '[' && chars.get(pos + 1) == Some(&':') {
+            let rest: String = chars[pos + 2..].iter().collect();
+            if let Some(end) = rest.find(":]") {
+                let class = named_class(&rest[..end])?;
+                items.push(ClassItem::Named(class));
+                pos += 2 + rest[..end].chars().count() + 2;
+                continue;
+            }
+        }
+        let ch = if ch == '\\' {
+            pos += 1;
+            *chars.get(pos)?
+        } else {
+            ch
+        };
+        if chars.get(pos + 1) == Some(&'-') && chars.get(pos + 2).is_some_and(|&c| c != ']') {
+            items.push(ClassItem::Range(ch, chars[pos + 2]));
+            pos += 3;
+        } else {
+            items.push(ClassItem::Char(ch));
+            pos += 1;
+        }
+    }
+}
+
+fn named_class(name: &str) -> Option<fn(char) -> bool> {
+    let class: fn(char) -> bool = match name {
+        "alpha" => char::is_alphabetic,
+        "digit" => |c| c.is_ascii_digit(),
+        "alnum" => char::is_alphanumeric,
+        "upper" => char::is_uppercase,
+        "lower" => char::is_lowercase,
+        "space" => char::is_whitespace,
+        "punct" => |c| c.is_ascii_punctuation(),
+        "xdigit" => |c| c.is_ascii_hexdigit(),
+        "blank" => |c| c == ' ' || c == '\t',
+        "cntrl" => char::is_control,
+        _ => return None,
+    };
+    Some(class)
+}
+
+fn class_matches(negated: bool, items: &[ClassItem], ch: char) -> bool {
+    let found = items.iter().any(|item| match *item {
+        ClassItem::Char(c) => c == ch,
+        ClassItem::Range(from, to) => (from..=to).contains(&ch),
+        ClassItem::Named(class) => class(ch),
+    });
+    found != negated
+}
+
+fn match_tokens(tokens: &[Token], text: &[char]) -> bool {
+    let Some((token, rest)) = tokens.split_first() else {
+        return text.is_empty();
+    };
+    match token {
+        Token::Literal(ch) => text.first() == Some(ch) && match_tokens(rest, &text[1..]),
+        Token::AnyChar => !text.is_empty() && match_tokens(rest, &text[1..]),
+        Token::Class { negated, items } => {
+            text.first().is_some_and(|&ch| class_matches(*negated, items, ch)) && match_tokens(rest, &text[1..])
+        }
+        Token::AnyString => (0..=text.len()).any(|skip| match_tokens(rest, &text[skip..])),
+        Token::Group { kind, alternatives } => (0..=text.len())
+            .any(|end| group_matches(*kind, alternatives, &text[..end]) && match_tokens(rest, &text[end..])),
+    }
+}
+
+fn group_matches(kind: GroupKind, alternatives: &[Vec<Token>], text: &[char]) -> bool {
+    let one = |text: &[char]| alternatives.iter().any(|alternative| match_tokens(alternative, text));
+    // One match followed by any number more
+    let repeated = |text: &[char]| (1..=text.len()).any(|end| {
+        one(&text[..end]) && (end == text.len() || group_matches(GroupKind::OneOrMore, alternatives, &text[end..]))
+    });
+    match kind {
+        GroupKind::ExactlyOne => one(text),
+        GroupKind::ZeroOrOne => text.is_empty() || one(text),
+        GroupKind::ZeroOrMore => text.is_empty() || repeated(text),
+        GroupKind::OneOrMore => repeated(text) || (text.is_empty() && one(text)),
+        GroupKind::Not => !one(text),
+    }
+}
+
+fn join(base: &str, name: &str) -> String {
+    if base.is_empty() {
+        name.to_string()
+    } else if base.ends_with('/') {
+        format!("{}{}", base, name)
+    } else {
+        format!("{}/{}", base, name)
+    }
+}
+
+fn list_dir(base: &str) -> Vec<(String, bool)> {
+    let dir = if base.is_empty() { "." } else { base };
+    let Ok(entries) = fs::read_dir(dir) else {
+        return Vec::new();
+    };
+    let mut names: Vec<(String, bool)> = entries
+        .filter_map(Result::ok)
+        .map(|entry| {
+            let is_dir = entry.path().is_dir();
+            (entry.file_name().to_string_lossy().into_owned(), is_dir)
+        })
+        .collect();
+    names.sort();
+    names
+}
+
+// Every directory below `base` (and with `files`, every file), not
+// descending through symlinks or, without dotglob, into hidden directories
+fn walk(base: &str, files: bool, dotglob: bool, found: &mut Vec<String>) {
+    for (name, is_dir) in list_dir(base) {
+        if !dotglob && name.starts_with('.') {
+            continue;
+        }
+        let path = join(base, &name);
+        let is_link = fs::symlink_metadata(&path).is_ok_and(|m| m.file_type().is_symlink());
+        if is_dir || files {
+            found.push(path.clone());
+        }
+        if is_dir && !is_link {
+            walk(&path, files, dotglob, found);
+        }
+    }
+}
+
+// Paths matching `pattern`, sorted; empty when nothing matches
+pub fn expand(pattern: &str, options: &ShellOptions) -> Vec<String> {
+    let dirs_only = pattern.ends_with('/');
+    let components: Vec<&str> = pattern.split('/').filter(|c| !c.is_empty()).collect();
+    let mut paths = vec![if pattern.starts_with('/') { "/".to_string() } else { String::new() }];
+    for (i, component) in components.iter().enumerate() {
+        let last = i + 1 == components.len();
+        let mut next = Vec::new();
+        if *component == "**" && options.globstar {
+            for base in &paths {
+                next.push(base.clone());
+                walk(base, last && !dirs_only, options.dotglob, &mut next);
+            }
+        } else {
+            let pattern = Pattern::new(component, options.extglob);
+            match pattern.literal() {
+                // Plain components are only checked for existence
+                Some(name) => next.extend(paths.iter().map(|base| join(base, &name))),
+                None => {
+                    for base in &paths {
+                        for (name, is_dir) in list_dir(base) {
+                            if (last || is_dir) && pattern.matches_file_name(&name, options.dotglob) {
+                                next.push(join(base, &name));
+                            }
+                        }
+                    }
+                }
+            }
+        }
+        paths = next;
+    }
+    let mut matches: Vec<String> = paths.into_iter()
+        .filter(|path| !path.is_empty() && fs::symlink_metadata(path).is_ok())
+        .filter(|path| !dirs_only || Path::new(path).is_dir())
+        .map(|path| if dirs_only && !path.ends_with('/') { path + "/" } else { path })
+        .collect();
+    matches.sort();
+    matches.dedup();
+    matches
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pattern_matching() {
+        let matches = |pattern: &str, name: &str| Pattern::new(pattern, true).matches(name);
+        assert!(matches("*.rs", "main.rs") && !matches("*.rs", "main.rs.bak"));
+        assert!(matches("?[a-c][!x]", "zbq") && !matches("?[a-c][!x]", "zbx"));
+        assert!(matches("[[:upper:]][[:digit:]]*", "A1.txt") && !matches("[[:upper:]]*", "a"));
+        assert!(matches("[]x]", "]") && matches("\\*", "*") && !matches("\\*", "a"));
+        assert!(matches("*.@(jpg|png)", "a.png") && !matches("*.@(jpg|png)", "a.gif"));
+        assert!(matches("+(ab)c", "ababc") && !matches("+(ab)c", "c"));
+        assert!(matches("*(ab)c", "c") && matches("?(x)y", "xy") && !matches("?(x)y", "xxy"));
+        assert!(matches("!(*.o)", "main.c") && !matches("!(*.o)", "main.o"));
+        assert!(!Pattern::new("+(a)", false).matches("a") && Pattern::new("+(a)", false).matches("+(a)"));
+        assert!(matches(&escape("a*(b)"), "a*(b)"));
+    }
+
+    #[test]
+    fn walks_directories() {
+        let root = std::env::temp_dir().join(format!("rshell-glob-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&root);
+        for dir in ["src/shell", "src/.hidden", "target"] {
+            fs::create_dir_all(root.join(dir)).unwrap();
+        }
+        for file in ["src/lib.rs", "src/shell/mod.rs", "src/.hidden/x.rs", "src/.dot.rs", "build.rs", "notes.md"] {
+            fs::write(root.join(file), "").unwrap();
+        }
+        let base = root.to_string_lossy().into_owned();
+        let options = ShellOptions::default();
+        let expand = |pattern: &str| -> Vec<String> {
+            expand(&format!("{}/{}", base, pattern), &options).into_iter()
+                .map(|path| path[base.len() + 1..].to_string())
+                .collect()
+        };
+        assert_eq!(expand("**/*.rs"), ["build.rs", "src/lib.rs", "src/shell/mod.rs"]);
+        assert_eq!(expand("*/"), ["src/", "target/"]);
+        assert_eq!(expand("src/.*.rs"), ["src/.dot.rs"]);
+        assert_eq!(expand("*.@(md|txt)"), ["notes.md"]);
+        assert_eq!(expand("src/*/mod.rs"), ["src/shell/mod.rs"]);
+        assert!(expand("*.nothing").is_empty());
+        let no_globstar = ShellOptions { globstar: false, ..ShellOptions::default() };
+        assert_eq!(super::expand(&format!("{}/**/*.rs", base), &no_globstar).len(), 1);
+        let dotglob = ShellOptions { dotglob: true, ..ShellOptions::default() };
+        assert_eq!(super::expand(&format!("{}/src/*.rs", base), &dotglob).len(), 2);
+        assert_eq!(super::expand(&format!("{}/**/x.rs", base), &dotglob).len(), 1);
+        fs::remove_dir_all(&root).unwrap();
+    }
+}
@@ -1,24 +1,115 @@
 // src/shell/mod.rs
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::rc::Rc;
+use std::sync::Arc;
 use std::env;
+use std::io::{self, Write};
+use std::path::Path;
+use std::time::Duration;
+use crate::alias::{self, AliasMap};
+use crate::audit::AuditLog;
+use crate::cache::CommandCache;
+use crate::command::FunctionBody;
 use crate::config::Config;
+use crate::shell::declare::VarAttributes;
+use crate::history::History;
+use crate::envrc::EnvrcState;
+use crate::plugin::PluginManager;
+use crate::memory_pool::{interner, LimitEnforcement, MemoryTracker};
+use crate::sysinfo;
+use crate::performance::{PerformanceMonitor, ResourceUsage};
+use crate::utils::helpers::{format_duration, truncate_start_to_width};
+use crate::utils::path_utils::{self, DirHistory};
+use nix::errno::Errno;
+use nix::sys::wait::{waitpid, WaitPidFlag, WaitStatus};
+use nix::unistd::Pid;
 
 pub mod parser;
+pub mod expand;
+pub mod globbing;
+pub mod conditional;
+pub mod arithmetic;
+pub mod calc;
+pub mod trap;
+pub mod read;
+pub mod getopts;
+pub mod declare;
+pub mod dirstack;
 pub mod executor;
 pub mod builtins;
+pub mod output;
 
 // Only export what's actually used
 
 pub struct Shell {
-    pub environment: HashMap<String, String>,
+    // Every variable, shell-local or exported
+    pub environment: HashMap<Arc<str>, String>,
+    // The names passed on to commands' environments: what rshell inherited,
+    // plus `export`. A name can be exported before it has a value.
+    pub exported: HashSet<Arc<str>>,
+    // `declare`/`readonly` attributes of the variables that have any
+    pub attributes: HashMap<Arc<str>, VarAttributes>,
+    // Logical working directory: the path as the user spelled it, symlinks kept
     pub current_dir: String,
+    pub dir_history: DirHistory,
+    // `pushd`'s directory stack, most recent first; the current directory
+    // is not in it
+    pub dir_stack: Vec<String>,
     pub last_exit_code: i32,
-    pub history: Vec<String>,
+    pub last_duration: Option<Duration>,
+    pub last_rusage: Option<ResourceUsage>,
+    pub history: History,
     pub jobs: Vec<Job>,
-    pub aliases: HashMap<String, String>,
+    pub aliases: AliasMap,
     pub config: Config,
     pub username: String,
     pub hostname: String,
+    pub perf: PerformanceMonitor,
+    pub command_cache: CommandCache,
+    pub memory: MemoryTracker,
+    pub limit_enforcement: LimitEnforcement,
+    pub audit: AuditLog,
+    pub plugins: PluginManager,
+    pub envrc: EnvrcState,
+    // Started as a login shell and ~/.rshell_logout has not run yet
+    pub login: bool,
+    // Reading commands from the user rather than from -c, a script or a pipe
+    pub interactive: bool,
+    // `$$`: subshells keep their parent's pid
+    pub pid: u32,
+    // `$!`
+    pub last_background_pid: Option<i32>,
+    // `$0`, and `$1`... (`$#` counts them)
+    pub arg0: String,
+    pub positional: Vec<String>,
+    // Loops being run, and a `break`/`continue` on its way out to one
+    pub loop_depth: usize,
+    pub control: Option<Control>,
+    // Functions by name, as parsed. A call holds on to its own reference,
+    // so the function can be redefined or unset while it runs.
+    pub functions: HashMap<String, Rc<FunctionBody>>,
+    // One frame per function call being run: the variables its `local`
+    // made, with the values they had before
+    pub locals: Vec<Vec<(String, Option<String>)>>,
+    // Conditions being tested (`if`, `while`, `!`, the left of `&&`/`||`),
+    // where a failure is an answer rather than an error for `set -e`
+    pub condition_depth: usize,
+    // `trap` actions by signal number, 0 for EXIT; "" ignores the signal
+    pub traps: BTreeMap<i32, String>,
+    // `getopts`: the OPTIND it last set, and the offset of the next option
+    // letter in that word
+    pub getopts_position: (usize, usize),
+    // `$LINENO`: the line of the script being run, 0 at the prompt
+    pub lineno: usize,
+}
+
+// Set by `break n`/`continue n` and `return`. Commands stop running until
+// the n-th loop out, or the function, takes it.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Control {
+    Break(usize),
+    Continue(usize),
+    Return(i32),
 }
 
 #[derive(Debug, Clone)]
@@ -36,15 +127,26 @@ pub enum JobStatus {
     Done,
 }
 
+impl Default for Shell {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl Shell {
     pub fn new() -> Self {
+        Self::with_config(Config::load().unwrap_or_default())
+    }
+    
+    pub fn with_config(config: Config) -> Self {
         let mut env = HashMap::new();
         for (key, value) in env::vars() {
-            env.insert(key, value);
+            env.insert(interner().intern(&key), value);
         }
+        let exported = env.keys().cloned().collect();
         
-        let config = Config::load().unwrap_or_default();
-        let aliases = config.aliases.clone();
+        let aliases = alias::from_config(&config.aliases);
+        let audit = AuditLog::new(config.audit.clone());
         
         let username = env::var("USER").unwrap_or_else(|_| "user".to_string());
         let hostname = gethostname::gethostname()
@@ -53,21 +155,204 @@ impl Shell {
         
         Self {
             environment: env,
-            current_dir: env::current_dir()
-                .unwrap_or_default()
-                .to_string_lossy()
-                .to_string(),
+            exported,
+            attributes: HashMap::new(),
+            current_dir: initial_dir(),
+            dir_history: DirHistory::default(),
+            dir_stack: Vec::new(),
             last_exit_code: 0,
-            history: Vec::new(),
+            last_duration: None,
+            last_rusage: None,
+            history: History::new(config.general.history_size),
             jobs: Vec::new(),
             aliases,
             config,
             username,
             hostname,
+            perf: PerformanceMonitor::new(),
+            command_cache: CommandCache::new(),
+            memory: MemoryTracker::new(),
+            limit_enforcement: LimitEnforcement::default(),
+            audit,
+            plugins: PluginManager::new(),
+            envrc: EnvrcState::default(),
+            login: false,
+            interactive: false,
+            pid: std::process::id(),
+            last_background_pid: None,
+            arg0: env::args().next().unwrap_or_else(|| "rshell".to_string()),
+            positional: Vec::new(),
+            loop_depth: 0,
+            control: None,
+            functions: HashMap::new(),
+            locals: Vec::new(),
+            condition_depth: 0,
+            traps: BTreeMap::new(),
+            getopts_position: (1, 1),
+            lineno: 0,
+        }
+    }
+    
+    // Ends the process, running ~/.rshell_logout in a login shell and then
+    // the EXIT trap
+    pub fn exit(&mut self, code: i32) -> ! {
+        crate::login::logout(self);
+        self.last_exit_code = code;
+        trap::run_exit(self);
+        let _ = io::stdout().flush();
+        std::process::exit(code);
+    }
+    
+    // `set -u` came across the unset `name`
+    pub fn unbound_variable(&mut self, name: &str) -> String {
+        self.expansion_error(format!("{}: unbound variable", name))
+    }
+    
+    // An expansion that must not go on, an unbound variable or `${VAR:?}`.
+    // A shell that is not interactive ends there, as POSIX asks; otherwise
+    // the message is handed back and only the command fails.
+    pub fn expansion_error(&mut self, message: String) -> String {
+        if !self.interactive {
+            eprintln!("rshell: {}", message);
+            self.exit(1);
+        }
+        message
+    }
+    
+    // Accumulates rusage of children reaped for the current command line
+    pub fn record_child_usage(&mut self, usage: &ResourceUsage) {
+        self.perf.record_child_usage(usage);
+        self.last_rusage.get_or_insert_with(ResourceUsage::default).merge(usage);
+    }
+    
+    // Changes directory the way `cd` does. Logically (the default) `..` undoes
+    // the last component even if it was a symlink; physically every link in
+    // the target is resolved first, relative to the real working directory.
+    // PWD and OLDPWD follow along.
+    pub fn change_dir(&mut self, target: &str, physical: bool) -> io::Result<()> {
+        let new_dir = if physical {
+            path_utils::resolve_physical(Path::new(target))?.path
+        } else {
+            path_utils::normalize_logical(Path::new(&self.current_dir), Path::new(target))
+        };
+        env::set_current_dir(&new_dir)?;
+        
+        let physical_dir = env::current_dir()?;
+        self.dir_history.push(new_dir.clone(), physical_dir);
+        let previous = std::mem::replace(&mut self.current_dir, new_dir.to_string_lossy().to_string());
+        self.export_var("OLDPWD", previous);
+        self.export_var("PWD", self.current_dir.clone());
+        Ok(())
+    }
+    
+    // Sets a variable; commands only see it in their environment once it is
+    // exported. A new PATH forgets the cached command lookups. Fails, saying
+    // why as `rshell: ...`, if the variable's attributes refuse the value.
+    pub fn set_var(&mut self, key: &str, value: String) -> bool {
+        match self.try_set_var(key, value) {
+            Ok(()) => true,
+            Err(e) => {
+                eprintln!("rshell: {}", e);
+                false
+            }
+        }
+    }
+    
+    // `set_var` leaving the error to the caller
+    pub fn try_set_var(&mut self, key: &str, value: String) -> Result<(), String> {
+        let value = self.attributes(key).apply(self, key, value)?;
+        if key == "PATH" {
+            self.command_cache.invalidate();
+        }
+        if self.exported.contains(key) {
+            env::set_var(key, &value);
         }
+        self.environment.insert(interner().intern(key), value);
+        Ok(())
+    }
+    
+    // Sets a variable and exports it
+    pub fn export_var(&mut self, key: &str, value: String) -> bool {
+        let set = self.set_var(key, value);
+        self.set_exported(key, true);
+        set
+    }
+    
+    pub fn attributes(&self, key: &str) -> VarAttributes {
+        self.attributes.get(key).copied().unwrap_or_default()
+    }
+    
+    pub fn set_attributes(&mut self, key: &str, attributes: VarAttributes) {
+        if attributes.is_empty() {
+            self.attributes.remove(key);
+        } else {
+            self.attributes.insert(interner().intern(key), attributes);
+        }
+    }
+    
+    // `export NAME` and `export -n NAME`: the value, if any, stays
+    pub fn set_exported(&mut self, key: &str, exported: bool) {
+        // Lookups go through the process environment's PATH
+        if key == "PATH" {
+            self.command_cache.invalidate();
+        }
+        if exported {
+            if let Some(value) = self.environment.get(key) {
+                env::set_var(key, value);
+            }
+            self.exported.insert(interner().intern(key));
+        } else {
+            env::remove_var(key);
+            self.exported.remove(key);
+        }
+    }
+    
+    pub fn is_exported(&self, key: &str) -> bool {
+        self.exported.contains(key)
+    }
+    
+    // Removes a variable and its attributes, unless it is readonly
+    pub fn unset_var(&mut self, key: &str) -> bool {
+        if self.attributes(key).readonly {
+            return false;
+        }
+        self.environment.remove(key);
+        self.attributes.remove(key);
+        self.set_exported(key, false);
+        interner().purge_unused();
+        true
+    }
+    
+    // Refreshes the per-consumer estimates in `self.memory`
+    pub fn update_memory_usage(&self) {
+        let jobs = self.jobs.capacity() * std::mem::size_of::<Job>()
+            + self.jobs.iter().map(|job| job.command.capacity()).sum::<usize>();
+        self.memory.set("history", self.history.memory_usage());
+        self.memory.set("command cache", self.command_cache.memory_usage());
+        self.memory.set("jobs", jobs);
+        self.memory.set("interned names", interner().heap_bytes());
+    }
+    
+    // Applies the [memory] soft limits; called after each command line
+    pub fn enforce_memory_limits(&mut self) {
+        let limits = &self.config.memory;
+        if limits.history_soft_limit_kb > 0 {
+            let dropped = self.history.compact(limits.history_soft_limit_kb * 1024);
+            if dropped > 0 {
+                self.limit_enforcement.history_compactions += 1;
+                self.limit_enforcement.history_entries_dropped += dropped;
+            }
+        }
+        if limits.cache_soft_limit_kb > 0
+            && self.command_cache.evict_to(limits.cache_soft_limit_kb * 1024)
+        {
+            self.limit_enforcement.cache_evictions += 1;
+        }
+        self.update_memory_usage();
     }
     
     pub fn add_job(&mut self, pid: i32, command: String) {
+        self.last_background_pid = Some(pid);
         let id = self.jobs.len() + 1;
         self.jobs.push(Job {
             id,
@@ -77,12 +362,44 @@ impl Shell {
         });
     }
     
+    // Marks the jobs that have ended since the last call as Done and
+    // returns them with how they ended. Never waits, so `jobs` can call it
+    // in scripts and `-c` as well as the prompt between commands.
+    pub fn reap_jobs(&mut self) -> Vec<(i32, WaitStatus)> {
+        let mut ended = Vec::new();
+        for job in self.jobs.iter_mut().filter(|job| !matches!(job.status, JobStatus::Done)) {
+            match waitpid(Pid::from_raw(job.pid), Some(WaitPidFlag::WNOHANG)) {
+                Ok(status @ (WaitStatus::Exited(..) | WaitStatus::Signaled(..))) => {
+                    job.status = JobStatus::Done;
+                    ended.push((job.pid, status));
+                }
+                // Reaped elsewhere, e.g. by `fg`
+                Err(Errno::ECHILD) => job.status = JobStatus::Done,
+                _ => {}
+            }
+        }
+        ended
+    }
+    
+    // Reaps the jobs that have ended and says so, as the prompt does
+    pub fn check_background_jobs(&mut self) {
+        for (pid, status) in self.reap_jobs() {
+            match status {
+                WaitStatus::Exited(_, code) => println!("[Done] {} exited with code {}", pid, code),
+                WaitStatus::Signaled(_, sig, _) => println!("[Done] {} terminated by signal {}", pid, sig),
+                _ => {}
+            }
+        }
+    }
+    
     pub fn format_prompt(&self) -> String {
         let mut prompt = self.config.general.prompt_format.clone();
         
         prompt = prompt.replace("{user}", &self.username);
         prompt = prompt.replace("{host}", &self.hostname);
         prompt = prompt.replace("{cwd}", &self.get_cwd_display());
+        prompt = prompt.replace("{duration}", &self.get_duration_display());
+        prompt = prompt.replace("{dirs}", &self.get_dirs_display());
         
         let symbol = if self.last_exit_code == 0 {
             &self.config.theme.prompt_symbol
@@ -90,20 +407,55 @@ impl Shell {
             &self.config.theme.prompt_symbol_error
         };
         prompt = prompt.replace("{symbol}", symbol);
+        prompt = sysinfo::expand_prompt_segments(&prompt);
+        if !self.plugins.is_empty() {
+            prompt = self.plugins.expand_prompt_segments(&prompt);
+        }
         
         prompt
     }
     
-    fn get_cwd_display(&self) -> String {
+    // Only shown once the last command ran longer than the configured threshold
+    fn get_duration_display(&self) -> String {
+        let threshold = Duration::from_millis(self.config.general.duration_threshold_ms);
+        match self.last_duration {
+            Some(duration) if duration >= threshold => format_duration(duration),
+            _ => String::new(),
+        }
+    }
+    
+    // The depth of the `pushd` stack; empty while there is none
+    fn get_dirs_display(&self) -> String {
+        match self.dir_stack.len() {
+            0 => String::new(),
+            depth => depth.to_string(),
+        }
+    }
+    
+    pub fn get_cwd_display(&self) -> String {
+        let mut cwd = self.current_dir.clone();
         if let Ok(home) = env::var("HOME") {
-            if self.current_dir.starts_with(&home) {
-                return self.current_dir.replacen(&home, "~", 1);
+            if cwd.starts_with(&home) {
+                cwd = cwd.replacen(&home, "~", 1);
             }
         }
-        self.current_dir.clone()
+        match self.config.general.prompt_cwd_max_width {
+            0 => cwd,
+            max_width => truncate_start_to_width(&cwd, max_width),
+        }
     }
     
     pub fn save_config(&self) -> std::io::Result<()> {
         self.config.save()
     }
-}
\ No newline at end of file
+}
+
+// Keep an inherited $PWD when it still names the working directory, so a shell
+// started inside a symlinked directory reports the path the user went through
+fn initial_dir() -> String {
+    let physical = env::current_dir().unwrap_or_default();
+    match env::var("PWD") {
+        Ok(pwd) if Path::new(&pwd).is_absolute() && path_utils::same_file(Path::new(&pwd), &physical) => pwd,
+        _ => physical.to_string_lossy().to_string(),
+    }
+}
@@ -0,0 +1,53 @@
+// src/shell/output.rs
+// Builtins print with `out!`/`outln!` rather than `print!`/`println!`,
+// which panic when stdout is a full disk or a pipe nobody reads. The first
+// write that fails is kept and the rest of the builtin's output dropped;
+// `builtins::execute_builtin` reports it once the builtin returns.
+use std::cell::RefCell;
+use std::fmt;
+use std::fs::File;
+use std::io::{self, Write};
+use std::mem::ManuallyDrop;
+use std::os::unix::io::FromRawFd;
+
+thread_local! {
+    static FAILED: RefCell<Option<io::Error>> = const { RefCell::new(None) };
+}
+
+pub fn write(args: fmt::Arguments) {
+    FAILED.with(|failed| {
+        let mut failed = failed.borrow_mut();
+        if failed.is_some() {
+            return;
+        }
+        // Straight to fd 1 in one write: text that failed must not wait in
+        // std's buffer and come out later wherever stdout points by then
+        let _ = io::stdout().flush();
+        let mut stdout = ManuallyDrop::new(unsafe { File::from_raw_fd(1) });
+        if let Err(e) = stdout.write_all(fmt::format(args).as_bytes()) {
+            *failed = Some(e);
+        }
+    });
+}
+
+// The first write that failed since the last call
+pub fn take_error() -> Option<io::Error> {
+    FAILED.with(|failed| failed.borrow_mut().take())
+}
+
+#[macro_export]
+macro_rules! out {
+    ($($arg:tt)*) => {
+        $crate::shell::output::write(format_args!($($arg)*))
+    };
+}
+
+#[macro_export]
+macro_rules! outln {
+    () => {
+        $crate::shell::output::write(format_args!("\n"))
+    };
+    ($($arg:tt)*) => {
+        $crate::shell::output::write(format_args!("{}\n", format_args!($($arg)*)))
+    };
+}
@@ -1,12 +1,43 @@
 // shell/parser.rs
-use crate::command::{Command, CommandType, RedirectType};
-use glob::glob;
+use crate::alias::AliasMap;
+use crate::command::{CommandType, CondExpr, HereDoc, PipeStage, RedirectTarget, RedirectType, Redirection, SimpleCommand};
+use crate::memory_pool::{word_pool, ZeroCopyParser};
+use crate::utils::helpers::is_valid_var_name;
+use bumpalo::collections::Vec as ArenaVec;
+use bumpalo::Bump;
+use std::borrow::Cow;
+use std::ops::Range;
+use std::sync::Arc;
 
+// Reserved words that end the list before them; only special where a
+// command name could be
+const CLOSING_KEYWORDS: &[&str] = &["then", "elif", "else", "fi", "do", "done", "}"];
+// Reserved words after which a command name is expected
+const COMMAND_KEYWORDS: &[&str] = &["if", "then", "elif", "else", "while", "until", "do", "{"];
+// Every reserved word, for `type`
+const KEYWORDS: &[&str] = &["if", "then", "elif", "else", "fi", "while", "until", "for", "in", "do", "done",
+                            "{", "}", "[[", "]]", "!", "coproc", "function"];
+
+// Operators of `[[ ]]`; `<` and `>` arrive as redirection tokens
+const UNARY_TESTS: &[&str] = &["-e", "-f", "-d", "-s", "-L", "-h", "-r", "-w", "-x", "-z", "-n"];
+const BINARY_TESTS: &[&str] = &["==", "=", "!=", "=~", "-eq", "-ne", "-lt", "-le", "-gt", "-ge"];
+
+// Words borrow from the input line where possible. The AST built from them
+// borrows too: from the line, or for words that had to be joined or
+// rewritten, from the arena the parser is given, which also holds the
+// nodes. Words are kept as typed, quotes and `$` included: the executor
+// expands them when the command runs (see shell::expand).
 #[derive(Debug, Clone, PartialEq)]
-pub enum Token {
-    Word(String),
+pub enum Token<'a> {
+    Word(Cow<'a, str>),
+    // `(( expression ))` in command position: the text between the parentheses
+    Arithmetic(Cow<'a, str>),
     Pipe,
+    // `|&`: stdout and stderr into the pipe
+    PipeBoth,
     Redirect(RedirectType),
+    // The `3` of `3>file`: a number right before a redirection operator
+    IoNumber(i32),
     And,
     Or,
     Background,
@@ -16,30 +47,133 @@ pub enum Token {
     Newline,
 }
 
-pub struct Parser {
-    tokens: Vec<Token>,
+impl Token<'_> {
+    pub fn into_owned(self) -> Token<'static> {
+        match self {
+            Token::Word(word) => Token::Word(Cow::Owned(word.into_owned())),
+            Token::Arithmetic(expr) => Token::Arithmetic(Cow::Owned(expr.into_owned())),
+            Token::Pipe => Token::Pipe,
+            Token::PipeBoth => Token::PipeBoth,
+            Token::Redirect(redirect) => Token::Redirect(redirect),
+            Token::IoNumber(fd) => Token::IoNumber(fd),
+            Token::And => Token::And,
+            Token::Or => Token::Or,
+            Token::Background => Token::Background,
+            Token::Semicolon => Token::Semicolon,
+            Token::LeftParen => Token::LeftParen,
+            Token::RightParen => Token::RightParen,
+            Token::Newline => Token::Newline,
+        }
+    }
+}
+
+// A token and the bytes of the input it was read from, for error messages
+#[derive(Debug, Clone, PartialEq)]
+pub struct Spanned<'a> {
+    pub token: Token<'a>,
+    pub span: Range<usize>,
+}
+
+impl<'a> Spanned<'a> {
+    fn new(token: Token<'a>, span: Range<usize>) -> Self {
+        Self { token, span }
+    }
+}
+
+pub struct Parser<'a> {
+    tokens: Vec<Spanned<'a>>,
     position: usize,
+    input: &'a str,
+    arena: &'a Bump,
+    // Compound commands opened and not yet closed; running out of input
+    // inside one means another line is needed rather than an error
+    open: usize,
 }
 
-impl Parser {
-    pub fn new(input: &str) -> Result<Self, ParseError> {
-        let expanded = expand_command_substitution(input)?;
-        let tokens = Self::tokenize(&expanded)?;
-        Ok(Self { tokens, position: 0 })
+impl<'a> Parser<'a> {
+    // The tree `parse` returns lives in `arena`, and borrows from `input`
+    pub fn new(input: &'a str, arena: &'a Bump) -> Result<Self, ParseError> {
+        Ok(Self { tokens: Self::tokenize(input)?, position: 0, input, arena, open: 0 })
     }
     
-    pub fn parse(&mut self) -> Result<CommandType, ParseError> {
-        self.parse_logical_or()
+    // Like `new`, with aliases in command position replaced by their values
+    pub fn with_aliases(input: &'a str, aliases: &AliasMap, arena: &'a Bump) -> Result<Self, ParseError> {
+        let mut tokens = Self::tokenize(input)?;
+        if !aliases.is_empty() {
+            let mut expanded = Vec::with_capacity(tokens.len());
+            splice_aliases(tokens, aliases, &mut Vec::new(), &mut expanded, true, None)?;
+            tokens = expanded;
+        }
+        Ok(Self { tokens, position: 0, input, arena, open: 0 })
     }
     
-    fn parse_logical_or(&mut self) -> Result<CommandType, ParseError> {
+    // Nothing but blank lines and comments
+    pub fn is_empty(&self) -> bool {
+        self.tokens.iter().all(|spanned| spanned.token == Token::Newline)
+    }
+    
+    pub fn parse(&mut self) -> Result<CommandType<'a>, ParseError> {
+        let command = self.parse_sequence()?;
+        self.skip_newlines();
+        match self.peek() {
+            None => Ok(command),
+            Some(token) => Err(self.error(format!("Unexpected {}", describe(token)))),
+        }
+    }
+    
+    // `a; b & c` or one per line: and-or lists run one after another. A
+    // trailing `;` is allowed.
+    fn parse_sequence(&mut self) -> Result<CommandType<'a>, ParseError> {
+        self.skip_newlines();
+        let first = self.parse_list_item()?;
+        let mut commands = ArenaVec::new_in(self.arena);
+        commands.push(first);
+        
+        loop {
+            let separated = match self.peek() {
+                Some(Token::Semicolon) => {
+                    self.consume()?;
+                    true
+                }
+                // A newline ends a command like `;`, so pasted blocks run line by line
+                Some(Token::Newline) => true,
+                // `&` already ended the previous command, and separates it too
+                Some(_) => matches!(commands.last(), Some(CommandType::Background(_))),
+                None => false,
+            };
+            self.skip_newlines();
+            if !separated || !matches!(self.peek(), Some(Token::Word(_) | Token::LeftParen | Token::Arithmetic(_))) || self.at_closing_keyword() {
+                break;
+            }
+            commands.push(self.parse_list_item()?);
+        }
+        
+        if commands.len() == 1 {
+            Ok(first)
+        } else {
+            Ok(CommandType::Sequence(commands.into_bump_slice()))
+        }
+    }
+    
+    // An and-or list, which a trailing `&` puts in the background as a whole
+    fn parse_list_item(&mut self) -> Result<CommandType<'a>, ParseError> {
+        let command = self.parse_logical_or()?;
+        if let Some(Token::Background) = self.peek() {
+            self.consume()?;
+            return Ok(CommandType::Background(self.arena.alloc(command)));
+        }
+        Ok(command)
+    }
+    
+    fn parse_logical_or(&mut self) -> Result<CommandType<'a>, ParseError> {
         let mut left = self.parse_logical_and()?;
         
         while self.position < self.tokens.len() {
             if let Some(Token::Or) = self.peek() {
                 self.consume()?;
+                self.skip_newlines();
                 let right = self.parse_logical_and()?;
-                left = CommandType::Or(Box::new(left), Box::new(right));
+                left = CommandType::Or(self.arena.alloc(left), self.arena.alloc(right));
             } else {
                 break;
             }
@@ -47,14 +181,15 @@ impl Parser {
         Ok(left)
     }
     
-    fn parse_logical_and(&mut self) -> Result<CommandType, ParseError> {
+    fn parse_logical_and(&mut self) -> Result<CommandType<'a>, ParseError> {
         let mut left = self.parse_pipeline()?;
         
         while self.position < self.tokens.len() {
             if let Some(Token::And) = self.peek() {
                 self.consume()?;
+                self.skip_newlines();
                 let right = self.parse_pipeline()?;
-                left = CommandType::And(Box::new(left), Box::new(right));
+                left = CommandType::And(self.arena.alloc(left), self.arena.alloc(right));
             } else {
                 break;
             }
@@ -62,238 +197,996 @@ impl Parser {
         Ok(left)
     }
     
-    fn parse_pipeline(&mut self) -> Result<CommandType, ParseError> {
-        let mut commands = vec![self.parse_simple_command()?];
+    fn parse_pipeline(&mut self) -> Result<CommandType<'a>, ParseError> {
+        if matches!(self.peek(), Some(Token::Word(word)) if word == "!") {
+            self.consume()?;
+            let inner = self.parse_pipeline()?;
+            return Ok(CommandType::Not(self.arena.alloc(inner)));
+        }
+        let first = self.parse_command()?;
+        if !matches!(self.peek(), Some(Token::Pipe | Token::PipeBoth)) {
+            return Ok(first);
+        }
+        let mut stages = ArenaVec::new_in(self.arena);
+        stages.push(PipeStage::new(first));
         
-        while self.position < self.tokens.len() {
-            if let Some(Token::Pipe) = self.peek() {
+        while let Some(Token::Pipe | Token::PipeBoth) = self.peek() {
+            if self.consume()? == Token::PipeBoth {
+                stages.last_mut().unwrap().pipe_stderr = true;
+            }
+            self.skip_newlines();
+            stages.push(PipeStage::new(self.parse_command()?));
+        }
+        
+        Ok(CommandType::Pipeline(stages.into_bump_slice()))
+    }
+    
+    // One pipeline stage: a simple command or a compound one
+    fn parse_command(&mut self) -> Result<CommandType<'a>, ParseError> {
+        match self.peek() {
+            Some(Token::LeftParen) => self.parse_subshell(),
+            Some(Token::Arithmetic(_)) => {
+                let Token::Arithmetic(expr) = self.consume()? else { unreachable!() };
+                Ok(CommandType::Arithmetic(self.word(expr)))
+            }
+            Some(Token::Word(word)) if word == "[[" => self.parse_conditional(),
+            Some(Token::Word(word)) if word == "coproc" => self.parse_coproc(),
+            Some(Token::Word(word)) if word == "if" => self.parse_if(),
+            Some(Token::Word(word)) if word == "while" || word == "until" => self.parse_while(),
+            Some(Token::Word(word)) if word == "for" => self.parse_for(),
+            Some(Token::Word(word)) if word == "select" && self.at_select_loop() => self.parse_for(),
+            Some(Token::Word(word)) if word == "{" => self.parse_group(),
+            Some(Token::Word(word)) if word == "function" => self.parse_function(),
+            Some(Token::Word(_)) if self.at_function_definition() => self.parse_function(),
+            Some(Token::Word(word)) if CLOSING_KEYWORDS.contains(&&**word) => {
+                Err(self.error(format!("Unexpected '{}'", word)))
+            }
+            _ => Ok(CommandType::Simple(self.parse_simple_command()?)),
+        }
+    }
+    
+    // `if list; then list; [elif list; then list;]... [else list;] fi`
+    fn parse_if(&mut self) -> Result<CommandType<'a>, ParseError> {
+        self.consume()?;
+        self.open += 1;
+        let mut branches = ArenaVec::new_in(self.arena);
+        let mut else_body = None;
+        loop {
+            let condition = self.parse_sequence()?;
+            self.expect_keyword("then")?;
+            let body = self.parse_sequence()?;
+            branches.push((condition, body));
+            if self.at_keyword("elif") {
                 self.consume()?;
-                commands.push(self.parse_simple_command()?);
-            } else {
+                continue;
+            }
+            if self.at_keyword("else") {
+                self.consume()?;
+                else_body = Some(&*self.arena.alloc(self.parse_sequence()?));
+            }
+            self.expect_keyword("fi")?;
+            break;
+        }
+        self.open -= 1;
+        Ok(CommandType::If { branches: branches.into_bump_slice(), else_body })
+    }
+    
+    // `while list; do list; done`, and the same with `until`
+    fn parse_while(&mut self) -> Result<CommandType<'a>, ParseError> {
+        let until = self.at_keyword("until");
+        self.consume()?;
+        self.open += 1;
+        let condition = self.parse_sequence()?;
+        self.expect_keyword("do")?;
+        let body = self.parse_sequence()?;
+        self.expect_keyword("done")?;
+        self.open -= 1;
+        Ok(CommandType::While { condition: self.arena.alloc(condition), body: self.arena.alloc(body), until })
+    }
+    
+    // `{ list; }`
+    fn parse_group(&mut self) -> Result<CommandType<'a>, ParseError> {
+        self.consume()?;
+        self.open += 1;
+        let inner = self.parse_sequence()?;
+        self.expect_keyword("}")?;
+        self.open -= 1;
+        Ok(CommandType::Group(self.arena.alloc(inner)))
+    }
+    
+    // `name()`, which starts a function definition
+    fn at_function_definition(&self) -> bool {
+        let token = |offset: usize| self.tokens.get(self.position + offset).map(|spanned| &spanned.token);
+        matches!(token(0), Some(Token::Word(word)) if is_function_name(word))
+            && token(1) == Some(&Token::LeftParen)
+            && token(2) == Some(&Token::RightParen)
+    }
+    
+    // `name() body` or `function name [()] body`, where the body is a
+    // compound command, usually `{ list; }`
+    fn parse_function(&mut self) -> Result<CommandType<'a>, ParseError> {
+        if self.at_keyword("function") {
+            self.consume()?;
+        }
+        if !matches!(self.peek(), Some(Token::Word(word)) if is_function_name(word)) {
+            return Err(self.error("Expected a function name".to_string()));
+        }
+        let name = self.consume_word()?;
+        if self.peek() == Some(&Token::LeftParen) {
+            self.consume()?;
+            if self.peek() != Some(&Token::RightParen) {
+                return Err(self.error("Expected ')'".to_string()));
+            }
+            self.consume()?;
+        }
+        self.open += 1;
+        self.skip_newlines();
+        let compound = match self.peek() {
+            Some(Token::LeftParen) => true,
+            Some(Token::Word(word)) => matches!(&**word, "{" | "if" | "while" | "until" | "for" | "[["),
+            _ => false,
+        };
+        if !compound {
+            return Err(self.error("Expected a function body".to_string()));
+        }
+        let body = self.parse_command()?;
+        self.open -= 1;
+        Ok(CommandType::Function { name, body: self.arena.alloc(body) })
+    }
+    
+    // `for name [in word...]; do list; done`
+    fn parse_for(&mut self) -> Result<CommandType<'a>, ParseError> {
+        let select = self.at_keyword("select");
+        self.consume()?;
+        self.open += 1;
+        if !matches!(self.peek(), Some(Token::Word(word)) if is_valid_var_name(word)) {
+            return Err(self.error("Expected a variable name".to_string()));
+        }
+        let name = self.consume_word()?;
+        self.skip_newlines();
+        let mut words = None;
+        if self.at_keyword("in") {
+            self.consume()?;
+            let mut list = ArenaVec::new_in(self.arena);
+            while let Some(Token::Word(_)) = self.peek() {
+                list.push(self.consume_word()?);
+            }
+            words = Some(list.into_bump_slice());
+        }
+        match self.peek() {
+            Some(Token::Semicolon | Token::Newline) => {
+                self.consume()?;
+            }
+            _ if self.at_keyword("do") => {}
+            _ => return Err(self.error("Expected 'do'".to_string())),
+        }
+        self.expect_keyword("do")?;
+        let body = self.parse_sequence()?;
+        self.expect_keyword("done")?;
+        self.open -= 1;
+        let body = &*self.arena.alloc(body);
+        Ok(if select { CommandType::Select { name, words, body } } else { CommandType::For { name, words, body } })
+    }
+    
+    // `select NAME in` or `select NAME; do` is the loop; any other `select`
+    // is the table builtin
+    fn at_select_loop(&self) -> bool {
+        let token = |offset: usize| self.tokens.get(self.position + offset).map(|spanned| &spanned.token);
+        let is_word = |offset: usize, text: &str| matches!(token(offset), Some(Token::Word(word)) if word == text);
+        matches!(token(1), Some(Token::Word(word)) if is_valid_var_name(word))
+            && (is_word(2, "in") || is_word(2, "do")
+                || (matches!(token(2), Some(Token::Semicolon | Token::Newline)) && is_word(3, "do")))
+    }
+    
+    // Whether the next token is the unquoted reserved word `keyword`
+    fn at_keyword(&self, keyword: &str) -> bool {
+        matches!(self.peek(), Some(Token::Word(word)) if word == keyword)
+    }
+    
+    // `then`, `fi` and the like end the list before them
+    fn at_closing_keyword(&self) -> bool {
+        matches!(self.peek(), Some(Token::Word(word)) if CLOSING_KEYWORDS.contains(&&**word))
+    }
+    
+    fn expect_keyword(&mut self, keyword: &str) -> Result<(), ParseError> {
+        self.skip_newlines();
+        if !self.at_keyword(keyword) {
+            return Err(self.error(format!("Expected '{}'", keyword)));
+        }
+        self.consume()?;
+        Ok(())
+    }
+    
+    // `coproc [NAME] command`. Only a compound command can be named, as in
+    // `coproc BC (bc -l)`; otherwise the first word is the command.
+    fn parse_coproc(&mut self) -> Result<CommandType<'a>, ParseError> {
+        self.consume()?;
+        let mut name = "COPROC";
+        let next = self.tokens.get(self.position + 1).map(|spanned| &spanned.token);
+        if let (Some(Token::Word(word)), Some(Token::LeftParen)) = (self.peek(), next) {
+            if is_valid_var_name(word) {
+                name = self.consume_word()?;
+            }
+        }
+        let command = self.parse_command()?;
+        Ok(CommandType::Coproc(name, self.arena.alloc(command)))
+    }
+    
+    // `[[ expression ]]`. Inside, `&&`, `||`, `!` and parentheses combine
+    // tests, and `<`, `>` compare strings instead of redirecting.
+    fn parse_conditional(&mut self) -> Result<CommandType<'a>, ParseError> {
+        self.consume()?;
+        let expr = self.parse_cond_or()?;
+        self.skip_newlines();
+        match self.peek() {
+            Some(Token::Word(word)) if word == "]]" => {
+                self.consume()?;
+            }
+            _ => return Err(self.error("Expected ']]'".to_string())),
+        }
+        Ok(CommandType::Conditional(self.arena.alloc(expr)))
+    }
+    
+    fn parse_cond_or(&mut self) -> Result<CondExpr<'a>, ParseError> {
+        let mut left = self.parse_cond_and()?;
+        self.skip_newlines();
+        while let Some(Token::Or) = self.peek() {
+            self.consume()?;
+            let right = self.parse_cond_and()?;
+            left = CondExpr::Or(self.arena.alloc(left), self.arena.alloc(right));
+            self.skip_newlines();
+        }
+        Ok(left)
+    }
+    
+    fn parse_cond_and(&mut self) -> Result<CondExpr<'a>, ParseError> {
+        let mut left = self.parse_cond_not()?;
+        self.skip_newlines();
+        while let Some(Token::And) = self.peek() {
+            self.consume()?;
+            let right = self.parse_cond_not()?;
+            left = CondExpr::And(self.arena.alloc(left), self.arena.alloc(right));
+            self.skip_newlines();
+        }
+        Ok(left)
+    }
+    
+    fn parse_cond_not(&mut self) -> Result<CondExpr<'a>, ParseError> {
+        self.skip_newlines();
+        match self.peek() {
+            Some(Token::Word(word)) if word == "!" => {
+                self.consume()?;
+                let inner = self.parse_cond_not()?;
+                Ok(CondExpr::Not(self.arena.alloc(inner)))
+            }
+            Some(Token::LeftParen) => {
+                self.consume()?;
+                let inner = self.parse_cond_or()?;
+                self.skip_newlines();
+                if self.peek() != Some(&Token::RightParen) {
+                    return Err(self.error("Expected ')'".to_string()));
+                }
+                self.consume()?;
+                Ok(inner)
+            }
+            _ => self.parse_cond_primary(),
+        }
+    }
+    
+    fn parse_cond_primary(&mut self) -> Result<CondExpr<'a>, ParseError> {
+        let first = self.cond_word()?;
+        if UNARY_TESTS.contains(&first) && matches!(self.peek(), Some(Token::Word(word)) if word != "]]") {
+            return Ok(CondExpr::Unary(first, self.cond_word()?));
+        }
+        let op = match self.peek() {
+            Some(Token::Redirect(RedirectType::Input)) => "<",
+            Some(Token::Redirect(RedirectType::Output)) => ">",
+            Some(Token::Word(word)) if BINARY_TESTS.contains(&&**word) => self.word(word.clone()),
+            _ => return Ok(CondExpr::Word(first)),
+        };
+        self.consume()?;
+        let right = if op == "=~" { self.cond_regex()? } else { self.cond_word()? };
+        Ok(CondExpr::Binary(first, op, right))
+    }
+    
+    // An operand; `]]` ends the expression rather than being one
+    fn cond_word(&mut self) -> Result<&'a str, ParseError> {
+        match self.peek() {
+            Some(Token::Word(word)) if word != "]]" => self.consume_word(),
+            _ => Err(self.error("Expected an operand in [[ ]]".to_string())),
+        }
+    }
+    
+    // The regex after `=~` is taken as written up to the next blank, since
+    // `(`, `|` and `{1,3}` would otherwise be read as shell syntax
+    fn cond_regex(&mut self) -> Result<&'a str, ParseError> {
+        let first = self.tokens.get(self.position)
+            .filter(|spanned| !matches!(&spanned.token, Token::Newline) && !matches!(&spanned.token, Token::Word(word) if word == "]]"));
+        let Some(first) = first else {
+            return Err(self.error("Expected a regular expression".to_string()));
+        };
+        let (start, mut end) = (first.span.start, first.span.end);
+        self.position += 1;
+        while let Some(next) = self.tokens.get(self.position) {
+            // Brace expansion leaves several words with the same span
+            if next.span.end <= end && next.span.start >= start {
+                self.position += 1;
+                continue;
+            }
+            if next.span.start != end || next.token == Token::Newline {
                 break;
             }
+            end = next.span.end;
+            self.position += 1;
         }
-        
-        if commands.len() == 1 {
-            Ok(CommandType::Simple(commands.into_iter().next().unwrap()))
-        } else {
-            Ok(CommandType::Pipeline(commands))
+        Ok(&self.input[start..end])
+    }
+    
+    // `( list )` runs the list in a forked copy of the shell
+    fn parse_subshell(&mut self) -> Result<CommandType<'a>, ParseError> {
+        self.consume()?;
+        self.open += 1;
+        self.skip_newlines();
+        if let Some(Token::RightParen) = self.peek() {
+            return Err(self.error("Empty subshell".to_string()));
+        }
+        let inner = self.parse_sequence()?;
+        self.skip_newlines();
+        match self.peek() {
+            Some(Token::RightParen) => {
+                self.consume()?;
+            }
+            _ => return Err(self.error("Expected ')'".to_string())),
         }
+        self.open -= 1;
+        Ok(CommandType::Subshell(self.arena.alloc(inner)))
     }
     
-    fn parse_simple_command(&mut self) -> Result<Command, ParseError> {
+    fn parse_simple_command(&mut self) -> Result<SimpleCommand<'a>, ParseError> {
         // Skip whitespace
         while let Some(Token::Newline) = self.peek() {
             self.consume()?;
         }
         
-        let program = match self.consume()? {
-            Token::Word(s) => s,
-            _ => return Err(ParseError::new("Expected command".to_string(), self.position)),
+        let mut env_overrides = ArenaVec::new_in(self.arena);
+        let program = loop {
+            match self.peek() {
+                Some(Token::Word(_)) => {
+                    let word = self.consume_word()?;
+                    match word.split_once('=').filter(|(name, _)| is_valid_var_name(name)) {
+                        Some(assignment) => env_overrides.push(assignment),
+                        None => break word,
+                    }
+                }
+                _ if !env_overrides.is_empty() => break "",
+                _ => return Err(self.error("Expected command".to_string())),
+            }
         };
         
-        let mut command = Command::new(program);
-        
+        let mut args = ArenaVec::new_in(self.arena);
+        let mut redirects = ArenaVec::new_in(self.arena);
         while self.position < self.tokens.len() {
             match self.peek() {
-                Some(Token::Word(s)) => {
-                    let word = s.clone();
-                    self.consume()?;
-                    
-                    // Apply glob expansion
-                    let expanded = expand_glob(&word);
-                    if expanded.is_empty() {
-                        command.args.push(word);
-                    } else {
-                        command.args.extend(expanded);
-                    }
-                }
-                Some(Token::Redirect(redirect_type)) => {
-                    let redirect = redirect_type.clone();
-                    self.consume()?;
-                    
-                    let target = match self.consume()? {
-                        Token::Word(s) => s,
-                        _ => return Err(ParseError::new("Expected redirect target".to_string(), self.position)),
-                    };
-                    
-                    match redirect {
-                        RedirectType::Input => command.stdin_redirect = Some(target),
-                        RedirectType::Output => {
-                            command.stdout_redirect = Some(target);
-                            command.append_stdout = false;
-                        }
-                        RedirectType::Append => {
-                            command.stdout_redirect = Some(target);
-                            command.append_stdout = true;
+                Some(Token::Word(_)) => args.push(self.consume_word()?),
+                Some(Token::Redirect(_)) | Some(Token::IoNumber(_)) => {
+                    let fd = match self.peek() {
+                        Some(Token::IoNumber(fd)) => {
+                            let fd = *fd;
+                            self.consume()?;
+                            Some(fd)
                         }
-                        RedirectType::Error => command.stderr_redirect = Some(target),
-                        RedirectType::ErrorAppend => command.stderr_redirect = Some(target),
+                        _ => None,
+                    };
+                    if !matches!(self.peek(), Some(Token::Redirect(_))) {
+                        return Err(self.error("Expected redirection".to_string()));
                     }
-                }
-                Some(Token::Background) => {
-                    command.background = true;
-                    self.consume()?;
-                    break;
+                    let Token::Redirect(redirect) = self.consume()? else { unreachable!() };
+                    self.parse_redirection(&mut redirects, fd, redirect)?;
                 }
                 Some(Token::Pipe) | Some(Token::And) | Some(Token::Or) | Some(Token::Semicolon) => break,
                 _ => break,
             }
         }
         
-        Ok(command)
+        Ok(SimpleCommand {
+            program,
+            args: args.into_bump_slice(),
+            redirects: redirects.into_bump_slice(),
+            env_overrides: env_overrides.into_bump_slice(),
+        })
     }
     
-    fn peek(&self) -> Option<&Token> {
-        self.tokens.get(self.position)
+    // Turns one redirection operator (and its target word) into the
+    // fd changes it stands for
+    fn parse_redirection(&mut self, redirects: &mut ArenaVec<'a, Redirection<&'a str>>, fd: Option<i32>,
+                         redirect: RedirectType) -> Result<(), ParseError> {
+        if redirect == RedirectType::DupStderrToStdout {
+            redirects.push(Redirection::new(2, RedirectTarget::Dup(1)));
+            return Ok(());
+        }
+        let target_start = self.offset();
+        if !matches!(self.peek(), Some(Token::Word(_))) {
+            return Err(self.error("Expected redirect target".to_string()));
+        }
+        let target = self.consume_word()?;
+        let redirection = match redirect {
+            RedirectType::Input => Redirection::new(fd.unwrap_or(0), RedirectTarget::Read(target)),
+            RedirectType::Output => Redirection::new(fd.unwrap_or(1), RedirectTarget::Write(target)),
+            RedirectType::Append => Redirection::new(fd.unwrap_or(1), RedirectTarget::Append(target)),
+            RedirectType::Error => Redirection::new(2, RedirectTarget::Write(target)),
+            RedirectType::ErrorAppend => Redirection::new(2, RedirectTarget::Append(target)),
+            RedirectType::HereDoc { expand } => {
+                Redirection::new(fd.unwrap_or(0), RedirectTarget::HereDoc(HereDoc { body: target, expand }))
+            }
+            RedirectType::OutputBoth | RedirectType::OutputBothAppend => {
+                let file = if redirect == RedirectType::OutputBoth {
+                    RedirectTarget::Write(target)
+                } else {
+                    RedirectTarget::Append(target)
+                };
+                redirects.push(Redirection::new(1, file));
+                Redirection::new(2, RedirectTarget::Dup(1))
+            }
+            RedirectType::DupOutput | RedirectType::DupInput => {
+                let default_fd = if redirect == RedirectType::DupOutput { 1 } else { 0 };
+                let source = match target {
+                    "-" => RedirectTarget::Close,
+                    n => match n.parse::<i32>() {
+                        Ok(n) if n >= 0 => RedirectTarget::Dup(n),
+                        _ if target.contains(['$', '`']) => RedirectTarget::DupWord(target),
+                        _ => return Err(ParseError::new(format!("{}: bad file descriptor", target), target_start)),
+                    },
+                };
+                Redirection::new(fd.unwrap_or(default_fd), source)
+            }
+            RedirectType::DupStderrToStdout => unreachable!(),
+        };
+        redirects.push(redirection);
+        Ok(())
+    }
+    
+    fn skip_newlines(&mut self) {
+        while let Some(Token::Newline) = self.peek() {
+            self.position += 1;
+        }
+    }
+    
+    fn peek(&self) -> Option<&Token<'a>> {
+        self.tokens.get(self.position).map(|spanned| &spanned.token)
+    }
+    
+    // The next token, which has to be a word, in the arena or the input
+    fn consume_word(&mut self) -> Result<&'a str, ParseError> {
+        let Token::Word(word) = self.consume()? else { unreachable!() };
+        Ok(self.word(word))
+    }
+    
+    // Joined and rewritten words are moved into the arena; the rest are
+    // slices of the input already
+    fn word(&self, word: Cow<'a, str>) -> &'a str {
+        match word {
+            Cow::Borrowed(word) => word,
+            Cow::Owned(word) => {
+                let copy = self.arena.alloc_str(&word);
+                word_pool().release(word);
+                copy
+            }
+        }
     }
     
-    fn consume(&mut self) -> Result<Token, ParseError> {
+    fn consume(&mut self) -> Result<Token<'a>, ParseError> {
         if self.position >= self.tokens.len() {
-            return Err(ParseError::new("Unexpected end of input".to_string(), self.position));
+            return Err(self.error("Unexpected end of input".to_string()));
         }
-        let token = self.tokens[self.position].clone();
+        // Tokens are only ever read at or after `position`, so take instead of cloning
+        let token = std::mem::replace(&mut self.tokens[self.position].token, Token::Newline);
         self.position += 1;
         Ok(token)
     }
     
-    fn tokenize(input: &str) -> Result<Vec<Token>, ParseError> {
-        let mut tokens = Vec::new();
-        let mut chars = input.chars().peekable();
-        let mut current_word = String::new();
-        let mut in_single_quote = false;
-        let mut in_double_quote = false;
+    // Byte offset of the next token, or the end of the input
+    fn offset(&self) -> usize {
+        self.tokens.get(self.position).map_or(self.input.len(), |spanned| spanned.span.start)
+    }
+    
+    // An error pointing at the next token; incomplete if the input ran out
+    // inside a compound command
+    fn error(&self, message: String) -> ParseError {
+        if self.open > 0 && self.position >= self.tokens.len() {
+            return ParseError::incomplete(message, self.offset());
+        }
+        ParseError::new(message, self.offset())
+    }
+    
+    fn tokenize(input: &str) -> Result<Vec<Spanned<'_>>, ParseError> {
+        let mut tokens = Vec::with_capacity(input.len() / 4 + 1);
+        let mut scanner = ZeroCopyParser::new(input);
+        let mut word = WordBuilder::new(input);
+        // Here-documents whose bodies start after the next newline
+        let mut pending: Vec<PendingHereDoc> = Vec::new();
         
-        while let Some(ch) = chars.next() {
-            // Handle quotes
-            if ch == '\'' && !in_double_quote {
-                in_single_quote = !in_single_quote;
+        while let Some(ch) = scanner.peek() {
+            let pos = scanner.position();
+            
+            // An unquoted `#` starting a word comments out the rest of the line
+            if ch == '#' && word.is_empty() {
+                scanner.consume_while(|c| c != '\n');
+                continue;
+            }
+            
+            // Take a whole run of ordinary characters at once
+            if is_word_char(ch) {
+                let run = scanner.consume_while(is_word_char);
+                // `2>` / `2>>` / `2>&1` redirect stderr; the 2 is not part of the word
+                if run.ends_with('2') && scanner.peek() == Some('>') {
+                    word.push_str(pos, &run[..run.len() - 1]);
+                    word.flush(&mut tokens);
+                    let start = scanner.position() - 1;
+                    scanner.advance(1);
+                    let redirect = if scanner.remaining().starts_with("&1") {
+                        scanner.advance(2);
+                        RedirectType::DupStderrToStdout
+                    } else if next_is(&mut scanner, '>') {
+                        RedirectType::ErrorAppend
+                    } else {
+                        RedirectType::Error
+                    };
+                    tokens.push(Spanned::new(Token::Redirect(redirect), start..scanner.position()));
+                } else if word.is_empty() && matches!(scanner.peek(), Some('<' | '>'))
+                    && run.bytes().all(|b| b.is_ascii_digit()) {
+                    match run.parse::<i32>() {
+                        Ok(fd) => tokens.push(Spanned::new(Token::IoNumber(fd), pos..scanner.position())),
+                        Err(_) => word.push_str(pos, run),
+                    }
+                } else {
+                    word.push_str(pos, run);
+                    // extglob groups like `*.@(jpg|png)` are part of the word
+                    if run.ends_with(['?', '*', '+', '@', '!']) {
+                        if let Some(len) = ext_group_len(scanner.remaining()) {
+                            word.push_str(scanner.position(), &scanner.remaining()[..len]);
+                            scanner.advance(len);
+                        }
+                    }
+                }
                 continue;
             }
-            if ch == '"' && !in_single_quote {
-                in_double_quote = !in_double_quote;
+            
+            // A backslash-newline joins two lines, even in the middle of a word
+            if scanner.remaining().starts_with("\\\n") {
+                scanner.advance(2);
                 continue;
             }
             
-            if in_single_quote || in_double_quote {
-                current_word.push(ch);
+            // Quotes, escapes and substitutions stay in the word as written,
+            // however many lines they span
+            if matches!(ch, '\'' | '"' | '`' | '$' | '\\') {
+                let Some(len) = quoted_len(scanner.remaining()) else {
+                    let what = if ch == '\'' || ch == '"' { "quote" } else { "substitution" };
+                    return Err(ParseError::incomplete(format!("Unterminated {}", what), pos));
+                };
+                word.push_str(pos, &scanner.remaining()[..len]);
+                scanner.advance(len);
                 continue;
             }
             
-            match ch {
-                ' ' | '\t' => {
-                    if !current_word.is_empty() {
-                        tokens.push(Token::Word(current_word.clone()));
-                        current_word.clear();
-                    }
+            // `((` starting a command is arithmetic, unless the parentheses
+            // close separately as nested subshells
+            if ch == '(' && word.is_empty() && at_command_start(&tokens) {
+                if let Some(len) = arithmetic_len(scanner.remaining()) {
+                    let expr = Cow::Borrowed(&input[pos + 2..pos + len - 2]);
+                    tokens.push(Spanned::new(Token::Arithmetic(expr), pos..pos + len));
+                    scanner.advance(len);
+                    continue;
                 }
+            }
+            
+            scanner.advance(ch.len_utf8());
+            word.flush(&mut tokens);
+            let token = match ch {
+                ' ' | '\t' => continue,
                 '\n' => {
-                    if !current_word.is_empty() {
-                        tokens.push(Token::Word(current_word.clone()));
-                        current_word.clear();
-                    }
-                    tokens.push(Token::Newline);
-                }
-                '|' => {
-                    if chars.peek() == Some(&'|') {
-                        chars.next();
-                        if !current_word.is_empty() {
-                            tokens.push(Token::Word(current_word.clone()));
-                            current_word.clear();
-                        }
-                        tokens.push(Token::Or);
-                    } else {
-                        if !current_word.is_empty() {
-                            tokens.push(Token::Word(current_word.clone()));
-                            current_word.clear();
-                        }
-                        tokens.push(Token::Pipe);
+                    tokens.push(Spanned::new(Token::Newline, pos..pos + 1));
+                    for here_doc in pending.drain(..) {
+                        let body = read_here_doc_body(&mut scanner, &here_doc)?;
+                        tokens[here_doc.token].token = Token::Word(Cow::Owned(body));
                     }
+                    continue;
                 }
-                '&' => {
-                    if chars.peek() == Some(&'&') {
-                        chars.next();
-                        if !current_word.is_empty() {
-                            tokens.push(Token::Word(current_word.clone()));
-                            current_word.clear();
-                        }
-                        tokens.push(Token::And);
+                '|' if next_is(&mut scanner, '|') => Token::Or,
+                '|' if next_is(&mut scanner, '&') => Token::PipeBoth,
+                '|' => Token::Pipe,
+                '&' if next_is(&mut scanner, '&') => Token::And,
+                '&' if next_is(&mut scanner, '>') => {
+                    if next_is(&mut scanner, '>') {
+                        Token::Redirect(RedirectType::OutputBothAppend)
                     } else {
-                        if !current_word.is_empty() {
-                            tokens.push(Token::Word(current_word.clone()));
-                            current_word.clear();
-                        }
-                        tokens.push(Token::Background);
+                        Token::Redirect(RedirectType::OutputBoth)
                     }
                 }
-                '<' => {
-                    if !current_word.is_empty() {
-                        tokens.push(Token::Word(current_word.clone()));
-                        current_word.clear();
-                    }
-                    tokens.push(Token::Redirect(RedirectType::Input));
-                }
-                '>' => {
-                    if !current_word.is_empty() {
-                        tokens.push(Token::Word(current_word.clone()));
-                        current_word.clear();
-                    }
-                    if chars.peek() == Some(&'>') {
-                        chars.next();
-                        tokens.push(Token::Redirect(RedirectType::Append));
-                    } else {
-                        tokens.push(Token::Redirect(RedirectType::Output));
-                    }
-                }
-                '2' if chars.peek() == Some(&'>') => {
-                    chars.next(); // consume '>'
-                    if !current_word.is_empty() {
-                        tokens.push(Token::Word(current_word.clone()));
-                        current_word.clear();
-                    }
-                    if chars.peek() == Some(&'>') {
-                        chars.next();
-                        tokens.push(Token::Redirect(RedirectType::ErrorAppend));
-                    } else {
-                        tokens.push(Token::Redirect(RedirectType::Error));
-                    }
-                }
-                ';' => {
-                    if !current_word.is_empty() {
-                        tokens.push(Token::Word(current_word.clone()));
-                        current_word.clear();
-                    }
-                    tokens.push(Token::Semicolon);
+                '&' => Token::Background,
+                '<' if next_is(&mut scanner, '<') => {
+                    // The body is filled in once the line ends; until
+                    // then the delimiter's slot holds an empty word
+                    let strip_tabs = next_is(&mut scanner, '-');
+                    let operator_end = scanner.position();
+                    let Some((delimiter, quoted)) = read_here_doc_delimiter(&mut scanner) else {
+                        return Err(ParseError::new("Expected here-document delimiter".to_string(), scanner.position()));
+                    };
+                    tokens.push(Spanned::new(Token::Redirect(RedirectType::HereDoc { expand: !quoted }), pos..operator_end));
+                    pending.push(PendingHereDoc { token: tokens.len(), delimiter, strip_tabs });
+                    tokens.push(Spanned::new(Token::Word(Cow::Borrowed("")), operator_end..scanner.position()));
+                    continue;
                 }
-                '(' => {
-                    if !current_word.is_empty() {
-                        tokens.push(Token::Word(current_word.clone()));
-                        current_word.clear();
-                    }
-                    tokens.push(Token::LeftParen);
+                '<' if next_is(&mut scanner, '&') => Token::Redirect(RedirectType::DupInput),
+                '<' => Token::Redirect(RedirectType::Input),
+                '>' if next_is(&mut scanner, '>') => Token::Redirect(RedirectType::Append),
+                '>' if next_is(&mut scanner, '&') => Token::Redirect(RedirectType::DupOutput),
+                '>' => Token::Redirect(RedirectType::Output),
+                ';' => Token::Semicolon,
+                '(' => Token::LeftParen,
+                ')' => Token::RightParen,
+                _ => unreachable!("'{}' is a word character", ch),
+            };
+            tokens.push(Spanned::new(token, pos..scanner.position()));
+        }
+        
+        word.flush(&mut tokens);
+        
+        if let Some(here_doc) = pending.first() {
+            return Err(ParseError::incomplete(format!("Here-document ended before '{}'", here_doc.delimiter), input.len()));
+        }
+        Ok(tokens)
+    }
+}
+
+// Copies `tokens` to `out`, replacing each unquoted word in command
+// position that names an alias with the tokens of its value, expanded in
+// turn. An alias is not expanded again inside its own value, and a value
+// ending in a blank makes the next word a candidate too (`alias sudo='sudo '`).
+// Tokens from a value take the span of the alias word (`span`). Returns
+// whether the word after the tokens would be in command position.
+fn splice_aliases<'a>(tokens: Vec<Spanned<'a>>, aliases: &AliasMap, active: &mut Vec<Arc<str>>,
+                      out: &mut Vec<Spanned<'a>>, mut command_position: bool,
+                      span: Option<&Range<usize>>) -> Result<bool, ParseError> {
+    // Words between `[[` and `]]` are operands, never commands
+    let mut conditional = false;
+    for spanned in tokens {
+        let span = span.unwrap_or(&spanned.span).clone();
+        if conditional {
+            conditional = !matches!(&spanned.token, Token::Word(word) if word == "]]");
+            out.push(Spanned::new(spanned.token, span));
+            continue;
+        }
+        if let Token::Word(word) = &spanned.token {
+            let alias = aliases.get_key_value(&**word).filter(|(name, _)| !active.contains(name));
+            if let Some((name, value)) = alias.filter(|_| command_position) {
+                let value_tokens = Parser::tokenize(value)
+                    .map_err(|e| ParseError::new(format!("alias {}: {}", name, e.message), span.start))?;
+                let value_tokens = value_tokens.into_iter()
+                    .map(|t| Spanned::new(t.token.into_owned(), t.span))
+                    .collect();
+                active.push(name.clone());
+                let next = splice_aliases(value_tokens, aliases, active, out, true, Some(&span))?;
+                active.pop();
+                command_position = next || value.ends_with([' ', '\t']);
+                continue;
+            }
+        }
+        command_position = match &spanned.token {
+            Token::Word(word) if command_position && word == "[[" => {
+                conditional = true;
+                false
+            }
+            // `A=1 ll`, `! ll`, `coproc ll` and `then ll` still expand `ll`
+            Token::Word(word) => command_position && (word == "!" || word == "coproc"
+                || COMMAND_KEYWORDS.contains(&&**word) || word.split_once('=')
+                .is_some_and(|(name, _)| is_valid_var_name(name))),
+            Token::Redirect(_) | Token::IoNumber(_) | Token::RightParen | Token::Arithmetic(_) => false,
+            _ => true,
+        };
+        out.push(Spanned::new(spanned.token, span));
+    }
+    Ok(command_position)
+}
+
+// True when `input` stops inside a quote, a here-document or a compound
+// command, or ends in a `\` or an operator (`|`, `&&`, `||`) that needs
+// another command, so another line has to be read before it can be parsed.
+// Nothing is executed.
+pub fn needs_more_input(input: &str) -> bool {
+    match Parser::tokenize(input) {
+        Err(e) => e.incomplete,
+        Ok(tokens) => {
+            let continued = match tokens.iter().rev().map(|spanned| &spanned.token).find(|token| **token != Token::Newline) {
+                // A backslash in a trailing comment is not a continuation
+                Some(Token::Word(_)) => {
+                    tokens.last().is_some_and(|last| last.span.end == input.len())
+                        && input.bytes().rev().take_while(|&b| b == b'\\').count() % 2 == 1
                 }
-                ')' => {
-                    if !current_word.is_empty() {
-                        tokens.push(Token::Word(current_word.clone()));
-                        current_word.clear();
-                    }
-                    tokens.push(Token::RightParen);
+                Some(Token::Pipe | Token::PipeBoth | Token::And | Token::Or) => true,
+                _ => false,
+            };
+            // Otherwise an `if`, a loop, a function or a `(` may still be open
+            continued || Parser { tokens, position: 0, input, arena: &Bump::new(), open: 0 }.parse().is_err_and(|e| e.incomplete)
+        }
+    }
+}
+
+struct PendingHereDoc {
+    // Index of the word token that receives the body
+    token: usize,
+    delimiter: String,
+    // `<<-` strips leading tabs from the body and the delimiter line
+    strip_tabs: bool,
+}
+
+// The word after `<<`, unquoted. Any quoting turns off expansion in the body.
+fn read_here_doc_delimiter(scanner: &mut ZeroCopyParser<'_>) -> Option<(String, bool)> {
+    scanner.consume_while(|c| c == ' ' || c == '\t');
+    let mut delimiter = String::new();
+    let mut quoted = false;
+    while let Some(ch) = scanner.peek() {
+        match ch {
+            '\'' | '"' => {
+                quoted = true;
+                scanner.advance(1);
+                delimiter.push_str(scanner.consume_while(|c| c != ch));
+                next_is(scanner, ch);
+            }
+            '\\' => {
+                quoted = true;
+                scanner.advance(1);
+                if let Some(next) = scanner.peek() {
+                    delimiter.push(next);
+                    scanner.advance(next.len_utf8());
                 }
-                '\\' if chars.peek().is_some() => {
-                    // Handle escape sequences
-                    if let Some(next_ch) = chars.next() {
-                        current_word.push(next_ch);
-                    }
+            }
+            c if is_word_char(c) => delimiter.push_str(scanner.consume_while(is_word_char)),
+            _ => break,
+        }
+    }
+    (!delimiter.is_empty()).then_some((delimiter, quoted))
+}
+
+// Consumes body lines up to and including the delimiter line
+fn read_here_doc_body(scanner: &mut ZeroCopyParser<'_>, here_doc: &PendingHereDoc) -> Result<String, ParseError> {
+    let mut body = String::new();
+    loop {
+        let rest = scanner.remaining();
+        if rest.is_empty() {
+            return Err(ParseError::incomplete(format!("Here-document ended before '{}'", here_doc.delimiter),
+                                              scanner.position()));
+        }
+        let (line, len) = match rest.find('\n') {
+            Some(end) => (&rest[..end], end + 1),
+            None => (rest, rest.len()),
+        };
+        scanner.advance(len);
+        let line = if here_doc.strip_tabs { line.trim_start_matches('\t') } else { line };
+        if line == here_doc.delimiter {
+            return Ok(body);
+        }
+        body.push_str(line);
+        body.push('\n');
+    }
+}
+
+// How a token is shown in error messages
+fn describe(token: &Token<'_>) -> String {
+    let text = match token {
+        Token::Word(word) => return format!("word '{}'", word),
+        Token::Arithmetic(_) => "((",
+        Token::Pipe => "|",
+        Token::PipeBoth => "|&",
+        Token::Redirect(RedirectType::Input) => "<",
+        Token::Redirect(RedirectType::Output) => ">",
+        Token::Redirect(RedirectType::Append) => ">>",
+        Token::Redirect(RedirectType::Error) => "2>",
+        Token::Redirect(RedirectType::ErrorAppend) => "2>>",
+        Token::Redirect(RedirectType::HereDoc { .. }) => "<<",
+        Token::Redirect(RedirectType::DupStderrToStdout) => "2>&1",
+        Token::Redirect(RedirectType::OutputBoth) => "&>",
+        Token::Redirect(RedirectType::OutputBothAppend) => "&>>",
+        Token::Redirect(RedirectType::DupOutput) => ">&",
+        Token::Redirect(RedirectType::DupInput) => "<&",
+        Token::IoNumber(fd) => return format!("'{}'", fd),
+        Token::And => "&&",
+        Token::Or => "||",
+        Token::Background => "&",
+        Token::Semicolon => ";",
+        Token::LeftParen => "(",
+        Token::RightParen => ")",
+        Token::Newline => "newline",
+    };
+    format!("'{}'", text)
+}
+
+fn is_word_char(ch: char) -> bool {
+    !matches!(ch, ' ' | '\t' | '\n' | '|' | '&' | '<' | '>' | ';' | '(' | ')' | '\\' | '\'' | '"' | '`' | '$')
+}
+
+// Byte length of a parenthesised pattern group at the start of `text`, or
+// None if it is not closed on this line
+fn ext_group_len(text: &str) -> Option<usize> {
+    if !text.starts_with('(') {
+        return None;
+    }
+    let mut depth = 0;
+    let mut i = 0;
+    while i < text.len() {
+        match text.as_bytes()[i] {
+            b'(' => depth += 1,
+            b')' => {
+                depth -= 1;
+                if depth == 0 {
+                    return Some(i + 1);
                 }
-                _ => current_word.push(ch),
             }
+            b'\n' => return None,
+            b'\'' | b'"' | b'\\' | b'`' | b'$' => {
+                i += quoted_len(&text[i..])?;
+                continue;
+            }
+            _ => {}
         }
-        
-        if !current_word.is_empty() {
-            tokens.push(Token::Word(current_word));
+        i += 1;
+    }
+    None
+}
+
+// Whether a token starting here would be in command position
+fn at_command_start(tokens: &[Spanned<'_>]) -> bool {
+    match tokens.last().map(|spanned| &spanned.token) {
+        None => true,
+        Some(Token::Word(word)) => word == "!" || COMMAND_KEYWORDS.contains(&&**word),
+        Some(token) => matches!(token, Token::Newline | Token::Semicolon | Token::And | Token::Or
+            | Token::Pipe | Token::PipeBoth | Token::Background | Token::LeftParen),
+    }
+}
+
+// Byte length of `(( expression ))` at the start of `text`, or None if it
+// is not one: the first `)` that closes the outer pair must be followed by
+// another, which `((cd /tmp); ls)` is not
+pub(crate) fn arithmetic_len(text: &str) -> Option<usize> {
+    if !text.starts_with("((") {
+        return None;
+    }
+    let bytes = text.as_bytes();
+    let mut depth = 0;
+    let mut i = 2;
+    while let Some(&b) = bytes.get(i) {
+        match b {
+            b'(' => depth += 1,
+            b')' if depth > 0 => depth -= 1,
+            b')' => return (bytes.get(i + 1) == Some(&b')')).then_some(i + 2),
+            b'\\' | b'\'' | b'"' | b'`' | b'$' => {
+                i += quoted_len(&text[i..])?;
+                continue;
+            }
+            _ => {}
+        }
+        i += 1;
+    }
+    None
+}
+
+// Byte length of the quoted string, escape or substitution at the start of
+// `text` (`'..'`, `".."`, `\x`, `` `..` ``, `$(..)`, `${..}`), or None if it
+// is not closed. Any other character, a lone `$` included, is just itself.
+pub(crate) fn quoted_len(text: &str) -> Option<usize> {
+    let bytes = text.as_bytes();
+    match bytes.first()? {
+        b'\'' => text[1..].find('\'').map(|end| end + 2),
+        b'\\' => Some(1 + text[1..].chars().next().map_or(0, char::len_utf8)),
+        b'"' => scan_to(text, 1, b'"'),
+        b'`' => scan_to(text, 1, b'`'),
+        b'$' => match bytes.get(1) {
+            Some(b'(') => scan_to(text, 2, b')'),
+            Some(b'{') => scan_to(text, 2, b'}'),
+            _ => Some(1),
+        },
+        _ => text.chars().next().map(char::len_utf8),
+    }
+}
+
+// Finds the `close` that ends the construct begun before `start`, stepping
+// over whatever is nested inside it; the length includes `close`
+fn scan_to(text: &str, start: usize, close: u8) -> Option<usize> {
+    let bytes = text.as_bytes();
+    let mut depth = 0;
+    let mut i = start;
+    while let Some(&b) = bytes.get(i) {
+        match b {
+            _ if b == close && depth == 0 => return Some(i + 1),
+            b'\\' => i += quoted_len(&text[i..])?,
+            // Only a backslash means anything between backticks
+            _ if close == b'`' => i += 1,
+            // and single quotes are literal between double quotes
+            b'\'' if close == b'"' => i += 1,
+            b'\'' | b'"' | b'`' | b'$' => i += quoted_len(&text[i..])?,
+            b'(' if close == b')' => {
+                depth += 1;
+                i += 1;
+            }
+            b')' if close == b')' => {
+                depth -= 1;
+                i += 1;
+            }
+            _ => i += 1,
+        }
+    }
+    None
+}
+
+fn next_is(scanner: &mut ZeroCopyParser<'_>, expected: char) -> bool {
+    if scanner.peek() == Some(expected) {
+        scanner.advance(expected.len_utf8());
+        true
+    } else {
+        false
+    }
+}
+
+// Accumulates one word: a contiguous slice of the input, since quotes and
+// escapes are kept as written, unless a line continuation splits it
+struct WordBuilder<'a> {
+    input: &'a str,
+    span: Option<(usize, usize)>,
+    // The word so far, once text that does not follow on has been added
+    joined: Option<String>,
+}
+
+impl<'a> WordBuilder<'a> {
+    fn new(input: &'a str) -> Self {
+        Self { input, span: None, joined: None }
+    }
+    
+    // Appends `text`, which starts at byte `pos` of the input
+    fn push_str(&mut self, pos: usize, text: &str) {
+        if text.is_empty() {
+            return;
+        }
+        if let Some(joined) = self.joined.as_mut() {
+            joined.push_str(text);
+            return;
+        }
+        let end = pos + text.len();
+        match self.span {
+            Some((start, span_end)) if span_end != pos => {
+                let mut joined = word_pool().acquire();
+                joined.push_str(&self.input[start..span_end]);
+                joined.push_str(text);
+                self.joined = Some(joined);
+            }
+            Some((start, _)) => self.span = Some((start, end)),
+            None => self.span = Some((pos, end)),
+        }
+    }
+    
+    fn is_empty(&self) -> bool {
+        self.span.is_none()
+    }
+    
+    fn flush(&mut self, tokens: &mut Vec<Spanned<'a>>) {
+        let Some((start, end)) = self.span.take() else { return };
+        let text = match self.joined.take() {
+            Some(joined) => Cow::Owned(joined),
+            None => Cow::Borrowed(&self.input[start..end]),
+        };
+        match expand_braces(&text) {
+            Some(words) => tokens.extend(words.into_iter().map(|w| Spanned::new(Token::Word(Cow::Owned(w)), start..end))),
+            None => tokens.push(Spanned::new(Token::Word(text), start..end)),
+        }
+    }
+}
+
+impl Drop for Parser<'_> {
+    fn drop(&mut self) {
+        for spanned in self.tokens.drain(..) {
+            if let Token::Word(Cow::Owned(word)) = spanned.token {
+                word_pool().release(word);
+            }
         }
-        
-        Ok(tokens)
     }
 }
 
@@ -301,88 +1194,592 @@ impl Parser {
 pub struct ParseError {
     pub message: String,
     pub position: usize,
+    // The input is fine so far but ends too early (open quote, here-document)
+    pub incomplete: bool,
 }
 
 impl ParseError {
     pub fn new(message: String, position: usize) -> Self {
-        Self { message, position }
+        Self { message, position, incomplete: false }
+    }
+    
+    pub fn incomplete(message: String, position: usize) -> Self {
+        Self { message, position, incomplete: true }
+    }
+    
+    // The line of `input` holding the error, with a caret under it:
+    //   ls > | wc
+    //        ^ Expected redirect target
+    pub fn render(&self, input: &str) -> String {
+        let position = self.position.min(input.len());
+        let line_start = input[..position].rfind('\n').map_or(0, |i| i + 1);
+        let line_end = input[position..].find('\n').map_or(input.len(), |i| position + i);
+        // Tabs stay tabs so the caret lines up however wide they are
+        let padding: String = input[line_start..position].chars()
+            .map(|c| if c == '\t' { '\t' } else { ' ' })
+            .collect();
+        format!("{}\n{}^ {}", &input[line_start..line_end], padding, self.message)
     }
 }
 
-// Command substitution: Handle $(...) and `...`
-fn expand_command_substitution(input: &str) -> Result<String, ParseError> {
-    let mut result = String::new();
-    let mut chars = input.chars().peekable();
-    
-    while let Some(ch) = chars.next() {
-        if ch == '$' && chars.peek() == Some(&'(') {
-            chars.next(); // consume '('
-            let mut depth = 1;
-            let mut cmd = String::new();
-            
-            while let Some(ch) = chars.next() {
-                if ch == '(' {
-                    depth += 1;
-                    cmd.push(ch);
-                } else if ch == ')' {
-                    depth -= 1;
-                    if depth == 0 {
-                        break;
-                    }
-                    cmd.push(ch);
-                } else {
-                    cmd.push(ch);
-                }
+// Brace expansion: `pre{a,b}post` and `{1..10}`, `{01..10..3}`, `{a..e}`.
+// None when the word has no expandable group, e.g. `${HOME}`, `{x}` or
+// `'{a,b}'`: braces and commas only count outside quotes.
+pub fn expand_braces(word: &str) -> Option<Vec<String>> {
+    if !word.contains('{') {
+        return None;
+    }
+    let chars = unquoted_chars(word);
+    for &(open, _) in chars.iter().filter(|(_, ch)| *ch == '{') {
+        let Some(close) = matching_brace(&chars, open) else {
+            continue;
+        };
+        let inner = &word[open + 1..close];
+        let Some(alternatives) = brace_alternatives(inner) else {
+            continue;
+        };
+        let (prefix, suffix) = (&word[..open], &word[close + 1..]);
+        let mut words = Vec::new();
+        for alternative in alternatives {
+            let candidate = format!("{}{}{}", prefix, alternative, suffix);
+            match expand_braces(&candidate) {
+                Some(expanded) => words.extend(expanded),
+                None => words.push(candidate),
             }
-            
-            // Execute the command and get its output
-            let output = execute_command_substitution(&cmd)?;
-            result.push_str(&output);
-        } else if ch == '`' {
-            let mut cmd = String::new();
-            while let Some(ch) = chars.next() {
-                if ch == '`' {
-                    break;
+        }
+        return Some(words);
+    }
+    None
+}
+
+// The characters of `word` that are not quoted, escaped or inside a
+// substitution, with their byte offsets
+fn unquoted_chars(word: &str) -> Vec<(usize, char)> {
+    let mut chars = Vec::new();
+    let mut i = 0;
+    while let Some(ch) = word[i..].chars().next() {
+        let len = quoted_len(&word[i..]).unwrap_or(word.len() - i);
+        if len == ch.len_utf8() {
+            chars.push((i, ch));
+        }
+        i += len;
+    }
+    chars
+}
+
+fn matching_brace(chars: &[(usize, char)], open: usize) -> Option<usize> {
+    let mut depth = 0;
+    for &(i, ch) in chars.iter().skip_while(|(i, _)| *i < open) {
+        match ch {
+            '{' => depth += 1,
+            '}' => {
+                depth -= 1;
+                if depth == 0 {
+                    return Some(i);
                 }
-                cmd.push(ch);
             }
-            let output = execute_command_substitution(&cmd)?;
-            result.push_str(&output);
-        } else {
-            result.push(ch);
+            _ => {}
+        }
+    }
+    None
+}
+
+// Top-level comma list (at least two parts) or a sequence
+fn brace_alternatives(inner: &str) -> Option<Vec<String>> {
+    let mut parts = Vec::new();
+    let mut depth = 0;
+    let mut start = 0;
+    for (i, ch) in unquoted_chars(inner) {
+        match ch {
+            '{' => depth += 1,
+            '}' => depth -= 1,
+            ',' if depth == 0 => {
+                parts.push(inner[start..i].to_string());
+                start = i + 1;
+            }
+            _ => {}
         }
     }
+    if !parts.is_empty() {
+        parts.push(inner[start..].to_string());
+        return Some(parts);
+    }
+    brace_sequence(inner)
+}
+
+fn brace_sequence(inner: &str) -> Option<Vec<String>> {
+    let bounds: Vec<&str> = inner.split("..").collect();
+    if !(2..=3).contains(&bounds.len()) {
+        return None;
+    }
+    let step = match bounds.get(2) {
+        Some(step) => step.parse::<i64>().ok()?.unsigned_abs().max(1),
+        None => 1,
+    };
+    let (first, last) = (bounds[0], bounds[1]);
     
-    Ok(result)
+    if let (Ok(from), Ok(to)) = (first.parse::<i64>(), last.parse::<i64>()) {
+        // A leading zero on either end pads every number to the same width
+        let padded = |s: &str| s.trim_start_matches('-').len() > 1 && s.trim_start_matches('-').starts_with('0');
+        let width = if padded(first) || padded(last) { first.len().max(last.len()) } else { 0 };
+        return Some(stepped(from, to, step).map(|n| format!("{:0width$}", n, width = width)).collect());
+    }
+    
+    let single = |s: &str| {
+        let mut chars = s.chars();
+        match (chars.next(), chars.next()) {
+            (Some(c), None) if c.is_ascii_alphabetic() => Some(c as i64),
+            _ => None,
+        }
+    };
+    let (from, to) = (single(first)?, single(last)?);
+    Some(stepped(from, to, step).map(|c| (c as u8 as char).to_string()).collect())
+}
+
+fn stepped(from: i64, to: i64, step: u64) -> impl Iterator<Item = i64> {
+    let count = from.abs_diff(to) / step + 1;
+    let step = if to < from { -(step as i64) } else { step as i64 };
+    (0..count as i64).map(move |i| from + i * step)
+}
+
+pub fn is_keyword(word: &str) -> bool {
+    KEYWORDS.contains(&word)
+}
+
+// Anything unquoted and unexpanded that isn't an assignment or reserved word
+fn is_function_name(word: &str) -> bool {
+    !word.is_empty() && !word.contains(['\'', '"', '\\', '$', '`', '=']) && !is_keyword(word)
 }
 
-fn execute_command_substitution(cmd: &str) -> Result<String, ParseError> {
-    use std::process::Command;
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn words<'a>(tokens: &'a [Spanned<'a>]) -> Vec<&'a Cow<'a, str>> {
+        tokens.iter().filter_map(|t| match &t.token { Token::Word(w) => Some(w), _ => None }).collect()
+    }
+
+    #[test]
+    fn plain_words_borrow_from_input() {
+        let tokens = Parser::tokenize("ls -la | grep foo>out").unwrap();
+        let words = words(&tokens);
+        assert_eq!(words, vec!["ls", "-la", "grep", "foo", "out"]);
+        assert!(words.iter().all(|w| matches!(w, Cow::Borrowed(_))));
+    }
+
+    #[test]
+    fn stderr_redirect_splits_off_the_two() {
+        let tokens = Parser::tokenize("make 2>err.log foo2>>x").unwrap();
+        let tokens: Vec<_> = tokens.into_iter().map(|spanned| spanned.token).collect();
+        assert_eq!(tokens, vec![
+            Token::Word(Cow::Borrowed("make")),
+            Token::Redirect(RedirectType::Error),
+            Token::Word(Cow::Borrowed("err.log")),
+            Token::Word(Cow::Borrowed("foo")),
+            Token::Redirect(RedirectType::ErrorAppend),
+            Token::Word(Cow::Borrowed("x")),
+        ]);
+    }
+
+    fn redirects(line: &str) -> Vec<Redirection> {
+        let arena = Bump::new();
+        let parsed = Parser::new(line, &arena).unwrap().parse().unwrap();
+        match parsed {
+            CommandType::Simple(command) => command.redirects.iter().map(|redirection| redirection.owned()).collect(),
+            other => panic!("{:?}", other),
+        }
+    }
+
+    #[test]
+    fn stderr_can_follow_stdout() {
+        assert_eq!(redirects("make > log 2>&1"), [
+            Redirection::new(1, RedirectTarget::Write("log".to_string())),
+            Redirection::new(2, RedirectTarget::Dup(1)),
+        ]);
+        assert_eq!(redirects("cc &>> all.log"), [
+            Redirection::new(1, RedirectTarget::Append("all.log".to_string())),
+            Redirection::new(2, RedirectTarget::Dup(1)),
+        ]);
+    }
+    
+    #[test]
+    fn numbered_redirections() {
+        let arena = Bump::new();
+        assert_eq!(redirects("cmd 3< in 4>&1 >&2 5>>log 3<&- x9>y"), [
+            Redirection::new(3, RedirectTarget::Read("in".to_string())),
+            Redirection::new(4, RedirectTarget::Dup(1)),
+            Redirection::new(1, RedirectTarget::Dup(2)),
+            Redirection::new(5, RedirectTarget::Append("log".to_string())),
+            Redirection::new(3, RedirectTarget::Close),
+            Redirection::new(1, RedirectTarget::Write("y".to_string())),
+        ]);
+        assert!(Parser::new("cmd >&x", &arena).unwrap().parse().is_err());
+    }
+    
+    #[test]
+    fn leading_assignments_are_env_overrides() {
+        let arena = Bump::new();
+        let parsed = Parser::new("A=1 B= env --x=y; C=3", &arena).unwrap().parse().unwrap();
+        let CommandType::Sequence(commands) = parsed else { panic!("{:?}", parsed) };
+        let CommandType::Simple(env) = &commands[0] else { panic!() };
+        assert_eq!(env.program, "env");
+        assert_eq!(env.args, ["--x=y"]);
+        assert_eq!(env.env_overrides, [("A", "1"), ("B", "")]);
+        let CommandType::Simple(assign) = &commands[1] else { panic!() };
+        assert!(assign.program.is_empty() && assign.env_overrides.len() == 1);
+    }
+    
+    #[test]
+    fn quotes_and_substitutions_stay_in_the_word() {
+        let tokens = Parser::tokenize(r#"echo "a b" pre'fix' x\ y $(echo ")" a;b) "${X} `c d`"|wc"#).unwrap();
+        let split = words(&tokens);
+        assert_eq!(split, vec!["echo", "\"a b\"", "pre'fix'", "x\\ y", "$(echo \")\" a;b)", "\"${X} `c d`\"", "wc"]);
+        assert!(split.iter().all(|w| matches!(w, Cow::Borrowed(_))));
+        assert!(needs_more_input("echo $(ls"));
+        assert!(needs_more_input("echo \"$(echo ')')"));
+        let tokens = Parser::tokenize("ls *.@(jpg|png) !(*.o)x|wc").unwrap();
+        assert_eq!(words(&tokens), vec!["ls", "*.@(jpg|png)", "!(*.o)x", "wc"]);
+    }
+
+    #[test]
+    fn semicolons_sequence_commands() {
+        let arena = Bump::new();
+        let parsed = Parser::new("cd /tmp; ls -l && pwd;", &arena).unwrap().parse().unwrap();
+        let CommandType::Sequence(commands) = parsed else { panic!("{:?}", parsed) };
+        assert_eq!(commands.len(), 2);
+        assert!(matches!(&commands[0], CommandType::Simple(c) if c.program == "cd"));
+        assert!(matches!(commands[1], CommandType::And(..)));
+        
+        let parsed = Parser::new("sleep 1 & echo hi", &arena).unwrap().parse().unwrap();
+        assert!(matches!(parsed, CommandType::Sequence(c) if c.len() == 2));
+        // `&` applies to the whole and-or list before it
+        let parsed = Parser::new("sleep 1 | cat && (a; b) & ls", &arena).unwrap().parse().unwrap();
+        let CommandType::Sequence(commands) = parsed else { panic!("{:?}", parsed) };
+        assert!(matches!(&commands[0], CommandType::Background(inner) if matches!(**inner, CommandType::And(..))));
+        assert_eq!(commands[0].to_string(), "sleep 1 | cat && (a; b) &");
+        assert!(Parser::new("ls & &", &arena).unwrap().parse().is_err());
+        assert!(Parser::new("; ls", &arena).unwrap().parse().is_err());
+        assert!(Parser::new("ls ;; pwd", &arena).unwrap().parse().is_err());
+    }
+    
+    #[test]
+    fn parentheses_make_subshells() {
+        let arena = Bump::new();
+        let parsed = Parser::new("(cd /tmp && ls); pwd", &arena).unwrap().parse().unwrap();
+        let CommandType::Sequence(commands) = parsed else { panic!("{:?}", parsed) };
+        assert!(matches!(&commands[0], CommandType::Subshell(inner) if matches!(**inner, CommandType::And(..))));
+        assert!(Parser::new("(ls", &arena).unwrap().parse().is_err());
+        assert!(Parser::new("()", &arena).unwrap().parse().is_err());
+        
+        let parsed = Parser::new("(ls; pwd) |\n wc -l | (cat)", &arena).unwrap().parse().unwrap();
+        let CommandType::Pipeline(stages) = parsed else { panic!("{:?}", parsed) };
+        let kinds: Vec<_> = stages.iter().map(|s| (matches!(s.command, CommandType::Simple(_)), s.pipe_stderr)).collect();
+        assert_eq!(kinds, [(false, false), (true, false), (false, false)]);
+        
+        let parsed = Parser::new("! grep -q x f && ! (false) | cat", &arena).unwrap().parse().unwrap();
+        let CommandType::And(left, right) = parsed else { panic!("{:?}", parsed) };
+        assert!(matches!(*left, CommandType::Not(inner) if matches!(inner, CommandType::Simple(_))));
+        assert!(matches!(*right, CommandType::Not(inner) if matches!(inner, CommandType::Pipeline(_))));
+        assert!(Parser::new("!", &arena).unwrap().parse().is_err());
+        
+        let parsed = Parser::new("make |& grep error | less", &arena).unwrap().parse().unwrap();
+        let CommandType::Pipeline(stages) = parsed else { panic!("{:?}", parsed) };
+        assert_eq!(stages.iter().map(|s| s.pipe_stderr).collect::<Vec<_>>(), [true, false, false]);
+    }
+    
+    #[test]
+    fn here_documents_collect_their_body() {
+        let arena = Bump::new();
+        let here_doc = |body: &str, expand| RedirectTarget::HereDoc(HereDoc { body: body.to_string(), expand });
+        assert_eq!(redirects("cat <<EOF\n  one $HOME\nEOF\n"), [Redirection::new(0, here_doc("  one $HOME\n", true))]);
+        
+        let parsed = Parser::new("cat <<-'END' | wc -l\n\tx\n\tEND", &arena).unwrap().parse().unwrap();
+        let CommandType::Pipeline(stages) = parsed else { panic!("{:?}", parsed) };
+        let CommandType::Simple(cat) = &stages[0].command else { panic!() };
+        assert_eq!(cat.redirects[0].owned(), Redirection::new(0, here_doc("x\n", false)));
+        
+        assert!(needs_more_input("cat <<EOF\nline"));
+        assert!(needs_more_input("echo 'open"));
+        assert!(!needs_more_input("cat <<EOF\nline\nEOF"));
+    }
+    
+    #[test]
+    fn errors_point_at_the_offending_token() {
+        let arena = Bump::new();
+        let spans: Vec<_> = Parser::tokenize("ls -la|wc 2>&1").unwrap().into_iter().map(|t| t.span).collect();
+        assert_eq!(spans, [0..2, 3..6, 6..7, 7..9, 10..14]);
+        
+        let error = |input: &str| Parser::new(input, &arena).unwrap().parse().unwrap_err().render(input);
+        assert_eq!(error("ls > | wc"), "ls > | wc\n     ^ Expected redirect target");
+        assert_eq!(error("ls >"), "ls >\n    ^ Expected redirect target");
+        assert_eq!(error("echo a;\n\tls )"), "\tls )\n\t   ^ Unexpected ')'");
+        assert_eq!(error("true && ;"), "true && ;\n        ^ Expected command");
+    }
+    
+    #[test]
+    fn aliases_expand_in_command_position() {
+        let arena = Bump::new();
+        let aliases: AliasMap = [("ll", "ls -l"), ("ls", "ls -F"), ("sudo", "sudo "), ("a", "b"), ("b", "a x"), ("e", "echo;")]
+            .into_iter()
+            .map(|(name, value)| (Arc::from(name), value.to_string()))
+            .collect();
+        let expand = |line: &str| -> String {
+            Parser::with_aliases(line, &aliases, &arena).unwrap().parse().unwrap().to_string()
+        };
+        assert_eq!(expand("ll ll | ll && X=1 ll"), "ls -F -l ll | ls -F -l && X=1 ls -F -l");
+        assert_eq!(expand("'ll'; \\ll; ls ll"), "'ll'; \\ll; ls -F ll");
+        assert_eq!(expand("sudo ll"), "sudo ls -F -l");
+        assert_eq!(expand("a"), "a x");
+        assert_eq!(expand("e ll"), "echo; ls -F -l");
+        assert_eq!(expand("(ll) & ! ll"), "(ls -F -l) & ! ls -F -l");
+        
+        let error = Parser::with_aliases("echo x; ll >", &aliases, &arena).unwrap().parse().unwrap_err();
+        assert_eq!(error.position, 12);
+        let spans: Vec<_> = Parser::with_aliases("ll x", &aliases, &arena).unwrap().tokens.iter().map(|t| t.span.clone()).collect();
+        assert_eq!(spans, [0..2, 0..2, 0..2, 3..4]);
+    }
     
-    match Command::new("sh").arg("-c").arg(cmd).output() {
-        Ok(output) => {
-            let stdout = String::from_utf8_lossy(&output.stdout);
-            // Remove trailing newline if present
-            Ok(stdout.trim_end().to_string())
+    #[test]
+    fn conditional_expressions() {
+        let arena = Bump::new();
+        let parse = |line: &str| Parser::new(line, &arena).unwrap().parse().map(|parsed| parsed.to_string());
+        assert_eq!(parse("[[ -f x && ( a < b || ! $y ) ]] && echo").unwrap(), "[[ -f x && ( a < b || ! $y ) ]] && echo");
+        assert_eq!(parse("[[ $v =~ ^(a|b){1,3}$ ]]").unwrap(), "[[ $v =~ ^(a|b){1,3}$ ]]");
+        assert_eq!(parse("[[ -n ]]").unwrap(), "[[ -n ]]");
+        assert_eq!(parse("[[ a ==").unwrap_err().message, "Expected an operand in [[ ]]");
+        assert_eq!(parse("[[ a b ]]").unwrap_err().message, "Expected ']]'");
+        
+        let aliases: AliasMap = [(Arc::from("x"), "oops".to_string())].into_iter().collect();
+        let parsed = Parser::with_aliases("[[ x ]] && x", &aliases, &arena).unwrap().parse().unwrap();
+        assert_eq!(parsed.to_string(), "[[ x ]] && oops");
+    }
+    
+    #[test]
+    fn coproc_names_only_compound_commands() {
+        let arena = Bump::new();
+        let parse = |line: &'static str| Parser::new(line, &arena).unwrap().parse().unwrap();
+        assert!(matches!(parse("coproc bc -l"), CommandType::Coproc(name, inner)
+            if name == "COPROC" && inner.to_string() == "bc -l"));
+        assert!(matches!(parse("coproc BC (bc -l) && echo"), CommandType::And(left, _)
+            if matches!(left, CommandType::Coproc(name, inner) if *name == "BC" && inner.to_string() == "(bc -l)")));
+        let CommandType::Simple(command) = parse("cat <&$BC >&- 2>&1") else { unreachable!() };
+        assert_eq!(command.redirects[0].target, RedirectTarget::DupWord("$BC"));
+    }
+    
+    #[test]
+    fn if_blocks() {
+        let arena = Bump::new();
+        let parse = |input: &str| Parser::new(input, &arena).unwrap().parse().map(|parsed| parsed.to_string());
+        assert_eq!(parse("if a; then b; elif c\nthen d; e\nelse\n  f\nfi").unwrap(), "if a; then b; elif c; then d; e; else f; fi");
+        assert_eq!(parse("if if a; then b; fi; then echo fi; fi && x").unwrap(), "if if a; then b; fi; then echo fi; fi && x");
+        assert_eq!(parse("if a; fi").unwrap_err().message, "Expected 'then'");
+        assert_eq!(parse("then b").unwrap_err().message, "Unexpected 'then'");
+        for input in ["if a", "if a; then", "if a; then b\n", "if a; then b; else", "(ls\n", "if a; then (b"] {
+            assert!(needs_more_input(input), "{}", input);
         }
-        Err(e) => Err(ParseError::new(format!("Command substitution failed: {}", e), 0)),
+        assert!(!needs_more_input("if a; then b; fi") && !needs_more_input("if a; fi") && !needs_more_input("echo if"));
+    }
+    
+    #[test]
+    fn while_and_until_loops() {
+        let arena = Bump::new();
+        let parse = |input: &str| Parser::new(input, &arena).unwrap().parse().map(|parsed| parsed.to_string());
+        assert_eq!(parse("while read l\ndo\n  echo $l; done").unwrap(), "while read l; do echo $l; done");
+        assert_eq!(parse("until a; do while b; do c; done; done").unwrap(), "until a; do while b; do c; done; done");
+        assert_eq!(parse("while a; done").unwrap_err().message, "Expected 'do'");
+        assert!(needs_more_input("while true; do") && needs_more_input("until a\ndo b\n"));
+        assert!(!needs_more_input("while true; do :; done"));
+    }
+    
+    #[test]
+    fn for_loops() {
+        let arena = Bump::new();
+        let parse = |input: &str| Parser::new(input, &arena).unwrap().parse().map(|parsed| parsed.to_string());
+        assert_eq!(parse("for f in *.txt; do mv \"$f\" \"$f.bak\"; done").unwrap(), "for f in *.txt; do mv \"$f\" \"$f.bak\"; done");
+        assert_eq!(parse("for i in {1..3} do\ndo echo $i\ndone").unwrap(), "for i in 1 2 3 do; do echo $i; done");
+        assert_eq!(parse("for arg\ndo echo; done").unwrap(), "for arg; do echo; done");
+        assert_eq!(parse("for x in; do :; done").unwrap(), "for x in; do :; done");
+        assert_eq!(parse("for 1 in a; do :; done").unwrap_err().message, "Expected a variable name");
+        assert!(needs_more_input("for x in a b\n") && needs_more_input("for x in a; do echo"));
+    }
+    
+    #[test]
+    fn select_loops_and_the_select_builtin() {
+        let arena = Bump::new();
+        let parse = |input: &str| Parser::new(input, &arena).unwrap().parse().map(|parsed| parsed.to_string());
+        assert_eq!(parse("select f in a b; do echo $f; break; done").unwrap(), "select f in a b; do echo $f; break; done");
+        assert_eq!(parse("select opt\ndo echo; done").unwrap(), "select opt; do echo; done");
+        assert!(matches!(Parser::new("select opt\ndo :; done", &arena).unwrap().parse(), Ok(CommandType::Select { .. })));
+        assert!(matches!(Parser::new("from-json | select name size", &arena).unwrap().parse(), Ok(CommandType::Pipeline(_))));
+        assert!(matches!(Parser::new("select name", &arena).unwrap().parse(), Ok(CommandType::Simple(_))));
+        assert!(needs_more_input("select f in a b\n"));
+    }
+    
+    #[test]
+    fn function_definitions() {
+        let arena = Bump::new();
+        let parse = |input: &str| Parser::new(input, &arena).unwrap().parse().map(|parsed| parsed.to_string());
+        assert_eq!(parse("greet() { echo hi $1; }").unwrap(), "greet() { echo hi $1; }");
+        assert_eq!(parse("function greet\n{\necho hi\n}").unwrap(), "greet() { echo hi; }");
+        assert_eq!(parse("function f() ( cd /tmp )").unwrap(), "f() (cd /tmp)");
+        assert_eq!(parse("{ ls; pwd; } && echo ok").unwrap(), "{ ls; pwd; } && echo ok");
+        assert_eq!(parse("f() echo").unwrap_err().message, "Expected a function body");
+        assert_eq!(parse("function if { :; }").unwrap_err().message, "Expected a function name");
+        assert!(needs_more_input("f() {") && needs_more_input("f()\n") && !needs_more_input("f() { :; }"));
+    }
+    
+    #[test]
+    fn arithmetic_commands() {
+        let arena = Bump::new();
+        let parse = |input: &str| Parser::new(input, &arena).unwrap().parse().map(|parsed| parsed.to_string());
+        assert_eq!(parse("while (( i++ < 10 )); do echo $i; done").unwrap(), "while (( i++ < 10 )); do echo $i; done");
+        assert_eq!(parse("(( x << 2 )) && ! ((y))").unwrap(), "(( x << 2 )) && ! ((y))");
+        assert_eq!(parse("((cd /tmp); ls)").unwrap(), "((cd /tmp); ls)");
+        assert_eq!(parse("echo $(( (1 + 2) * 3 ))").unwrap(), "echo $(( (1 + 2) * 3 ))");
+        assert!(needs_more_input("(( a +") && !needs_more_input("(( a + 1 ))"));
     }
-}
 
-// Glob expansion
-fn expand_glob(pattern: &str) -> Vec<String> {
-    // Don't expand if pattern doesn't contain glob characters
-    if !pattern.contains('*') && !pattern.contains('?') && !pattern.contains('[') {
-        return vec![];
+    #[test]
+    fn comments_end_at_the_newline() {
+        let arena = Bump::new();
+        let tokens = Parser::tokenize("ls # list files\n#whole line\necho a#b '#' \\# $# ${#X} #c").unwrap();
+        assert_eq!(words(&tokens), vec!["ls", "echo", "a#b", "'#'", "\\#", "$#", "${#X}"]);
+        assert!(Parser::new("# only a comment\n  # and another", &arena).unwrap().is_empty());
     }
     
-    match glob(pattern) {
-        Ok(paths) => {
-            paths
-                .filter_map(Result::ok)
-                .map(|path| path.to_string_lossy().to_string())
-                .collect()
+    #[test]
+    fn lines_continue_after_backslash_and_operators() {
+        let arena = Bump::new();
+        for input in ["ls \\", "ls |", "make &&", "false ||\n", "ls |&"] {
+            assert!(needs_more_input(input), "{}", input);
         }
-        Err(_) => vec![],
+        assert!(!needs_more_input("echo \\\\") && !needs_more_input("ls &"));
+        assert!(!needs_more_input("ls # a comment \\") && !needs_more_input("ls # |"));
+        
+        let tokens = Parser::tokenize("ec\\\nho a\\\n  b \"c\\\nd\"").unwrap();
+        assert_eq!(words(&tokens), vec!["echo", "a", "b", "\"c\\\nd\""]);
+        let parsed = Parser::new("true &&\n\n echo x ||\n echo y", &arena).unwrap().parse().unwrap();
+        assert!(matches!(parsed, CommandType::Or(left, _) if matches!(*left, CommandType::And(..))));
+    }
+    
+    #[test]
+    fn newlines_separate_commands() {
+        let arena = Bump::new();
+        let parse = |input: &str| Parser::new(input, &arena).unwrap().parse().unwrap().to_string();
+        assert_eq!(parse("\n\necho a\necho b\n\n# note\nls &\npwd\n"), "echo a; echo b; ls & pwd");
+        assert_eq!(parse("(cd /\nls) | wc\ntrue"), "(cd /; ls) | wc; true");
+        assert_eq!(parse("cat <<EOF\nbody\nEOF\necho after"), "cat; echo after");
+    }
+    
+    #[test]
+    fn brace_expansion() {
+        let expand = |word: &str| expand_braces(word).map(|w| w.join(" "));
+        assert_eq!(expand("file.{c,h}").as_deref(), Some("file.c file.h"));
+        assert_eq!(expand("a{1,2}b{x,y}").as_deref(), Some("a1bx a1by a2bx a2by"));
+        assert_eq!(expand("{a,{b,c}}d").as_deref(), Some("ad bd cd"));
+        assert_eq!(expand("dir{1..3}").as_deref(), Some("dir1 dir2 dir3"));
+        assert_eq!(expand("{5..1..2}").as_deref(), Some("5 3 1"));
+        assert_eq!(expand("{08..10}").as_deref(), Some("08 09 10"));
+        assert_eq!(expand("{a..c}").as_deref(), Some("a b c"));
+        assert_eq!(expand("{x}"), None);
+        assert_eq!(expand("${HOME}"), None);
+        
+        let tokens = Parser::tokenize("cp x.{c,h} '{a,b}'").unwrap();
+        assert_eq!(words(&tokens), vec!["cp", "x.c", "x.h", "'{a,b}'"]);
+        assert_eq!(expand("{'a b',c}").as_deref(), Some("'a b' c"));
+        assert_eq!(expand("'{'a,b}"), None);
+    }
+    
+    #[test]
+    fn pipelines_of_compound_commands() {
+        let arena = Bump::new();
+        let parse = |input: &'static str| Parser::new(input, &arena).unwrap().parse().map_err(|e| e.render(input));
+        let parsed = parse("if a; then b; fi | tee log | for i in 1 2; do echo $i; done").unwrap();
+        let CommandType::Pipeline(stages) = parsed else { panic!("{:?}", parsed) };
+        assert!(matches!(stages[0].command, CommandType::If { .. }));
+        assert!(matches!(stages[1].command, CommandType::Simple(c) if c.program == "tee"));
+        assert!(matches!(stages[2].command, CommandType::For { name: "i", .. }));
+        assert_eq!(parsed.to_string(), "if a; then b; fi | tee log | for i in 1 2; do echo $i; done");
+        assert_eq!(parse("{ a; b; } | while read l; do :; done").unwrap().to_string(), "{ a; b; } | while read l; do :; done");
+        
+        assert_eq!(parse("(ls) | | wc").unwrap_err(), "(ls) | | wc\n       ^ Expected command");
+        assert_eq!(parse("ls | )").unwrap_err(), "ls | )\n     ^ Expected command");
+        assert_eq!(parse("| wc").unwrap_err(), "| wc\n^ Expected command");
+        assert_eq!(parse("if a; then b; fi |").unwrap_err(), "if a; then b; fi |\n                  ^ Expected command");
+    }
+    
+    #[test]
+    fn pipe_stderr_operator() {
+        let arena = Bump::new();
+        let parse = |input: &'static str| Parser::new(input, &arena).unwrap().parse().map_err(|e| e.render(input));
+        let spans: Vec<_> = Parser::tokenize("make|&grep x").unwrap().into_iter().map(|t| t.span).collect();
+        assert_eq!(spans, [0..4, 4..6, 6..10, 11..12]);
+        
+        let parsed = parse("(make) |& tee log | wc -l").unwrap();
+        let CommandType::Pipeline(stages) = parsed else { panic!("{:?}", parsed) };
+        assert_eq!(stages.iter().map(|s| s.pipe_stderr).collect::<Vec<_>>(), [true, false, false]);
+        assert_eq!(parsed.to_string(), "(make) |& tee log | wc -l");
+        
+        assert_eq!(parse("|& wc").unwrap_err(), "|& wc\n^ Expected command");
+        assert_eq!(parse("ls |& | wc").unwrap_err(), "ls |& | wc\n      ^ Expected command");
+        assert_eq!(parse("ls |& ;").unwrap_err(), "ls |& ;\n      ^ Expected command");
+        assert_eq!(parse("ls | & wc").unwrap_err(), "ls | & wc\n     ^ Expected command");
     }
-}
\ No newline at end of file
+    
+    #[test]
+    fn negated_pipelines() {
+        let arena = Bump::new();
+        let parse = |input: &'static str| Parser::new(input, &arena).unwrap().parse().map_err(|e| e.render(input));
+        assert!(matches!(parse("! ! ls").unwrap(), CommandType::Not(CommandType::Not(CommandType::Simple(_)))));
+        let parsed = parse("if ! grep -q x f; then :; fi").unwrap();
+        let CommandType::If { branches, .. } = parsed else { panic!("{:?}", parsed) };
+        assert!(matches!(branches[0].0, CommandType::Not(CommandType::Simple(c)) if c.program == "grep"));
+        // `!` only negates as a word of its own
+        assert!(matches!(parse("!ls").unwrap(), CommandType::Simple(c) if c.program == "!ls"));
+        assert!(matches!(parse("echo !").unwrap(), CommandType::Simple(c) if c.args == ["!"]));
+        
+        assert_eq!(parse("!").unwrap_err(), "!\n ^ Expected command");
+        assert_eq!(parse("! ;").unwrap_err(), "! ;\n  ^ Expected command");
+        assert_eq!(parse("ls && !").unwrap_err(), "ls && !\n       ^ Expected command");
+    }
+    
+    #[test]
+    fn background_compound_commands() {
+        let arena = Bump::new();
+        let parse = |input: &'static str| Parser::new(input, &arena).unwrap().parse().map_err(|e| e.render(input));
+        assert!(matches!(parse("sleep 1 | cat &").unwrap(), CommandType::Background(CommandType::Pipeline(stages)) if stages.len() == 2));
+        assert!(matches!(parse("(a && b) &").unwrap(), CommandType::Background(CommandType::Subshell(_))));
+        assert!(matches!(parse("if a; then b; fi &").unwrap(), CommandType::Background(CommandType::If { .. })));
+        let parsed = parse("{ a; } & b &").unwrap();
+        let CommandType::Sequence(commands) = parsed else { panic!("{:?}", parsed) };
+        assert!(matches!(commands[0], CommandType::Background(CommandType::Group(_))));
+        assert!(matches!(commands[1], CommandType::Background(CommandType::Simple(_))));
+        
+        assert_eq!(parse("& ls").unwrap_err(), "& ls\n^ Expected command");
+        assert_eq!(parse("ls & | wc").unwrap_err(), "ls & | wc\n     ^ Unexpected '|'");
+        assert_eq!(parse("ls & &").unwrap_err(), "ls & &\n     ^ Unexpected '&'");
+    }
+    
+    #[test]
+    fn quoted_empty_words_are_kept() {
+        let arena = Bump::new();
+        let tokens = Parser::tokenize("echo '' \"\" x''y").unwrap();
+        let tokens: Vec<_> = tokens.into_iter().map(|t| (t.token, t.span)).collect();
+        assert_eq!(tokens, [
+            (Token::Word(Cow::Borrowed("echo")), 0..4),
+            (Token::Word(Cow::Borrowed("''")), 5..7),
+            (Token::Word(Cow::Borrowed("\"\"")), 8..10),
+            (Token::Word(Cow::Borrowed("x''y")), 11..15),
+        ]);
+        let parsed = Parser::new("X='' cmd \"\" | wc ''", &arena).unwrap().parse().unwrap();
+        let CommandType::Pipeline(stages) = parsed else { panic!("{:?}", parsed) };
+        let CommandType::Simple(cmd) = stages[0].command else { panic!() };
+        assert_eq!(cmd.args, ["\"\""]);
+        assert_eq!(cmd.env_overrides, [("X", "''")]);
+        assert_eq!(parsed.to_string(), "X='' cmd \"\" | wc ''");
+        
+        let error = |input: &str| Parser::tokenize(input).unwrap_err().render(input);
+        assert_eq!(error("echo '' \""), "echo '' \"\n        ^ Unterminated quote");
+        assert_eq!(error("echo ''\"\"'"), "echo ''\"\"'\n         ^ Unterminated quote");
+    }
+}
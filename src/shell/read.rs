@@ -0,0 +1,190 @@
+// shell/read.rs
+//
+// `read [-r] [-s] [-p PROMPT] [NAME...]`: one line from stdin, split at
+// $IFS into the NAMEs, the last taking the rest of the line (REPLY if none
+// are given). Without -r a backslash quotes the next character and a
+// backslash-newline continues the line. Stdin is read a byte at a time so
+// nothing past the line is taken from commands that read it next.
+use crate::shell::Shell;
+use crate::utils::helpers::is_valid_var_name;
+use std::io::{self, IsTerminal, Write};
+
+pub fn builtin_read(shell: &mut Shell, args: &[String]) -> i32 {
+    let mut raw = false;
+    let mut silent = false;
+    let mut prompt = None;
+    let mut args = args.iter();
+    let mut names = Vec::new();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--" => {
+                names.extend(args.by_ref());
+                break;
+            }
+            "-p" => match args.next() {
+                Some(text) => prompt = Some(text.as_str()),
+                None => {
+                    eprintln!("read: -p: option requires an argument");
+                    return 2;
+                }
+            },
+            flags if flags.starts_with('-') && flags.len() > 1 => {
+                for flag in flags[1..].chars() {
+                    match flag {
+                        'r' => raw = true,
+                        's' => silent = true,
+                        _ => {
+                            eprintln!("read: -{}: invalid option", flag);
+                            eprintln!("read: usage: read [-rs] [-p prompt] [name...]");
+                            return 2;
+                        }
+                    }
+                }
+            }
+            _ => {
+                names.push(arg);
+                names.extend(args.by_ref());
+                break;
+            }
+        }
+    }
+    if let Some(name) = names.iter().find(|name| !is_valid_var_name(name)) {
+        eprintln!("read: `{}': not a valid identifier", name);
+        return 1;
+    }
+
+    let terminal = io::stdin().is_terminal();
+    if let Some(prompt) = prompt.filter(|_| terminal) {
+        eprint!("{}", prompt);
+        let _ = io::stderr().flush();
+    }
+    let echo = (silent && terminal).then(disable_echo).flatten();
+    let line = read_line(raw);
+    if let Some(saved) = echo {
+        restore_echo(&saved);
+    }
+    let (bytes, complete) = match line {
+        Ok(line) => line,
+        Err(_) => return 130,
+    };
+
+    let text = String::from_utf8_lossy(&bytes);
+    let chars = unescape(&text, raw);
+    let ifs = shell.environment.get("IFS").map_or(" \t\n", String::as_str).to_string();
+    if names.is_empty() {
+        // REPLY gets the line as it was, without splitting or trimming
+        shell.set_var("REPLY", chars.iter().map(|&(ch, _)| ch).collect());
+    } else {
+        let fields = split_fields(&chars, &ifs, names.len());
+        for (i, name) in names.iter().enumerate() {
+            shell.set_var(name, fields.get(i).cloned().unwrap_or_default());
+        }
+    }
+    if complete { 0 } else { 1 }
+}
+
+// The bytes of one line without its newline, and whether the newline was
+// there (false at end of input). Without `raw`, an escaped newline does not
+// end the line.
+pub(crate) fn read_line(raw: bool) -> nix::Result<(Vec<u8>, bool)> {
+    let mut line = Vec::new();
+    let mut byte = [0u8; 1];
+    loop {
+        match nix::unistd::read(0, &mut byte) {
+            Ok(0) => return Ok((line, false)),
+            Ok(_) => {}
+            Err(nix::errno::Errno::EINTR) if !crate::signal_handler::take_interrupt() => continue,
+            Err(e) => return Err(e),
+        }
+        if byte[0] == b'\n' {
+            let escaped = !raw && line.iter().rev().take_while(|&&b| b == b'\\').count() % 2 == 1;
+            if !escaped {
+                return Ok((line, true));
+            }
+        }
+        line.push(byte[0]);
+    }
+}
+
+// Characters of the line, each with whether a backslash quoted it
+fn unescape(text: &str, raw: bool) -> Vec<(char, bool)> {
+    let mut chars = Vec::with_capacity(text.len());
+    let mut iter = text.chars();
+    while let Some(ch) = iter.next() {
+        if raw || ch != '\\' {
+            chars.push((ch, false));
+            continue;
+        }
+        match iter.next() {
+            // Line continuation
+            Some('\n') => {}
+            Some(next) => chars.push((next, true)),
+            None => {}
+        }
+    }
+    chars
+}
+
+// At most `count` fields, the last with the rest of the line. IFS
+// whitespace around fields is dropped; each other IFS character ends a
+// field, so `a::b` with IFS=: is three. Quoted characters never split.
+fn split_fields(chars: &[(char, bool)], ifs: &str, count: usize) -> Vec<String> {
+    let is_delimiter = |&(ch, quoted): &(char, bool)| !quoted && ifs.contains(ch);
+    let is_space = |c: &(char, bool)| is_delimiter(c) && matches!(c.0, ' ' | '\t' | '\n');
+    let text = |chars: &[(char, bool)]| chars.iter().map(|&(ch, _)| ch).collect::<String>();
+
+    let mut pos = chars.iter().take_while(|c| is_space(c)).count();
+    let mut fields = Vec::new();
+    while fields.len() + 1 < count && pos < chars.len() {
+        let len = chars[pos..].iter().take_while(|c| !is_delimiter(c)).count();
+        fields.push(text(&chars[pos..pos + len]));
+        pos += len;
+        pos += chars[pos..].iter().take_while(|c| is_space(c)).count();
+        if chars.get(pos).is_some_and(|c| is_delimiter(c) && !is_space(c)) {
+            pos += 1;
+            pos += chars[pos..].iter().take_while(|c| is_space(c)).count();
+        }
+    }
+    if pos < chars.len() {
+        let rest = &chars[pos..];
+        let trailing = rest.iter().rev().take_while(|c| is_space(c)).count();
+        fields.push(text(&rest[..rest.len() - trailing]));
+    }
+    fields
+}
+
+// Turns off terminal echo on stdin, returning the settings to restore
+fn disable_echo() -> Option<libc::termios> {
+    let mut settings: libc::termios = unsafe { std::mem::zeroed() };
+    if unsafe { libc::tcgetattr(0, &mut settings) } != 0 {
+        return None;
+    }
+    let saved = settings;
+    settings.c_lflag &= !libc::ECHO;
+    (unsafe { libc::tcsetattr(0, libc::TCSANOW, &settings) } == 0).then_some(saved)
+}
+
+fn restore_echo(saved: &libc::termios) {
+    unsafe { libc::tcsetattr(0, libc::TCSANOW, saved) };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn split(line: &str, ifs: &str, count: usize, raw: bool) -> Vec<String> {
+        split_fields(&unescape(line, raw), ifs, count)
+    }
+
+    #[test]
+    fn fields_follow_ifs() {
+        assert_eq!(split("  one  two   three four  ", " \t\n", 2, false), ["one", "two   three four"]);
+        assert_eq!(split("a b", " \t\n", 3, false), ["a", "b"]);
+        assert_eq!(split("root:x::0:/root", ":", 4, false), ["root", "x", "", "0:/root"]);
+        assert_eq!(split("a : b", " :", 2, false), ["a", "b"]);
+        assert_eq!(split("a\\ b c", " ", 2, false), ["a b", "c"]);
+        assert_eq!(split("a\\ b c", " ", 2, true), ["a\\", "b c"]);
+        assert_eq!(split("  keep  ", "", 1, false), ["  keep  "]);
+        assert!(split("   ", " ", 1, false).is_empty());
+    }
+}
@@ -0,0 +1,163 @@
+// shell/trap.rs
+//
+// `trap ACTION SIG...`. Signal handlers only mark a trapped signal pending
+// (see signal_handler); the executor runs the actions between commands.
+// EXIT (or 0) runs as the shell ends, whether through `exit`, `set -e` or
+// end of input. An empty action ignores the signal and `-` resets it.
+use crate::shell::executor::Executor;
+use crate::shell::parser::Parser;
+use crate::shell::Shell;
+use crate::signal_handler;
+use crate::utils::string_utils::shell_quote;
+use crate::outln;
+use bumpalo::Bump;
+use nix::sys::signal::Signal;
+
+// The trap number of EXIT, which is no signal
+const EXIT: i32 = 0;
+
+// `INT`, `SIGINT`, `int` or `2`, and `EXIT`/`0`
+fn parse_signal(name: &str) -> Option<i32> {
+    if name == "0" || name.eq_ignore_ascii_case("EXIT") {
+        return Some(EXIT);
+    }
+    signal_handler::parse_signal(name).map(|signal| signal as i32)
+}
+
+fn signal_name(number: i32) -> String {
+    match Signal::try_from(number) {
+        Ok(signal) => signal.as_str().to_string(),
+        Err(_) => "EXIT".to_string(),
+    }
+}
+
+pub fn builtin_trap(shell: &mut Shell, args: &[String]) -> i32 {
+    match args.first().map(String::as_str) {
+        None | Some("-p") => {
+            for (number, action) in &shell.traps {
+                outln!("trap -- {} {}", shell_quote(action), signal_name(*number));
+            }
+            return 0;
+        }
+        Some("-l") => {
+            for signal in Signal::iterator() {
+                outln!("{:2}) {}", signal as i32, signal.as_str());
+            }
+            return 0;
+        }
+        _ => {}
+    }
+    let args = if args[0] == "--" { &args[1..] } else { args };
+    // A lone signal, or a list starting with a number, is reset as by `-`
+    let (action, signals) = match args {
+        [] => {
+            eprintln!("trap: usage: trap [-lp] [[ACTION] SIGNAL...]");
+            return 2;
+        }
+        [_] => ("-", args),
+        [first, ..] if first.parse::<u32>().is_ok() => ("-", args),
+        [first, rest @ ..] => (first.as_str(), rest),
+    };
+    let mut status = 0;
+    for name in signals {
+        let Some(number) = parse_signal(name) else {
+            eprintln!("trap: {}: invalid signal specification", name);
+            status = 1;
+            continue;
+        };
+        if let Err(e) = set_trap(shell, number, action) {
+            eprintln!("trap: {}: {}", name, e.desc());
+            status = 1;
+        }
+    }
+    status
+}
+
+fn set_trap(shell: &mut Shell, number: i32, action: &str) -> Result<(), nix::Error> {
+    let signal = Signal::try_from(number).ok();
+    match action {
+        "-" => {
+            if let Some(signal) = signal {
+                signal_handler::reset_signal(signal)?;
+            }
+            shell.traps.remove(&number);
+        }
+        _ => {
+            if let Some(signal) = signal {
+                if action.is_empty() {
+                    signal_handler::ignore_signal(signal)?;
+                } else {
+                    signal_handler::catch_signal(signal)?;
+                }
+            }
+            shell.traps.insert(number, action.to_string());
+        }
+    }
+    Ok(())
+}
+
+// Runs `action` like a line typed at the prompt, leaving `$?` as it was
+fn run_action(shell: &mut Shell, action: &str) {
+    let status = shell.last_exit_code;
+    let arena = Bump::new();
+    match Parser::with_aliases(action, &shell.aliases, &arena).and_then(|mut parser| parser.parse()) {
+        Ok(parsed) => {
+            Executor::new().execute(shell, parsed);
+        }
+        Err(e) => eprintln!("trap: {}", e.message),
+    }
+    shell.last_exit_code = status;
+}
+
+// Actions for the trapped signals that arrived since the last call
+pub fn run_pending(shell: &mut Shell) {
+    let pending = signal_handler::take_pending_traps();
+    if pending == 0 {
+        return;
+    }
+    let actions: Vec<String> = shell.traps.iter()
+        .filter(|(&number, action)| number != EXIT && pending & (1 << number) != 0 && !action.is_empty())
+        .map(|(_, action)| action.clone())
+        .collect();
+    for action in actions {
+        run_action(shell, &action);
+    }
+}
+
+// The EXIT trap, once; `$?` is the status the shell is exiting with
+pub fn run_exit(shell: &mut Shell) {
+    if let Some(action) = shell.traps.remove(&EXIT) {
+        run_action(shell, &action);
+    }
+}
+
+// Subshells start without the traps, except that ignored signals stay
+// ignored
+pub fn reset_for_subshell(shell: &mut Shell) {
+    shell.traps.retain(|&number, action| {
+        if action.is_empty() {
+            return true;
+        }
+        if let Ok(signal) = Signal::try_from(number) {
+            let _ = signal_handler::reset_signal(signal);
+        }
+        false
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn signal_names() {
+        assert_eq!(parse_signal("INT"), Some(2));
+        assert_eq!(parse_signal("sigterm"), Some(15));
+        assert_eq!(parse_signal("EXIT"), Some(0));
+        assert_eq!(parse_signal("0"), Some(0));
+        assert_eq!(parse_signal("NOPE"), None);
+        assert_eq!(parse_signal("99"), None);
+        assert_eq!(signal_name(2), "SIGINT");
+        assert_eq!(signal_name(0), "EXIT");
+    }
+}
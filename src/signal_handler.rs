@@ -1,63 +1,124 @@
 use nix::sys::signal::{self, Signal, SigHandler, SigSet, SigAction, SaFlags};
-use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
 
 static SIGINT_RECEIVED: AtomicBool = AtomicBool::new(false);
 static SIGTSTP_RECEIVED: AtomicBool = AtomicBool::new(false);
 
-extern "C" fn handle_sigint(_: i32) {
+// One bit per signal number: signals with a `trap`, and those of them that
+// arrived since the traps last ran
+static TRAPPED: AtomicU64 = AtomicU64::new(0);
+static PENDING_TRAPS: AtomicU64 = AtomicU64::new(0);
+
+fn mark_pending(sig: i32) {
+    let bit = 1u64 << sig;
+    if TRAPPED.load(Ordering::SeqCst) & bit != 0 {
+        PENDING_TRAPS.fetch_or(bit, Ordering::SeqCst);
+    }
+}
+
+extern "C" fn handle_sigint(sig: i32) {
     SIGINT_RECEIVED.store(true, Ordering::SeqCst);
+    mark_pending(sig);
     println!();
 }
 
-extern "C" fn handle_sigtstp(_: i32) {
+extern "C" fn handle_sigtstp(sig: i32) {
     SIGTSTP_RECEIVED.store(true, Ordering::SeqCst);
+    mark_pending(sig);
 }
 
-extern "C" fn handle_sigchld(_: i32) {
+extern "C" fn handle_sigchld(sig: i32) {
     // Child process status changed
     // We'll check this in the main loop
+    mark_pending(sig);
 }
 
-pub fn setup_signal_handlers() -> Result<(), nix::Error> {
-    // SIGINT handler (Ctrl+C)
-    let sig_action = SigAction::new(
-        SigHandler::Handler(handle_sigint),
-        SaFlags::empty(),
-        SigSet::empty(),
-    );
+extern "C" fn handle_trapped(sig: i32) {
+    mark_pending(sig);
+}
+
+fn install(signal: Signal, handler: extern "C" fn(i32), flags: SaFlags) -> Result<(), nix::Error> {
+    let sig_action = SigAction::new(SigHandler::Handler(handler), flags, SigSet::empty());
     unsafe {
-        signal::sigaction(Signal::SIGINT, &sig_action)?;
+        signal::sigaction(signal, &sig_action)?;
     }
+    Ok(())
+}
+
+pub fn setup_signal_handlers() -> Result<(), nix::Error> {
+    // SIGINT handler (Ctrl+C)
+    install(Signal::SIGINT, handle_sigint, SaFlags::empty())?;
     
     // SIGTSTP handler (Ctrl+Z)
-    let sig_action = SigAction::new(
-        SigHandler::Handler(handle_sigtstp),
-        SaFlags::empty(),
-        SigSet::empty(),
-    );
-    unsafe {
-        signal::sigaction(Signal::SIGTSTP, &sig_action)?;
-    }
+    install(Signal::SIGTSTP, handle_sigtstp, SaFlags::empty())?;
     
     // SIGCHLD handler (child process status change)
-    let sig_action = SigAction::new(
-        SigHandler::Handler(handle_sigchld),
-        SaFlags::SA_RESTART | SaFlags::SA_NOCLDSTOP,
-        SigSet::empty(),
-    );
+    install(Signal::SIGCHLD, handle_sigchld, SaFlags::SA_RESTART | SaFlags::SA_NOCLDSTOP)?;
+    
+    Ok(())
+}
+
+// `INT`, `SIGINT`, `int` or `2`, as `trap` and `kill` take them
+pub fn parse_signal(name: &str) -> Option<Signal> {
+    if let Ok(number) = name.parse::<i32>() {
+        return Signal::try_from(number).ok();
+    }
+    let name = name.to_ascii_uppercase();
+    let full = if name.starts_with("SIG") { name } else { format!("SIG{}", name) };
+    full.parse().ok()
+}
+
+// `trap ACTION SIG`: the handler only marks the signal pending, and the
+// shell runs the action between commands. INT, TSTP and CHLD keep the
+// shell's own handling as well.
+pub fn catch_signal(signal: Signal) -> Result<(), nix::Error> {
+    if !matches!(signal, Signal::SIGINT | Signal::SIGTSTP | Signal::SIGCHLD) {
+        install(signal, handle_trapped, SaFlags::SA_RESTART)?;
+    }
+    TRAPPED.fetch_or(1 << signal as i32, Ordering::SeqCst);
+    Ok(())
+}
+
+// `trap '' SIG`; commands the shell starts inherit this
+pub fn ignore_signal(signal: Signal) -> Result<(), nix::Error> {
     unsafe {
-        signal::sigaction(Signal::SIGCHLD, &sig_action)?;
+        signal::signal(signal, SigHandler::SigIgn)?;
     }
-    
+    TRAPPED.fetch_and(!(1 << signal as i32), Ordering::SeqCst);
     Ok(())
 }
 
+// `trap - SIG`: back to how the shell handles it without a trap
+pub fn reset_signal(signal: Signal) -> Result<(), nix::Error> {
+    TRAPPED.fetch_and(!(1 << signal as i32), Ordering::SeqCst);
+    match signal {
+        Signal::SIGINT => install(signal, handle_sigint, SaFlags::empty()),
+        Signal::SIGTSTP => install(signal, handle_sigtstp, SaFlags::empty()),
+        Signal::SIGCHLD => install(signal, handle_sigchld, SaFlags::SA_RESTART | SaFlags::SA_NOCLDSTOP),
+        _ => unsafe { signal::signal(signal, SigHandler::SigDfl).map(drop) },
+    }
+}
+
+pub fn has_pending_traps() -> bool {
+    PENDING_TRAPS.load(Ordering::SeqCst) != 0
+}
+
+// The trapped signals that arrived, as a bit set, clearing them
+pub fn take_pending_traps() -> u64 {
+    PENDING_TRAPS.swap(0, Ordering::SeqCst)
+}
+
 pub fn check_signals() -> (bool, bool) {
     let sigint = SIGINT_RECEIVED.swap(false, Ordering::SeqCst);
     let sigtstp = SIGTSTP_RECEIVED.swap(false, Ordering::SeqCst);
     (sigint, sigtstp)
 }
 
+// Takes a pending Ctrl+C, for loops that stop at the next iteration
+pub fn take_interrupt() -> bool {
+    SIGINT_RECEIVED.swap(false, Ordering::SeqCst)
+}
+
 pub fn ignore_signals() -> Result<(), nix::Error> {
     unsafe {
         signal::signal(Signal::SIGINT, SigHandler::SigIgn)?;
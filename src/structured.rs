@@ -0,0 +1,363 @@
+// src/structured.rs
+//
+// Tabular data builtins: `from-json`/`from-csv` turn text into a table,
+// `select`, `where` and `sort-by` transform it and `to-table`/`to-json`
+// print it. Between pipeline stages a table travels as a JSON array of
+// objects; a stage writing to a terminal renders the table instead.
+use crate::utils::helpers::{display_width, pad_to_width};
+use crate::{out, outln};
+use serde_json::{Map, Value};
+use std::cmp::Ordering;
+use std::fs;
+use std::io::Read;
+use std::mem::ManuallyDrop;
+use std::os::unix::io::FromRawFd;
+
+pub type Row = Map<String, Value>;
+
+pub const COMMANDS: &[&str] = &[
+    "from-json", "from-csv", "select", "where", "sort-by", "to-table", "to-json",
+];
+
+// Arrays of objects are tables already; a lone object is one row and
+// anything else becomes a `value` column
+pub fn table_from_value(value: Value) -> Vec<Row> {
+    let wrap = |value: Value| match value {
+        Value::Object(row) => row,
+        other => {
+            let mut row = Row::new();
+            row.insert("value".to_string(), other);
+            row
+        }
+    };
+    match value {
+        Value::Array(items) => items.into_iter().map(wrap).collect(),
+        other => vec![wrap(other)],
+    }
+}
+
+// RFC 4180-style CSV with a header line. Quoted fields may contain commas,
+// newlines and doubled quotes. Cells stay strings; comparisons still treat
+// numeric strings as numbers.
+pub fn parse_csv(text: &str) -> Result<Vec<Row>, String> {
+    let mut records = Vec::new();
+    let mut record = Vec::new();
+    let mut field = String::new();
+    let mut in_quotes = false;
+    let mut chars = text.chars().peekable();
+
+    while let Some(ch) = chars.next() {
+        match ch {
+            '"' if in_quotes => {
+                if chars.next_if_eq(&'"').is_some() {
+                    field.push('"');
+                } else {
+                    in_quotes = false;
+                }
+            }
+            '"' if field.is_empty() => in_quotes = true,
+            ',' if !in_quotes => record.push(std::mem::take(&mut field)),
+            '\r' if !in_quotes && chars.peek() == Some(&'\n') => {}
+            '\n' if !in_quotes => {
+                record.push(std::mem::take(&mut field));
+                records.push(std::mem::take(&mut record));
+            }
+            _ => field.push(ch),
+        }
+    }
+    if in_quotes {
+        return Err("unterminated quoted field".to_string());
+    }
+    if !field.is_empty() || !record.is_empty() {
+        record.push(field);
+        records.push(record);
+    }
+
+    let mut records = records.into_iter().filter(|r| !(r.len() == 1 && r[0].is_empty()));
+    let header = records.next().ok_or("no header line")?;
+    Ok(records
+        .map(|values| {
+            header
+                .iter()
+                .cloned()
+                .zip(values.into_iter().map(Value::String).chain(std::iter::repeat(Value::Null)))
+                .collect()
+        })
+        .collect())
+}
+
+// Dotted paths reach into nested objects and arrays: `owner.login`, `tags.0`
+fn lookup<'a>(row: &'a Row, path: &str) -> Option<&'a Value> {
+    let mut parts = path.split('.');
+    let mut value = row.get(parts.next()?)?;
+    for part in parts {
+        value = match value {
+            Value::Object(map) => map.get(part)?,
+            Value::Array(items) => items.get(part.parse::<usize>().ok()?)?,
+            _ => return None,
+        };
+    }
+    Some(value)
+}
+
+pub fn select(rows: Vec<Row>, fields: &[String]) -> Vec<Row> {
+    rows.into_iter()
+        .map(|row| {
+            fields
+                .iter()
+                .map(|field| (field.clone(), lookup(&row, field).cloned().unwrap_or(Value::Null)))
+                .collect()
+        })
+        .collect()
+}
+
+fn as_number(value: &Value) -> Option<f64> {
+    match value {
+        Value::Number(n) => n.as_f64(),
+        Value::String(s) => s.trim().parse().ok(),
+        _ => None,
+    }
+}
+
+fn as_text(value: &Value) -> String {
+    match value {
+        Value::String(s) => s.clone(),
+        Value::Null => String::new(),
+        other => other.to_string(),
+    }
+}
+
+// Numbers (including numeric strings) compare numerically, everything else as text
+fn compare(a: &Value, b: &Value) -> Ordering {
+    match (as_number(a), as_number(b)) {
+        (Some(x), Some(y)) => x.total_cmp(&y),
+        _ => as_text(a).cmp(&as_text(b)),
+    }
+}
+
+// `where <field> <op> <value>`; the value is read as JSON when it parses
+// (numbers, true, null) and as a plain string otherwise. Word operators
+// exist because `<` and `>` need quoting on the command line.
+pub fn filter(rows: Vec<Row>, field: &str, op: &str, operand: &str) -> Result<Vec<Row>, String> {
+    let operand = serde_json::from_str(operand).unwrap_or_else(|_| Value::String(operand.to_string()));
+    let test: Box<dyn Fn(&Value) -> bool> = match op {
+        "==" | "=" | "eq" => Box::new(|v| compare(v, &operand) == Ordering::Equal),
+        "!=" | "ne" => Box::new(|v| compare(v, &operand) != Ordering::Equal),
+        "<" | "lt" => Box::new(|v| compare(v, &operand) == Ordering::Less),
+        "<=" | "le" => Box::new(|v| compare(v, &operand) != Ordering::Greater),
+        ">" | "gt" => Box::new(|v| compare(v, &operand) == Ordering::Greater),
+        ">=" | "ge" => Box::new(|v| compare(v, &operand) != Ordering::Less),
+        "=~" | "contains" => Box::new(|v| as_text(v).contains(&as_text(&operand))),
+        _ => return Err(format!("unknown operator '{}' (use eq ne lt le gt ge contains)", op)),
+    };
+    Ok(rows.into_iter().filter(|row| lookup(row, field).is_some_and(&test)).collect())
+}
+
+pub fn sort_by(mut rows: Vec<Row>, field: &str, reverse: bool) -> Vec<Row> {
+    rows.sort_by(|a, b| {
+        let ordering = match (lookup(a, field), lookup(b, field)) {
+            (Some(x), Some(y)) => compare(x, y),
+            (Some(_), None) => Ordering::Less,
+            (None, Some(_)) => Ordering::Greater,
+            (None, None) => Ordering::Equal,
+        };
+        if reverse { ordering.reverse() } else { ordering }
+    });
+    rows
+}
+
+fn cell(value: Option<&Value>) -> String {
+    match value {
+        Some(Value::String(s)) => s.replace('\n', "\\n"),
+        Some(Value::Null) | None => String::new(),
+        Some(other) => other.to_string(),
+    }
+}
+
+// Box-drawn table with a row-number column. Columns appear in the order
+// their keys are first seen; numbers are right-aligned.
+pub fn render_table(rows: &[Row]) -> String {
+    if rows.is_empty() {
+        return "(empty table)\n".to_string();
+    }
+    let mut columns: Vec<&String> = Vec::new();
+    for row in rows {
+        for key in row.keys() {
+            if !columns.contains(&key) {
+                columns.push(key);
+            }
+        }
+    }
+
+    let mut header = vec!["#".to_string()];
+    header.extend(columns.iter().map(|c| c.to_string()));
+    let body: Vec<Vec<(String, bool)>> = rows
+        .iter()
+        .enumerate()
+        .map(|(i, row)| {
+            let mut cells = vec![(i.to_string(), true)];
+            cells.extend(columns.iter().map(|c| {
+                let value = row.get(*c);
+                (cell(value), matches!(value, Some(Value::Number(_))))
+            }));
+            cells
+        })
+        .collect();
+
+    let widths: Vec<usize> = (0..header.len())
+        .map(|i| body.iter().map(|r| display_width(&r[i].0)).chain([display_width(&header[i])]).max().unwrap_or(0))
+        .collect();
+    let rule = |left: &str, mid: &str, right: &str| {
+        let segments: Vec<String> = widths.iter().map(|w| "─".repeat(w + 2)).collect();
+        format!("{}{}{}\n", left, segments.join(mid), right)
+    };
+    let line = |cells: Vec<(String, bool)>| {
+        let cells: Vec<String> = cells
+            .into_iter()
+            .zip(&widths)
+            .map(|((text, numeric), &width)| {
+                if numeric {
+                    format!("{}{}", " ".repeat(width - display_width(&text)), text)
+                } else {
+                    pad_to_width(&text, width)
+                }
+            })
+            .collect();
+        format!("│ {} │\n", cells.join(" │ "))
+    };
+
+    let mut out = rule("╭", "┬", "╮");
+    out.push_str(&line(header.into_iter().map(|h| (h, false)).collect()));
+    out.push_str(&rule("├", "┼", "┤"));
+    for cells in body {
+        out.push_str(&line(cells));
+    }
+    out.push_str(&rule("╰", "┴", "╯"));
+    out
+}
+
+fn read_input(file: Option<&String>) -> Result<String, String> {
+    match file {
+        Some(path) => fs::read_to_string(path).map_err(|e| format!("{}: {}", path, e)),
+        None => {
+            // Read fd 0 directly: in a forked pipeline stage io::stdin() may
+            // still hold input the parent had buffered but not consumed
+            let mut stdin = ManuallyDrop::new(unsafe { fs::File::from_raw_fd(0) });
+            let mut text = String::new();
+            stdin.read_to_string(&mut text).map_err(|e| e.to_string())?;
+            Ok(text)
+        }
+    }
+}
+
+fn read_table(text: &str) -> Result<Vec<Row>, String> {
+    if text.trim().is_empty() {
+        return Ok(Vec::new());
+    }
+    serde_json::from_str(text)
+        .map(table_from_value)
+        .map_err(|e| format!("input is not JSON: {}", e))
+}
+
+fn write_table(rows: &[Row], force_table: bool) {
+    let text = if force_table || crate::terminal::stdout_is_terminal() {
+        render_table(rows)
+    } else {
+        format!("{}\n", Value::Array(rows.iter().cloned().map(Value::Object).collect()))
+    };
+    out!("{}", text);
+}
+
+fn run(command: &str, args: &[String]) -> Result<(), String> {
+    match command {
+        "from-json" => write_table(&read_table(&read_input(args.first())?)?, false),
+        "from-csv" => write_table(&parse_csv(&read_input(args.first())?)?, false),
+        "select" => {
+            if args.is_empty() {
+                return Err("usage: select <field>...".to_string());
+            }
+            write_table(&select(read_table(&read_input(None)?)?, args), false);
+        }
+        "where" => {
+            let [field, op, value] = args else {
+                return Err("usage: where <field> <op> <value>".to_string());
+            };
+            write_table(&filter(read_table(&read_input(None)?)?, field, op, value)?, false);
+        }
+        "sort-by" => {
+            let reverse = args.iter().any(|a| a == "-r" || a == "--reverse");
+            let field = args.iter().find(|a| !a.starts_with('-')).ok_or("usage: sort-by <field> [-r]")?;
+            write_table(&sort_by(read_table(&read_input(None)?)?, field, reverse), false);
+        }
+        "to-table" => write_table(&read_table(&read_input(args.first())?)?, true),
+        "to-json" => {
+            let rows = read_table(&read_input(args.first())?)?;
+            let value = Value::Array(rows.into_iter().map(Value::Object).collect());
+            outln!("{}", serde_json::to_string_pretty(&value).map_err(|e| e.to_string())?);
+        }
+        _ => return Err("unknown command".to_string()),
+    }
+    Ok(())
+}
+
+pub fn builtin_structured(command: &str, args: &[String]) -> i32 {
+    match run(command, args) {
+        Ok(()) => 0,
+        Err(e) => {
+            eprintln!("{}: {}", command, e);
+            1
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn rows(value: Value) -> Vec<Row> {
+        table_from_value(value)
+    }
+
+    #[test]
+    fn csv_parsing() {
+        let table = parse_csv("name,note\nann,\"likes \"\"tea\"\", cake\"\r\nbob,\n").unwrap();
+        assert_eq!(table, rows(json!([
+            {"name": "ann", "note": "likes \"tea\", cake"},
+            {"name": "bob", "note": ""},
+        ])));
+        assert!(parse_csv("a\n\"open").is_err());
+    }
+
+    #[test]
+    fn select_where_sort() {
+        let table = rows(json!([
+            {"name": "ann", "age": 31, "owner": {"login": "a1"}},
+            {"name": "bob", "age": "9"},
+            {"name": "cy", "age": 45},
+        ]));
+        let adults = filter(table.clone(), "age", "gt", "18").unwrap();
+        assert_eq!(filter(table.clone(), "age", ">=", "45").unwrap().len(), 1);
+        assert!(filter(table.clone(), "age", "~", "4").is_err());
+        assert_eq!(adults.len(), 2);
+        let sorted = sort_by(table.clone(), "age", true);
+        assert_eq!(sorted[0]["name"], "cy");
+        assert_eq!(sorted[2]["name"], "bob");
+        let picked = select(table, &["name".to_string(), "owner.login".to_string()]);
+        assert_eq!(picked[0], rows(json!({"name": "ann", "owner.login": "a1"}))[0]);
+        assert_eq!(picked[1]["owner.login"], Value::Null);
+    }
+
+    #[test]
+    fn table_rendering() {
+        let table = render_table(&rows(json!([{"name": "日本", "n": 5}, {"name": "x", "n": 10}])));
+        assert_eq!(table, "\
+╭───┬──────┬────╮
+│ # │ name │ n  │
+├───┼──────┼────┤
+│ 0 │ 日本 │  5 │
+│ 1 │ x    │ 10 │
+╰───┴──────┴────╯
+");
+    }
+}
@@ -0,0 +1,89 @@
+// src/sysinfo.rs
+use crate::shell::Shell;
+use crate::utils::helpers::{self, get_system_info};
+use crate::outln;
+
+// Rows shown by `sysinfo`, in order; missing fields are skipped
+const FIELDS: &[(&str, &str)] = &[
+    ("os", "OS"),
+    ("kernel", "Kernel"),
+    ("arch", "Arch"),
+    ("uptime", "Uptime"),
+    ("load", "Load"),
+    ("memory", "Memory"),
+    ("cpus", "CPUs"),
+    ("terminal", "Terminal"),
+];
+
+pub fn builtin_sysinfo(shell: &mut Shell, args: &[String]) -> i32 {
+    let info = get_system_info();
+
+    // `sysinfo <field>...` prints bare values, for scripts
+    if !args.is_empty() {
+        let mut status = 0;
+        for field in args {
+            match info.get(field.as_str()) {
+                Some(value) => outln!("{}", value),
+                None => {
+                    eprintln!("sysinfo: {}: unknown or unavailable field", field);
+                    status = 1;
+                }
+            }
+        }
+        return status;
+    }
+
+    let theme = &shell.config.theme;
+    let title = format!("{}@{}", shell.username, shell.hostname);
+    outln!("{}{}{}", theme.prompt_color, title, theme.reset_color);
+    outln!("{}", "-".repeat(helpers::display_width(&title)));
+    for (key, label) in FIELDS {
+        if let Some(value) = info.get(*key) {
+            outln!("{}{:<9}{} {}", theme.builtin_color, format!("{}:", label), theme.reset_color, value);
+        }
+    }
+    outln!("{}{:<9}{} {} {}", theme.builtin_color, "Shell:", theme.reset_color,
+             info["shell"], info["version"]);
+    0
+}
+
+// Fills the system placeholders of a prompt format. Each one reads /proc (or
+// sysctl) or calls into libc, so only the placeholders actually used are computed.
+pub fn expand_prompt_segments(prompt: &str) -> String {
+    let mut prompt = prompt.to_string();
+    if prompt.contains("{os}") {
+        prompt = prompt.replace("{os}", &helpers::os_name().unwrap_or_default());
+    }
+    if prompt.contains("{kernel}") {
+        let kernel = helpers::kernel_info().map(|(kernel, _)| kernel).unwrap_or_default();
+        prompt = prompt.replace("{kernel}", &kernel);
+    }
+    if prompt.contains("{load}") {
+        let load = helpers::load_average()
+            .map(|(one, _, _)| format!("{:.2}", one))
+            .unwrap_or_default();
+        prompt = prompt.replace("{load}", &load);
+    }
+    if prompt.contains("{uptime}") {
+        let uptime = helpers::system_uptime().map(helpers::format_duration).unwrap_or_default();
+        prompt = prompt.replace("{uptime}", &uptime);
+    }
+    if prompt.contains("{mem}") {
+        let mem = helpers::memory_info()
+            .map(|(total, available)| format!("{}%", total.saturating_sub(available) * 100 / total.max(1)))
+            .unwrap_or_default();
+        prompt = prompt.replace("{mem}", &mem);
+    }
+    prompt
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn unused_segments_are_left_alone() {
+        assert_eq!(expand_prompt_segments("{user} $ "), "{user} $ ");
+        assert!(!expand_prompt_segments("[{load}|{mem}] $ ").contains('{'));
+    }
+}
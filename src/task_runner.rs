@@ -0,0 +1,446 @@
+// src/task_runner.rs
+use crate::config::TaskConfig;
+use crate::parallel_exec::{self, BatchProgress, CommandResult, ResultFormat};
+use crate::scripting::ScriptEngine;
+use crate::shell::{trap, Shell};
+use crossbeam::channel::{unbounded, Sender};
+use crate::utils::helpers::pad_to_width;
+use crate::{out, outln};
+use nix::fcntl::OFlag;
+use nix::sys::wait::{waitpid, WaitStatus};
+use nix::unistd::{close, dup2, fork, pipe2, ForkResult};
+use serde::Deserialize;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::fs::{self, File};
+use std::io::{Read, Write};
+use std::os::unix::io::{FromRawFd, RawFd};
+use std::path::{Path, PathBuf};
+use std::thread;
+use std::time::{Duration, Instant};
+
+const TASK_FILE: &str = "rtasks.toml";
+const JOB_OUTPUT: &str = "job output";
+
+#[derive(Debug, Deserialize)]
+struct TaskFile {
+    tasks: HashMap<String, TaskConfig>,
+}
+
+// Tasks from ./rtasks.toml take precedence over the [tasks] config section
+pub fn load_tasks(shell: &Shell) -> Result<HashMap<String, TaskConfig>, String> {
+    let path = Path::new(TASK_FILE);
+    if path.exists() {
+        let contents = fs::read_to_string(path)
+            .map_err(|e| format!("{}: {}", TASK_FILE, e))?;
+        let file: TaskFile = toml::from_str(&contents)
+            .map_err(|e| format!("{}: {}", TASK_FILE, e))?;
+        return Ok(file.tasks);
+    }
+    Ok(shell.config.tasks.clone())
+}
+
+// Collects the requested targets and everything they depend on, grouped into
+// waves: every task in a wave only depends on tasks from earlier waves. `run`
+// does not wait for whole waves; they order the tasks for the critical path
+// and show there is no cycle.
+pub fn plan_waves(
+    tasks: &HashMap<String, TaskConfig>,
+    targets: &[String],
+) -> Result<Vec<Vec<String>>, String> {
+    let mut needed = HashSet::new();
+    let mut stack: Vec<String> = targets.to_vec();
+    while let Some(name) = stack.pop() {
+        let task = tasks.get(&name).ok_or_else(|| format!("unknown task '{}'", name))?;
+        if needed.insert(name.clone()) {
+            stack.extend(task.deps.iter().cloned());
+        }
+    }
+    
+    let mut done: HashSet<String> = HashSet::new();
+    let mut waves = Vec::new();
+    while done.len() < needed.len() {
+        let mut wave: Vec<String> = needed
+            .iter()
+            .filter(|name| !done.contains(*name))
+            .filter(|name| tasks[*name].deps.iter().all(|dep| done.contains(dep)))
+            .cloned()
+            .collect();
+        
+        if wave.is_empty() {
+            let mut pending: Vec<_> = needed.difference(&done).cloned().collect();
+            pending.sort();
+            return Err(format!("dependency cycle between: {}", pending.join(", ")));
+        }
+        
+        wave.sort();
+        done.extend(wave.iter().cloned());
+        waves.push(wave);
+    }
+    
+    Ok(waves)
+}
+
+// Longest chain of dependent tasks by accumulated duration
+pub fn critical_path(
+    tasks: &HashMap<String, TaskConfig>,
+    durations: &HashMap<String, Duration>,
+    waves: &[Vec<String>],
+) -> (Vec<String>, Duration) {
+    let mut finish: HashMap<&str, (Duration, Option<&str>)> = HashMap::new();
+    
+    for name in waves.iter().flatten() {
+        let (start, prev) = tasks[name]
+            .deps
+            .iter()
+            .filter_map(|dep| finish.get(dep.as_str()).map(|(t, _)| (*t, Some(dep.as_str()))))
+            .max_by_key(|(t, _)| *t)
+            .unwrap_or((Duration::ZERO, None));
+        let own = durations.get(name).copied().unwrap_or_default();
+        finish.insert(name.as_str(), (start + own, prev));
+    }
+    
+    let Some((&last, &(total, _))) = finish.iter().max_by_key(|(_, (t, _))| *t) else {
+        return (Vec::new(), Duration::ZERO);
+    };
+    
+    let mut path = vec![last.to_string()];
+    let mut current = last;
+    while let Some((_, Some(prev))) = finish.get(current) {
+        path.push(prev.to_string());
+        current = prev;
+    }
+    path.reverse();
+    (path, total)
+}
+
+// Starts `task` in a forked copy of the shell, which runs the configured
+// command with rshell's own parser and executor, as a subshell does: the
+// task sees the shell's functions, aliases and variables. A thread collects
+// its output and status and sends them to `done`.
+fn spawn_task(shell: &mut Shell, name: &str, task: &TaskConfig, done: &Sender<(String, CommandResult)>) {
+    let start = Instant::now();
+    let failed = |error: String| CommandResult {
+        command: task.command.clone(),
+        exit_code: -1,
+        stdout: String::new(),
+        stderr: error,
+        duration: start.elapsed(),
+    };
+    let pipes = pipe2(OFlag::O_CLOEXEC).and_then(|stdout| Ok((stdout, pipe2(OFlag::O_CLOEXEC)?)));
+    let ((stdout_read, stdout_write), (stderr_read, stderr_write)) = match pipes {
+        Ok(pipes) => pipes,
+        Err(e) => {
+            let _ = done.send((name.to_string(), failed(e.to_string())));
+            return;
+        }
+    };
+    let _ = std::io::stdout().flush();
+    match unsafe { fork() } {
+        Ok(ForkResult::Child) => {
+            let _ = dup2(stdout_write, 1);
+            let _ = dup2(stderr_write, 2);
+            trap::reset_for_subshell(shell);
+            let code = ScriptEngine::new().execute_str(shell, &task.command).unwrap_or_else(|e| {
+                eprintln!("rshell: {}", e);
+                2
+            });
+            let _ = std::io::stdout().flush();
+            std::process::exit(code);
+        }
+        Ok(ForkResult::Parent { child }) => {
+            let _ = close(stdout_write);
+            let _ = close(stderr_write);
+            let (name, command, done) = (name.to_string(), task.command.clone(), done.clone());
+            thread::spawn(move || {
+                let stderr = thread::spawn(move || read_all(stderr_read));
+                let stdout = read_all(stdout_read);
+                let stderr = stderr.join().unwrap_or_default();
+                let exit_code = match waitpid(child, None) {
+                    Ok(WaitStatus::Exited(_, code)) => code,
+                    Ok(WaitStatus::Signaled(_, signal, _)) => 128 + signal as i32,
+                    _ => -1,
+                };
+                let result = CommandResult { command, exit_code, stdout, stderr, duration: start.elapsed() };
+                let _ = done.send((name, result));
+            });
+        }
+        Err(e) => {
+            for fd in [stdout_read, stdout_write, stderr_read, stderr_write] {
+                let _ = close(fd);
+            }
+            let _ = done.send((name.to_string(), failed(format!("fork failed: {}", e))));
+        }
+    }
+}
+
+fn read_all(fd: RawFd) -> String {
+    let mut output = Vec::new();
+    let _ = unsafe { File::from_raw_fd(fd) }.read_to_end(&mut output);
+    String::from_utf8_lossy(&output).into_owned()
+}
+
+// Runs the planned tasks, at most `jobs` at a time, each as soon as the
+// last of its deps has finished, and hands every result to `report` as it
+// comes in, along with the shell. Once `report` returns false nothing new starts, but the tasks
+// already running are waited for.
+pub fn run_tasks(
+    shell: &mut Shell,
+    tasks: &HashMap<String, TaskConfig>,
+    waves: &[Vec<String>],
+    jobs: usize,
+    mut report: impl FnMut(&mut Shell, &str, CommandResult) -> bool,
+) {
+    // How many deps each task still waits for, and who waits for each
+    let mut waiting: HashMap<&str, usize> = HashMap::new();
+    let mut dependents: HashMap<&str, Vec<&str>> = HashMap::new();
+    for name in waves.iter().flatten() {
+        waiting.insert(name, tasks[name].deps.len());
+        for dep in &tasks[name].deps {
+            dependents.entry(dep.as_str()).or_default().push(name);
+        }
+    }
+    
+    let (done, finished) = unbounded();
+    let mut ready: VecDeque<&str> = waves.first().into_iter().flatten().map(String::as_str).collect();
+    let mut running = 0;
+    let mut stopped = false;
+    loop {
+        while running < jobs.max(1) && !stopped {
+            let Some(name) = ready.pop_front() else { break };
+            spawn_task(shell, name, &tasks[name], &done);
+            running += 1;
+        }
+        if running == 0 {
+            break;
+        }
+        let Ok((name, result)) = finished.recv() else { break };
+        running -= 1;
+        stopped |= !report(shell, &name, result);
+        if stopped {
+            continue;
+        }
+        for dependent in dependents.get(name.as_str()).into_iter().flatten() {
+            let count = waiting.get_mut(dependent).expect("dependent is planned");
+            *count -= 1;
+            if *count == 0 {
+                ready.push_back(dependent);
+            }
+        }
+    }
+}
+
+pub fn builtin_run(shell: &mut Shell, args: &[String]) -> i32 {
+    let mut targets = Vec::new();
+    let mut list = false;
+    let mut output_format = None;
+    let mut output_dir: Option<PathBuf> = None;
+    
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        match arg.as_str() {
+            "-l" | "--list" => list = true,
+            "--output" => match iter.next().map(|f| f.parse::<ResultFormat>()) {
+                Some(Ok(format)) => output_format = Some(format),
+                Some(Err(e)) => {
+                    eprintln!("run: {}", e);
+                    return 1;
+                }
+                None => {
+                    eprintln!("run: --output requires an argument");
+                    return 1;
+                }
+            },
+            // stdout and stderr go to files there, the records name the files
+            "--output-dir" => match iter.next() {
+                Some(dir) => output_dir = Some(PathBuf::from(dir)),
+                None => {
+                    eprintln!("run: --output-dir requires an argument");
+                    return 1;
+                }
+            },
+            _ => targets.push(arg.clone()),
+        }
+    }
+    
+    if output_dir.is_some() && output_format.is_none() {
+        eprintln!("run: --output-dir needs --output json|tsv");
+        return 1;
+    }
+    
+    let tasks = match load_tasks(shell) {
+        Ok(tasks) => tasks,
+        Err(e) => {
+            eprintln!("run: {}", e);
+            return 1;
+        }
+    };
+    
+    if list || targets.is_empty() {
+        if tasks.is_empty() {
+            outln!("No tasks defined (add a [tasks] section to the config or create {})", TASK_FILE);
+            return if list { 0 } else { 1 };
+        }
+        let mut names: Vec<_> = tasks.keys().collect();
+        names.sort();
+        for name in names {
+            let task = &tasks[name];
+            if task.deps.is_empty() {
+                outln!("  {} {}", pad_to_width(name, 15), task.command);
+            } else {
+                outln!("  {} {} (after {})", pad_to_width(name, 15), task.command, task.deps.join(", "));
+            }
+        }
+        return 0;
+    }
+    
+    let waves = match plan_waves(&tasks, &targets) {
+        Ok(waves) => waves,
+        Err(e) => {
+            eprintln!("run: {}", e);
+            return 1;
+        }
+    };
+    
+    let mut durations = HashMap::new();
+    let mut all_results: Vec<(String, CommandResult)> = Vec::new();
+    let mut failed = None;
+    // Output held until the end of the run, counted in `stats memory` meanwhile
+    let mut buffered = 0;
+    let progress = BatchProgress::new(waves.iter().map(Vec::len).sum());
+    run_tasks(shell, &tasks, &waves, num_cpus::get(), |shell, name, result| {
+        durations.insert(name.to_string(), result.duration);
+        progress.clear();
+        if output_format.is_none() {
+            let status = if result.exit_code == 0 { "ok" } else { "FAILED" };
+            outln!("==> {} ({:.2}s) {}", name, result.duration.as_secs_f64(), status);
+            out!("{}", result.stdout);
+            eprint!("{}", result.stderr);
+        }
+        if result.exit_code != 0 && failed.is_none() {
+            failed = Some((name.to_string(), result.exit_code));
+        }
+        progress.record(&result);
+        let size = result.stdout.capacity() + result.stderr.capacity();
+        shell.memory.track(JOB_OUTPUT, size);
+        buffered += size;
+        all_results.push((name.to_string(), result));
+        failed.is_none()
+    });
+    progress.finish();
+    
+    if let Some(format) = output_format {
+        match parallel_exec::build_records(&all_results, output_dir.as_deref()) {
+            Ok(records) => out!("{}", parallel_exec::format_records(&records, format)),
+            Err(e) => eprintln!("run: {}", e),
+        }
+    } else {
+        let (path, total) = critical_path(&tasks, &durations, &waves);
+        outln!("Critical path: {} ({:.2}s)", path.join(" -> "), total.as_secs_f64());
+    }
+    drop(all_results);
+    shell.memory.untrack(JOB_OUTPUT, buffered);
+    
+    match failed {
+        Some((name, code)) => {
+            eprintln!("run: task '{}' failed with exit code {}", name, code);
+            if code > 0 { code } else { 1 }
+        }
+        None => 0,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+    
+    fn task(deps: &[&str]) -> TaskConfig {
+        task_running("true", deps)
+    }
+    
+    fn task_running(command: &str, deps: &[&str]) -> TaskConfig {
+        TaskConfig {
+            command: command.to_string(),
+            deps: deps.iter().map(|d| d.to_string()).collect(),
+        }
+    }
+    
+    #[test]
+    fn test_plan_waves_and_critical_path() {
+        let mut tasks = HashMap::new();
+        tasks.insert("build".to_string(), task(&["gen", "fetch"]));
+        tasks.insert("gen".to_string(), task(&[]));
+        tasks.insert("fetch".to_string(), task(&[]));
+        tasks.insert("lint".to_string(), task(&[]));
+        
+        let waves = plan_waves(&tasks, &["build".to_string()]).unwrap();
+        assert_eq!(waves, vec![
+            vec!["fetch".to_string(), "gen".to_string()],
+            vec!["build".to_string()],
+        ]);
+        
+        let mut durations = HashMap::new();
+        durations.insert("fetch".to_string(), Duration::from_secs(1));
+        durations.insert("gen".to_string(), Duration::from_secs(3));
+        durations.insert("build".to_string(), Duration::from_secs(2));
+        let (path, total) = critical_path(&tasks, &durations, &waves);
+        assert_eq!(path, vec!["gen".to_string(), "build".to_string()]);
+        assert_eq!(total, Duration::from_secs(5));
+    }
+    
+    #[test]
+    fn test_plan_waves_detects_cycles() {
+        let mut tasks = HashMap::new();
+        tasks.insert("a".to_string(), task(&["b"]));
+        tasks.insert("b".to_string(), task(&["a"]));
+        assert!(plan_waves(&tasks, &["a".to_string()]).is_err());
+        assert!(plan_waves(&tasks, &["missing".to_string()]).is_err());
+    }
+    
+    #[test]
+    fn test_run_tasks_starts_each_when_its_deps_finish() {
+        let mut tasks = HashMap::new();
+        tasks.insert("slow".to_string(), task_running("sleep 1", &[]));
+        tasks.insert("fast".to_string(), task(&[]));
+        tasks.insert("after-fast".to_string(), task(&["fast"]));
+        tasks.insert("all".to_string(), task(&["slow", "after-fast"]));
+        let waves = plan_waves(&tasks, &["all".to_string()]).unwrap();
+        
+        let mut shell = Shell::with_config(Config::default());
+        let mut finished = Vec::new();
+        run_tasks(&mut shell, &tasks, &waves, 4, |_, name, _| {
+            finished.push(name.to_string());
+            true
+        });
+        // after-fast does not wait for slow, which is in the wave before it
+        assert_eq!(finished, ["fast", "after-fast", "slow", "all"]);
+        
+        let mut finished = Vec::new();
+        run_tasks(&mut shell, &tasks, &waves, 4, |_, name, _| {
+            finished.push(name.to_string());
+            name != "fast"
+        });
+        assert_eq!(finished, ["fast", "slow"]);
+    }
+    
+    #[test]
+    fn test_tasks_run_in_the_shell() {
+        let mut tasks = HashMap::new();
+        tasks.insert("greet".to_string(), task_running("greet world | tr a-z A-Z; echo oops >&2; exit 3", &[]));
+        let waves = plan_waves(&tasks, &["greet".to_string()]).unwrap();
+        
+        let mut shell = Shell::with_config(Config::default());
+        ScriptEngine::new().execute_str(&mut shell, "greet() { echo \"hello $1\"; }").unwrap();
+        let mut results = Vec::new();
+        run_tasks(&mut shell, &tasks, &waves, 1, |_, name, result| {
+            results.push((name.to_string(), result));
+            true
+        });
+        let (name, result) = &results[0];
+        assert_eq!(name, "greet");
+        assert_eq!(result.command, "greet world | tr a-z A-Z; echo oops >&2; exit 3");
+        assert_eq!(result.stdout, "HELLO WORLD\n");
+        assert_eq!(result.stderr, "oops\n");
+        assert_eq!(result.exit_code, 3);
+    }
+}
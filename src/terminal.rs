@@ -0,0 +1,146 @@
+// src/terminal.rs
+use crate::config::Theme;
+use crate::multiplexer::{self, Multiplexer};
+use crate::shell::Shell;
+use std::fmt::Write as _;
+use std::io::{self, Write};
+
+pub fn stdout_is_terminal() -> bool {
+    unsafe { libc::isatty(libc::STDOUT_FILENO) == 1 }
+}
+
+// The width of the terminal on stdout
+pub fn columns() -> Option<u16> {
+    let mut size: libc::winsize = unsafe { std::mem::zeroed() };
+    let ok = unsafe { libc::ioctl(libc::STDOUT_FILENO, libc::TIOCGWINSZ, &mut size) } == 0;
+    (ok && size.ws_col > 0).then_some(size.ws_col)
+}
+
+// OSC 7: tells the terminal the working directory as a file:// URL
+pub fn osc7_cwd(hostname: &str, cwd: &str) -> String {
+    format!("\x1b]7;file://{}{}\x1b\\", hostname, percent_encode_path(cwd))
+}
+
+// OSC 0: sets the window and tab title. Control characters would end the
+// sequence early, so they are dropped.
+pub fn osc0_title(title: &str) -> String {
+    let title: String = title.chars().filter(|c| !c.is_control()).collect();
+    format!("\x1b]0;{}\x07", title)
+}
+
+// Keeps RFC 3986 unreserved characters and '/', escapes every other byte
+fn percent_encode_path(path: &str) -> String {
+    let mut encoded = String::with_capacity(path.len());
+    for byte in path.bytes() {
+        if byte.is_ascii_alphanumeric() || matches!(byte, b'/' | b'-' | b'_' | b'.' | b'~') {
+            encoded.push(byte as char);
+        } else {
+            let _ = write!(encoded, "%{:02X}", byte);
+        }
+    }
+    encoded
+}
+
+// OSC 133 (FinalTerm) semantic marks
+pub const PROMPT_START: &str = "\x1b]133;A\x07";
+pub const INPUT_START: &str = "\x1b]133;B\x07";
+pub const OUTPUT_START: &str = "\x1b]133;C\x07";
+
+pub fn command_finished(exit_code: i32) -> String {
+    format!("\x1b]133;D;{}\x07", exit_code)
+}
+
+// Inside tmux or screen, sequences the multiplexer would swallow are
+// passed through to the outer terminal. Titles are not: they name the pane.
+fn passthrough(theme: &Theme, sequence: &str) -> String {
+    match Multiplexer::detect() {
+        Some(mux) if theme.multiplexer_passthrough => mux.passthrough(sequence),
+        _ => sequence.to_string(),
+    }
+}
+
+// Brackets the displayed prompt with the prompt/input marks. Only the
+// highlighted prompt carries them; line editing measures the plain one.
+pub fn mark_prompt(theme: &Theme, prompt: &str) -> String {
+    format!("{}{}{}", passthrough(theme, PROMPT_START), prompt, passthrough(theme, INPUT_START))
+}
+
+fn window_name(theme: &Theme, name: &str) -> String {
+    if theme.multiplexer_window_names && Multiplexer::detect().is_some() && !name.is_empty() {
+        multiplexer::window_name(name)
+    } else {
+        String::new()
+    }
+}
+
+fn render_title(shell: &Shell, format: &str, command: &str) -> String {
+    format
+        .replace("{user}", &shell.username)
+        .replace("{host}", &shell.hostname)
+        .replace("{cwd}", &shell.get_cwd_display())
+        .replace("{command}", command)
+}
+
+fn emit(sequences: &str) {
+    if sequences.is_empty() || !stdout_is_terminal() {
+        return;
+    }
+    let mut stdout = io::stdout();
+    let _ = stdout.write_all(sequences.as_bytes());
+    let _ = stdout.flush();
+}
+
+// Sent before each prompt: where we are, and the idle title
+pub fn report_prompt(shell: &Shell) {
+    let theme = &shell.config.theme;
+    let mut sequences = String::new();
+    if theme.report_cwd {
+        sequences.push_str(&passthrough(theme, &osc7_cwd(&shell.hostname, &shell.current_dir)));
+    }
+    let dir_name = std::path::Path::new(&shell.current_dir).file_name()
+        .map(|name| name.to_string_lossy().to_string())
+        .unwrap_or_else(|| "/".to_string());
+    sequences.push_str(&window_name(theme, &dir_name));
+    if !theme.title_format.is_empty() {
+        sequences.push_str(&osc0_title(&render_title(shell, &theme.title_format, "")));
+    }
+    emit(&sequences);
+}
+
+// Sent when a command line starts: the title shows what is running and
+// everything printed from here on is the command's output
+pub fn report_command_start(shell: &Shell, command: &str) {
+    let theme = &shell.config.theme;
+    let mut sequences = String::new();
+    if !theme.command_title_format.is_empty() {
+        sequences.push_str(&osc0_title(&render_title(shell, &theme.command_title_format, command)));
+    }
+    sequences.push_str(&window_name(theme, command.split_whitespace().next().unwrap_or("")));
+    if theme.semantic_prompts {
+        sequences.push_str(&passthrough(theme, OUTPUT_START));
+    }
+    emit(&sequences);
+}
+
+// Closes the output of a command line with its exit status
+pub fn report_command_end(shell: &Shell) {
+    let theme = &shell.config.theme;
+    if theme.semantic_prompts {
+        emit(&passthrough(theme, &command_finished(shell.last_exit_code)));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn osc_sequences() {
+        assert_eq!(osc7_cwd("box", "/home/me/My Docs"), "\x1b]7;file://box/home/me/My%20Docs\x1b\\");
+        assert_eq!(osc7_cwd("box", "/tmp/ü"), "\x1b]7;file://box/tmp/%C3%BC\x1b\\");
+        assert_eq!(osc0_title("me@box: vim\x07x"), "\x1b]0;me@box: vimx\x07");
+        let theme = Theme { multiplexer_passthrough: false, ..Theme::default() };
+        assert_eq!(mark_prompt(&theme, "$ "), "\x1b]133;A\x07$ \x1b]133;B\x07");
+        assert_eq!(command_finished(127), "\x1b]133;D;127\x07");
+    }
+}
@@ -3,6 +3,10 @@ use std::collections::HashMap;
 use std::fs::File;
 use std::io::{self,Write,BufRead,BufReader};
 use std::path::Path;
+use unicode_segmentation::UnicodeSegmentation;
+use unicode_width::UnicodeWidthStr;
+
+pub use crate::platform::{memory_info,os_name,system_uptime};
 
 pub fn print_error(message:&str){
     eprintln!("rshell: {}",message);
@@ -78,24 +82,62 @@ pub fn save_config(config:&HashMap<String,String>,config_path:&Path)->io::Result
     Ok(())
 }
 
+// Short human-readable duration: "850ns", "340µs", "12.3ms", "4.56s", "12.5s",
+// "2m 5s", "1h 2m 3s", "3d 4h 5m". Precision drops as the value grows.
 pub fn format_duration(duration:std::time::Duration)->String{
+    let nanos=duration.as_nanos();
+    if nanos<1_000{
+        return format!("{}ns",nanos);
+    }
+    if nanos<1_000_000{
+        return format!("{}µs",nanos/1_000);
+    }
+    if nanos<1_000_000_000{
+        return format!("{:.1}ms",nanos as f64/1e6);
+    }
     let total_seconds=duration.as_secs();
-    let hours=total_seconds/3600;
+    let days=total_seconds/86400;
+    let hours=(total_seconds%86400)/3600;
     let minutes=(total_seconds%3600)/60;
     let seconds=total_seconds%60;
-    if hours>0{
+    if days>0{
+        format!("{}d {}h {}m",days,hours,minutes)
+    }else if hours>0{
         format!("{}h {}m {}s", hours,minutes,seconds)
     }else if minutes>0{
         format!("{}m {}s",minutes,seconds)
+    }else if total_seconds<10{
+        format!("{:.2}s",duration.as_secs_f64())
     }else{
-        format!("{}s",seconds)
+        format!("{:.1}s",duration.as_secs_f64())
     }
 }
 
+// Binary units: "512 B", "1.5 KiB", "3.2 MiB", "1.0 GiB"
+pub fn format_bytes(bytes:u64)->String{
+    const UNITS:[&str;5]=["KiB","MiB","GiB","TiB","PiB"];
+    if bytes<1024{
+        return format!("{} B",bytes);
+    }
+    let mut value=bytes as f64/1024.0;
+    let mut unit=0;
+    while value>=1024.0 && unit<UNITS.len()-1{
+        value/=1024.0;
+        unit+=1;
+    }
+    format!("{:.1} {}",value,UNITS[unit])
+}
+
 pub fn is_valid_command_name(name: &str)->bool{
     !name.is_empty() && !name.contains('\0') && !name.contains('/') && name.chars().all(|c|c.is_alphanumeric() || c=='_' || c=='-')
 }
 
+// Shell variable names: a letter or underscore, then letters, digits, underscores
+pub fn is_valid_var_name(name:&str)->bool{
+    let mut chars=name.chars();
+    matches!(chars.next(),Some(c) if c.is_ascii_alphabetic()||c=='_') && chars.all(|c|c.is_ascii_alphanumeric()||c=='_')
+}
+
 pub fn sanitize_string(input:&str)->String{
     input.chars().filter(|&c|c!='\0'&&c.is_ascii()).collect()
 }
@@ -121,13 +163,91 @@ pub fn get_default_prompt(current_dir:&str,username:&str,hostname:&str)->String{
     format!("{}@{}:{}$ ",username,hostname,current_dir)
 }
 
-// Truncate a string to a maximum length, adding "..." if truncated
+// Truncate a string to a maximum display width, adding "..." if truncated
 pub fn truncate_string(s:&str,max_len:usize)->String{
-    if s.len()<=max_len{
-        s.to_string()
-    }else{
-        format!("{}...", &s[..max_len.saturating_sub(3)])
+    truncate_to_width(s,max_len)
+}
+
+// Terminal columns taken by `s`. ANSI escape sequences take none, wide (CJK,
+// most emoji) characters take two, and combining marks none.
+pub fn display_width(s:&str)->usize{
+    strip_ansi(s).width()
+}
+
+// Removes CSI (`ESC [ ... final`) and OSC (`ESC ] ... BEL|ESC \`) sequences
+pub fn strip_ansi(s:&str)->String{
+    let mut out=String::with_capacity(s.len());
+    let mut chars=s.chars().peekable();
+    while let Some(ch)=chars.next(){
+        if ch!='\x1b'{
+            out.push(ch);
+            continue;
+        }
+        match chars.next(){
+            Some('[')=>{
+                for c in chars.by_ref(){
+                    if ('@'..='~').contains(&c){
+                        break;
+                    }
+                }
+            }
+            Some(']')=>{
+                while let Some(c)=chars.next(){
+                    if c=='\x07' || (c=='\x1b' && chars.next_if_eq(&'\\').is_some()){
+                        break;
+                    }
+                }
+            }
+            _=>{}
+        }
+    }
+    out
+}
+
+// Keeps whole grapheme clusters from the start while they fit in `max_width`
+// columns, appending "..." when something was cut
+pub fn truncate_to_width(s:&str,max_width:usize)->String{
+    if s.width()<=max_width{
+        return s.to_string();
+    }
+    let budget=max_width.saturating_sub(3);
+    let mut used=0;
+    let mut out=String::new();
+    for grapheme in s.graphemes(true){
+        let w=grapheme.width();
+        if used+w>budget{
+            break;
+        }
+        used+=w;
+        out.push_str(grapheme);
     }
+    out.push_str(&"..."[..max_width.min(3)]);
+    out
+}
+
+// Like truncate_to_width but keeps the end, for paths: ".../src/shell"
+pub fn truncate_start_to_width(s:&str,max_width:usize)->String{
+    if s.width()<=max_width{
+        return s.to_string();
+    }
+    let budget=max_width.saturating_sub(3);
+    let mut used=0;
+    let mut start=s.len();
+    for (idx,grapheme) in s.grapheme_indices(true).rev(){
+        let w=grapheme.width();
+        if used+w>budget{
+            break;
+        }
+        used+=w;
+        start=idx;
+    }
+    format!("{}{}",&"..."[..max_width.min(3)],&s[start..])
+}
+
+// Pads with spaces to `width` display columns (format!'s `{:<N}` counts chars)
+pub fn pad_to_width(s:&str,width:usize)->String{
+    let pad=width.saturating_sub(display_width(s));
+    format!("{}{}",s," ".repeat(pad))
 }
 
 pub fn is_interactive()->bool{
@@ -140,14 +260,90 @@ pub fn get_system_info()->HashMap<String,String>{
     if let Ok(username)=std::env::var("USER"){
         info.insert("username".to_string(),username);
     }
-    if let Ok(hostname)=std::env::var("HOSTNAME"){
-        info.insert("hostname".to_string(),hostname);
-    }
+    let hostname=std::env::var("HOSTNAME")
+        .unwrap_or_else(|_|gethostname::gethostname().to_string_lossy().to_string());
+    info.insert("hostname".to_string(),hostname);
     info.insert("shell".to_string(),"rshell".to_string());
     info.insert("version".to_string(),env!("CARGO_PKG_VERSION").to_string());
+    if let Some(os)=os_name(){
+        info.insert("os".to_string(),os);
+    }
+    if let Some((kernel,arch))=kernel_info(){
+        info.insert("kernel".to_string(),kernel);
+        info.insert("arch".to_string(),arch);
+    }
+    if let Some(uptime)=system_uptime(){
+        info.insert("uptime".to_string(),format_duration(uptime));
+    }
+    if let Some((one,five,fifteen))=load_average(){
+        info.insert("load".to_string(),format!("{:.2} {:.2} {:.2}",one,five,fifteen));
+    }
+    if let Some((total_kb,available_kb))=memory_info(){
+        let used_kb=total_kb.saturating_sub(available_kb);
+        info.insert("memory".to_string(),format!("{} / {} ({}%)",
+            format_bytes(used_kb*1024),format_bytes(total_kb*1024),used_kb*100/total_kb.max(1)));
+    }
+    info.insert("cpus".to_string(),num_cpus::get().to_string());
+    if let Some(terminal)=terminal_name(){
+        info.insert("terminal".to_string(),terminal);
+    }
     info
 }
 
+// Kernel name and release, and the machine architecture, from uname(2)
+pub fn kernel_info()->Option<(String,String)>{
+    let mut uts:libc::utsname=unsafe{std::mem::zeroed()};
+    if unsafe{libc::uname(&mut uts)}!=0{
+        return None;
+    }
+    let field=|chars:&[libc::c_char]|unsafe{std::ffi::CStr::from_ptr(chars.as_ptr())}
+        .to_string_lossy().to_string();
+    Some((format!("{} {}",field(&uts.sysname),field(&uts.release)),field(&uts.machine)))
+}
+
+pub fn load_average()->Option<(f64,f64,f64)>{
+    let mut loads=[0f64;3];
+    if unsafe{libc::getloadavg(loads.as_mut_ptr(),3)}!=3{
+        return None;
+    }
+    Some((loads[0],loads[1],loads[2]))
+}
+
+// $TERM plus the controlling tty, e.g. "xterm-256color (/dev/pts/3)"
+pub fn terminal_name()->Option<String>{
+    let term=std::env::var("TERM").ok();
+    match (term,tty_name()){
+        (Some(term),Some(tty))=>Some(format!("{} ({})",term,tty)),
+        (Some(term),None)=>Some(term),
+        (None,tty)=>tty,
+    }
+}
+
+// Controlling terminal of stdin, e.g. "/dev/pts/3"
+pub fn tty_name()->Option<String>{
+    unsafe{
+        let name=libc::ttyname(libc::STDIN_FILENO);
+        (!name.is_null()).then(||std::ffi::CStr::from_ptr(name).to_string_lossy().to_string())
+    }
+}
+
+// Seconds since the epoch as "2024-03-09T14:05:00Z"
+pub fn format_utc_timestamp(secs:u64)->String{
+    let days=(secs/86400) as i64;
+    let rem=secs%86400;
+    // Civil-from-days (Howard Hinnant), valid for any date after 1970
+    let z=days+719468;
+    let era=z/146097;
+    let doe=z-era*146097;
+    let yoe=(doe-doe/1460+doe/36524-doe/146096)/365;
+    let doy=doe-(365*yoe+yoe/4-yoe/100);
+    let mp=(5*doy+2)/153;
+    let day=doy-(153*mp+2)/5+1;
+    let month=if mp<10{mp+3}else{mp-9};
+    let year=yoe+era*400+i64::from(month<=2);
+    format!("{:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z",year,month,day,rem/3600,rem%3600/60,rem%60)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -171,10 +367,49 @@ mod tests {
         assert!(parse_exit_code("abc").is_err());
     }
     
+    #[test]
+    fn test_format_duration(){
+        use std::time::Duration;
+        assert_eq!(format_duration(Duration::from_nanos(850)),"850ns");
+        assert_eq!(format_duration(Duration::from_micros(340)),"340µs");
+        assert_eq!(format_duration(Duration::from_micros(12_345)),"12.3ms");
+        assert_eq!(format_duration(Duration::from_millis(4_560)),"4.56s");
+        assert_eq!(format_duration(Duration::from_millis(12_500)),"12.5s");
+        assert_eq!(format_duration(Duration::from_secs(125)),"2m 5s");
+        assert_eq!(format_duration(Duration::from_secs(3723)),"1h 2m 3s");
+        assert_eq!(format_duration(Duration::from_secs(3*86400+4*3600+5*60)),"3d 4h 5m");
+    }
+    
+    #[test]
+    fn test_format_bytes(){
+        assert_eq!(format_bytes(512),"512 B");
+        assert_eq!(format_bytes(1536),"1.5 KiB");
+        assert_eq!(format_bytes(3*1024*1024+200*1024),"3.2 MiB");
+        assert_eq!(format_bytes(1<<30),"1.0 GiB");
+    }
+    
+    #[test]
+    fn test_format_utc_timestamp(){
+        assert_eq!(format_utc_timestamp(0),"1970-01-01T00:00:00Z");
+        assert_eq!(format_utc_timestamp(951868799),"2000-02-29T23:59:59Z");
+        assert_eq!(format_utc_timestamp(1709993100),"2024-03-09T14:05:00Z");
+    }
+    
     #[test]
     fn test_truncate_string(){
         assert_eq!(truncate_string("hello", 10),"hello");
         assert_eq!(truncate_string("hello world",8),"hello...");
         assert_eq!(truncate_string("hi",5),"hi");
     }
+    
+    #[test]
+    fn test_unicode_widths(){
+        assert_eq!(display_width("\x1b[1;32m日本\x1b[0m ok"),7);
+        assert_eq!(display_width("\x1b]0;title\x07e\u{301}"),1);
+        assert_eq!(truncate_to_width("日本語のパス",8),"日本...");
+        assert_eq!(truncate_to_width("🦀🦀🦀",5),"🦀...");
+        assert_eq!(truncate_start_to_width("/home/me/プロジェクト",12),"...ジェクト");
+        assert_eq!(truncate_start_to_width("/home/me/プロジェクト",16),".../プロジェクト");
+        assert_eq!(pad_to_width("日本",6),"日本  ");
+    }
 }
\ No newline at end of file
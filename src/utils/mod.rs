@@ -4,9 +4,12 @@ pub mod helpers;
 // Only export what's actually used
 
 pub mod string_utils{
+    // Splits on blanks outside quotes. A word with quotes in it is kept
+    // even if empty, so `a "" b` is three words.
     pub fn smart_split(input:&str)->Vec<String>{
         let mut result=Vec::new();
         let mut current=String::new();
+        let mut quoted=false;
         let mut in_quotes=false;
         let mut quote_char='"';
         let mut chars=input.chars().peekable();
@@ -14,15 +17,16 @@ pub mod string_utils{
             match ch{
                 '"' | '\'' if !in_quotes => {
                     in_quotes=true;
+                    quoted=true;
                     quote_char=ch;
                 }
                 ch if in_quotes && ch==quote_char=>{
                     in_quotes=false;
                 }
                 ' ' | '\t' if !in_quotes => {
-                    if !current.is_empty(){
-                        result.push(current.clone());
-                        current.clear();
+                    if !current.is_empty() || quoted{
+                        result.push(std::mem::take(&mut current));
+                        quoted=false;
                     }
                 }
                 '\\' if chars.peek().is_some()=>{
@@ -44,7 +48,7 @@ pub mod string_utils{
                 _=>current.push(ch),
             }
         }
-        if !current.is_empty(){
+        if !current.is_empty() || quoted{
             result.push(current);
         }
         result
@@ -63,10 +67,80 @@ pub mod string_utils{
     pub fn has_metacharacters(s:&str)->bool{
         s.chars().any(|c| matches!(c, '|' | '&' | ';' | '<' | '>' | '(' | ')' | '$' | '`' | '"' | '\'' | ' ' | '\t' | '\n'))
     }
+    // Characters that never need quoting anywhere in a word
+    fn is_shell_safe(c:char)->bool{
+        c.is_alphanumeric() || matches!(c, '-' | '_' | '.' | '/' | ':' | '=' | '@' | '%' | '+' | ',')
+    }
+    // Quotes `s` so the parser reads it back as exactly one word with the same
+    // text. Plain words are left alone; anything else is single-quoted, with
+    // embedded single quotes written as '\''.
+    pub fn shell_quote(s:&str)->String{
+        if !s.is_empty() && s.chars().all(is_shell_safe){
+            return s.to_string();
+        }
+        format!("'{}'", s.replace('\'', "'\\''"))
+    }
+    // Backslash-escapes a file name for insertion at the cursor, which keeps
+    // the word open so completion can continue into a directory. Names with
+    // control characters fall back to single quotes.
+    pub fn shell_quote_filename(name:&str)->String{
+        if name.chars().any(|c| c.is_control()){
+            return shell_quote(name);
+        }
+        let mut quoted=String::with_capacity(name.len());
+        for c in name.chars(){
+            if !is_shell_safe(c){
+                quoted.push('\\');
+            }
+            quoted.push(c);
+        }
+        quoted
+    }
+    #[cfg(test)]
+    mod tests{
+        use super::*;
+        #[test]
+        fn quoting_round_trips_through_the_parser(){
+            use crate::config::Config;
+            use crate::shell::{expand::expand_command, parser::Parser, Shell};
+            use crate::CommandType;
+            use bumpalo::Bump;
+            let arena=Bump::new();
+            let mut shell=Shell::with_config(Config::default());
+            assert_eq!(shell_quote("ls"),"ls");
+            assert_eq!(shell_quote(""),"''");
+            assert_eq!(shell_quote("it's $HOME"),"'it'\\''s $HOME'");
+            assert_eq!(shell_quote_filename("My Documents/"),"My\\ Documents/");
+            assert_eq!(shell_quote_filename("a\nb"),"'a\nb'");
+            for word in ["My Documents/", "it's", "a&b|c;d", "~x*", "tab\there", "'"]{
+                for quoted in [shell_quote(word), shell_quote_filename(word)]{
+                    let line=format!("echo {}", quoted);
+                    let parsed=Parser::new(&line,&arena).unwrap().parse().unwrap();
+                    match parsed{
+                        CommandType::Simple(cmd)=>{
+                            let cmd=expand_command(&mut shell,&cmd).unwrap();
+                            assert_eq!(cmd.args, vec![word.to_string()], "{}", line)
+                        }
+                        other=>panic!("unexpected parse {:?}", other),
+                    }
+                }
+            }
+            // Empty quoted words are arguments like any other
+            let CommandType::Simple(cmd)=Parser::new("printf %s \"\" a'' ''\"\"",&arena).unwrap().parse().unwrap() else { unreachable!() };
+            let cmd=expand_command(&mut shell,&cmd).unwrap();
+            assert_eq!(cmd.args, ["%s", "", "a", ""]);
+            assert_eq!(smart_split("x '' \"a\"\"\" y"), ["x", "", "a", "y"]);
+        }
+    }
 }
 
 pub mod path_utils{
-    use std::path::{Path, PathBuf};
+    use std::collections::VecDeque;
+    use std::ffi::OsString;
+    use std::fs;
+    use std::io;
+    use std::os::unix::fs::MetadataExt;
+    use std::path::{Component, Path, PathBuf};
     use std::env;
     pub fn find_in_path(program:&str)->Option<PathBuf>{
         if program.contains('/'){// If program contains a slash, treat it as a path
@@ -104,44 +178,424 @@ pub mod path_utils{
             Ok(current_dir.join(path))
         }
     }
+
+    // Joins `path` onto `base` and collapses `.` and `..` textually, without
+    // following symlinks: this is `cd -L`, where `link/..` is where you came from
+    pub fn normalize_logical(base: &Path, path: &Path) -> PathBuf {
+        let mut result = if path.is_absolute() { PathBuf::from("/") } else { base.to_path_buf() };
+        for component in path.components() {
+            match component {
+                Component::RootDir | Component::Prefix(_) => result = PathBuf::from("/"),
+                Component::CurDir => {}
+                Component::ParentDir => {
+                    result.pop();
+                }
+                Component::Normal(name) => result.push(name),
+            }
+        }
+        result
+    }
+
+    // A fully resolved path and the symlinks followed to reach it, in order
+    #[derive(Debug, Clone, PartialEq)]
+    pub struct PhysicalPath {
+        pub path: PathBuf,
+        pub symlinks: Vec<(PathBuf, PathBuf)>,
+    }
+
+    // Same limit as Linux before it reports ELOOP
+    const MAX_SYMLINKS: usize = 40;
+
+    // Resolves every symlink in `path` (relative paths start at the current
+    // directory). Fails if a component does not exist or links loop.
+    pub fn resolve_physical(path: &Path) -> io::Result<PhysicalPath> {
+        let mut pending: VecDeque<OsString> = VecDeque::new();
+        let mut resolved = if path.is_absolute() { PathBuf::from("/") } else { env::current_dir()? };
+        let mut symlinks = Vec::new();
+        push_components(&mut pending, path);
+
+        while let Some(name) = pending.pop_front() {
+            if name == ".." {
+                resolved.pop();
+                continue;
+            }
+            let candidate = resolved.join(&name);
+            if !fs::symlink_metadata(&candidate)?.file_type().is_symlink() {
+                resolved = candidate;
+                continue;
+            }
+            if symlinks.len() == MAX_SYMLINKS {
+                return Err(io::Error::other(format!(
+                    "{}: too many levels of symbolic links", path.display()
+                )));
+            }
+            let target = fs::read_link(&candidate)?;
+            if target.is_absolute() {
+                resolved = PathBuf::from("/");
+            }
+            let mut rest = VecDeque::new();
+            push_components(&mut rest, &target);
+            rest.extend(pending.drain(..));
+            pending = rest;
+            symlinks.push((candidate, target));
+        }
+        Ok(PhysicalPath { path: resolved, symlinks })
+    }
+
+    fn push_components(queue: &mut VecDeque<OsString>, path: &Path) {
+        for component in path.components() {
+            match component {
+                Component::Normal(name) => queue.push_back(name.to_os_string()),
+                Component::ParentDir => queue.push_back(OsString::from("..")),
+                _ => {}
+            }
+        }
+    }
+
+    // The first `CDPATH` entry holding a directory `dir`, joined onto it.
+    // An empty entry means the current directory. Only plain relative names
+    // are looked up, never `/x`, `./x` or `../x`.
+    pub fn find_in_cdpath(cdpath: &str, dir: &str) -> Option<PathBuf> {
+        if dir.is_empty() || dir.starts_with('/') || dir == "." || dir == ".."
+            || dir.starts_with("./") || dir.starts_with("../")
+        {
+            return None;
+        }
+        cdpath.split(':').find_map(|entry| {
+            let base = if entry.is_empty() { Path::new(".") } else { Path::new(entry) };
+            let candidate = base.join(dir);
+            candidate.is_dir().then_some(candidate)
+        })
+    }
+
+    pub fn same_file(a: &Path, b: &Path) -> bool {
+        match (fs::metadata(a), fs::metadata(b)) {
+            (Ok(a), Ok(b)) => a.dev() == b.dev() && a.ino() == b.ino(),
+            _ => false,
+        }
+    }
+
+    // Recently visited directories, most recent last. Entries are compared by
+    // physical location, so reaching a directory through a symlink again moves
+    // the existing entry instead of adding a duplicate.
+    #[derive(Debug, Clone)]
+    pub struct DirHistory {
+        entries: VecDeque<(PathBuf, PathBuf)>,
+        max_size: usize,
+    }
+
+    impl Default for DirHistory {
+        fn default() -> Self {
+            Self::new(100)
+        }
+    }
+
+    impl DirHistory {
+        pub fn new(max_size: usize) -> Self {
+            Self { entries: VecDeque::new(), max_size }
+        }
+
+        pub fn push(&mut self, logical: PathBuf, physical: PathBuf) {
+            self.entries.retain(|(_, existing)| *existing != physical);
+            self.entries.push_back((logical, physical));
+            if self.entries.len() > self.max_size {
+                self.entries.pop_front();
+            }
+        }
+
+        // Logical paths, oldest first
+        pub fn entries(&self) -> impl Iterator<Item = &Path> {
+            self.entries.iter().map(|(logical, _)| logical.as_path())
+        }
+
+        pub fn len(&self) -> usize {
+            self.entries.len()
+        }
+
+        pub fn is_empty(&self) -> bool {
+            self.entries.is_empty()
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+        use std::os::unix::fs::symlink;
+
+        #[test]
+        fn logical_normalization() {
+            let base = Path::new("/home/me/link");
+            assert_eq!(normalize_logical(base, Path::new("..")), PathBuf::from("/home/me"));
+            assert_eq!(normalize_logical(base, Path::new("./a/../b")), PathBuf::from("/home/me/link/b"));
+            assert_eq!(normalize_logical(base, Path::new("/usr/../etc/")), PathBuf::from("/etc"));
+            assert_eq!(normalize_logical(base, Path::new("../../../..")), PathBuf::from("/"));
+        }
+
+        #[test]
+        fn cdpath_lookup() {
+            let root = env::temp_dir().join(format!("rshell-cdpath-{}", std::process::id()));
+            let _ = fs::remove_dir_all(&root);
+            fs::create_dir_all(root.join("code/proj")).unwrap();
+            let cdpath = format!("/nonexistent:{}", root.join("code").display());
+
+            assert_eq!(find_in_cdpath(&cdpath, "proj"), Some(root.join("code/proj")));
+            assert_eq!(find_in_cdpath(&cdpath, "missing"), None);
+            assert_eq!(find_in_cdpath(&cdpath, "./proj"), None);
+            assert_eq!(find_in_cdpath(&cdpath, "/proj"), None);
+            let _ = fs::remove_dir_all(&root);
+        }
+
+        #[test]
+        fn physical_resolution_follows_links() {
+            let root = env::temp_dir().join(format!("rshell-paths-{}", std::process::id()));
+            let _ = fs::remove_dir_all(&root);
+            fs::create_dir_all(root.join("real/sub")).unwrap();
+            symlink("real/sub", root.join("link")).unwrap();
+            symlink("loop", root.join("loop")).unwrap();
+            let root = resolve_physical(&root).unwrap().path;
+
+            let resolved = resolve_physical(&root.join("link/..")).unwrap();
+            assert_eq!(resolved.path, root.join("real"));
+            assert_eq!(resolved.symlinks, vec![(root.join("link"), PathBuf::from("real/sub"))]);
+            assert!(resolve_physical(&root.join("loop")).is_err());
+            assert!(resolve_physical(&root.join("missing")).is_err());
+
+            let mut history = DirHistory::default();
+            history.push(root.join("link"), root.join("real/sub"));
+            history.push(root.join("real"), root.join("real"));
+            history.push(root.join("real/sub"), root.join("real/sub"));
+            let entries: Vec<_> = history.entries().collect();
+            assert_eq!(entries, vec![root.join("real"), root.join("real/sub")]);
+            fs::remove_dir_all(&root).unwrap();
+        }
+    }
 }
 pub mod env_utils{
+    use std::borrow::Borrow;
     use std::collections::HashMap;
     use std::env;
+    use std::hash::Hash;
+    use glob::Pattern;
+    use crate::memory_pool::ZeroCopyParser;
     pub fn get_all_env()->HashMap<String,String>{
         env::vars().collect()
     }
-pub fn expand_variables(input:&str,env:&HashMap<String,String>)->String{
-    let mut result=input.to_string();
-    while let Some(start)=result.find("${"){
-        if let Some(end)=result[start..].find('}'){
-            let var_name=&result[start+2..start + end];
-            let replacement=env.get(var_name).map(|s| s.as_str()).unwrap_or("");
-            result.replace_range(start..start+end+1,replacement);
-        }else{
-            break;
+// What `${NAME...}` does with the parameter's value
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ParamOp<'a> {
+    Value,
+    // `${#NAME}`
+    Length,
+    // `-`, `=`, `+` and `?`; with the colon an empty value counts as unset
+    Default { word: &'a str, colon: bool },
+    Assign { word: &'a str, colon: bool },
+    Alternate { word: &'a str, colon: bool },
+    Error { word: &'a str, colon: bool },
+    // `#` / `##` and `%` / `%%`: shortest or longest match of a glob pattern
+    RemovePrefix { pattern: &'a str, longest: bool },
+    RemoveSuffix { pattern: &'a str, longest: bool },
+    // `${NAME:offset}` and `${NAME:offset:length}`, in characters; negative
+    // numbers (written `${NAME: -2}`) count from the end
+    Substring { offset: i64, length: Option<i64> },
+}
+
+#[derive(Debug, PartialEq)]
+pub enum ParamValue {
+    Value(String),
+    // `${NAME:=word}`: the parameter is set to this, which is also the value
+    Assign(String),
+    // `${NAME:?message}` on an unset parameter
+    Error(String),
+}
+
+// Length of the parameter name `expr` starts with: a variable name, a
+// number, or one special character
+fn parameter_name_len(expr: &str) -> usize {
+    match expr.as_bytes().first() {
+        Some(b) if b.is_ascii_alphabetic() || *b == b'_' => {
+            expr.bytes().take_while(|b| b.is_ascii_alphanumeric() || *b == b'_').count()
         }
+        Some(b) if b.is_ascii_digit() => expr.bytes().take_while(u8::is_ascii_digit).count(),
+        Some(b'?' | b'$' | b'!' | b'#' | b'@' | b'*' | b'-') => 1,
+        _ => 0,
     }
-    let mut chars:Vec<char>=result.chars().collect();
-    let mut i=0;
-    while i<chars.len(){
-        if chars[i]=='$' && i+1<chars.len() && (chars[i+1].is_alphabetic() || chars[i+1]=='_') {
-            let start=i;
-            i+=1;
-            while i<chars.len() && (chars[i].is_alphanumeric()||chars[i]=='_'){
-                i+=1;
-            }
-            let var_name: String=chars[start + 1..i].iter().collect();
-            let replacement=env.get(&var_name).map(|s| s.as_str()).unwrap_or("");
-            let original_var:String = chars[start..i].iter().collect();
-            result=result.replace(&format!("${}", var_name), replacement);
-            result=result.replace(&original_var, replacement);
-            chars=result.chars().collect();
-            i=start+replacement.len();
-        }else{
-            i+=1;
+}
+
+// Splits the inside of `${...}` into the parameter name and operator; None
+// for a bad substitution
+pub fn parse_parameter(expr: &str) -> Option<(&str, ParamOp<'_>)> {
+    if let Some(name) = expr.strip_prefix('#').filter(|name| !name.is_empty()) {
+        return (parameter_name_len(name) == name.len()).then_some((name, ParamOp::Length));
+    }
+    let len = parameter_name_len(expr);
+    if len == 0 {
+        return None;
+    }
+    let (name, rest) = expr.split_at(len);
+    let (colon, after) = match rest.strip_prefix(':') {
+        Some(after) => (true, after),
+        None => (false, rest),
+    };
+    let op = match after.chars().next() {
+        None if !colon => ParamOp::Value,
+        Some('-') => ParamOp::Default { word: &after[1..], colon },
+        Some('=') => ParamOp::Assign { word: &after[1..], colon },
+        Some('+') => ParamOp::Alternate { word: &after[1..], colon },
+        Some('?') => ParamOp::Error { word: &after[1..], colon },
+        _ if colon => {
+            let (offset, length) = match after.split_once(':') {
+                Some((offset, length)) => (offset, Some(length.trim().parse().ok()?)),
+                None => (after, None),
+            };
+            ParamOp::Substring { offset: offset.trim().parse().ok()?, length }
+        }
+        Some('#') => match rest.strip_prefix("##") {
+            Some(pattern) => ParamOp::RemovePrefix { pattern, longest: true },
+            None => ParamOp::RemovePrefix { pattern: &rest[1..], longest: false },
+        },
+        Some('%') => match rest.strip_prefix("%%") {
+            Some(pattern) => ParamOp::RemoveSuffix { pattern, longest: true },
+            None => ParamOp::RemoveSuffix { pattern: &rest[1..], longest: false },
+        },
+        _ => return None,
+    };
+    Some((name, op))
+}
+
+// Applies `op` to the parameter's value (None when unset). `expand` expands
+// the word or pattern operand, and is only called when it is needed.
+pub fn apply_parameter(name: &str, value: Option<&str>, op: ParamOp<'_>,
+                       mut expand: impl FnMut(&str) -> String) -> ParamValue {
+    let unset = |colon: bool| value.is_none_or(|v| colon && v.is_empty());
+    let current = value.unwrap_or_default();
+    let text = match op {
+        ParamOp::Value => current.to_string(),
+        ParamOp::Length => current.chars().count().to_string(),
+        ParamOp::Default { word, colon } if unset(colon) => expand(word),
+        ParamOp::Assign { word, colon } if unset(colon) => return ParamValue::Assign(expand(word)),
+        ParamOp::Alternate { word, colon } => if unset(colon) { String::new() } else { expand(word) },
+        ParamOp::Error { word, colon } if unset(colon) => {
+            let message = if word.is_empty() { "parameter null or not set".to_string() } else { expand(word) };
+            return ParamValue::Error(format!("{}: {}", name, message));
+        }
+        ParamOp::Default { .. } | ParamOp::Assign { .. } | ParamOp::Error { .. } => current.to_string(),
+        ParamOp::RemovePrefix { pattern, longest } => {
+            let pattern = glob_pattern(&expand(pattern));
+            let mut ends: Vec<usize> = current.char_indices().map(|(i, _)| i).chain([current.len()]).collect();
+            if longest {
+                ends.reverse();
+            }
+            ends.into_iter().find(|&end| pattern.matches(&current[..end])).map_or(current, |end| &current[end..]).to_string()
+        }
+        ParamOp::RemoveSuffix { pattern, longest } => {
+            let pattern = glob_pattern(&expand(pattern));
+            let mut starts: Vec<usize> = current.char_indices().map(|(i, _)| i).chain([current.len()]).collect();
+            if !longest {
+                starts.reverse();
+            }
+            starts.into_iter().find(|&start| pattern.matches(&current[start..])).map_or(current, |start| &current[..start]).to_string()
+        }
+        ParamOp::Substring { offset, length } => {
+            let chars: Vec<char> = current.chars().collect();
+            let len = chars.len() as i64;
+            let start = if offset < 0 { (len + offset).max(0) } else { offset.min(len) };
+            let end = match length {
+                None => len,
+                Some(length) if length < 0 => (len + length).max(start),
+                Some(length) => (start + length).min(len),
+            };
+            chars[start as usize..end as usize].iter().collect()
+        }
+    };
+    ParamValue::Value(text)
+}
+
+// A pattern that does not compile matches itself literally
+fn glob_pattern(pattern: &str) -> Pattern {
+    Pattern::new(pattern).unwrap_or_else(|_| Pattern::new(&Pattern::escape(pattern)).expect("escaped pattern"))
+}
+
+// Byte offset of the `}` closing a `${` whose inside starts `text`
+fn closing_brace(text: &str) -> Option<usize> {
+    let mut depth = 0;
+    for (i, ch) in text.char_indices() {
+        match ch {
+            '{' => depth += 1,
+            '}' if depth == 0 => return Some(i),
+            '}' => depth -= 1,
+            _ => {}
+        }
+    }
+    None
+}
+
+// Expands `$NAME` and `${...}` parameter expressions in a single pass. Unset
+// variables expand to nothing; a `$` that does not start a name, or an
+// unterminated or malformed `${`, is kept. `${NAME:=word}` does not assign.
+pub fn expand_variables<K>(input: &str, env: &HashMap<K, String>) -> String
+where
+    K: Borrow<str> + Hash + Eq,
+{
+    let lookup = |name: &str| env.get(name).map(|s| s.as_str()).unwrap_or("");
+    let mut parser = ZeroCopyParser::new(input);
+    let mut result = String::with_capacity(input.len());
+    loop {
+        result.push_str(parser.consume_while(|c| c != '$'));
+        if parser.peek().is_none() {
+            break;
+        }
+        parser.advance(1);
+        let rest = parser.remaining();
+        if let Some(braced) = rest.strip_prefix('{') {
+            let parsed = closing_brace(braced)
+                .and_then(|end| parse_parameter(&braced[..end]).map(|parsed| (end, parsed)));
+            match parsed {
+                Some((end, (name, op))) => {
+                    let value = env.get(name).map(String::as_str);
+                    match apply_parameter(name, value, op, |word| expand_variables(word, env)) {
+                        ParamValue::Value(value) | ParamValue::Assign(value) => result.push_str(&value),
+                        ParamValue::Error(message) => eprintln!("rshell: {}", message),
+                    }
+                    parser.advance(end + 2);
+                }
+                None => result.push('$'),
+            }
+        } else if rest.starts_with(|c: char| c.is_alphabetic() || c == '_') {
+            result.push_str(lookup(parser.consume_while(|c| c.is_alphanumeric() || c == '_')));
+        } else {
+            result.push('$');
         }
     }
     result
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn expands_in_one_pass() {
+        let env: HashMap<String, String> = [("HOME", "/home/me"), ("PRICE", "$5"), ("A_1", "x")]
+            .iter().map(|(k, v)| (k.to_string(), v.to_string())).collect();
+        assert_eq!(expand_variables("$HOME/bin:${A_1}y", &env), "/home/me/bin:xy");
+        assert_eq!(expand_variables("cost $PRICE, not $HOME", &env), "cost $5, not /home/me");
+        assert_eq!(expand_variables("$UNSET-$ $1 ${A_1", &env), "-$ $1 ${A_1");
+        assert_eq!(expand_variables("ünï $HOME€", &env), "ünï /home/me€");
+    }
+
+    #[test]
+    fn parameter_operators() {
+        let env: HashMap<String, String> = [("F", "dir/name.tar.gz"), ("E", ""), ("S", "héllo")]
+            .iter().map(|(k, v)| (k.to_string(), v.to_string())).collect();
+        let expand = |input: &str| expand_variables(input, &env);
+        assert_eq!(expand("${U:-a b} ${E:-x} ${E-x} ${U-${S}}"), "a b x  héllo");
+        assert_eq!(expand("${S:+set} ${E:+set}|${E+set} ${U:=v}"), "set |set v");
+        assert_eq!(expand("${#S} ${#U} ${F#*.} ${F##*.} ${F%.*} ${F%%.*}"), "5 0 tar.gz gz dir/name.tar dir/name");
+        assert_eq!(expand("${S:1} ${S:1:2} ${S: -2} ${S:1:-1} ${S:9}|"), "éllo él lo éll |");
+        assert_eq!(expand("${F#nomatch} ${S:x} ${!bad}"), "dir/name.tar.gz ${S:x} ${!bad}");
+        assert_eq!(parse_parameter("#"), Some(("#", ParamOp::Value)));
+        assert_eq!(apply_parameter("U", None, ParamOp::Error { word: "", colon: true }, |w| w.to_string()),
+                   ParamValue::Error("U: parameter null or not set".to_string()));
+    }
+}
 }
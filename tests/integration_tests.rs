@@ -1,7 +1,241 @@
+use std::io::Write;
+use std::os::unix::process::ExitStatusExt;
+use std::process::{Command, Output, Stdio};
+
+// `rshell -c script`, in a home of its own so no user config applies
+fn rshell_command(script: &str) -> Command {
+    let home = std::env::temp_dir().join(format!("rshell-it-home-{}", std::process::id()));
+    std::fs::create_dir_all(&home).unwrap();
+    let mut command = Command::new(env!("CARGO_BIN_EXE_rshell"));
+    command.args(["-c", script]).env("HOME", &home);
+    command
+}
+
+fn rshell(script: &str) -> Output {
+    rshell_command(script).output().unwrap()
+}
+
 #[test]
 fn test_command_execution() {
 }
 
 #[test]
 fn test_pipeline_execution() {
-}
\ No newline at end of file
+}
+
+#[test]
+fn test_hash_lists_commands_that_ran() {
+    let output = rshell("hash; sh -c true; hash");
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let mut lines = stdout.lines();
+    assert_eq!(lines.next(), Some("hash: hash table empty"));
+    let entry = lines.next().unwrap_or_default();
+    assert!(entry.starts_with("sh\t/") && entry.ends_with("/sh"), "{}", entry);
+    
+    let output = rshell("sh -c true; hash -r; hash");
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "hash: hash table empty\n");
+}
+
+#[test]
+fn test_command_not_found_suggests_close_names() {
+    let output = rshell("alias rshelltool=true; rshelltoo; echo $?");
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.starts_with("rshell: rshelltoo: command not found\nrshell: did you mean: "), "{}", stderr);
+    assert!(stderr.contains("rshelltool"), "{}", stderr);
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "127\n");
+}
+
+#[test]
+fn test_script_parse_error_is_reported_once() {
+    let output = rshell("echo a\nls >\necho b");
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "a\n");
+    assert_eq!(String::from_utf8_lossy(&output.stderr), "rshell: line 2:\nls >\n    ^ Expected redirect target\n");
+    assert_eq!(output.status.code(), Some(2));
+}
+
+#[test]
+fn test_nounset_ends_a_non_interactive_shell() {
+    let output = rshell("set -u\necho \"$RSHELL_UNSET_VAR\"\necho after");
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "");
+    assert_eq!(String::from_utf8_lossy(&output.stderr), "rshell: RSHELL_UNSET_VAR: unbound variable\n");
+    assert_eq!(output.status.code(), Some(1));
+    
+    let output = rshell("set -u; echo $((RSHELL_UNSET_VAR + 1)); echo after");
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "");
+    assert_eq!(output.status.code(), Some(1));
+}
+
+#[test]
+fn test_parameter_error_ends_a_non_interactive_shell() {
+    let output = rshell("trap 'echo bye' EXIT; echo \"${RSHELL_UNSET_VAR-ok}\"; echo ${RSHELL_UNSET_VAR:?not here}; echo after");
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "ok\nbye\n");
+    assert_eq!(String::from_utf8_lossy(&output.stderr), "rshell: RSHELL_UNSET_VAR: not here\n");
+    assert_eq!(output.status.code(), Some(1));
+    
+    let output = rshell("RSHELL_EMPTY_VAR=; echo \"${RSHELL_EMPTY_VAR?}\"; echo \"${RSHELL_EMPTY_VAR:?}\"; echo after");
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "\n");
+    assert_eq!(String::from_utf8_lossy(&output.stderr), "rshell: RSHELL_EMPTY_VAR: parameter null or not set\n");
+    assert_eq!(output.status.code(), Some(1));
+}
+
+#[test]
+fn test_builtin_write_errors_fail_the_builtin() {
+    let output = rshell("echo x > /dev/full; echo alive $?");
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "alive 1\n");
+    assert!(String::from_utf8_lossy(&output.stderr).starts_with("rshell: echo: write error: No space left on device"));
+    assert_eq!(output.status.code(), Some(0));
+    
+    // Nobody reads stdout by the time `echo` writes to it
+    let mut child = rshell_command("read line; echo hi; echo $? >&2")
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    drop(child.stdout.take());
+    child.stdin.take().unwrap().write_all(b"go\n").unwrap();
+    let output = child.wait_with_output().unwrap();
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.starts_with("rshell: echo: write error: Broken pipe"), "{}", stderr);
+    assert!(stderr.ends_with("\n1\n"), "{}", stderr);
+    assert_eq!(output.status.code(), Some(0));
+}
+
+#[test]
+fn test_login_profile_that_execs_rshell_does_not_recurse() {
+    let home = std::env::temp_dir().join(format!("rshell-it-login-{}", std::process::id()));
+    std::fs::create_dir_all(&home).unwrap();
+    // Gives up after five rounds, so a shell that does recurse still ends
+    let profile = format!(
+        "echo run >> \"$HOME/profile-runs\"\n[ $(wc -l < \"$HOME/profile-runs\") -lt 5 ] && exec {} -l\n",
+        env!("CARGO_BIN_EXE_rshell"),
+    );
+    std::fs::write(home.join(".profile"), profile).unwrap();
+    
+    let output = Command::new(env!("CARGO_BIN_EXE_rshell"))
+        .args(["-l", "-c", "echo hi"])
+        .env("HOME", &home)
+        .env_remove("RSHELL_IMPORTING_PROFILE")
+        .stdin(Stdio::null())
+        .output()
+        .unwrap();
+    let runs = std::fs::read_to_string(home.join("profile-runs")).unwrap();
+    std::fs::remove_dir_all(&home).unwrap();
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "hi\n");
+    assert_eq!(runs, "run\n");
+}
+
+#[test]
+fn test_prefixes_and_redirections_reach_the_command() {
+    let dir = std::env::temp_dir().join(format!("rshell-it-launch-{}", std::process::id()));
+    std::fs::create_dir_all(dir.join("bin")).unwrap();
+    let tool = dir.join("bin/rshelltool");
+    std::fs::write(&tool, "#!/bin/sh\necho \"tool $RSHELL_IT_V\"\n").unwrap();
+    let mode = std::os::unix::fs::PermissionsExt::from_mode(0o755);
+    std::fs::set_permissions(&tool, mode).unwrap();
+    
+    let script = format!(
+        "cd {}; RSHELL_IT_V=1 PATH=$PWD/bin:$PATH rshelltool > out; echo \"[$RSHELL_IT_V]\"\n\
+         RSHELL_IT_V=2 sh -c 'echo \"$RSHELL_IT_V\" >&3' 3>>out | cat; cat < out; cat <&9",
+        dir.display(),
+    );
+    let output = rshell(&script);
+    std::fs::remove_dir_all(&dir).unwrap();
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "[]\ntool 1\n2\n");
+    assert_eq!(String::from_utf8_lossy(&output.stderr), "rshell: 9: Bad file number\n");
+}
+
+#[test]
+fn test_jobs_reaps_finished_jobs_in_scripts() {
+    let output = rshell("sh -c 'kill $$' & sh -c 'exit 3' & sleep 0.2; echo all; jobs; echo running; jobs -r");
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.ends_with("all\n[1]  Done sh -c 'kill $$'\n[2]  Done sh -c 'exit 3'\nrunning\n"), "{}", stdout);
+}
+
+#[test]
+fn test_exec_replaces_the_shell_or_redirects_it() {
+    let output = rshell("echo before; exec sh -c 'echo replaced $0' arg; echo after");
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "before\nreplaced arg\n");
+    assert_eq!(output.status.code(), Some(0));
+    
+    let dir = std::env::temp_dir().join(format!("rshell-it-exec-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let log = dir.join("log");
+    let output = rshell(&format!("exec > {}; echo into log; exec 3>&1; echo via three >&3; exec 3>&-; echo x >&3", log.display()));
+    let logged = std::fs::read_to_string(&log).unwrap();
+    std::fs::remove_dir_all(&dir).unwrap();
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "");
+    assert_eq!(logged, "into log\nvia three\n");
+    assert!(!output.status.success());
+    
+    let output = rshell("exec /nonexistent/rshell-cmd; echo after");
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "");
+    assert_eq!(String::from_utf8_lossy(&output.stderr), "exec: /nonexistent/rshell-cmd: No such file or directory\n");
+    assert_eq!(output.status.code(), Some(127));
+}
+
+#[test]
+fn test_errexit_stops_scripts_at_the_failing_line() {
+    let output = rshell("set -e\nf() { false; echo nope; }\necho start\nf\necho after");
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "start\n");
+    assert!(String::from_utf8_lossy(&output.stderr).ends_with(": line 4: exiting with status 1 (set -e)\n"));
+    assert_eq!(output.status.code(), Some(1));
+    
+    // Conditions, negations and all but the last command of && and || are exempt
+    let output = rshell("set -e; false || true; if false; then :; fi; ! true; false && true\n\
+                         while false; do :; done; false | true; echo survived; true | false; echo no");
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "survived\n");
+    assert!(String::from_utf8_lossy(&output.stderr).ends_with(": line 2: exiting with status 1 (set -e)\n"));
+    assert_eq!(output.status.code(), Some(1));
+    
+    let output = rshell("set -e; set +e; false; echo off; set -o errexit; sh -c 'exit 4'; echo no");
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "off\n");
+    assert_eq!(output.status.code(), Some(4));
+    
+    let dir = std::env::temp_dir().join(format!("rshell-it-errexit-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let script = dir.join("script.sh");
+    std::fs::write(&script, "set -e\necho one\nfalse\necho two\n").unwrap();
+    let output = Command::new(env!("CARGO_BIN_EXE_rshell")).arg(&script).env("HOME", &dir).output().unwrap();
+    std::fs::remove_dir_all(&dir).unwrap();
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "one\n");
+    assert_eq!(String::from_utf8_lossy(&output.stderr), format!("{}: line 3: exiting with status 1 (set -e)\n", script.display()));
+    assert_eq!(output.status.code(), Some(1));
+}
+
+#[test]
+fn test_functions_can_be_listed_and_unset() {
+    let output = rshell("f() { echo f; }; g() { if true; then echo g; fi; }; declare -f; declare -f g; declare -F; declare -f nope; echo $?");
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "f() { echo f; }\ng() { if true; then echo g; fi; }\n\
+                                                         g() { if true; then echo g; fi; }\n\
+                                                         declare -f f\ndeclare -f g\n1\n");
+    
+    // Plain `unset` prefers the variable, -f and -v pick one
+    let output = rshell("f() { echo fn; }; f=var; unset f; f; echo \"[$f]\"; f=var; unset -v f; f; echo \"[$f]\"; unset -f f; f; echo $?");
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "fn\n[]\nfn\n[]\n127\n");
+    
+    let output = rshell("unset -x f");
+    assert_eq!(String::from_utf8_lossy(&output.stderr), "unset: -x: invalid option\nunset: usage: unset [-f] [-v] [name ...]\n");
+    assert_eq!(output.status.code(), Some(2));
+}
+
+#[test]
+fn test_kill_takes_signal_names_and_numbers() {
+    let output = rshell("kill -l | head -n 2; kill -l 9; kill -l 137; kill -l HUP; kill -l SIGTERM; kill -l 99; echo $?");
+    assert_eq!(String::from_utf8_lossy(&output.stdout), " 1) SIGHUP\n 2) SIGINT\nKILL\nKILL\n1\n15\n1\n");
+    assert_eq!(String::from_utf8_lossy(&output.stderr), "kill: 99: invalid signal specification\n");
+    
+    let output = rshell("sleep 5 > /dev/null 2>&1 & kill -9 %1; sleep 0.1; jobs; kill -BOGUS 1; echo $?");
+    assert!(String::from_utf8_lossy(&output.stdout).ends_with("Sent SIGKILL to job 1\n[1]  Done sleep 5\n1\n"));
+    assert_eq!(String::from_utf8_lossy(&output.stderr), "kill: BOGUS: invalid signal specification\n");
+    
+    let output = rshell("kill -SIGHUP $$; echo survived");
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "");
+    assert_eq!(output.status.signal(), Some(1));
+}
+
+#[test]
+fn test_quotes_keep_trailing_spaces_across_lines() {
+    let output = rshell("  echo \"a  \nb\"   \n   \n\t# note\nprintf '[%s]\\n' 'x \n y'");
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "a  \nb\n[x \n y]\n");
+}
@@ -1,14 +1,22 @@
 #[cfg(test)]
 mod tests {
-    use crate::shell::{Shell, builtins};
-    use crate::shell::parser::Parser;
-    use crate::command::CommandType;
+    use rust_shell::shell::{Shell, builtins};
+    use rust_shell::shell::parser::Parser;
+    use rust_shell::command::CommandType;
+    use bumpalo::Bump;
     
     #[test]
     fn test_simple_command_parsing() {
-        let mut parser = Parser::new("ls -la").unwrap();
+        let arena = Bump::new();
+        let mut parser = Parser::new("ls -la", &arena).unwrap();
         let result = parser.parse().unwrap();
-        // Add assertions here
+        match result {
+            CommandType::Simple(cmd) => {
+                assert_eq!(cmd.program, "ls");
+                assert_eq!(cmd.args, ["-la"]);
+            }
+            other => panic!("expected simple command, got {:?}", other),
+        }
     }
     
     #[test]
@@ -17,4 +25,4 @@ mod tests {
         let result = builtins::execute_builtin(&mut shell, "cd", &["/tmp".to_string()]);
         assert_eq!(result, Some(0));
     }
-}
\ No newline at end of file
+}